@@ -30,12 +30,17 @@ fn process(fname: &str, start_fn: &str) -> BytecodeEngine {
     //TODO: FIXME: we should probably take &str or Path
     let path = Path::new(fname).canonicalize().unwrap();
     bc.set_project_root(path.parent().unwrap().to_str().unwrap());
-    bc.load_file(path.file_name().unwrap().to_str().unwrap());
+    let result = bc.load_file(path.file_name().unwrap().to_str().unwrap())
+        // Step 2: Convert to bytecode from the given location
+        // We assume the starting function is found in scope 0, the starting scope
+        .and_then(|_| bc.process_fn(start_fn, 0));
 
-    // Step 2: Convert to bytecode from the given location
-    // We assume the starting function is found in scope 0, the starting scope
-    bc.process_fn(start_fn, 0);
-    //println!("{:#?}", bc.processed_fns);
+    if let Err(errors) = result {
+        for diagnostic in errors {
+            eprintln!("{}", diagnostic);
+        }
+        ::std::process::exit(1);
+    }
 
     bc
 }
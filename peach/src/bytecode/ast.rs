@@ -1,105 +1,568 @@
-use bytecode::engine::{Bytecode, BytecodeEngine, Definition, DefinitionId, Fun, Param, Scope,
-                       ScopeId, VarStack};
+use bytecode::diagnostic::{Diagnostic, SourceSpan};
+use bytecode::engine::{BorrowKind, Bytecode, BytecodeEngine, Capture, Closure, Definition, DefinitionId, DynTrait,
+                       ForRange, Fun, Loop, Overflow, Param, PendingClosure, Scope, ScopeId, StructKind, Trait,
+                       VarStack, Visibility};
+use bytecode::exhaustiveness::{int_bounds, int_pattern_value, Constructor, Pattern};
+use syn::punctuated::Punctuated;
+use syn::token::Comma;
 use bytecode::typecheck::builtin_type;
-use proc_macro2::TokenStream;
+use proc_macro2::{Delimiter, Group, Op, Spacing, Term, TokenStream, TokenTree};
 use std::ptr;
-use syn::{self, BinOp, Block, Expr, FnArg, GenericParam, IntSuffix, Item, Lit, Member, Pat,
-          ReturnType, Stmt, Type, UnOp};
+use syn::spanned::Spanned;
+use syn::token::Add;
+use syn::{self, BinOp, Block, Expr, ExprArray, ExprBinary, ExprIf, ExprMatch, ExprRepeat, ExprStruct, ExprTuple,
+          FloatSuffix, FnArg, GenericArgument, GenericParam, Generics, IntSuffix, Item, Lifetime, Lit, Member, Pat,
+          PatTuple, PathArguments, RangeLimits, ReturnType, Stmt, Type, TypeParamBound, UnOp, WherePredicate};
+
+/// Rewrites the arguments of `println!("{}", expr)` into a call to the `__debug__` intrinsic.
+/// The tokens keep their original spans so that errors inside the arguments point at the right place.
+fn debug_call_tokens(mac: &syn::Macro) -> Vec<TokenTree> {
+    let span = mac.path.segments[0].ident.span();
+    let args: TokenStream = mac.tts.clone().into_iter().skip(2).collect();
+    let mut group = Group::new(Delimiter::Parenthesis, args);
+    group.set_span(span);
+
+    vec![
+        TokenTree::Term(Term::new("__debug__", span)),
+        TokenTree::Group(group),
+    ]
+}
+
+/// Whether a place can be changed, and if not, why
+#[derive(Clone, Copy, PartialEq)]
+enum Mutability {
+    Mutable,
+    /// The place is in a variable that wasn't declared with `mut`
+    ImmutableVar(usize),
+    /// The place is reached through a `&` reference
+    BehindSharedRef,
+}
+
+/// How a place is about to be changed
+#[derive(Clone, Copy, PartialEq)]
+enum Mutation {
+    Assign,
+    MutableBorrow,
+}
 
 impl BytecodeEngine {
     pub(crate) fn convert_fn_to_bytecode(
         &mut self,
         definition_id: DefinitionId,
         scope_id: ScopeId,
-    ) -> Fun {
+    ) -> Result<Fun, Diagnostic> {
         let defn_state = self.definitions[definition_id].clone();
 
         match defn_state {
-            Definition::Fun(fun) => fun,
+            Definition::Fun(fun) => Ok(fun),
             Definition::LazyFn(item_fn) => {
                 let mut bytecode = Vec::new();
 
-                let mut ty_params = vec![];
-                for generic_param in item_fn.decl.generics.params {
-                    match generic_param {
-                        GenericParam::Type(type_param) => {
-                            self.definitions.push(Definition::TypeVariable);
-                            self.scopes[scope_id]
-                                .definitions
-                                .insert(type_param.ident.to_string(), self.definitions.len() - 1);
-                            ty_params.push(self.definitions.len() - 1);
+                let (scope_id, ty_params) = self.declare_ty_params(&item_fn.decl.generics, scope_id);
+
+                if let Some(ref where_clause) = item_fn.decl.generics.where_clause {
+                    for predicate in &where_clause.predicates {
+                        match predicate {
+                            WherePredicate::Type(ref predicate_type) => {
+                                let ty = self.resolve_type(&predicate_type.bounded_ty, scope_id)?;
+                                let bounds = self.resolve_bounds(&predicate_type.bounds, scope_id);
+                                match self.definitions[ty] {
+                                    Definition::TypeVariable(_, ref mut ty_bounds) => {
+                                        ty_bounds.extend(bounds)
+                                    }
+                                    _ => {
+                                        return Err(self.unsupported(
+                                            &predicate_type.bounded_ty,
+                                            "Where clauses are only supported on type parameters",
+                                        ))
+                                    }
+                                }
+                            }
+                            _ => {
+                                return Err(self.unsupported(
+                                    predicate,
+                                    "Where clauses on lifetimes are not yet supported",
+                                ))
+                            }
                         }
-                        _ => {}
                     }
                 }
 
                 let return_ty = match &item_fn.decl.output {
                     ReturnType::Default => builtin_type::VOID,
-                    ReturnType::Type(_, ref box_ty) => self.resolve_type(box_ty, scope_id),
+                    ReturnType::Type(_, ref box_ty) => self.resolve_type(box_ty, scope_id)?,
                 };
 
                 let mut var_stack = VarStack::new();
                 let mut params = vec![];
+                let mut pattern_params = vec![];
 
                 // process function params
                 for input in &item_fn.decl.inputs {
                     match input {
                         FnArg::Captured(ref capture) => {
-                            match capture.pat {
-                                Pat::Ident(ref pi) => {
-                                    let ident = pi.ident.to_string();
-                                    let definition_id = self.resolve_type(&capture.ty, scope_id);
-                                    let var_id = var_stack.add_var(ident.clone(), definition_id);
-                                    params.push(Param::new(ident, var_id, definition_id));
-                                }
-                                _ => {
-                                    unimplemented!("Unsupported pattern type in function parameter")
-                                }
-                            };
+                            let definition_id = self.resolve_type(&capture.ty, scope_id)?;
+                            declare_param(&capture.pat, definition_id, &mut params, &mut pattern_params, &mut var_stack);
+                        }
+                        FnArg::SelfRef(ref self_ref) => {
+                            let self_ty = self.self_type(input, scope_id);
+                            let ty = self.ref_type(self_ty, self_ref.mutability.is_some());
+                            let var_id = var_stack.add_var("self".to_string(), ty, false);
+                            params.push(Param::new("self".to_string(), var_id, ty, false));
+                        }
+                        FnArg::SelfValue(ref self_value) => {
+                            let ty = self.self_type(input, scope_id);
+                            let mutable = self_value.mutability.is_some();
+                            let var_id = var_stack.add_var("self".to_string(), ty, mutable);
+                            params.push(Param::new("self".to_string(), var_id, ty, mutable));
+                        }
+                        _ => {
+                            return Err(self.unsupported(
+                                input,
+                                "Function parameters without a name are not yet supported",
+                            ))
                         }
-                        _ => unimplemented!("Function argument of {:?} is not supported", input),
                     }
                 }
 
-                let block_ty = self.convert_block_to_bytecode(
+                self.convert_pattern_params(pattern_params, &mut bytecode, scope_id, &mut var_stack)?;
+
+                let block_ty = self.convert_block_expecting(
                     &item_fn.block,
                     return_ty,
+                    return_ty,
                     &mut bytecode,
                     Some(scope_id),
                     &mut var_stack,
-                );
-
-                match block_ty {
-                    builtin_type::VOID => bytecode.push(Bytecode::ReturnVoid),
-                    _ => bytecode.push(Bytecode::ReturnLastStackValue),
-                }
+                )?;
+                self.report_uncalled_closures();
 
-                match bytecode.last() {
-                    Some(Bytecode::ReturnVoid) | Some(Bytecode::ReturnLastStackValue) => {}
-                    _ => {
-                        if !self.assignment_compatible(return_ty, block_ty) {
-                            unimplemented!(
+                // A body ending in `return` has already had its value checked, and one ending in a `loop` that's
+                // never left, or in anything else that never finishes, can only return from inside it
+                let returned = match bytecode.last() {
+                    _ if block_ty == builtin_type::NEVER => true,
+                    Some(Bytecode::ReturnVoid) | Some(Bytecode::ReturnLastStackValue) => true,
+                    Some(Bytecode::EndWhile(offset, builtin_type::VOID)) => {
+                        !loop_exits(&bytecode[bytecode.len() - 1 - offset..])
+                    }
+                    _ => false,
+                };
+                if !returned {
+                    if !self.assignment_compatible(return_ty, block_ty) {
+                        self.report(Diagnostic::new(
+                            "E0308",
+                            format!(
                                 "Mismatched return types: {} and {}",
                                 self.printable_name(block_ty),
                                 self.printable_name(return_ty),
-                            );
-                        }
+                            ),
+                        ).with_primary(
+                            self.span_of(&item_fn.block),
+                            &format!("expected {}", self.printable_name(return_ty)),
+                        ));
+                    }
+
+                    match block_ty {
+                        builtin_type::VOID => bytecode.push(Bytecode::ReturnVoid),
+                        _ => bytecode.push(Bytecode::ReturnLastStackValue),
                     }
                 }
 
-                Fun {
+                let fun = Fun {
                     ty_params,
                     params,
                     return_ty,
                     vars: var_stack.vars,
                     bytecode,
+                    spans: var_stack.spans,
                     extern_name: None,
-                }
+                };
+
+                self.check_borrows(&fun);
+
+                Ok(fun)
             }
             _ => unimplemented!("Could not find function"),
         }
     }
 
+    /// Destructures the params given as patterns, now that all of the params are declared
+    fn convert_pattern_params(
+        &mut self,
+        pattern_params: Vec<(&Pat, usize, DefinitionId)>,
+        bytecode: &mut Vec<Bytecode>,
+        scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<(), Diagnostic> {
+        for (pat, var_id, ty) in pattern_params {
+            let span = self.span_of(pat);
+            let span_id = var_stack.add_span(span.clone());
+            let (_, pattern) = self.convert_pattern_to_bytecode(
+                pat,
+                ty,
+                &[Bytecode::Var(var_id, span_id)],
+                bytecode,
+                scope_id,
+                var_stack,
+            )?;
+            self.check_irrefutable(&pattern, ty, span, "function argument");
+        }
+        Ok(())
+    }
+
+    /// Converts the body of a closure to a function, if it hasn't been already.  Params without a type take the type
+    /// of the argument they're given in the first call.  The body can see the variables in scope where the closure
+    /// was made, which are declared first in it, and those it uses are the ones it captures
+    fn convert_closure(&mut self, closure_ty: DefinitionId, arg_tys: &[DefinitionId]) -> Result<(), Diagnostic> {
+        let pending = match self.pending_closures.remove(&closure_ty) {
+            Some(pending) => pending,
+            None => return Ok(()),
+        };
+        let ec = &pending.expr;
+        let scope_id = pending.scope_id;
+        let mut var_stack = VarStack::new();
+        let outer_var_ids = pending.var_stack.visible();
+        for var_id in &outer_var_ids {
+            let var = &pending.var_stack.vars[*var_id];
+            var_stack.add_var(var.ident.clone(), var.ty, var.mutable);
+        }
+
+        let mut bytecode = vec![];
+        let mut params = vec![];
+        let mut pattern_params = vec![];
+        for (index, input) in ec.inputs.iter().enumerate() {
+            match input {
+                FnArg::Captured(ref capture) => {
+                    let ty = self.resolve_type(&capture.ty, scope_id)?;
+                    declare_param(&capture.pat, ty, &mut params, &mut pattern_params, &mut var_stack);
+                }
+                FnArg::Inferred(ref pat) => {
+                    let ty = builtin_type::fallback(arg_tys.get(index).cloned().unwrap_or(builtin_type::ERROR));
+                    declare_param(pat, ty, &mut params, &mut pattern_params, &mut var_stack);
+                }
+                _ => return Err(self.unsupported(input, "This kind of closure parameter is not supported")),
+            }
+        }
+        self.convert_pattern_params(pattern_params, &mut bytecode, scope_id, &mut var_stack)?;
+
+        let return_ty = match ec.output {
+            ReturnType::Default => None,
+            ReturnType::Type(_, ref ty) => Some(self.resolve_type(ty, scope_id)?),
+        };
+
+        // The body is converted on its own, wherever the closure is first called from.  Its loops are its own, as
+        // is what it returns from
+        let outer_loops = ::std::mem::take(&mut self.loops);
+        self.captures_mutated.push(vec![false; outer_var_ids.len()]);
+        let body_ty = self.convert_expr_to_bytecode(
+            &ec.body,
+            return_ty.unwrap_or(builtin_type::UNKNOWN),
+            &mut bytecode,
+            scope_id,
+            &mut var_stack,
+        );
+        let mut mutated = self.captures_mutated.pop().unwrap_or_default();
+        self.loops = outer_loops;
+        let body_ty = body_ty?;
+
+        let return_ty = match return_ty {
+            Some(return_ty) => {
+                if !self.assignment_compatible(return_ty, body_ty) {
+                    self.report(Diagnostic::new(
+                        "E0308",
+                        format!(
+                            "Mismatched return types: {} and {}",
+                            self.printable_name(body_ty),
+                            self.printable_name(return_ty),
+                        ),
+                    ).with_primary(
+                        self.span_of(&ec.body),
+                        &format!("expected {}", self.printable_name(return_ty)),
+                    ));
+                }
+                return_ty
+            }
+            None => builtin_type::fallback(pushed_type(body_ty)),
+        };
+        match body_ty {
+            builtin_type::VOID => bytecode.push(Bytecode::ReturnVoid),
+            // A body that never finishes can only return from inside it
+            builtin_type::NEVER => {}
+            _ => bytecode.push(Bytecode::ReturnLastStackValue),
+        }
+
+        // Closures made in the body capture through it what they capture from outside it
+        let mut used = vec![false; outer_var_ids.len()];
+        for code in &bytecode {
+            match code {
+                Bytecode::Var(var_id, _) | Bytecode::LValueVar(var_id, _) if *var_id < used.len() => {
+                    used[*var_id] = true
+                }
+                Bytecode::MakeClosure(inner_ty, _) => {
+                    if let Definition::Closure(ref inner) = self.definitions[*inner_ty] {
+                        for capture in &inner.captures {
+                            if capture.var_id < used.len() {
+                                used[capture.var_id] = true;
+                                mutated[capture.var_id] |= capture.mutated;
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        let captures = (0..used.len())
+            .filter(|inner_var_id| used[*inner_var_id])
+            .map(|inner_var_id| Capture {
+                var_id: outer_var_ids[inner_var_id],
+                inner_var_id,
+                ty: var_stack.vars[inner_var_id].ty,
+                mutated: mutated[inner_var_id],
+            })
+            .collect();
+
+        let fun = Fun {
+            ty_params: vec![],
+            params,
+            return_ty,
+            vars: var_stack.vars,
+            bytecode,
+            spans: var_stack.spans,
+            extern_name: None,
+        };
+        self.check_borrows(&fun);
+
+        if let Definition::Closure(ref mut closure) = self.definitions[closure_ty] {
+            closure.captures = captures;
+            closure.fun = Some(fun);
+        }
+        Ok(())
+    }
+
+    /// Calls the closure in the given variable, which it borrows for the call.  The args are converted first, as
+    /// the first call decides the types of any params without one
+    fn convert_closure_call(
+        &mut self,
+        ec: &syn::ExprCall,
+        var_id: usize,
+        expected_return_type: DefinitionId,
+        bytecode: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<DefinitionId, Diagnostic> {
+        let closure_ty = var_stack.vars[var_id].ty;
+        let mut args_bytecode = vec![];
+        let mut arg_tys = vec![];
+        for arg in &ec.args {
+            arg_tys.push(self.convert_expr_to_bytecode(
+                arg,
+                expected_return_type,
+                &mut args_bytecode,
+                current_scope_id,
+                var_stack,
+            )?);
+        }
+        self.convert_closure(closure_ty, &arg_tys)?;
+
+        let (params, return_ty, mutates) = match self.definitions[closure_ty] {
+            Definition::Closure(Closure {
+                fun: Some(ref fun),
+                ..
+            }) => (fun.params.clone(), fun.return_ty, self.closure_mutates(closure_ty)),
+            // The body had errors, which have been reported
+            _ => return Ok(builtin_type::ERROR),
+        };
+
+        if params.len() != arg_tys.len() {
+            return Ok(self.report(Diagnostic::new(
+                "E0057",
+                format!(
+                    "This closure takes {} argument(s) but {} were supplied",
+                    params.len(),
+                    arg_tys.len()
+                ),
+            ).with_primary(
+                self.span_of(ec),
+                &format!("expected {} argument(s)", params.len()),
+            )));
+        }
+        for ((param, arg_ty), arg) in params.iter().zip(arg_tys).zip(ec.args.iter()) {
+            if !self.assignment_compatible(param.ty, arg_ty) {
+                self.report(Diagnostic::new(
+                    "E0308",
+                    format!(
+                        "Argument {} expects {}, found {}",
+                        param.name,
+                        self.printable_name(param.ty),
+                        self.printable_name(arg_ty)
+                    ),
+                ).with_primary(
+                    self.span_of(arg),
+                    &format!("expected {}", self.printable_name(param.ty)),
+                ));
+            }
+        }
+
+        // A closure that changes what it captured has to be borrowed mutably to be called
+        let span_id = var_stack.add_span(self.span_of(&*ec.func));
+        bytecode.push(Bytecode::LValueVar(var_id, span_id));
+        if mutates {
+            let mutability = if var_stack.vars[var_id].mutable {
+                Mutability::Mutable
+            } else {
+                Mutability::ImmutableVar(var_id)
+            };
+            self.check_mutable(&ec.func, &place_name(&ec.func), Mutation::MutableBorrow, mutability, var_stack);
+            bytecode.push(Bytecode::Ref(BorrowKind::Mutable, span_id));
+        } else {
+            bytecode.push(Bytecode::Ref(BorrowKind::Shared, span_id));
+        }
+        bytecode.extend(args_bytecode);
+        bytecode.push(Bytecode::CallClosure(closure_ty));
+
+        Ok(return_ty)
+    }
+
+    /// Calls through a function pointer, which is whatever the called expression gives
+    fn convert_fn_ptr_call(
+        &mut self,
+        ec: &syn::ExprCall,
+        expected_return_type: DefinitionId,
+        bytecode: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<DefinitionId, Diagnostic> {
+        let fn_ptr_ty = self.convert_expr_to_bytecode(
+            &ec.func,
+            builtin_type::UNKNOWN,
+            bytecode,
+            current_scope_id,
+            var_stack,
+        )?;
+        let (param_tys, return_ty) = match self.definitions[fn_ptr_ty] {
+            Definition::FnPtr(ref param_tys, return_ty) => (param_tys.clone(), return_ty),
+            _ if fn_ptr_ty == builtin_type::ERROR => return Ok(builtin_type::ERROR),
+            _ => {
+                return Ok(self.report(Diagnostic::new(
+                    "E0618",
+                    format!("Can not call function on type {}", self.printable_name(fn_ptr_ty)),
+                ).with_primary(self.span_of(&*ec.func), "not a function")))
+            }
+        };
+
+        if param_tys.len() != ec.args.len() {
+            return Ok(self.report(Diagnostic::new(
+                "E0061",
+                format!(
+                    "This function takes {} argument(s) but {} were supplied",
+                    param_tys.len(),
+                    ec.args.len()
+                ),
+            ).with_primary(
+                self.span_of(ec),
+                &format!("expected {} argument(s)", param_tys.len()),
+            )));
+        }
+
+        let params: Vec<Param> = param_tys
+            .iter()
+            .enumerate()
+            .map(|(index, param_ty)| Param::new(index.to_string(), 0, *param_ty, false))
+            .collect();
+        self.convert_args_to_bytecode(
+            &ec.args,
+            &params,
+            expected_return_type,
+            bytecode,
+            current_scope_id,
+            var_stack,
+        )?;
+        bytecode.push(Bytecode::CallPtr(fn_ptr_ty));
+
+        Ok(return_ty)
+    }
+
+    /// Calls a function of one of the traits a type parameter is bound by, like `T::new()`, which is resolved once
+    /// the generic function is instantiated
+    fn convert_bound_fn_call(
+        &mut self,
+        ec: &syn::ExprCall,
+        ty_var: DefinitionId,
+        expected_return_type: DefinitionId,
+        bytecode: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<DefinitionId, Diagnostic> {
+        let fn_ident = match *ec.func {
+            Expr::Path(ref ep) => &ep.path.segments[1].ident,
+            _ => unimplemented!("Call through a type parameter that isn't a path"),
+        };
+        let (call, params, return_ty) = match self.bound_method(ty_var, fn_ident)? {
+            Some(found) => found,
+            None => {
+                return Ok(self.report(Diagnostic::new(
+                    "E0599",
+                    format!(
+                        "No function or associated item named `{}` found for type parameter {}",
+                        fn_ident,
+                        self.printable_name(ty_var)
+                    ),
+                ).with_primary(self.span_of(fn_ident), "function or associated item not found")))
+            }
+        };
+
+        if params.len() != ec.args.len() {
+            return Ok(self.report(Diagnostic::new(
+                "E0061",
+                format!(
+                    "Function {} takes {} argument(s) but {} were supplied",
+                    fn_ident,
+                    params.len(),
+                    ec.args.len()
+                ),
+            ).with_primary(
+                self.span_of(ec),
+                &format!("expected {} argument(s)", params.len()),
+            )));
+        }
+
+        self.convert_args_to_bytecode(
+            &ec.args,
+            &params,
+            expected_return_type,
+            bytecode,
+            current_scope_id,
+            var_stack,
+        )?;
+        bytecode.push(call);
+
+        Ok(return_ty)
+    }
+
+    /// Checks if calling the closure changes it, either through what it borrows or in what was moved into it
+    fn closure_mutates(&self, closure_ty: DefinitionId) -> bool {
+        match self.definitions[closure_ty] {
+            Definition::Closure(ref closure) => closure.mutates(),
+            _ => false,
+        }
+    }
+
+    /// Reports the closures made in the function that were never called, as nothing gave their params types
+    fn report_uncalled_closures(&mut self) {
+        let mut uncalled: Vec<(DefinitionId, PendingClosure)> = self.pending_closures.drain().collect();
+        uncalled.sort_by_key(|(closure_ty, _)| *closure_ty);
+        for (_, pending) in uncalled {
+            let untyped = pending.expr.inputs.iter().find_map(|input| match input {
+                FnArg::Inferred(ref pat) => Some(self.span_of(pat)),
+                _ => None,
+            });
+            if let Some(span) = untyped {
+                self.report(Diagnostic::new("E0282", "Type annotations needed".to_string())
+                    .with_primary(span, "consider giving this closure parameter an explicit type"));
+            }
+        }
+    }
+
     pub(crate) fn convert_block_to_bytecode(
         &mut self,
         block: &Block,
@@ -107,7 +570,27 @@ impl BytecodeEngine {
         bytecode: &mut Vec<Bytecode>,
         parent: Option<ScopeId>,
         var_stack: &mut VarStack,
-    ) -> DefinitionId {
+    ) -> Result<DefinitionId, Diagnostic> {
+        self.convert_block_expecting(
+            block,
+            builtin_type::UNKNOWN,
+            expected_return_type,
+            bytecode,
+            parent,
+            var_stack,
+        )
+    }
+
+    /// Converts a block whose value is expected to have the given type, which its last expression is converted with
+    fn convert_block_expecting(
+        &mut self,
+        block: &Block,
+        expected_ty: DefinitionId,
+        expected_return_type: DefinitionId,
+        bytecode: &mut Vec<Bytecode>,
+        parent: Option<ScopeId>,
+        var_stack: &mut VarStack,
+    ) -> Result<DefinitionId, Diagnostic> {
         //TODO: there may be more efficient ways to do this, but this will do for now
         let mut block_var_stack = var_stack.clone();
         let mut return_definition_id = builtin_type::VOID;
@@ -121,31 +604,56 @@ impl BytecodeEngine {
             //TODO: FIXME: proper macro processing should probably be done higher
             if let Stmt::Item(Item::Macro(im)) = stmt {
                 if im.mac.path.segments[0].ident.as_ref() == "println" {
-                    let token_stream: TokenStream = im.mac.tts.into_iter().skip(2).collect();
-                    let call = String::new() + "__debug__(" + &token_stream.to_string() + ");";
-                    let result: Stmt = syn::parse_str(&call).unwrap();
+                    let mut tokens = debug_call_tokens(&im.mac);
+                    tokens.push(TokenTree::Op(Op::new(';', Spacing::Alone)));
+                    let result: Stmt = syn::parse2(tokens.into_iter().collect()).map_err(|e| {
+                        Diagnostic::uncoded(format!("couldn't parse println! arguments: {}", e))
+                            .with_primary(self.span_of(&im.mac), "in this macro")
+                    })?;
                     processed_block.push(result);
+                } else {
+                    self.report(self.unsupported(&im.mac, "Macros other than `println!` are not yet supported"));
                 }
             } else if let Stmt::Item(ref item) = stmt {
-                self.prepare_item(item.clone(), current_scope_id);
+                self.prepare_item(item.clone(), current_scope_id)?;
             } else {
                 processed_block.push(stmt.clone());
             }
         }
 
-        for stmt in &processed_block {
-            return_definition_id = self.convert_stmt_to_bytecode(
-                stmt,
-                expected_return_type,
-                bytecode,
-                current_scope_id,
-                &mut block_var_stack,
-            );
+        for (index, stmt) in processed_block.iter().enumerate() {
+            let stmt_start = bytecode.len();
+            let converted = match stmt {
+                Stmt::Expr(ref e) if index == processed_block.len() - 1 => self.convert_expr_expecting(
+                    e,
+                    expected_ty,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    &mut block_var_stack,
+                ),
+                _ => self.convert_stmt_to_bytecode(
+                    stmt,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    &mut block_var_stack,
+                ),
+            };
+            // Errors that stop a statement from being converted are recorded so we can carry on with the next one.
+            // What was converted of it is dropped, so the bytecode left is still whole
+            return_definition_id = match converted {
+                Ok(definition_id) => definition_id,
+                Err(diagnostic) => {
+                    bytecode.truncate(stmt_start);
+                    self.report(diagnostic)
+                }
+            };
         }
 
-        var_stack.vars = block_var_stack.vars;
+        var_stack.leave_block(block_var_stack);
 
-        return_definition_id
+        Ok(return_definition_id)
     }
 
     pub fn convert_stmt_to_bytecode(
@@ -155,17 +663,21 @@ impl BytecodeEngine {
         bytecode: &mut Vec<Bytecode>,
         current_scope_id: ScopeId,
         var_stack: &mut VarStack,
-    ) -> DefinitionId {
+    ) -> Result<DefinitionId, Diagnostic> {
         match stmt {
             Stmt::Semi(ref e, _) => {
-                self.convert_expr_to_bytecode(
+                let ty = self.convert_expr_to_bytecode(
                     e,
                     expected_return_type,
                     bytecode,
                     current_scope_id,
                     var_stack,
-                );
-                builtin_type::VOID
+                )?;
+                // The value is dropped, ending any borrows it holds
+                if pushed_type(ty) != builtin_type::VOID {
+                    bytecode.push(Bytecode::Pop);
+                }
+                Ok(builtin_type::VOID)
             }
             Stmt::Expr(ref e) => self.convert_expr_to_bytecode(
                 e,
@@ -175,717 +687,4989 @@ impl BytecodeEngine {
                 var_stack,
             ),
             Stmt::Local(ref l) => {
-                let ident = match l.pats[0] {
-                    Pat::Ident(ref pi) => pi.ident.to_string(),
-                    _ => unimplemented!("Unsupported pattern in variable declaration"),
+                // Anything more than a name is destructured from a temporary holding the whole value, which
+                // can be borrowed mutably like any other temporary
+                let (ident, mutable) = match *unparenthesized(&l.pats[0]) {
+                    Pat::Ident(ref pi) if pi.by_ref.is_none() && pi.subpat.is_none() => {
+                        (pi.ident.to_string(), pi.mutability.is_some())
+                    }
+                    _ => (String::new(), true),
                 };
+                let destructured = ident.is_empty();
                 match l.init {
                     Some(ref foo) => {
-                        let rhs_ty = self.convert_expr_to_bytecode(
+                        // A variable is destructured in place, so that `ref` bindings borrow the variable itself
+                        if let Expr::Path(ref ep) = *foo.1 {
+                            if destructured
+                                && l.ty.is_none()
+                                && ep.path.segments.len() == 1
+                                && ep.path.leading_colon.is_none()
+                            {
+                                if let Some(var_id) = var_stack.find_var(ep.path.segments[0].ident.as_ref()) {
+                                    let span_id = var_stack.add_span(self.span_of(ep));
+                                    let var_ty = var_stack.vars[var_id].ty;
+                                    self.convert_local_pattern(
+                                        &l.pats[0],
+                                        var_ty,
+                                        &[Bytecode::Var(var_id, span_id)],
+                                        bytecode,
+                                        current_scope_id,
+                                        var_stack,
+                                    )?;
+                                    return Ok(builtin_type::VOID);
+                                }
+                            }
+                        }
+
+                        let explicit_ty = match l.ty {
+                            Some(ref explicit_ty) => Some(self.resolve_type(&*explicit_ty.1, current_scope_id)?),
+                            None => None,
+                        };
+                        let rhs_ty = self.convert_expr_expecting(
                             &*foo.1,
+                            explicit_ty.unwrap_or(builtin_type::UNKNOWN),
                             expected_return_type,
                             bytecode,
                             current_scope_id,
                             var_stack,
-                        );
-
-                        match l.ty {
-                            None => {
-                                let var_id = var_stack.add_var(ident, rhs_ty);
-                                bytecode.push(Bytecode::VarDecl(var_id));
-                                builtin_type::VOID
-                            }
-                            Some(ref explicit_ty) => {
-                                let var_ty = self.resolve_type(&*explicit_ty.1, current_scope_id);
+                        )?;
 
+                        let (var_id, var_ty) = match (&l.ty, explicit_ty) {
+                            (Some(ref explicit_ty), Some(var_ty)) => {
                                 if !self.assignment_compatible(var_ty, rhs_ty) {
-                                    unimplemented!(
-                                        "Explicit variable type '{}' does not match expression type '{}'", self.printable_name(var_ty), self.printable_name(rhs_ty)
+                                    self.report(Diagnostic::new(
+                                        "E0308",
+                                        format!(
+                                            "Explicit variable type '{}' does not match expression type '{}'",
+                                            self.printable_name(var_ty),
+                                            self.printable_name(rhs_ty)
+                                        ),
+                                    ).with_primary(
+                                        self.span_of(&*foo.1),
+                                        &format!(
+                                            "expected {}, found {}",
+                                            self.printable_name(var_ty),
+                                            self.printable_name(rhs_ty)
+                                        ),
                                     )
+                                        .with_secondary(
+                                            self.span_of(&*explicit_ty.1),
+                                            "expected due to this type",
+                                        ));
                                 }
 
-                                let var_id = var_stack.add_var(ident, var_ty);
-                                bytecode.push(Bytecode::As(var_ty));
-                                bytecode.push(Bytecode::VarDecl(var_id));
+                                let var_id = var_stack.add_var(ident, var_ty, mutable);
+                                // The unit value is nothing at all, so there's nothing to keep
+                                if var_ty != builtin_type::VOID {
+                                    bytecode.push(Bytecode::As(var_ty));
+                                    bytecode.push(Bytecode::VarDecl(var_id));
+                                }
 
-                                builtin_type::VOID
+                                (var_id, var_ty)
                             }
+                            _ => {
+                                self.check_fallback_literals(&foo.1, rhs_ty);
+                                let var_id = var_stack.add_var(ident, rhs_ty, mutable);
+                                if rhs_ty != builtin_type::VOID {
+                                    bytecode.push(Bytecode::VarDecl(var_id));
+                                }
+                                (var_id, rhs_ty)
+                            }
+                        };
+
+                        if !destructured {
+                            return Ok(builtin_type::VOID);
                         }
+
+                        let span_id = var_stack.add_span(self.span_of(&l.pats[0]));
+                        self.convert_local_pattern(
+                            &l.pats[0],
+                            var_ty,
+                            &[Bytecode::Var(var_id, span_id)],
+                            bytecode,
+                            current_scope_id,
+                            var_stack,
+                        )?;
+
+                        Ok(builtin_type::VOID)
                     }
                     None => {
+                        if destructured {
+                            self.report(self.unsupported(
+                                &l.pats[0],
+                                "Destructuring a variable declared without a value is not yet supported",
+                            ));
+                            // The names are still declared, so that their uses aren't reported as well
+                            let mut names = vec![];
+                            pattern_names(&l.pats[0], &mut names);
+                            for name in names {
+                                let var_id = var_stack.add_var(name, builtin_type::ERROR, true);
+                                bytecode.push(Bytecode::VarDeclUninit(var_id));
+                            }
+                            return Ok(builtin_type::VOID);
+                        }
+
                         match l.ty {
                             None => {
-                                let var_id = var_stack.add_var(ident, builtin_type::UNKNOWN);
+                                let var_id = var_stack.add_var(ident, builtin_type::UNKNOWN, mutable);
                                 bytecode.push(Bytecode::VarDeclUninit(var_id));
                             }
                             Some(ref explicit_ty) => {
-                                let var_ty = self.resolve_type(&*explicit_ty.1, current_scope_id);
+                                let var_ty =
+                                    self.resolve_type(&*explicit_ty.1, current_scope_id)?;
 
-                                let var_id = var_stack.add_var(ident, var_ty);
+                                let var_id = var_stack.add_var(ident, var_ty, mutable);
                                 bytecode.push(Bytecode::VarDeclUninit(var_id));
                             }
                         }
 
-                        builtin_type::VOID
+                        Ok(builtin_type::VOID)
                     }
                 }
             }
-            _ => builtin_type::VOID, // ignore Item(s) as we've already processed them
+            _ => Ok(builtin_type::VOID), // ignore Item(s) as we've already processed them
         }
     }
 
+    /// Converts a place that's about to be changed, reporting an error if it can't be
     fn convert_lhs_expr_to_bytecode(
         &mut self,
         expr: &Expr,
+        mutation: Option<Mutation>,
         expected_return_type: DefinitionId,
         bytecode: &mut Vec<Bytecode>,
         current_scope_id: ScopeId,
         var_stack: &mut VarStack,
-    ) -> DefinitionId {
-        match expr {
-            Expr::Path(ep) => {
-                let ident = ep.path.segments[0].ident.to_string();
-
-                let var_id = var_stack.find_var(&ident);
-                if var_id.is_none() {
-                    unimplemented!("Could not find variable: {}", ident);
-                }
-                let var_id = var_id.unwrap();
-                let var = &mut var_stack.vars[var_id];
-                bytecode.push(Bytecode::LValueVar(var_id));
-
-                var.ty
-            }
-            Expr::Field(ef) => {
-                let ty = self.convert_lhs_expr_to_bytecode(
-                    &*ef.base,
-                    expected_return_type,
-                    bytecode,
-                    current_scope_id,
-                    var_stack,
-                );
-
-                if let Definition::Struct(ref st) = self.definitions[ty] {
-                    match ef.member {
-                        Member::Named(ident) => {
-                            bytecode.push(Bytecode::LValueDot(ident.to_string()));
-                            for field in &st.fields {
-                                if field.0 == ident.as_ref() {
-                                    return field.1;
-                                }
-                            }
-                            unimplemented!("Field access of {} not found", ident);
-                        }
-                        _ => unimplemented!("Unsupported member access"),
-                    }
-                } else {
-                    unimplemented!("Member access on non-struct types");
-                }
-            }
-            _ => unimplemented!("Unsupport lvalue type"),
+    ) -> Result<DefinitionId, Diagnostic> {
+        let (ty, mutability) = self.convert_lvalue_to_bytecode(
+            expr,
+            expected_return_type,
+            bytecode,
+            current_scope_id,
+            var_stack,
+        )?;
+        if let Some(mutation) = mutation {
+            self.check_mutable(expr, &place_name(expr), mutation, mutability, var_stack);
         }
+        Ok(ty)
     }
-    pub fn convert_expr_to_bytecode(
+
+    fn convert_lvalue_to_bytecode(
         &mut self,
         expr: &Expr,
         expected_return_type: DefinitionId,
         bytecode: &mut Vec<Bytecode>,
         current_scope_id: ScopeId,
         var_stack: &mut VarStack,
-    ) -> DefinitionId {
+    ) -> Result<(DefinitionId, Mutability), Diagnostic> {
         match expr {
-            Expr::Return(er) => {
-                let actual_return_type = match er.expr {
-                    Some(ref inner) => self.convert_expr_to_bytecode(
-                        inner,
-                        expected_return_type,
-                        bytecode,
-                        current_scope_id,
-                        var_stack,
-                    ),
-                    None => builtin_type::VOID,
-                };
+            Expr::Path(ep) => {
+                let ident = ep.path.segments[0].ident.to_string();
 
-                if self.assignment_compatible(expected_return_type, actual_return_type) {
-                    match actual_return_type {
-                        builtin_type::VOID => bytecode.push(Bytecode::ReturnVoid),
-                        _ => bytecode.push(Bytecode::ReturnLastStackValue),
+                let var_id = match var_stack.find_var(&ident) {
+                    Some(var_id) => var_id,
+                    None => {
+                        let ty = self.report(Diagnostic::new(
+                            "E0425",
+                            format!("Could not find variable: {}", ident),
+                        ).with_primary(self.span_of(ep), "not found in this scope"));
+                        return Ok((ty, Mutability::Mutable));
                     }
-                    builtin_type::VOID
+                };
+                let span_id = var_stack.add_span(self.span_of(ep));
+                bytecode.push(Bytecode::LValueVar(var_id, span_id));
+
+                let var = &var_stack.vars[var_id];
+                let mutability = if var.mutable {
+                    Mutability::Mutable
                 } else {
-                    unimplemented!(
-                        "Mismatched return types: {} and {}",
-                        self.printable_name(actual_return_type),
-                        self.printable_name(expected_return_type)
-                    );
-                }
+                    Mutability::ImmutableVar(var_id)
+                };
+                Ok((var.ty, mutability))
             }
-            Expr::Lit(el) => match el.lit {
-                Lit::Int(ref li) => match li.suffix() {
-                    IntSuffix::U64 => {
-                        bytecode.push(Bytecode::PushU64(li.value()));
-                        builtin_type::U64
-                    }
-                    IntSuffix::U32 => {
-                        bytecode.push(Bytecode::PushU32(li.value() as u32));
-                        builtin_type::U32
-                    }
-                    IntSuffix::I64 => {
-                        bytecode.push(Bytecode::PushI64(li.value() as i64));
-                        builtin_type::I64
-                    }
-                    IntSuffix::I32 => {
-                        bytecode.push(Bytecode::PushI32(li.value() as i32));
-                        builtin_type::I32
-                    }
-                    _ => {
-                        bytecode.push(Bytecode::PushUnknownInt(li.value() as i32));
-                        builtin_type::UNKNOWN_INT
-                    }
-                },
-                Lit::Bool(ref lb) => {
-                    bytecode.push(Bytecode::PushBool(lb.value));
-                    builtin_type::BOOL
-                }
-                _ => unimplemented!("unknown literal: {:?}", el),
-            },
-            Expr::Paren(ep) => self.convert_expr_to_bytecode(
-                &*ep.expr,
-                expected_return_type,
-                bytecode,
-                current_scope_id,
-                var_stack,
-            ),
-            Expr::Block(eb) => self.convert_block_to_bytecode(
-                &eb.block,
-                expected_return_type,
-                bytecode,
-                Some(current_scope_id),
-                var_stack,
-            ),
-            Expr::Assign(ea) => {
-                let rhs_type = self.convert_expr_to_bytecode(
-                    &*ea.right,
+            Expr::Field(ef) => {
+                let (ty, mutability) = self.convert_lvalue_to_bytecode(
+                    &*ef.base,
                     expected_return_type,
                     bytecode,
                     current_scope_id,
                     var_stack,
-                );
+                )?;
+                let (ty, mutability) = self.auto_deref_place(ty, mutability, bytecode);
 
-                let lhs_type = self.convert_lhs_expr_to_bytecode(
-                    &*ea.left,
+                let (field_name, field_ty) = self.field_type(ef, ty, current_scope_id);
+                bytecode.push(Bytecode::LValueDot(field_name));
+                Ok((field_ty, mutability))
+            }
+            Expr::Index(ei) => {
+                let (ty, mutability) = self.convert_lvalue_to_bytecode(
+                    &ei.expr,
                     expected_return_type,
                     bytecode,
                     current_scope_id,
                     var_stack,
-                );
-
-                if self.assignment_compatible(lhs_type, rhs_type) {
-                    let tighter_type = self.tighter_of_types(lhs_type, rhs_type);
-                    match bytecode.last() {
-                        Some(Bytecode::LValueVar(var_id)) => {
-                            var_stack.vars[*var_id].ty = tighter_type;
-                        }
-                        _ => {}
-                    }
-                } else {
-                    unimplemented!("Assignment between {:?} and {:?}", lhs_type, rhs_type);
-                }
-
-                bytecode.push(Bytecode::Assign);
-
-                builtin_type::VOID
-            }
-            Expr::If(ei) => {
-                let cond_type = self.convert_expr_to_bytecode(
-                    &*ei.cond,
+                )?;
+                let (ty, mutability) = self.auto_deref_place(ty, mutability, bytecode);
+                let index_ty = self.convert_expr_expecting(
+                    &ei.index,
+                    builtin_type::USIZE,
                     expected_return_type,
                     bytecode,
                     current_scope_id,
                     var_stack,
-                );
-
-                match cond_type {
-                    builtin_type::BOOL => {}
-                    _ => unimplemented!("If condition needs to be boolean"),
-                }
-
-                bytecode.push(Bytecode::If(0, builtin_type::VOID));
-                let before_then_block_len = bytecode.len();
+                )?;
 
-                let then_ty = self.convert_block_to_bytecode(
-                    &ei.then_branch,
+                let element_ty = self.element_type(ei, ty, index_ty);
+                bytecode.push(Bytecode::LValueIndex(ty));
+                Ok((element_ty, mutability))
+            }
+            Expr::Unary(eu) if is_deref(eu) => {
+                // The place a reference points at is the reference itself
+                let (ty, mutability) = self.convert_place_expr_to_bytecode(
+                    &eu.expr,
                     expected_return_type,
                     bytecode,
-                    Some(current_scope_id),
+                    current_scope_id,
                     var_stack,
-                );
-                let after_then_block_len = bytecode.len();
-
-                if let Some(ref else_branch) = ei.else_branch {
-                    bytecode.push(Bytecode::Else(0, builtin_type::VOID));
-                    match *else_branch.1 {
-                        Expr::Block(ref eb) => {
-                            let else_ty = self.convert_block_to_bytecode(
-                                &eb.block,
-                                expected_return_type,
-                                bytecode,
-                                Some(current_scope_id),
-                                var_stack,
-                            );
+                )?;
+                bytecode.push(Bytecode::Deref);
+                Ok((self.deref_type(eu, ty), self.pointee_mutability(ty, mutability)))
+            }
+            Expr::Paren(ep) => self.convert_lvalue_to_bytecode(
+                &ep.expr,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                var_stack,
+            ),
+            _ => {
+                let ty = self.report(Diagnostic::new(
+                    "E0070",
+                    "Invalid left-hand side of assignment".to_string(),
+                ).with_primary(self.span_of(expr), "cannot assign to this expression"));
+                Ok((ty, Mutability::Mutable))
+            }
+        }
+    }
 
-                            if then_ty != else_ty {
-                                unimplemented!("If then/else blocks have mismatching types");
-                            }
-                            bytecode[after_then_block_len] = Bytecode::Else(
-                                bytecode.len() - after_then_block_len,
-                                else_ty.clone(),
-                            );
-                        }
-                        _ => unimplemented!("Unsupported else block"),
-                    }
+    /// Reports an error if a place is being changed but wasn't declared as something that can be
+    fn check_mutable(
+        &mut self,
+        expr: &Expr,
+        place: &str,
+        mutation: Mutation,
+        mutability: Mutability,
+        var_stack: &VarStack,
+    ) {
+        // A closure that changes a variable it captures has to borrow it mutably
+        if let Some(var_id) = place_root(expr).and_then(|ident| var_stack.find_var(&ident)) {
+            if let Some(mutated) = self.captures_mutated.last_mut() {
+                if var_id < mutated.len() {
+                    mutated[var_id] = true;
                 }
-                bytecode.push(Bytecode::EndIf(then_ty));
-
-                // Patch the original offset to the correct offset
-                bytecode[before_then_block_len - 1] = Bytecode::If(
-                    after_then_block_len - before_then_block_len + 2,
-                    then_ty.clone(),
-                );
+            }
+        }
 
-                then_ty
+        // A variable declared without a value can still be given one, which the borrow checker tracks
+        if let (Mutability::ImmutableVar(_), Mutation::Assign) = (mutability, mutation) {
+            if is_var_path(expr) {
+                return;
             }
-            Expr::While(ew) => {
-                let before_cond_len = bytecode.len();
-                bytecode.push(Bytecode::BeginWhile);
+        }
+        if let Some(diagnostic) = self.mutability_error(self.span_of(expr), place, mutation, mutability, var_stack) {
+            self.report(diagnostic);
+        }
+    }
 
-                let cond_type = self.convert_expr_to_bytecode(
-                    &*ew.cond,
-                    expected_return_type,
-                    bytecode,
-                    current_scope_id,
-                    var_stack,
-                );
+    /// Reports an error if the place a pattern matches can't be borrowed mutably, as a `ref mut` binding does
+    /// `access` is the bytecode that pushes the value being matched
+    fn check_access_mutable(&mut self, access: &[Bytecode], span: SourceSpan, var_stack: &VarStack) {
+        let var_id = match access.first() {
+            Some(Bytecode::Var(var_id, _)) => *var_id,
+            _ => return,
+        };
+
+        // A closure that borrows a variable it captures mutably has to borrow it mutably itself
+        if let Some(mutated) = self.captures_mutated.last_mut() {
+            if var_id < mutated.len() {
+                mutated[var_id] = true;
+            }
+        }
 
-                if cond_type != builtin_type::BOOL {
-                    unimplemented!("While condition needs to be boolean");
+        let var = &var_stack.vars[var_id];
+        let mut ty = var.ty;
+        let mut mutability = if var.mutable {
+            Mutability::Mutable
+        } else {
+            Mutability::ImmutableVar(var_id)
+        };
+        let mut place = var.ident.clone();
+        for code in &access[1..] {
+            match code {
+                Bytecode::Deref => {
+                    mutability = self.pointee_mutability(ty, mutability);
+                    ty = self.pointee(ty).unwrap_or(builtin_type::ERROR);
+                    place = format!("*{}", place);
                 }
+                Bytecode::Dot(field) => place = format!("{}.{}", place, field),
+                Bytecode::VariantField(_, index) => place = format!("{}.{}", place, index),
+                _ => {}
+            }
+        }
 
-                bytecode.push(Bytecode::WhileCond(0));
-                let before_block_len = bytecode.len();
+        if let Some(diagnostic) =
+            self.mutability_error(span, &place, Mutation::MutableBorrow, mutability, var_stack)
+        {
+            self.report(diagnostic);
+        }
+    }
 
-                let while_ty = self.convert_block_to_bytecode(
-                    &ew.body,
-                    expected_return_type,
-                    bytecode,
-                    Some(current_scope_id),
-                    var_stack,
-                );
+    /// The error for changing a place that can't be, if it can't
+    fn mutability_error(
+        &self,
+        span: SourceSpan,
+        place: &str,
+        mutation: Mutation,
+        mutability: Mutability,
+        var_stack: &VarStack,
+    ) -> Option<Diagnostic> {
+        let diagnostic = match (mutability, mutation) {
+            (Mutability::Mutable, _) => return None,
+            (Mutability::ImmutableVar(var_id), mutation) => {
+                let ident = &var_stack.vars[var_id].ident;
+                let (code, action, label) = match mutation {
+                    Mutation::Assign => ("E0594", format!("assign to `{}`", place), "cannot assign"),
+                    Mutation::MutableBorrow => (
+                        "E0596",
+                        format!("borrow `{}` as mutable", place),
+                        "cannot borrow as mutable",
+                    ),
+                };
+                let owner = if ident == place {
+                    "it".to_string()
+                } else {
+                    format!("`{}`", ident)
+                };
+                Diagnostic::new(
+                    code,
+                    format!("Cannot {}, as {} is not declared as mutable", action, owner),
+                ).with_primary(span, label)
+                    .with_note(&format!("consider changing this to be mutable: `mut {}`", ident))
+            }
+            (Mutability::BehindSharedRef, Mutation::Assign) => Diagnostic::new(
+                "E0594",
+                format!("Cannot assign to `{}`, which is behind a `&` reference", place),
+            ).with_primary(span, "cannot assign through a `&` reference")
+                .with_note("consider changing it to a `&mut` reference"),
+            (Mutability::BehindSharedRef, Mutation::MutableBorrow) => Diagnostic::new(
+                "E0596",
+                format!("Cannot borrow `{}` as mutable, as it is behind a `&` reference", place),
+            ).with_primary(span, "cannot borrow as mutable")
+                .with_note("consider changing it to a `&mut` reference"),
+        };
+        Some(diagnostic)
+    }
 
-                let after_block_len = bytecode.len();
-                bytecode.push(Bytecode::EndWhile(after_block_len - before_cond_len));
+    /// Checks the types of the operands of an arithmetic, bitwise or shift operator, including the compound
+    /// assignment ones like `+=`.  Returns the bytecode for the operator and the type of its result
+    fn binary_operator(
+        &mut self,
+        op: &BinOp,
+        lhs: (&Expr, DefinitionId),
+        rhs: (&Expr, DefinitionId),
+    ) -> (Bytecode, DefinitionId) {
+        let (lhs, lhs_type) = lhs;
+        let (rhs, rhs_type) = rhs;
+        let ty = self.tighter_of_types(lhs_type, rhs_type);
 
-                // Patch the original offset to the correct offset
-                bytecode[before_block_len - 1] =
-                    Bytecode::WhileCond(after_block_len - before_block_len + 1);
+        let (verb, code) = match op {
+            BinOp::Add(_) | BinOp::AddEq(_) => ("add", Bytecode::Add(ty)),
+            BinOp::Sub(_) | BinOp::SubEq(_) => ("subtract", Bytecode::Sub(ty)),
+            BinOp::Mul(_) | BinOp::MulEq(_) => ("multiply", Bytecode::Mul(ty)),
+            BinOp::Div(_) | BinOp::DivEq(_) => ("divide", Bytecode::Div(ty)),
+            BinOp::Rem(_) | BinOp::RemEq(_) => ("calculate the remainder of", Bytecode::Rem(ty)),
+            BinOp::BitAnd(_) | BinOp::BitAndEq(_) => ("and", Bytecode::BitAnd),
+            BinOp::BitOr(_) | BinOp::BitOrEq(_) => ("or", Bytecode::BitOr),
+            BinOp::BitXor(_) | BinOp::BitXorEq(_) => ("xor", Bytecode::BitXor),
+            BinOp::Shl(_) | BinOp::ShlEq(_) => ("shift", Bytecode::Shl(lhs_type)),
+            BinOp::Shr(_) | BinOp::ShrEq(_) => ("shift", Bytecode::Shr(lhs_type)),
+            _ => unimplemented!("Unknown operator: {:?}", op),
+        };
 
-                while_ty
+        let compatible = match code {
+            Bytecode::Rem(_) => {
+                self.integer_operator_compatible(lhs_type, rhs_type)
+                    || self.float_operator_compatible(lhs_type, rhs_type)
             }
-            Expr::Unary(eu) => match eu.op {
-                UnOp::Neg(_a) => {
-                    let expr_type = self.convert_expr_to_bytecode(
-                        &*eu.expr,
-                        expected_return_type,
-                        bytecode,
-                        current_scope_id,
-                        var_stack,
-                    );
-
-                    match expr_type {
-                        builtin_type::U32 => {
-                            unimplemented!("Can't negate value of type u32");
-                        }
-                        builtin_type::U64 => {
-                            unimplemented!("Can't negate value of type u64");
-                        }
-                        builtin_type::UNKNOWN_INT => {
-                            bytecode.push(Bytecode::Neg);
-                            builtin_type::UNKNOWN_INT
-                        }
-                        builtin_type::I32 => {
-                            bytecode.push(Bytecode::Neg);
-                            builtin_type::I32
-                        }
-                        builtin_type::I64 => {
-                            bytecode.push(Bytecode::Neg);
-                            builtin_type::I64
-                        }
-                        _ => unimplemented!("Negate of non-numeric type"),
-                    }
-                }
-                _ => unimplemented!("Unsupport unary operator"),
-            },
-            Expr::Binary(eb) => match eb.op {
-                BinOp::Add(_a) => {
-                    let lhs_type = self.convert_expr_to_bytecode(
-                        &*eb.left,
-                        expected_return_type,
-                        bytecode,
-                        current_scope_id,
-                        var_stack,
-                    );
-                    let rhs_type = self.convert_expr_to_bytecode(
-                        &*eb.right,
-                        expected_return_type,
-                        bytecode,
-                        current_scope_id,
-                        var_stack,
-                    );
-                    if self.operator_compatible(lhs_type, rhs_type) {
-                        bytecode.push(Bytecode::Add);
-                        self.tighter_of_types(lhs_type, rhs_type)
-                    } else {
-                        unimplemented!(
-                            "Can't add values of {:?} and {:?}",
-                            self.printable_name(lhs_type),
-                            self.printable_name(rhs_type)
-                        );
-                    }
-                }
-                BinOp::Sub(_a) => {
-                    let lhs_type = self.convert_expr_to_bytecode(
-                        &*eb.left,
-                        expected_return_type,
-                        bytecode,
-                        current_scope_id,
-                        var_stack,
-                    );
-                    let rhs_type = self.convert_expr_to_bytecode(
-                        &*eb.right,
-                        expected_return_type,
-                        bytecode,
-                        current_scope_id,
-                        var_stack,
-                    );
-                    if self.operator_compatible(lhs_type, rhs_type) {
-                        bytecode.push(Bytecode::Sub);
-                        self.tighter_of_types(lhs_type, rhs_type)
-                    } else {
-                        unimplemented!(
-                            "Can't subtract values of {:?} and {:?}",
-                            lhs_type,
-                            rhs_type
-                        );
-                    }
-                }
-                BinOp::Mul(_a) => {
-                    let lhs_type = self.convert_expr_to_bytecode(
-                        &*eb.left,
-                        expected_return_type,
-                        bytecode,
-                        current_scope_id,
-                        var_stack,
-                    );
-                    let rhs_type = self.convert_expr_to_bytecode(
-                        &*eb.right,
-                        expected_return_type,
-                        bytecode,
-                        current_scope_id,
-                        var_stack,
-                    );
-                    if self.operator_compatible(lhs_type, rhs_type) {
-                        bytecode.push(Bytecode::Mul);
-                        self.tighter_of_types(lhs_type, rhs_type)
-                    } else {
-                        unimplemented!(
-                            "Can't multiply values of {:?} and {:?}",
-                            lhs_type,
-                            rhs_type
-                        );
-                    }
-                }
-                BinOp::Div(_a) => {
-                    let lhs_type = self.convert_expr_to_bytecode(
-                        &*eb.left,
-                        expected_return_type,
-                        bytecode,
-                        current_scope_id,
-                        var_stack,
-                    );
-                    let rhs_type = self.convert_expr_to_bytecode(
-                        &*eb.right,
-                        expected_return_type,
-                        bytecode,
-                        current_scope_id,
-                        var_stack,
-                    );
-                    if self.operator_compatible(lhs_type, rhs_type) {
-                        bytecode.push(Bytecode::Div);
-                        self.tighter_of_types(lhs_type, rhs_type)
-                    } else {
-                        unimplemented!("Can't divide values of {:?} and {:?}", lhs_type, rhs_type);
-                    }
-                }
-                BinOp::Lt(_a) => {
-                    let lhs_type = self.convert_expr_to_bytecode(
-                        &*eb.left,
-                        expected_return_type,
-                        bytecode,
-                        current_scope_id,
-                        var_stack,
-                    );
-                    let rhs_type = self.convert_expr_to_bytecode(
-                        &*eb.right,
-                        expected_return_type,
-                        bytecode,
-                        current_scope_id,
-                        var_stack,
-                    );
+            Bytecode::BitAnd | Bytecode::BitOr | Bytecode::BitXor => self.bitwise_compatible(lhs_type, rhs_type),
+            Bytecode::Shl(_) | Bytecode::Shr(_) => self.shift_compatible(lhs_type, rhs_type),
+            _ => self.operator_compatible(lhs_type, rhs_type),
+        };
 
-                    if self.operator_compatible(lhs_type, rhs_type) {
-                        bytecode.push(Bytecode::Lt);
-                        builtin_type::BOOL
-                    } else {
-                        unimplemented!("Can't compare values of {:?} and {:?}", lhs_type, rhs_type);
-                    }
-                }
-                _ => unimplemented!("Unknown operator: {:?}", eb.op),
-            },
-            Expr::Struct(es) => {
-                let mut fields = vec![];
-                for field in &es.fields {
-                    match field.member {
-                        Member::Named(name) => {
-                            fields.push((name.to_string(), &field.expr));
-                        }
-                        _ => unimplemented!("Unnamed struct members not yet supported"),
-                    }
-                }
+        // A shift gives a value of the type being shifted, whatever the type of the amount
+        let ty = match code {
+            Bytecode::Shl(_) | Bytecode::Shr(_) => lhs_type,
+            _ => ty,
+        };
 
-                //TODO: FIXME: would be great to not have to clone here
-                fields.sort_by_key(|x| x.0.clone());
+        if compatible {
+            (code, ty)
+        } else {
+            self.report(Diagnostic::new(
+                "E0369",
+                format!(
+                    "Can't {} values of {} and {}",
+                    verb,
+                    self.printable_name(lhs_type),
+                    self.printable_name(rhs_type)
+                ),
+            ).with_primary(self.span_of(op), "incompatible operand types")
+                .with_secondary(self.span_of(lhs), &self.printable_name(lhs_type))
+                .with_secondary(self.span_of(rhs), &self.printable_name(rhs_type)));
+            (code, builtin_type::ERROR)
+        }
+    }
 
-                for field in fields {
-                    self.convert_expr_to_bytecode(
-                        field.1,
-                        expected_return_type,
-                        bytecode,
-                        current_scope_id,
-                        var_stack,
-                    );
-                }
-                if let Some(definition_id) = self.process_path(&es.path, current_scope_id) {
-                    if let Definition::Struct(_) = self.definitions[definition_id] {
-                        bytecode.push(Bytecode::Call(definition_id));
-                        definition_id
-                    } else {
-                        unimplemented!("Unsupport definition type in struct call");
-                    }
-                } else {
-                    unimplemented!("Can't process struct");
+    /// Finds how many loops out a `break` or `continue` goes, which is to the innermost loop unless it has a label.
+    /// Reports it if there's no such loop
+    fn loop_target(&mut self, label: &Option<Lifetime>, keyword: &str, span: SourceSpan) -> Option<usize> {
+        let found = match label {
+            Some(label) => {
+                let name = label.to_string();
+                let found = self.loops.iter().rev().position(|l| l.label.as_ref() == Some(&name));
+                if found.is_none() {
+                    self.report(Diagnostic::new(
+                        "E0426",
+                        format!("Use of undeclared label `{}`", name),
+                    ).with_primary(span, &format!("undeclared label `{}`", name)));
+                    return None;
                 }
+                found
             }
-            Expr::Path(ep) => {
-                if let Some(definition_id) = self.process_path(&ep.path, current_scope_id) {
-                    if let Definition::Struct(_) = self.definitions[definition_id] {
-                        bytecode.push(Bytecode::Call(definition_id));
-                        definition_id
-                    } else {
-                        unimplemented!("Unsupport definition type in struct call");
-                    }
-                } else {
-                    let ident = ep.path.segments[0].ident.to_string();
-                    let var_id = var_stack.find_var(&ident);
-                    if var_id.is_none() {
-                        if ident == "NULL" {
-                            bytecode.push(Bytecode::PushRawPtr(ptr::null()));
-                            builtin_type::VOID_PTR
-                        } else {
-                            unimplemented!("Could not find {}", ident);
-                        }
-                    } else {
-                        let var_id = var_id.unwrap();
-                        let var = &var_stack.vars[var_id];
+            None => if self.loops.is_empty() {
+                None
+            } else {
+                Some(0)
+            },
+        };
+        if found.is_none() {
+            self.report(Diagnostic::new(
+                "E0268",
+                format!("`{}` outside of a loop", keyword),
+            ).with_primary(span, &format!("cannot `{}` outside of a loop", keyword)));
+        }
+        found
+    }
 
-                        if var.ty == builtin_type::UNKNOWN {
-                            unimplemented!("{} used before being given a value", ident);
-                        }
+    /// Finds an arithmetic method of an integer type that handles overflow, like `wrapping_add` or `checked_shl`.
+    /// Gives the call along with the signature the method has in Rust, where only the methods Rust has are found:
+    /// there's no `saturating_rem` or `saturating_shl`, and only a signed integer has `saturating_neg`
+    fn integer_method(
+        &mut self,
+        self_ty: DefinitionId,
+        method_name: &str,
+    ) -> Option<(Bytecode, Vec<Param>, DefinitionId, bool)> {
+        // The method is called on an integer without a type as if it were an i32
+        let ty = builtin_type::fallback(self_ty);
+        let (_, signed) = builtin_type::int_layout(ty)?;
 
-                        bytecode.push(Bytecode::Var(var_id));
+        let mut parts = method_name.splitn(2, '_');
+        let overflow = match parts.next() {
+            Some("wrapping") => Overflow::Wrapping,
+            Some("checked") => Overflow::Checked,
+            Some("saturating") => Overflow::Saturating,
+            _ => return None,
+        };
+        let op = match (parts.next(), overflow) {
+            (Some("add"), _) => Bytecode::Add(ty),
+            (Some("sub"), _) => Bytecode::Sub(ty),
+            (Some("mul"), _) => Bytecode::Mul(ty),
+            (Some("div"), _) => Bytecode::Div(ty),
+            (Some("rem"), Overflow::Wrapping | Overflow::Checked) => Bytecode::Rem(ty),
+            (Some("neg"), Overflow::Wrapping | Overflow::Checked) => Bytecode::Neg(ty),
+            (Some("neg"), Overflow::Saturating) if signed => Bytecode::Neg(ty),
+            (Some("shl"), Overflow::Wrapping | Overflow::Checked) => Bytecode::Shl(ty),
+            (Some("shr"), Overflow::Wrapping | Overflow::Checked) => Bytecode::Shr(ty),
+            _ => return None,
+        };
+        let return_ty = match overflow {
+            Overflow::Checked => self.option_type(ty),
+            _ => ty,
+        };
 
-                        var.ty
-                    }
-                }
+        // Negating takes no argument, and the amount to shift by is a u32 whatever the type being shifted
+        let mut params = vec![Param::new("self".to_string(), 0, ty, false)];
+        match op {
+            Bytecode::Neg(_) => {}
+            Bytecode::Shl(_) | Bytecode::Shr(_) => {
+                params.push(Param::new("rhs".to_string(), 1, builtin_type::U32, false));
             }
-            Expr::Call(ec) => match *ec.func {
-                Expr::Path(ref ep) => {
-                    if ep.path.segments.len() == 1 && ep.path.segments[0].ident == "__debug__" {
-                        let definition_id = self.convert_expr_to_bytecode(
-                            &ec.args[0],
-                            expected_return_type,
-                            bytecode,
-                            current_scope_id,
-                            var_stack,
-                        );
-                        bytecode.push(Bytecode::DebugPrint(definition_id));
-                        builtin_type::VOID
-                    } else {
-                        // If we're in a single ident path, check values in scope
-                        if ep.path.segments.len() == 1 && ep.path.leading_colon.is_none() {
-                            let ident = ep.path.segments[0].ident;
-                            let var_result = var_stack.find_var(ident.as_ref());
-                            if let Some(var_id) = var_result {
-                                //TODO: FIXME: in the future check this for lambda
-                                unimplemented!(
-                                    "Can not call function on type {:?}",
-                                    var_stack.vars[var_id].ty
-                                );
-                            }
-                        }
+            _ => params.push(Param::new("rhs".to_string(), 1, ty, false)),
+        }
+        Some((
+            Bytecode::OverflowingOp(Box::new(op), overflow, return_ty),
+            params,
+            return_ty,
+            false,
+        ))
+    }
 
-                        let definition_id = self.process_path(&ep.path, current_scope_id);
+    /// Converts a comparison between two numbers or two bools, which gives a bool
+    fn convert_comparison_to_bytecode(
+        &mut self,
+        eb: &ExprBinary,
+        comparison: Bytecode,
+        expected_return_type: DefinitionId,
+        bytecode: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<DefinitionId, Diagnostic> {
+        let (lhs_type, rhs_type) = self.convert_operands_to_bytecode(
+            eb,
+            "compare",
+            expected_return_type,
+            bytecode,
+            current_scope_id,
+            var_stack,
+        )?;
 
-                        if definition_id.is_none() {
-                            unimplemented!("Could not find call for {:?}", ep.path);
-                        }
+        let operand_type = self.tighter_of_types(lhs_type, rhs_type);
+        if operand_type != builtin_type::BOOL
+            && operand_type != builtin_type::ERROR
+            && int_bounds(operand_type).is_none()
+            && !builtin_type::is_float(operand_type)
+        {
+            self.report(Diagnostic::new(
+                "E0369",
+                format!(
+                    "Can't compare values of type {}",
+                    self.printable_name(operand_type)
+                ),
+            ).with_primary(self.span_of(&eb.op), "no comparison for this type"));
+        }
 
-                        //TODO: FIXME: please don't do this
-                        let definition_id = definition_id.unwrap();
+        bytecode.push(comparison);
+        Ok(builtin_type::BOOL)
+    }
 
-                        if let Definition::Fun(ref target_fn) = self.definitions[definition_id] {
-                            if target_fn.ty_params.len() > 0 {
-                                let mut arg_tys: Vec<DefinitionId> = vec![];
+    /// Converts an arithmetic, bitwise or shift operator.  The operands take the number type expected of the result,
+    /// or else the rhs takes the type of the lhs, so that numbers without a type are checked at the width they'll
+    /// have.  The amount of a shift can be any integer, so it's left to its own type
+    fn convert_arithmetic_to_bytecode(
+        &mut self,
+        eb: &ExprBinary,
+        expected_ty: DefinitionId,
+        expected_return_type: DefinitionId,
+        bytecode: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<DefinitionId, Diagnostic> {
+        let is_number = |ty| {
+            ty != builtin_type::UNKNOWN_INT && ty != builtin_type::UNKNOWN_FLOAT
+                && (int_bounds(ty).is_some() || builtin_type::is_float(ty))
+        };
+        let operand_ty = if is_number(expected_ty) { expected_ty } else { builtin_type::UNKNOWN };
 
-                                for arg in &ec.args {
-                                    let arg_ty = self.convert_expr_to_bytecode(
-                                        arg,
-                                        expected_return_type,
-                                        bytecode,
-                                        current_scope_id,
-                                        var_stack,
-                                    );
-                                    arg_tys.push(arg_ty);
-                                }
+        let lhs_type = self.convert_expr_expecting(
+            &eb.left,
+            operand_ty,
+            expected_return_type,
+            bytecode,
+            current_scope_id,
+            var_stack,
+        )?;
+        let rhs_expected_ty = match eb.op {
+            BinOp::Shl(_) | BinOp::Shr(_) => builtin_type::UNKNOWN,
+            _ if operand_ty == builtin_type::UNKNOWN && is_number(lhs_type) => lhs_type,
+            _ => operand_ty,
+        };
+        let rhs_type = self.convert_expr_expecting(
+            &eb.right,
+            rhs_expected_ty,
+            expected_return_type,
+            bytecode,
+            current_scope_id,
+            var_stack,
+        )?;
 
-                                //TODO: FIXME: probably not the best scope id for this
-                                let (instance_definition_id, return_ty) = self.instantiate_generic_fn(
-                                    definition_id,
-                                    current_scope_id,
-                                    &arg_tys,
-                                );
+        let (op, ty) = self.binary_operator(&eb.op, (&eb.left, lhs_type), (&eb.right, rhs_type));
+        bytecode.push(op);
+        Ok(ty)
+    }
 
-                                bytecode.push(Bytecode::Call(instance_definition_id));
+    /// Converts `&&` or `||`, where the rhs is only run if the lhs doesn't already decide the result
+    fn convert_logical_to_bytecode(
+        &mut self,
+        eb: &ExprBinary,
+        expected_return_type: DefinitionId,
+        bytecode: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<DefinitionId, Diagnostic> {
+        let is_and = matches!(eb.op, BinOp::And(_));
 
-                                return_ty
-                            } else {
-                                let return_ty = target_fn.return_ty;
+        let lhs_type = self.convert_expr_to_bytecode(
+            &eb.left,
+            expected_return_type,
+            bytecode,
+            current_scope_id,
+            var_stack,
+        )?;
+        self.check_logical_operand(eb, &eb.left, lhs_type);
 
-                                for arg in &ec.args {
-                                    self.convert_expr_to_bytecode(
-                                        arg,
-                                        expected_return_type,
-                                        bytecode,
-                                        current_scope_id,
-                                        var_stack,
-                                    );
-                                }
+        // `a && b` is `if a { b } else { false }` and `a || b` is `if a { true } else { b }`
+        let if_pos = bytecode.len();
+        bytecode.push(Bytecode::If(0, builtin_type::BOOL));
+        if !is_and {
+            bytecode.push(Bytecode::PushBool(true));
+            bytecode.push(Bytecode::Else(0, builtin_type::BOOL));
+        }
 
-                                bytecode.push(Bytecode::Call(definition_id));
+        let rhs_type = self.convert_expr_to_bytecode(
+            &eb.right,
+            expected_return_type,
+            bytecode,
+            current_scope_id,
+            var_stack,
+        )?;
+        self.check_logical_operand(eb, &eb.right, rhs_type);
 
-                                return_ty
-                            }
-                        } else {
-                            unimplemented!(
-                                "Processed function {:?} did not process correctly",
-                                ep.path
-                            );
-                        }
+        if is_and {
+            let else_pos = bytecode.len();
+            bytecode.push(Bytecode::Else(2, builtin_type::BOOL));
+            bytecode.push(Bytecode::PushBool(false));
+            bytecode[if_pos] = Bytecode::If(else_pos - if_pos + 1, builtin_type::BOOL);
+        } else {
+            let else_pos = if_pos + 2;
+            bytecode[if_pos] = Bytecode::If(else_pos - if_pos + 1, builtin_type::BOOL);
+            bytecode[else_pos] = Bytecode::Else(bytecode.len() - else_pos, builtin_type::BOOL);
+        }
+        bytecode.push(Bytecode::EndIf(builtin_type::BOOL));
+
+        Ok(builtin_type::BOOL)
+    }
+
+    /// Reports an error if an operand of `&&` or `||` isn't a bool
+    fn check_logical_operand(&mut self, eb: &ExprBinary, operand: &Expr, ty: DefinitionId) {
+        if ty != builtin_type::BOOL && ty != builtin_type::ERROR {
+            let op = match eb.op {
+                BinOp::And(_) => "&&",
+                _ => "||",
+            };
+            self.report(Diagnostic::new(
+                "E0308",
+                format!("Operands of `{}` need to be boolean", op),
+            ).with_primary(
+                self.span_of(operand),
+                &format!("expected bool, found {}", self.printable_name(ty)),
+            ));
+        }
+    }
+
+    /// Finds the name and type of the field being accessed on a struct or tuple type, reporting an error if
+    /// there's no such field, or it can't be used from here.  Unnamed fields, like `.0`, are named after their
+    /// position
+    fn field_type(
+        &mut self,
+        ef: &syn::ExprField,
+        ty: DefinitionId,
+        current_scope_id: ScopeId,
+    ) -> (String, DefinitionId) {
+        let field_name = member_name(&ef.member);
+        if ty == builtin_type::ERROR {
+            return (field_name, builtin_type::ERROR);
+        }
+        self.check_field_visible("E0616", ty, &field_name, self.span_of(&ef.member), current_scope_id);
+
+        let field_ty = match self.definitions[ty] {
+            Definition::Struct(ref st) => Some(
+                st.fields
+                    .iter()
+                    .find(|field| field.0 == field_name)
+                    .map(|field| field.1),
+            ),
+            Definition::Tuple(ref element_tys) => Some(
+                field_name
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| element_tys.get(index).cloned()),
+            ),
+            _ => None,
+        };
+
+        let diagnostic = match field_ty {
+            Some(Some(field_ty)) => return (field_name, field_ty),
+            Some(None) => Diagnostic::new(
+                "E0609",
+                format!(
+                    "Field access of {} not found on type {}",
+                    field_name,
+                    self.printable_name(ty)
+                ),
+            ).with_primary(self.span_of(&ef.member), "unknown field"),
+            None => Diagnostic::new(
+                "E0610",
+                format!(
+                    "Member access on non-struct type {}",
+                    self.printable_name(ty)
+                ),
+            ).with_primary(self.span_of(&ef.member), "unknown field")
+                .with_secondary(
+                    self.span_of(&*ef.base),
+                    &format!("this has type {}", self.printable_name(ty)),
+                ),
+        };
+
+        (field_name, self.report(diagnostic))
+    }
+
+    /// Checks if the definition is an enum variant with named fields, which has already been reported as unsupported
+    fn has_named_fields(&self, definition_id: DefinitionId) -> bool {
+        match self.definitions[definition_id] {
+            Definition::Variant(ref variant) => variant.has_named_fields,
+            _ => false,
+        }
+    }
+
+    /// Reports an error if the named field of a struct can't be used from the given scope
+    fn check_field_visible(
+        &mut self,
+        code: &'static str,
+        struct_id: DefinitionId,
+        field_name: &str,
+        span: SourceSpan,
+        current_scope_id: ScopeId,
+    ) {
+        let visibility = match self.definitions[struct_id] {
+            Definition::Struct(ref st) => match st.field_visibility.get(field_name) {
+                Some(visibility) => *visibility,
+                None => return,
+            },
+            _ => return,
+        };
+
+        if !self.is_visible(visibility, current_scope_id) {
+            self.report(Diagnostic::new(
+                code,
+                format!(
+                    "Field `{}` of struct `{}` is private",
+                    field_name,
+                    self.printable_name(struct_id)
+                ),
+            ).with_primary(span, "private field"));
+        }
+    }
+
+    /// Reports an error if a tuple struct can't be built or matched from the given scope, which is the case when any
+    /// of its fields can't be used there
+    fn check_constructor_visible(&mut self, struct_id: DefinitionId, path: &syn::Path, current_scope_id: ScopeId) {
+        let private = match self.definitions[struct_id] {
+            Definition::Struct(ref st) => st
+                .fields
+                .iter()
+                .any(|field| !self.is_visible(st.field_visibility[&field.0], current_scope_id)),
+            _ => false,
+        };
+
+        if private {
+            let ident = &path.segments[path.segments.len() - 1].ident;
+            self.report(Diagnostic::new(
+                "E0603",
+                format!("Tuple struct constructor `{}` is private", ident),
+            ).with_primary(self.span_of(ident), "private tuple struct constructor")
+                .with_note("a constructor is private if any of the fields is private"));
+        }
+    }
+
+    /// Finds the type of the elements of an array being indexed, reporting an error if it's not an array or the
+    /// index isn't an integer
+    fn element_type(&mut self, ei: &syn::ExprIndex, ty: DefinitionId, index_ty: DefinitionId) -> DefinitionId {
+        if index_ty != builtin_type::ERROR && index_ty != builtin_type::USIZE {
+            self.report(Diagnostic::new(
+                "E0277",
+                format!(
+                    "Arrays can only be indexed by usize, found {}",
+                    self.printable_name(index_ty)
+                ),
+            ).with_primary(
+                self.span_of(&*ei.index),
+                &format!("this has type {}", self.printable_name(index_ty)),
+            ));
+        }
+
+        match self.definitions[ty] {
+            Definition::Array(element_ty, _) => element_ty,
+            _ if ty == builtin_type::ERROR => builtin_type::ERROR,
+            _ => self.report(Diagnostic::new(
+                "E0608",
+                format!("Can not index into a value of type {}", self.printable_name(ty)),
+            ).with_primary(
+                self.span_of(&*ei.expr),
+                &format!("this has type {}", self.printable_name(ty)),
+            )),
+        }
+    }
+
+    /// Follows references to the value they refer to, eg) so that `self.x` works when `self` is `&Self`
+    fn auto_deref(&self, ty: DefinitionId, bytecode: &mut Vec<Bytecode>) -> DefinitionId {
+        self.auto_deref_place(ty, Mutability::Mutable, bytecode).0
+    }
+
+    /// Follows pointers like `auto_deref`, keeping track of whether the place reached can be changed
+    fn auto_deref_place(
+        &self,
+        ty: DefinitionId,
+        mutability: Mutability,
+        bytecode: &mut Vec<Bytecode>,
+    ) -> (DefinitionId, Mutability) {
+        let mut ty = ty;
+        let mut mutability = mutability;
+        while let Some(referred_ty) = self.pointee(ty) {
+            // A trait object can only be used through its pointer
+            if let Definition::DynTrait(_) = self.definitions[referred_ty] {
+                break;
+            }
+            bytecode.push(Bytecode::Deref);
+            mutability = self.pointee_mutability(ty, mutability);
+            ty = referred_ty;
+        }
+        (ty, mutability)
+    }
+
+    /// Whether what a pointer points at can be changed, given whether the pointer's own place can be.  A Box
+    /// owns what it points at, but a `&mut` can be written through wherever it's kept, unless it's behind a `&`
+    fn pointee_mutability(&self, ty: DefinitionId, mutability: Mutability) -> Mutability {
+        match self.definitions[ty] {
+            Definition::Ref(_, true) if mutability != Mutability::BehindSharedRef => Mutability::Mutable,
+            Definition::Ref(_, _) => Mutability::BehindSharedRef,
+            _ => mutability,
+        }
+    }
+
+    /// The type pointed at, if the type is a reference or a Box
+    fn pointee(&self, ty: DefinitionId) -> Option<DefinitionId> {
+        match self.definitions[ty] {
+            Definition::Ref(referred_ty, _) | Definition::Box(referred_ty) => Some(referred_ty),
+            _ => None,
+        }
+    }
+
+    /// Finds the type a dereference gives, reporting an error if what's being dereferenced isn't a pointer
+    fn deref_type(&mut self, eu: &syn::ExprUnary, ty: DefinitionId) -> DefinitionId {
+        match self.pointee(ty) {
+            Some(referred_ty) => match self.definitions[referred_ty] {
+                Definition::DynTrait(_) => self.report(Diagnostic::new(
+                    "E0161",
+                    format!(
+                        "Can not dereference {}, as trait objects can only be used through a pointer",
+                        self.printable_name(ty)
+                    ),
+                ).with_primary(self.span_of(eu), "the size of this value is not known")),
+                _ => referred_ty,
+            },
+            None if ty == builtin_type::ERROR => builtin_type::ERROR,
+            None => self.report(Diagnostic::new(
+                "E0614",
+                format!("Type {} cannot be dereferenced", self.printable_name(ty)),
+            ).with_primary(
+                self.span_of(eu),
+                &format!("this has type {}", self.printable_name(ty)),
+            )),
+        }
+    }
+
+    /// Converts an expression that's about to be borrowed or assigned through, leaving the place it's stored
+    /// at on the stack.  Values that aren't already somewhere in memory are kept in a temporary
+    fn convert_place_expr_to_bytecode(
+        &mut self,
+        expr: &Expr,
+        expected_return_type: DefinitionId,
+        bytecode: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<(DefinitionId, Mutability), Diagnostic> {
+        if self.is_place_expr(expr, var_stack) {
+            return self.convert_lvalue_to_bytecode(
+                expr,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                var_stack,
+            );
+        }
+
+        let ty = self.convert_expr_to_bytecode(
+            expr,
+            expected_return_type,
+            bytecode,
+            current_scope_id,
+            var_stack,
+        )?;
+        let var_id = var_stack.add_var(String::new(), ty, true);
+        let span_id = var_stack.add_span(self.span_of(expr));
+        bytecode.push(Bytecode::VarDecl(var_id));
+        bytecode.push(Bytecode::LValueVar(var_id, span_id));
+        Ok((ty, Mutability::Mutable))
+    }
+
+    /// Finds the type `self` has in the method being converted
+    fn self_type(&mut self, input: &FnArg, scope_id: ScopeId) -> DefinitionId {
+        match self.get_defn("Self", scope_id) {
+            Some((self_ty, _)) => self_ty,
+            None => self.report(Diagnostic::new(
+                "E0424",
+                "`self` parameter is only allowed in methods".to_string(),
+            ).with_primary(self.span_of(input), "not allowed outside of an impl")),
+        }
+    }
+
+    /// Checks if the expression refers to a place in memory that a reference can point at
+    fn is_place_expr(&self, expr: &Expr, var_stack: &VarStack) -> bool {
+        match expr {
+            Expr::Path(ep) => {
+                ep.path.segments.len() == 1
+                    && var_stack
+                        .find_var(ep.path.segments[0].ident.as_ref())
+                        .is_some()
+            }
+            Expr::Field(ef) => self.is_place_expr(&ef.base, var_stack),
+            Expr::Index(ei) => self.is_place_expr(&ei.expr, var_stack),
+            Expr::Paren(ep) => self.is_place_expr(&ep.expr, var_stack),
+            Expr::Unary(eu) => is_deref(eu),
+            _ => false,
+        }
+    }
+
+    /// Converts the arguments to a call, checking them against the parameters of the function being called
+    /// Returns the types of the arguments
+    fn convert_args_to_bytecode(
+        &mut self,
+        args: &Punctuated<Expr, Comma>,
+        params: &[Param],
+        expected_return_type: DefinitionId,
+        bytecode: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<Vec<DefinitionId>, Diagnostic> {
+        let mut arg_tys: Vec<DefinitionId> = vec![];
+
+        for (arg, param) in args.iter().zip(params.iter()) {
+            let arg_ty = self.convert_expr_expecting(
+                arg,
+                param.ty,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                var_stack,
+            )?;
+
+            if self.has_type_variable(param.ty) {
+                // Generic parameters are checked when the function is instantiated
+            } else if !self.assignment_compatible(param.ty, arg_ty) {
+                self.report(Diagnostic::new(
+                    "E0308",
+                    format!(
+                        "Argument {} expects {}, found {}",
+                        param.name,
+                        self.printable_name(param.ty),
+                        self.printable_name(arg_ty)
+                    ),
+                ).with_primary(
+                    self.span_of(arg),
+                    &format!("expected {}", self.printable_name(param.ty)),
+                ));
+            }
+
+            arg_tys.push(arg_ty);
+        }
+
+        Ok(arg_tys)
+    }
+
+    /// Checks the values given for the fields of a generic tuple struct that use its type parameters, against the
+    /// instance they decided.  The others were checked as they were given
+    fn check_generic_args(
+        &mut self,
+        instance_id: DefinitionId,
+        field_tys: &[DefinitionId],
+        arg_tys: &[DefinitionId],
+        args: &Punctuated<Expr, Comma>,
+    ) {
+        let instance_fields = match self.definitions[instance_id] {
+            Definition::Struct(ref st) => st.fields.clone(),
+            _ => return,
+        };
+        for (((field_ty, arg_ty), arg), instance_field) in field_tys.iter().zip(arg_tys).zip(args).zip(instance_fields) {
+            if self.has_type_variable(*field_ty) && !self.assignment_compatible(instance_field.1, *arg_ty) {
+                self.report(Diagnostic::new(
+                    "E0308",
+                    format!(
+                        "Field {} expects {}, found {}",
+                        instance_field.0,
+                        self.printable_name(instance_field.1),
+                        self.printable_name(*arg_ty)
+                    ),
+                ).with_primary(
+                    self.span_of(arg),
+                    &format!("expected {}", self.printable_name(instance_field.1)),
+                ));
+            }
+        }
+    }
+
+    /// Checks that the types a generic function is called with implement the traits its type parameters are
+    /// bound by.  Returns false if any don't, after reporting them
+    /// As in Rust, an integer literal given for a bound type parameter falls back to i32
+    fn check_trait_bounds<T: Spanned>(
+        &mut self,
+        params: &[Param],
+        arg_tys: &mut [DefinitionId],
+        call: &T,
+    ) -> bool {
+        let mut satisfied = true;
+
+        for (param, arg_ty) in params.iter().zip(arg_tys.iter_mut()) {
+            let bounds = match self.definitions[param.ty] {
+                Definition::TypeVariable(_, ref bounds) if !bounds.is_empty() => bounds.clone(),
+                _ => continue,
+            };
+
+            *arg_ty = builtin_type::fallback(*arg_ty);
+
+            for trait_id in bounds {
+                if !self.implements_trait(*arg_ty, trait_id) {
+                    let diagnostic = self.unsatisfied_bound(*arg_ty, trait_id, call);
+                    self.report(diagnostic);
+                    satisfied = false;
+                }
+            }
+        }
+
+        satisfied
+    }
+
+    /// The error for a type being used where it needs to implement a trait that it doesn't
+    fn unsatisfied_bound<T: Spanned>(
+        &self,
+        ty: DefinitionId,
+        trait_id: DefinitionId,
+        node: &T,
+    ) -> Diagnostic {
+        Diagnostic::new(
+            "E0277",
+            format!(
+                "The trait bound `{}: {}` is not satisfied",
+                self.printable_name(ty),
+                self.printable_name(trait_id)
+            ),
+        ).with_primary(
+            self.span_of(node),
+            &format!(
+                "the trait `{}` is not implemented for `{}`",
+                self.printable_name(trait_id),
+                self.printable_name(ty)
+            ),
+        )
+    }
+
+    /// Finds the traits named as bounds on a type parameter
+    fn resolve_bounds(
+        &mut self,
+        bounds: &Punctuated<TypeParamBound, Add>,
+        scope_id: ScopeId,
+    ) -> Vec<DefinitionId> {
+        let mut trait_ids = vec![];
+
+        for bound in bounds {
+            if let TypeParamBound::Trait(ref trait_bound) = *bound {
+                match self.resolve_trait(&trait_bound.path, scope_id) {
+                    Ok(trait_id) => trait_ids.push(trait_id),
+                    Err(diagnostic) => {
+                        self.report(diagnostic);
                     }
                 }
-                _ => unimplemented!("unknown function call type: {:#?}", ec.func),
-            },
-            Expr::Field(ef) => {
-                let definition_id = self.convert_expr_to_bytecode(
-                    &*ef.base,
-                    expected_return_type,
-                    bytecode,
-                    current_scope_id,
-                    var_stack,
+            }
+        }
+
+        trait_ids
+    }
+
+    /// Declares the type parameters of a generic item as type variables, in a scope of their own so that they don't
+    /// clash with those of other items.  Returns that scope, or the given one if there are none
+    pub(crate) fn declare_ty_params(
+        &mut self,
+        generics: &Generics,
+        scope_id: ScopeId,
+    ) -> (ScopeId, Vec<DefinitionId>) {
+        if generics.params.is_empty() {
+            return (scope_id, vec![]);
+        }
+        self.scopes.push(Scope::new(Some(scope_id), false));
+        let scope_id = self.scopes.len() - 1;
+
+        let mut ty_params = vec![];
+        for generic_param in &generics.params {
+            if let GenericParam::Type(ref type_param) = *generic_param {
+                let bounds = self.resolve_bounds(&type_param.bounds, scope_id);
+                self.definitions.push(Definition::TypeVariable(type_param.ident.to_string(), bounds));
+                self.scopes[scope_id].definitions.insert(
+                    type_param.ident.to_string(),
+                    (self.definitions.len() - 1, Visibility::Public),
                 );
+                ty_params.push(self.definitions.len() - 1);
+            }
+        }
+        (scope_id, ty_params)
+    }
 
-                if let Definition::Struct(ref st) = self.definitions[definition_id] {
-                    match ef.member {
-                        Member::Named(ident) => {
-                            bytecode.push(Bytecode::Dot(ident.to_string()));
-                            for field in &st.fields {
-                                if field.0 == ident.as_ref() {
-                                    return field.1;
-                                }
-                            }
-                            unimplemented!("Field access of {} not found", ident);
-                        }
-                        _ => unimplemented!("Unsupported member access"),
+    /// Resolves the signature of a trait's method, with `Self` standing for the type it's called on.
+    /// Returns None if the trait has no method with that name
+    fn trait_method_signature(
+        &mut self,
+        trait_id: DefinitionId,
+        method_name: &str,
+        self_ty: DefinitionId,
+    ) -> Result<Option<(Vec<Param>, DefinitionId)>, Diagnostic> {
+        let (trait_scope_id, decl) = match self.definitions[trait_id] {
+            Definition::Trait(ref trait_def) => {
+                match trait_def.methods.iter().find(|method| method.name == method_name) {
+                    Some(method) => (trait_def.scope_id, method.decl.clone()),
+                    None => return Ok(None),
+                }
+            }
+            _ => return Ok(None),
+        };
+
+        self.scopes.push(Scope::new(Some(trait_scope_id), false));
+        let signature_scope_id = self.scopes.len() - 1;
+        self.scopes[signature_scope_id]
+            .definitions
+            .insert("Self".to_string(), (self_ty, Visibility::Public));
+
+        let mut params = vec![];
+        for input in &decl.inputs {
+            let (name, ty) = match input {
+                FnArg::Captured(ref capture) => match capture.pat {
+                    Pat::Ident(ref pi) => (
+                        pi.ident.to_string(),
+                        self.resolve_type(&capture.ty, signature_scope_id)?,
+                    ),
+                    ref pat => {
+                        return Err(self.unsupported(pat, "Patterns in trait method parameters are not yet supported"))
                     }
-                } else {
-                    unimplemented!("Member access on non-struct types");
+                },
+                FnArg::SelfRef(ref self_ref) => (
+                    "self".to_string(),
+                    self.ref_type(self_ty, self_ref.mutability.is_some()),
+                ),
+                FnArg::SelfValue(_) => ("self".to_string(), self_ty),
+                _ => {
+                    return Err(self.unsupported(
+                        input,
+                        "Function parameters without a name are not yet supported",
+                    ))
                 }
+            };
+            // The var id is never used, as the method is only ever called
+            params.push(Param::new(name, 0, ty, false));
+        }
+
+        let return_ty = match decl.output {
+            ReturnType::Default => builtin_type::VOID,
+            ReturnType::Type(_, ref box_ty) => self.resolve_type(box_ty, signature_scope_id)?,
+        };
+
+        Ok(Some((params, return_ty)))
+    }
+
+    /// Finds the method with the given name among the traits a type variable is bound by, as the call to make
+    /// once the function is instantiated and the method's signature.  Returns None if none of them have it
+    fn bound_method(
+        &mut self,
+        ty_var: DefinitionId,
+        method_ident: &syn::Ident,
+    ) -> Result<Option<(Bytecode, Vec<Param>, DefinitionId)>, Diagnostic> {
+        let bounds = match self.definitions[ty_var] {
+            Definition::TypeVariable(_, ref bounds) => bounds.clone(),
+            _ => return Ok(None),
+        };
+
+        let method_name = method_ident.to_string();
+        let mut candidates = vec![];
+        for trait_id in bounds {
+            if let Some((params, return_ty)) = self.trait_method_signature(trait_id, &method_name, ty_var)? {
+                candidates.push((trait_id, params, return_ty));
             }
-            Expr::Macro(em) => {
-                if em.mac.path.segments[0].ident.as_ref() == "println" {
-                    let em = em.clone();
-                    let token_stream: TokenStream = em.mac.tts.into_iter().skip(2).collect();
-                    let call = String::new() + "__debug__(" + &token_stream.to_string() + ")";
-                    let result: Expr = syn::parse_str(&call).unwrap();
-                    let definition_id = self.convert_expr_to_bytecode(
-                        &result,
+        }
+
+        if candidates.len() > 1 {
+            let trait_ids: Vec<DefinitionId> = candidates.iter().map(|x| x.0).collect();
+            return Err(self.ambiguous_method(method_ident, ty_var, &trait_ids));
+        }
+        Ok(candidates.pop().map(|(trait_id, params, return_ty)| {
+            (
+                Bytecode::CallTraitMethod(ty_var, trait_id, method_name, return_ty),
+                params,
+                return_ty,
+            )
+        }))
+    }
+
+    /// Converts both sides of a binary operator, checking they can be used together
+    /// Returns the types of the lhs and rhs
+    fn convert_operands_to_bytecode(
+        &mut self,
+        eb: &ExprBinary,
+        verb: &str,
+        expected_return_type: DefinitionId,
+        bytecode: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<(DefinitionId, DefinitionId), Diagnostic> {
+        let lhs_type = self.convert_expr_to_bytecode(
+            &*eb.left,
+            expected_return_type,
+            bytecode,
+            current_scope_id,
+            var_stack,
+        )?;
+        let rhs_type = self.convert_expr_to_bytecode(
+            &*eb.right,
+            expected_return_type,
+            bytecode,
+            current_scope_id,
+            var_stack,
+        )?;
+
+        if self.operator_compatible(lhs_type, rhs_type) {
+            Ok((lhs_type, rhs_type))
+        } else {
+            self.report(Diagnostic::new(
+                "E0369",
+                format!(
+                    "Can't {} values of {} and {}",
+                    verb,
+                    self.printable_name(lhs_type),
+                    self.printable_name(rhs_type)
+                ),
+            ).with_primary(self.span_of(&eb.op), "incompatible operand types")
+                .with_secondary(self.span_of(&*eb.left), &self.printable_name(lhs_type))
+                .with_secondary(self.span_of(&*eb.right), &self.printable_name(rhs_type)));
+            Ok((builtin_type::ERROR, builtin_type::ERROR))
+        }
+    }
+
+    pub fn convert_expr_to_bytecode(
+        &mut self,
+        expr: &Expr,
+        expected_return_type: DefinitionId,
+        bytecode: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<DefinitionId, Diagnostic> {
+        match expr {
+            Expr::Return(er) => {
+                let actual_return_type = match er.expr {
+                    Some(ref inner) => self.convert_expr_expecting(
+                        inner,
+                        expected_return_type,
                         expected_return_type,
                         bytecode,
                         current_scope_id,
                         var_stack,
-                    );
-                    definition_id
-                } else {
-                    unimplemented!("Can not resolve macro type");
+                    )?,
+                    None => builtin_type::VOID,
+                };
+
+                if !self.assignment_compatible(expected_return_type, actual_return_type) {
+                    self.report(Diagnostic::new(
+                        "E0308",
+                        format!(
+                            "Mismatched return types: {} and {}",
+                            self.printable_name(actual_return_type),
+                            self.printable_name(expected_return_type)
+                        ),
+                    ).with_primary(
+                        self.span_of(er),
+                        &format!(
+                            "expected {}, found {}",
+                            self.printable_name(expected_return_type),
+                            self.printable_name(actual_return_type)
+                        ),
+                    ));
+                }
+
+                match actual_return_type {
+                    builtin_type::VOID => bytecode.push(Bytecode::ReturnVoid),
+                    _ => bytecode.push(Bytecode::ReturnLastStackValue),
                 }
+                Ok(builtin_type::NEVER)
             }
-            Expr::Unsafe(eu) => self.convert_block_to_bytecode(
-                &eu.block,
+            Expr::Lit(el) => Ok(self.convert_lit_to_bytecode(&el.lit, false, bytecode)),
+            Expr::Cast(ec) => {
+                let from_ty = self.convert_expr_to_bytecode(
+                    &ec.expr,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                )?;
+                let to_ty = self.resolve_type(&ec.ty, current_scope_id)?;
+                if from_ty == builtin_type::ERROR || to_ty == builtin_type::ERROR {
+                    return Ok(builtin_type::ERROR);
+                }
+
+                // A number without a type is cast from its fallback type
+                let from_ty = builtin_type::fallback(from_ty);
+                if !self.cast_compatible(from_ty, to_ty) {
+                    let code = match (&self.definitions[from_ty], &self.definitions[to_ty]) {
+                        (Definition::Builtin, Definition::Builtin) => "E0606",
+                        _ => "E0605",
+                    };
+                    return Ok(self.report(Diagnostic::new(
+                        code,
+                        format!(
+                            "Can't cast {} as {}",
+                            self.printable_name(from_ty),
+                            self.printable_name(to_ty)
+                        ),
+                    ).with_primary(self.span_of(ec), "invalid cast")));
+                }
+
+                if from_ty != to_ty {
+                    bytecode.push(Bytecode::Cast(from_ty, to_ty));
+                }
+                Ok(to_ty)
+            }
+            Expr::Paren(ep) => self.convert_expr_to_bytecode(
+                &*ep.expr,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                var_stack,
+            ),
+            Expr::Block(eb) => self.convert_block_to_bytecode(
+                &eb.block,
                 expected_return_type,
                 bytecode,
                 Some(current_scope_id),
                 var_stack,
             ),
-            _ => unimplemented!("Unknown expr type: {:#?}", expr),
-        }
-    }
+            Expr::Assign(ea) => {
+                // The value is pushed before the place it goes in, but the place is converted first so that the value
+                // can take its type
+                let mut lhs_bytecode = vec![];
+                let lhs_type = self.convert_lhs_expr_to_bytecode(
+                    &*ea.left,
+                    Some(Mutation::Assign),
+                    expected_return_type,
+                    &mut lhs_bytecode,
+                    current_scope_id,
+                    var_stack,
+                )?;
 
-    pub(crate) fn resolve_type(&mut self, tp: &Type, current_scope_id: ScopeId) -> DefinitionId {
-        match *tp {
-            Type::Path(ref tp) => match tp.path.segments[0].ident.as_ref() {
-                "u64" => builtin_type::U64,
-                "u32" => builtin_type::U32,
-                "i64" => builtin_type::I64,
-                "i32" => builtin_type::I32,
-                "bool" => builtin_type::BOOL,
-                _ => {
-                    if let Some(definition_id) = self.process_path(&tp.path, current_scope_id) {
-                        if let Definition::Struct(_) = self.definitions[definition_id] {
-                            definition_id
-                        } else if let Definition::TypeVariable = self.definitions[definition_id] {
-                            definition_id
-                        } else {
-                            unimplemented!("Could not find processed struct for type");
-                        }
-                    } else {
-                        unimplemented!("Could not find processed struct for type");
-                    }
-                }
-            },
-            Type::Ptr(_) => {
-                //TODO: FIXME: Currently we only support void pointers, so we assume that's what it is
-                builtin_type::VOID_PTR
-            }
-            _ => unimplemented!("Unsupported type"),
-        }
+                let rhs_type = self.convert_expr_expecting(
+                    &*ea.right,
+                    lhs_type,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                )?;
+                bytecode.extend(lhs_bytecode);
+
+                if self.assignment_compatible(lhs_type, rhs_type) {
+                    let tighter_type = self.tighter_of_types(lhs_type, rhs_type);
+                    match bytecode.last() {
+                        Some(Bytecode::LValueVar(var_id, _)) => {
+                            var_stack.vars[*var_id].ty = tighter_type;
+                        }
+                        _ => {}
+                    }
+                } else {
+                    self.report(Diagnostic::new(
+                        "E0308",
+                        format!(
+                            "Assignment between {} and {}",
+                            self.printable_name(lhs_type),
+                            self.printable_name(rhs_type)
+                        ),
+                    ).with_primary(
+                        self.span_of(&*ea.right),
+                        &format!(
+                            "expected {}, found {}",
+                            self.printable_name(lhs_type),
+                            self.printable_name(rhs_type)
+                        ),
+                    )
+                        .with_secondary(
+                            self.span_of(&*ea.left),
+                            &format!("this has type {}", self.printable_name(lhs_type)),
+                        ));
+                }
+
+                bytecode.push(Bytecode::Assign);
+
+                Ok(builtin_type::VOID)
+            }
+            Expr::AssignOp(eao) => {
+                let rhs_type = self.convert_expr_to_bytecode(
+                    &eao.right,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                )?;
+
+                let lhs_type = self.convert_lhs_expr_to_bytecode(
+                    &eao.left,
+                    Some(Mutation::Assign),
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                )?;
+
+                let (op, ty) = self.binary_operator(&eao.op, (&eao.left, lhs_type), (&eao.right, rhs_type));
+                if ty != builtin_type::ERROR {
+                    if let Some(Bytecode::LValueVar(var_id, _)) = bytecode.last() {
+                        var_stack.vars[*var_id].ty = ty;
+                    }
+                }
+                bytecode.push(Bytecode::CompoundAssign(Box::new(op), ty));
+
+                Ok(builtin_type::VOID)
+            }
+            Expr::If(ei) => self.convert_if_to_bytecode(
+                ei,
+                builtin_type::UNKNOWN,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                var_stack,
+            ),
+            Expr::IfLet(eil) => {
+                if eil.pats.len() != 1 {
+                    return Ok(self.report(self.unsupported(&eil.pats, "Alternative patterns are not yet supported")));
+                }
+
+                let (access, scrutinee_ty) = self.convert_scrutinee_to_bytecode(
+                    &eil.expr,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                )?;
+
+                // The pattern's variables are only in scope in the then block
+                let mut then_var_stack = var_stack.clone();
+                let mut bindings = vec![];
+                let (test, _) = self.convert_pattern_to_bytecode(
+                    &eil.pats[0],
+                    scrutinee_ty,
+                    &access,
+                    &mut bindings,
+                    current_scope_id,
+                    &mut then_var_stack,
+                )?;
+                match test {
+                    Some(test) => bytecode.extend(test),
+                    None => bytecode.push(Bytecode::PushBool(true)),
+                }
+
+                bytecode.push(Bytecode::If(0, builtin_type::VOID));
+                let before_then_block_len = bytecode.len();
+
+                bytecode.extend(bindings);
+                let then_ty = self.convert_block_to_bytecode(
+                    &eil.then_branch,
+                    expected_return_type,
+                    bytecode,
+                    Some(current_scope_id),
+                    &mut then_var_stack,
+                )?;
+                var_stack.leave_block(then_var_stack);
+                let mut placeholders: Vec<usize> = diverged_placeholder(then_ty, bytecode).into_iter().collect();
+                let after_then_block_len = bytecode.len();
+
+                let mut if_ty = pushed_type(then_ty);
+                if let Some(ref else_branch) = eil.else_branch {
+                    let then_span = self.span_of(&eil.then_branch);
+                    let (else_if_ty, else_placeholder) = self.convert_else_to_bytecode(
+                        &else_branch.1,
+                        (then_ty, then_span),
+                        (builtin_type::UNKNOWN, expected_return_type),
+                        bytecode,
+                        current_scope_id,
+                        var_stack,
+                    )?;
+                    if_ty = else_if_ty;
+                    placeholders.extend(else_placeholder);
+                }
+                fill_placeholders(&placeholders, if_ty, bytecode);
+                bytecode.push(Bytecode::EndIf(pushed_type(if_ty)));
+
+                bytecode[before_then_block_len - 1] = Bytecode::If(
+                    after_then_block_len - before_then_block_len + 2,
+                    pushed_type(if_ty),
+                );
+
+                Ok(if_ty)
+            }
+            Expr::Match(em) => self.convert_match_to_bytecode(
+                em,
+                builtin_type::UNKNOWN,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                var_stack,
+            ),
+            Expr::WhileLet(ewl) => {
+                if ewl.pats.len() != 1 {
+                    return Ok(self.report(self.unsupported(&ewl.pats, "Alternative patterns are not yet supported")));
+                }
+
+                let before_cond_len = bytecode.len();
+                bytecode.push(Bytecode::BeginWhile(builtin_type::VOID));
+
+                // The value is matched again each time around the loop, and the pattern's variables are only in
+                // scope in the body
+                let mut body_var_stack = var_stack.clone();
+                let (access, scrutinee_ty) = self.convert_scrutinee_to_bytecode(
+                    &ewl.expr,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    &mut body_var_stack,
+                )?;
+                let mut bindings = vec![];
+                let (test, _) = self.convert_pattern_to_bytecode(
+                    &ewl.pats[0],
+                    scrutinee_ty,
+                    &access,
+                    &mut bindings,
+                    current_scope_id,
+                    &mut body_var_stack,
+                )?;
+                match test {
+                    Some(test) => bytecode.extend(test),
+                    None => bytecode.push(Bytecode::PushBool(true)),
+                }
+
+                bytecode.push(Bytecode::WhileCond(0));
+                let before_block_len = bytecode.len();
+
+                bytecode.extend(bindings);
+                self.loops.push(Loop::new(&ewl.label, false));
+                let while_ty = self.convert_block_to_bytecode(
+                    &ewl.body,
+                    expected_return_type,
+                    bytecode,
+                    Some(current_scope_id),
+                    &mut body_var_stack,
+                );
+                self.loops.pop();
+                let while_ty = while_ty?;
+                var_stack.leave_block(body_var_stack);
+
+                let after_block_len = bytecode.len();
+                bytecode.push(Bytecode::EndWhile(after_block_len - before_cond_len, builtin_type::VOID));
+
+                bytecode[before_block_len - 1] =
+                    Bytecode::WhileCond(after_block_len - before_block_len + 1);
+                patch_loop_exits(&mut bytecode[before_cond_len..]);
+
+                Ok(while_ty)
+            }
+            Expr::While(ew) => {
+                let before_cond_len = bytecode.len();
+                bytecode.push(Bytecode::BeginWhile(builtin_type::VOID));
+
+                let cond_type = self.convert_expr_to_bytecode(
+                    &*ew.cond,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                )?;
+
+                if cond_type != builtin_type::BOOL && cond_type != builtin_type::ERROR {
+                    self.report(Diagnostic::new(
+                        "E0308",
+                        "While condition needs to be boolean".to_string(),
+                    ).with_primary(
+                        self.span_of(&*ew.cond),
+                        &format!("expected bool, found {}", self.printable_name(cond_type)),
+                    ));
+                }
+
+                bytecode.push(Bytecode::WhileCond(0));
+                let before_block_len = bytecode.len();
+
+                self.loops.push(Loop::new(&ew.label, false));
+                let while_ty = self.convert_block_to_bytecode(
+                    &ew.body,
+                    expected_return_type,
+                    bytecode,
+                    Some(current_scope_id),
+                    var_stack,
+                );
+                self.loops.pop();
+                let while_ty = while_ty?;
+
+                let after_block_len = bytecode.len();
+                bytecode.push(Bytecode::EndWhile(after_block_len - before_cond_len, builtin_type::VOID));
+
+                // Patch the original offset to the correct offset
+                bytecode[before_block_len - 1] =
+                    Bytecode::WhileCond(after_block_len - before_block_len + 1);
+                patch_loop_exits(&mut bytecode[before_cond_len..]);
+
+                Ok(while_ty)
+            }
+            Expr::ForLoop(efl) => {
+                let iter = match range_iter(&efl.expr) {
+                    Some(iter) => iter,
+                    None => {
+                        // Only ranges can be iterated over, so find the type of whatever was given to say so
+                        let iter_ty = self.convert_expr_to_bytecode(
+                            &efl.expr,
+                            expected_return_type,
+                            &mut vec![],
+                            current_scope_id,
+                            &mut var_stack.clone(),
+                        )?;
+                        if iter_ty != builtin_type::ERROR {
+                            self.report(Diagnostic::new(
+                                "E0277",
+                                format!("`{}` is not an iterator", self.printable_name(iter_ty)),
+                            ).with_primary(self.span_of(&*efl.expr), "not an iterator")
+                                .with_note(
+                                    "`for` loops can only go through ranges of integers, like `0..n`, `(0..=n).rev()` \
+                                     or `(0..n).step_by(2)`",
+                                ));
+                        }
+                        return Ok(builtin_type::ERROR);
+                    }
+                };
+                let (start, end) = match (&iter.range.from, &iter.range.to) {
+                    (Some(start), Some(end)) => (start, end),
+                    _ => {
+                        return Ok(self.report(self.unsupported(
+                            &iter.range,
+                            "Ranges without a start or an end are not yet supported",
+                        )))
+                    }
+                };
+
+                let start_ty = self.convert_expr_to_bytecode(
+                    start,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                )?;
+                let end_ty = self.convert_expr_to_bytecode(
+                    end,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                )?;
+                // A range of integers without a type gives integers without a type, which take their type from how
+                // they're used, as the value of a `let` would
+                let ty = self.tighter_of_types(start_ty, end_ty);
+                if !self.integer_operator_compatible(start_ty, end_ty) {
+                    self.report(Diagnostic::new(
+                        "E0277",
+                        format!("Can't iterate over a range of {}", self.printable_name(builtin_type::fallback(ty))),
+                    ).with_primary(self.span_of(iter.range), "not a range of integers"));
+                    return Ok(builtin_type::ERROR);
+                }
+
+                match iter.step {
+                    Some(step) => {
+                        let step_ty = self.convert_expr_to_bytecode(
+                            step,
+                            expected_return_type,
+                            bytecode,
+                            current_scope_id,
+                            var_stack,
+                        )?;
+                        if !self.assignment_compatible(builtin_type::USIZE, step_ty) {
+                            self.report(Diagnostic::new(
+                                "E0308",
+                                "Ranges can only be stepped through by a usize".to_string(),
+                            ).with_primary(
+                                self.span_of(step),
+                                &format!("expected usize, found {}", self.printable_name(step_ty)),
+                            ));
+                        }
+                    }
+                    None => bytecode.push(Bytecode::PushUsize(1)),
+                }
+
+                let state_var_id = var_stack.add_var(String::new(), ty, false);
+                bytecode.push(Bytecode::BeginFor(
+                    state_var_id,
+                    ForRange {
+                        ty,
+                        inclusive: iter.inclusive,
+                        rev: iter.rev,
+                    },
+                ));
+
+                let before_loop_len = bytecode.len();
+                bytecode.push(Bytecode::ForNext(0, state_var_id, ty));
+
+                // Each integer is bound to the pattern like a `let`, in scope only in the body
+                let mut body_var_stack = var_stack.clone();
+                match *unparenthesized(&efl.pat) {
+                    Pat::Ident(ref pi) if pi.by_ref.is_none() && pi.subpat.is_none() => {
+                        let var_id = body_var_stack.add_var(pi.ident.to_string(), ty, pi.mutability.is_some());
+                        bytecode.push(Bytecode::VarDecl(var_id));
+                    }
+                    ref pat => {
+                        let var_id = body_var_stack.add_var(String::new(), ty, true);
+                        bytecode.push(Bytecode::VarDecl(var_id));
+
+                        let span = self.span_of(pat);
+                        let span_id = body_var_stack.add_span(span.clone());
+                        let (_, pattern) = self.convert_pattern_to_bytecode(
+                            pat,
+                            ty,
+                            &[Bytecode::Var(var_id, span_id)],
+                            bytecode,
+                            current_scope_id,
+                            &mut body_var_stack,
+                        )?;
+                        self.check_irrefutable(&pattern, ty, span, "`for` loop binding");
+                    }
+                }
+
+                self.loops.push(Loop::new(&efl.label, false));
+                let body_ty = self.convert_block_to_bytecode(
+                    &efl.body,
+                    expected_return_type,
+                    bytecode,
+                    Some(current_scope_id),
+                    &mut body_var_stack,
+                );
+                self.loops.pop();
+                let body_ty = body_ty?;
+                var_stack.leave_block(body_var_stack);
+
+                if body_ty != builtin_type::VOID && body_ty != builtin_type::ERROR {
+                    self.report(Diagnostic::new(
+                        "E0308",
+                        "The body of a `for` loop can't give a value".to_string(),
+                    ).with_primary(
+                        self.span_of(&efl.body),
+                        &format!("expected (), found {}", self.printable_name(body_ty)),
+                    ));
+                }
+
+                let after_block_len = bytecode.len();
+                bytecode.push(Bytecode::EndWhile(after_block_len - before_loop_len, builtin_type::VOID));
+                bytecode[before_loop_len] = Bytecode::ForNext(after_block_len - before_loop_len, state_var_id, ty);
+                patch_loop_exits(&mut bytecode[before_loop_len..]);
+
+                Ok(builtin_type::VOID)
+            }
+            Expr::Closure(ec) => {
+                self.definitions.push(Definition::Closure(Closure {
+                    by_move: ec.capture.is_some(),
+                    captures: vec![],
+                    fun: None,
+                }));
+                let closure_ty = self.definitions.len() - 1;
+                self.pending_closures.insert(
+                    closure_ty,
+                    PendingClosure {
+                        expr: ec.clone(),
+                        scope_id: current_scope_id,
+                        var_stack: var_stack.clone(),
+                    },
+                );
+
+                // A closure whose params all have types doesn't need to wait to be called to be converted
+                let typed = ec.inputs.iter().all(|input| matches!(input, FnArg::Captured(_)));
+                if typed {
+                    self.convert_closure(closure_ty, &[])?;
+                }
+
+                let span_id = var_stack.add_span(self.span_of(ec));
+                bytecode.push(Bytecode::MakeClosure(closure_ty, span_id));
+                Ok(closure_ty)
+            }
+            Expr::Range(er) => {
+                self.report(Diagnostic::new(
+                    "E0658",
+                    "Ranges are only supported as the iterator of a `for` loop".to_string(),
+                ).with_primary(self.span_of(er), "range used as a value"));
+                Ok(builtin_type::ERROR)
+            }
+            Expr::Loop(el) => {
+                let before_loop_len = bytecode.len();
+                bytecode.push(Bytecode::BeginWhile(builtin_type::VOID));
+
+                self.loops.push(Loop::new(&el.label, true));
+                let body_ty = self.convert_block_to_bytecode(
+                    &el.body,
+                    expected_return_type,
+                    bytecode,
+                    Some(current_scope_id),
+                    var_stack,
+                );
+                let loop_def = self.loops.pop().unwrap();
+                let body_ty = body_ty?;
+
+                if body_ty != builtin_type::VOID && body_ty != builtin_type::ERROR {
+                    self.report(Diagnostic::new(
+                        "E0308",
+                        "The body of a loop can't give a value".to_string(),
+                    ).with_primary(
+                        self.span_of(&el.body),
+                        &format!("expected (), found {}", self.printable_name(body_ty)),
+                    ).with_note("use `break` to give a value from the loop"));
+                }
+
+                // Without a `break` giving a value, the loop is a statement
+                let loop_ty = match loop_def.break_ty {
+                    Some((ty, _)) => ty,
+                    None => builtin_type::VOID,
+                };
+                let after_block_len = bytecode.len();
+                bytecode.push(Bytecode::EndWhile(after_block_len - before_loop_len, loop_ty));
+                bytecode[before_loop_len] = Bytecode::BeginWhile(loop_ty);
+                patch_loop_exits(&mut bytecode[before_loop_len..]);
+
+                Ok(loop_ty)
+            }
+            Expr::Break(eb) => {
+                let loops_out = match self.loop_target(&eb.label, "break", self.span_of(eb)) {
+                    Some(loops_out) => loops_out,
+                    None => return Ok(builtin_type::ERROR),
+                };
+                let loop_index = self.loops.len() - 1 - loops_out;
+
+                let break_ty = match eb.expr {
+                    Some(ref value) => {
+                        let value_ty = self.convert_expr_to_bytecode(
+                            value,
+                            expected_return_type,
+                            bytecode,
+                            current_scope_id,
+                            var_stack,
+                        )?;
+                        if !self.loops[loop_index].gives_value {
+                            self.report(Diagnostic::new(
+                                "E0571",
+                                "`break` with value from a `while` loop".to_string(),
+                            ).with_primary(self.span_of(eb), "can only break with a value inside `loop`"));
+                            return Ok(builtin_type::ERROR);
+                        }
+                        value_ty
+                    }
+                    None => builtin_type::VOID,
+                };
+
+                // Breaks giving numbers without a type take the type of the other breaks, as match arms do
+                let span = self.span_of(eb);
+                match self.loops[loop_index].break_ty.clone() {
+                    None => self.loops[loop_index].break_ty = Some((break_ty, span)),
+                    Some((ty, _))
+                        if ty == builtin_type::ERROR
+                            || ((ty == builtin_type::UNKNOWN_INT || ty == builtin_type::UNKNOWN_FLOAT)
+                                && self.operator_compatible(ty, break_ty)) =>
+                    {
+                        self.loops[loop_index].break_ty = Some((break_ty, span))
+                    }
+                    Some((ty, first_span)) => {
+                        let compatible = if ty == builtin_type::VOID || break_ty == builtin_type::VOID {
+                            ty == break_ty
+                        } else {
+                            self.operator_compatible(ty, break_ty)
+                        };
+                        if !compatible && break_ty != builtin_type::ERROR {
+                            self.report(Diagnostic::new(
+                                "E0308",
+                                "Breaks from the loop give incompatible types".to_string(),
+                            ).with_primary(
+                                span,
+                                &format!(
+                                    "expected {}, found {}",
+                                    self.printable_name(ty),
+                                    self.printable_name(break_ty)
+                                ),
+                            ).with_secondary(
+                                first_span,
+                                &format!("this break gives {}", self.printable_name(ty)),
+                            ));
+                        }
+                    }
+                }
+
+                bytecode.push(Bytecode::Break(0, loops_out, break_ty));
+                Ok(builtin_type::NEVER)
+            }
+            Expr::Continue(ec) => {
+                match self.loop_target(&ec.label, "continue", self.span_of(ec)) {
+                    Some(loops_out) => {
+                        bytecode.push(Bytecode::Continue(0, loops_out));
+                        Ok(builtin_type::NEVER)
+                    }
+                    None => Ok(builtin_type::ERROR),
+                }
+            }
+            Expr::Unary(eu) => match eu.op {
+                UnOp::Neg(_a) => {
+                    let expr_type = match *eu.expr {
+                        Expr::Lit(ref el) => self.convert_lit_to_bytecode(&el.lit, true, bytecode),
+                        _ => self.convert_expr_to_bytecode(
+                            &*eu.expr,
+                            expected_return_type,
+                            bytecode,
+                            current_scope_id,
+                            var_stack,
+                        )?,
+                    };
+
+                    match builtin_type::int_layout(expr_type) {
+                        Some((_, true)) => {
+                            // A negative literal is pushed already negated, as it may only fit in its type once
+                            // it is, like `-128i8`
+                            let negated = match (&*eu.expr, bytecode.last()) {
+                                (Expr::Lit(_), Some(code)) => negated_literal(code),
+                                _ => None,
+                            };
+                            match negated {
+                                Some(code) => {
+                                    bytecode.pop();
+                                    bytecode.push(code);
+                                }
+                                None => bytecode.push(Bytecode::Neg(expr_type)),
+                            }
+                            Ok(expr_type)
+                        }
+                        _ if builtin_type::is_float(expr_type) => {
+                            bytecode.push(Bytecode::Neg(expr_type));
+                            Ok(expr_type)
+                        }
+                        _ if expr_type == builtin_type::ERROR => Ok(builtin_type::ERROR),
+                        _ => Ok(self.report(Diagnostic::new(
+                            "E0600",
+                            format!(
+                                "Can't negate value of type {}",
+                                self.printable_name(expr_type)
+                            ),
+                        ).with_primary(self.span_of(eu), "cannot apply unary operator `-`"))),
+                    }
+                }
+                UnOp::Deref(_) => {
+                    let ty = self.convert_expr_to_bytecode(
+                        &eu.expr,
+                        expected_return_type,
+                        bytecode,
+                        current_scope_id,
+                        var_stack,
+                    )?;
+                    bytecode.push(Bytecode::Deref);
+                    Ok(self.deref_type(eu, ty))
+                }
+                UnOp::Not(_) => {
+                    let expr_type = self.convert_expr_to_bytecode(
+                        &eu.expr,
+                        expected_return_type,
+                        bytecode,
+                        current_scope_id,
+                        var_stack,
+                    )?;
+
+                    match expr_type {
+                        builtin_type::BOOL => {
+                            bytecode.push(Bytecode::Not(expr_type));
+                            Ok(expr_type)
+                        }
+                        // On an integer, `!` flips each of its bits
+                        _ if int_bounds(expr_type).is_some() => {
+                            bytecode.push(Bytecode::Not(expr_type));
+                            Ok(expr_type)
+                        }
+                        builtin_type::ERROR => Ok(builtin_type::ERROR),
+                        _ => Ok(self.report(Diagnostic::new(
+                            "E0600",
+                            format!(
+                                "Can't apply `!` to value of type {}",
+                                self.printable_name(expr_type)
+                            ),
+                        ).with_primary(self.span_of(eu), "cannot apply unary operator `!`"))),
+                    }
+                }
+            },
+            Expr::Binary(eb) => match eb.op {
+                BinOp::Add(_)
+                | BinOp::Sub(_)
+                | BinOp::Mul(_)
+                | BinOp::Div(_)
+                | BinOp::Rem(_)
+                | BinOp::BitAnd(_)
+                | BinOp::BitOr(_)
+                | BinOp::BitXor(_)
+                | BinOp::Shl(_)
+                | BinOp::Shr(_) => self.convert_arithmetic_to_bytecode(
+                    eb,
+                    builtin_type::UNKNOWN,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                ),
+                BinOp::Lt(_) => self.convert_comparison_to_bytecode(
+                    eb,
+                    Bytecode::Lt,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                ),
+                BinOp::Le(_) => self.convert_comparison_to_bytecode(
+                    eb,
+                    Bytecode::Le,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                ),
+                BinOp::Gt(_) => self.convert_comparison_to_bytecode(
+                    eb,
+                    Bytecode::Gt,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                ),
+                BinOp::Ge(_) => self.convert_comparison_to_bytecode(
+                    eb,
+                    Bytecode::Ge,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                ),
+                BinOp::Eq(_) => self.convert_comparison_to_bytecode(
+                    eb,
+                    Bytecode::Eq,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                ),
+                BinOp::Ne(_) => self.convert_comparison_to_bytecode(
+                    eb,
+                    Bytecode::Ne,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                ),
+                BinOp::And(_) | BinOp::Or(_) => self.convert_logical_to_bytecode(
+                    eb,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                ),
+                _ => unimplemented!("Unknown operator: {:?}", eb.op),
+            },
+            Expr::Struct(es) => self.convert_struct_to_bytecode(
+                es,
+                builtin_type::UNKNOWN,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                var_stack,
+            ),
+            Expr::Tuple(et) => self.convert_tuple_to_bytecode(
+                et,
+                builtin_type::UNKNOWN,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                var_stack,
+            ),
+            Expr::Array(ea) => self.convert_array_to_bytecode(
+                ea,
+                builtin_type::UNKNOWN,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                var_stack,
+            ),
+            Expr::Repeat(er) => self.convert_repeat_to_bytecode(
+                er,
+                builtin_type::UNKNOWN,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                var_stack,
+            ),
+            Expr::Reference(er) => {
+                let (referred_ty, mutability) = self.convert_place_expr_to_bytecode(
+                    &er.expr,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                )?;
+                if er.mutability.is_some() {
+                    let place = place_name(&er.expr);
+                    self.check_mutable(&er.expr, &place, Mutation::MutableBorrow, mutability, var_stack);
+                }
+                if referred_ty == builtin_type::ERROR {
+                    return Ok(builtin_type::ERROR);
+                }
+
+                let kind = match er.mutability {
+                    Some(_) => BorrowKind::Mutable,
+                    None => BorrowKind::Shared,
+                };
+                let span_id = var_stack.add_span(self.span_of(er));
+                bytecode.push(Bytecode::Ref(kind, span_id));
+                Ok(self.ref_type(referred_ty, er.mutability.is_some()))
+            }
+            Expr::Index(ei) => {
+                let ty = self.convert_expr_to_bytecode(
+                    &ei.expr,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                )?;
+                let ty = self.auto_deref(ty, bytecode);
+                let index_ty = self.convert_expr_expecting(
+                    &ei.index,
+                    builtin_type::USIZE,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                )?;
+
+                let element_ty = self.element_type(ei, ty, index_ty);
+                bytecode.push(Bytecode::Index(ty));
+                Ok(element_ty)
+            }
+            Expr::Path(ep) => {
+                if let Some(definition_id) = self.process_path(&ep.path, current_scope_id)? {
+                    if self.is_unit_struct(definition_id) {
+                        bytecode.push(Bytecode::Call(definition_id));
+                        Ok(definition_id)
+                    } else if let Some(enum_id) = self.unit_variant_enum(definition_id) {
+                        bytecode.push(Bytecode::Call(definition_id));
+                        Ok(enum_id)
+                    } else if let Definition::Fun(ref fun) = self.definitions[definition_id] {
+                        // Naming a function without calling it gives a pointer to it
+                        if fun.is_generic() {
+                            return Ok(self.report(Diagnostic::new(
+                                "E0282",
+                                "Type annotations needed".to_string(),
+                            ).with_primary(
+                                self.span_of(ep),
+                                "cannot infer the type parameters of a generic function",
+                            )));
+                        }
+                        let param_tys = fun.params.iter().map(|param| param.ty).collect();
+                        let return_ty = fun.return_ty;
+                        bytecode.push(Bytecode::PushFn(definition_id));
+                        Ok(self.fn_ptr_type(param_tys, return_ty))
+                    } else {
+                        Ok(self.report(Diagnostic::new(
+                            "E0423",
+                            format!(
+                                "Expected value, found {}",
+                                self.printable_path(&ep.path)
+                            ),
+                        ).with_primary(self.span_of(ep), "not a value")))
+                    }
+                } else {
+                    let ident = ep.path.segments[0].ident.to_string();
+                    let var_id = var_stack.find_var(&ident);
+                    if var_id.is_none() {
+                        if ident == "NULL" {
+                            bytecode.push(Bytecode::PushRawPtr(ptr::null()));
+                            Ok(builtin_type::VOID_PTR)
+                        } else {
+                            Ok(self.report(Diagnostic::new(
+                                "E0425",
+                                format!("Could not find {}", ident),
+                            ).with_primary(self.span_of(ep), "not found in this scope")))
+                        }
+                    } else {
+                        let var_id = var_id.unwrap();
+                        let var_ty = var_stack.vars[var_id].ty;
+
+                        if var_ty == builtin_type::UNKNOWN {
+                            return Ok(self.report(Diagnostic::new(
+                                "E0381",
+                                format!("{} used before being given a value", ident),
+                            ).with_primary(self.span_of(ep), "use of possibly uninitialized variable")));
+                        }
+
+                        // A variable holding the unit value has nothing to read
+                        if var_ty != builtin_type::VOID {
+                            let span_id = var_stack.add_span(self.span_of(ep));
+                            bytecode.push(Bytecode::Var(var_id, span_id));
+                        }
+
+                        Ok(var_ty)
+                    }
+                }
+            }
+            Expr::Call(ec) => match *ec.func {
+                Expr::Path(ref ep) => {
+                    if ep.path.segments.len() == 1 && ep.path.segments[0].ident == "__debug__" {
+                        let definition_id = self.convert_expr_to_bytecode(
+                            &ec.args[0],
+                            expected_return_type,
+                            bytecode,
+                            current_scope_id,
+                            var_stack,
+                        )?;
+                        self.check_fallback_literals(&ec.args[0], definition_id);
+                        bytecode.push(Bytecode::DebugPrint(definition_id));
+                        Ok(builtin_type::VOID)
+                    } else if ep.path.segments.len() == 2
+                        && ep.path.segments[0].ident == "Box"
+                        && ep.path.segments[1].ident == "new"
+                        && ec.args.len() == 1
+                    {
+                        // Boxing a value moves it to the heap
+                        let boxed_ty = self.convert_expr_to_bytecode(
+                            &ec.args[0],
+                            expected_return_type,
+                            bytecode,
+                            current_scope_id,
+                            var_stack,
+                        )?;
+                        if boxed_ty == builtin_type::ERROR {
+                            return Ok(builtin_type::ERROR);
+                        }
+                        let box_ty = self.box_type(boxed_ty);
+                        bytecode.push(Bytecode::BoxNew(box_ty));
+                        Ok(box_ty)
+                    } else {
+                        // If we're in a single ident path, check values in scope
+                        if ep.path.segments.len() == 1 && ep.path.leading_colon.is_none() {
+                            let ident = ep.path.segments[0].ident;
+                            let var_result = var_stack.find_var(ident.as_ref());
+                            if let Some(var_id) = var_result {
+                                match self.definitions[var_stack.vars[var_id].ty] {
+                                    Definition::Closure(_) => {
+                                        return self.convert_closure_call(
+                                            ec,
+                                            var_id,
+                                            expected_return_type,
+                                            bytecode,
+                                            current_scope_id,
+                                            var_stack,
+                                        )
+                                    }
+                                    Definition::FnPtr(_, _) => {
+                                        return self.convert_fn_ptr_call(
+                                            ec,
+                                            expected_return_type,
+                                            bytecode,
+                                            current_scope_id,
+                                            var_stack,
+                                        )
+                                    }
+                                    _ => {}
+                                }
+                                return Ok(self.report(Diagnostic::new(
+                                    "E0618",
+                                    format!(
+                                        "Can not call function on type {}",
+                                        self.printable_name(var_stack.vars[var_id].ty)
+                                    ),
+                                ).with_primary(self.span_of(ep), "not a function")));
+                            }
+                        }
+
+                        // A path like `T::new` calls a function of one of the traits the type parameter is bound by
+                        if ep.path.segments.len() == 2 && ep.path.leading_colon.is_none() {
+                            let ty_var = self
+                                .get_defn(ep.path.segments[0].ident.as_ref(), current_scope_id)
+                                .map(|(definition_id, _)| definition_id)
+                                .filter(|definition_id| {
+                                    matches!(self.definitions[*definition_id], Definition::TypeVariable(..))
+                                });
+                            if let Some(ty_var) = ty_var {
+                                return self.convert_bound_fn_call(
+                                    ec,
+                                    ty_var,
+                                    expected_return_type,
+                                    bytecode,
+                                    current_scope_id,
+                                    var_stack,
+                                );
+                            }
+                        }
+
+                        let definition_id = match self.process_path(&ep.path, current_scope_id)? {
+                            Some(definition_id) => definition_id,
+                            None => {
+                                return Ok(self.report(Diagnostic::new(
+                                    "E0425",
+                                    format!(
+                                        "Could not find function {}",
+                                        self.printable_path(&ep.path)
+                                    ),
+                                ).with_primary(self.span_of(ep), "not found in this scope")))
+                            }
+                        };
+
+                        // Calling a tuple struct or tuple variant constructs it
+                        let constructed = match self.definitions[definition_id] {
+                            Definition::Variant(ref variant) => {
+                                Some((variant.fields.clone(), variant.enum_id))
+                            }
+                            Definition::Struct(ref st) if st.kind == StructKind::Tuple => Some((
+                                st.fields.iter().map(|field| field.1).collect(),
+                                definition_id,
+                            )),
+                            _ => None,
+                        };
+                        if let Some((field_tys, constructed_ty)) = constructed {
+                            if constructed_ty == definition_id {
+                                self.check_constructor_visible(definition_id, &ep.path, current_scope_id);
+                            }
+                            if field_tys.len() != ec.args.len() {
+                                return Ok(self.report(Diagnostic::new(
+                                    "E0061",
+                                    format!(
+                                        "{} has {} field(s) but {} were supplied",
+                                        self.printable_path(&ep.path),
+                                        field_tys.len(),
+                                        ec.args.len()
+                                    ),
+                                ).with_primary(
+                                    self.span_of(ec),
+                                    &format!("expected {} field(s)", field_tys.len()),
+                                )));
+                            }
+
+                            let params: Vec<Param> = field_tys
+                                .iter()
+                                .enumerate()
+                                .map(|(index, field_ty)| Param::new(index.to_string(), 0, *field_ty, false))
+                                .collect();
+                            let arg_tys = self.convert_args_to_bytecode(
+                                &ec.args,
+                                &params,
+                                expected_return_type,
+                                bytecode,
+                                current_scope_id,
+                                var_stack,
+                            )?;
+
+                            // A generic tuple struct is built as the instance for the types of its values, which are
+                            // checked once it's known
+                            let values: Vec<(DefinitionId, DefinitionId)> =
+                                field_tys.iter().cloned().zip(arg_tys.iter().cloned()).collect();
+                            let known_ty_args = self.known_struct_ty_args(
+                                constructed_ty,
+                                &ep.path,
+                                builtin_type::UNKNOWN,
+                                current_scope_id,
+                            )?;
+                            let instance_id = self.infer_struct_instance(constructed_ty, &known_ty_args, &values, ec);
+                            if instance_id != constructed_ty {
+                                if instance_id == builtin_type::ERROR {
+                                    return Ok(builtin_type::ERROR);
+                                }
+                                self.check_generic_args(instance_id, &field_tys, &arg_tys, &ec.args);
+                                bytecode.push(Bytecode::Call(instance_id));
+                                return Ok(instance_id);
+                            }
+
+                            bytecode.push(Bytecode::Call(definition_id));
+                            return Ok(constructed_ty);
+                        }
+
+                        let target_fn = if let Definition::Fun(ref target_fn) =
+                            self.definitions[definition_id]
+                        {
+                            target_fn.clone()
+                        } else {
+                            return Ok(self.report(Diagnostic::new(
+                                "E0618",
+                                format!(
+                                    "Expected function, found {}",
+                                    self.printable_path(&ep.path)
+                                ),
+                            ).with_primary(self.span_of(ep), "not a function")));
+                        };
+
+                        if target_fn.params.len() != ec.args.len() {
+                            return Ok(self.report(Diagnostic::new(
+                                "E0061",
+                                format!(
+                                    "Function {} takes {} argument(s) but {} were supplied",
+                                    self.printable_path(&ep.path),
+                                    target_fn.params.len(),
+                                    ec.args.len()
+                                ),
+                            ).with_primary(
+                                self.span_of(ec),
+                                &format!("expected {} argument(s)", target_fn.params.len()),
+                            )));
+                        }
+
+                        let mut arg_tys = self.convert_args_to_bytecode(
+                            &ec.args,
+                            &target_fn.params,
+                            expected_return_type,
+                            bytecode,
+                            current_scope_id,
+                            var_stack,
+                        )?;
+
+                        if !target_fn.ty_params.is_empty() {
+                            if !self.check_trait_bounds(&target_fn.params, &mut arg_tys, ec) {
+                                return Ok(builtin_type::ERROR);
+                            }
+
+                            let (instance_definition_id, return_ty) =
+                                self.instantiate_generic_fn(definition_id, &arg_tys)?;
+
+                            bytecode.push(Bytecode::Call(instance_definition_id));
+
+                            Ok(return_ty)
+                        } else {
+                            bytecode.push(Bytecode::Call(definition_id));
+
+                            Ok(target_fn.return_ty)
+                        }
+                    }
+                }
+                _ => self.convert_fn_ptr_call(
+                    ec,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                ),
+            },
+            Expr::MethodCall(emc) => {
+                // Convert the receiver on its own first, as we need its type to find the method before we know
+                // how the method wants it passed
+                let mut receiver_bytecode = vec![];
+                let receiver_ty = self.convert_expr_to_bytecode(
+                    &emc.receiver,
+                    expected_return_type,
+                    &mut receiver_bytecode,
+                    current_scope_id,
+                    var_stack,
+                )?;
+                if receiver_ty == builtin_type::ERROR {
+                    return Ok(builtin_type::ERROR);
+                }
+
+                let self_ty = {
+                    let mut self_ty = receiver_ty;
+                    while let Some(referred_ty) = self.pointee(self_ty) {
+                        self_ty = referred_ty;
+                    }
+                    self_ty
+                };
+
+                // Find the call to make and the signature of the method.  On a type parameter, the method comes
+                // from one of the traits it's bound by, and the call is resolved once the function is instantiated.
+                // On a trait object, the method is found through its vtable when the call is made
+                let method_name = emc.method.to_string();
+                let found = if let Some(found) = self.integer_method(self_ty, &method_name) {
+                    Some(found)
+                } else if let Definition::DynTrait(ref dyn_trait) = self.definitions[self_ty] {
+                    let method_index = match self.definitions[dyn_trait.trait_id] {
+                        Definition::Trait(ref trait_def) => trait_def
+                            .methods
+                            .iter()
+                            .position(|method| method.name == method_name),
+                        _ => None,
+                    };
+                    method_index.map(|method_index| {
+                        let (ref params, return_ty) = dyn_trait.methods[method_index];
+                        (
+                            Bytecode::CallDyn(self_ty, method_index),
+                            params.clone(),
+                            return_ty,
+                            false,
+                        )
+                    })
+                } else if let Definition::TypeVariable(..) = self.definitions[self_ty] {
+                    self.bound_method(self_ty, &emc.method)?
+                        .map(|(call, params, return_ty)| (call, params, return_ty, false))
+                } else {
+                    self.check_associated_fn_visible(self_ty, &emc.method, current_scope_id);
+                    match self.process_associated_fn(self_ty, &emc.method)? {
+                        Some(method_id) => {
+                            if let Definition::Fun(ref target_fn) = self.definitions[method_id] {
+                                Some((
+                                    Bytecode::Call(method_id),
+                                    target_fn.params.clone(),
+                                    target_fn.return_ty,
+                                    target_fn.is_generic(),
+                                ))
+                            } else {
+                                unimplemented!("Method call of a definition that isn't a function");
+                            }
+                        }
+                        None => None,
+                    }
+                };
+
+                let (call, params, return_ty, is_generic) = match found {
+                    Some(found) => found,
+                    None if self_ty == builtin_type::ERROR => return Ok(builtin_type::ERROR),
+                    None => {
+                        return Ok(self.report(Diagnostic::new(
+                            "E0599",
+                            format!(
+                                "No method named `{}` found for type {}",
+                                method_name,
+                                self.printable_name(self_ty)
+                            ),
+                        ).with_primary(self.span_of(&emc.method), "method not found")))
+                    }
+                };
+
+                let receiver_param_ty = match params.first() {
+                    Some(param) if param.name == "self" => param.ty,
+                    _ => {
+                        return Ok(self.report(Diagnostic::new(
+                            "E0599",
+                            format!(
+                                "`{}` is an associated function, not a method",
+                                method_name
+                            ),
+                        ).with_primary(self.span_of(&emc.method), "this is an associated function")
+                            .with_note(&format!(
+                                "call it with `{}::{}()` instead",
+                                self.printable_name(self_ty),
+                                method_name
+                            ))))
+                    }
+                };
+
+                if let Definition::Ref(_, receiver_mutable) = self.definitions[receiver_param_ty] {
+                    if self.pointee(receiver_ty).is_some() {
+                        // Already a pointer, so follow it until it points at the receiver itself
+                        bytecode.extend(receiver_bytecode);
+                        let mut ty = receiver_ty;
+                        let mut mutability = Mutability::Mutable;
+                        while let Some(referred_ty) = self.pointee(ty) {
+                            if self.pointee(referred_ty).is_none() {
+                                break;
+                            }
+                            bytecode.push(Bytecode::Deref);
+                            mutability = self.pointee_mutability(ty, mutability);
+                            ty = referred_ty;
+                        }
+                        if receiver_mutable {
+                            let place = format!("*{}", place_name(&emc.receiver));
+                            let mutability = self.pointee_mutability(ty, mutability);
+                            self.check_mutable(
+                                &emc.receiver,
+                                &place,
+                                Mutation::MutableBorrow,
+                                mutability,
+                                var_stack,
+                            );
+                        }
+                    } else {
+                        // Borrow the receiver, keeping it in a temporary if it's not already somewhere in memory
+                        if self.is_place_expr(&emc.receiver, var_stack) {
+                            let mutation = if receiver_mutable {
+                                Some(Mutation::MutableBorrow)
+                            } else {
+                                None
+                            };
+                            self.convert_lhs_expr_to_bytecode(
+                                &emc.receiver,
+                                mutation,
+                                expected_return_type,
+                                bytecode,
+                                current_scope_id,
+                                var_stack,
+                            )?;
+                        } else {
+                            bytecode.extend(receiver_bytecode);
+                            let var_id = var_stack.add_var(String::new(), receiver_ty, true);
+                            let span_id = var_stack.add_span(self.span_of(&emc.receiver));
+                            bytecode.push(Bytecode::VarDecl(var_id));
+                            bytecode.push(Bytecode::LValueVar(var_id, span_id));
+                        }
+                        let kind = if receiver_mutable {
+                            BorrowKind::TwoPhase
+                        } else {
+                            BorrowKind::Shared
+                        };
+                        let span_id = var_stack.add_span(self.span_of(&emc.receiver));
+                        bytecode.push(Bytecode::Ref(kind, span_id));
+                    }
+                } else {
+                    bytecode.extend(receiver_bytecode);
+                    self.auto_deref(receiver_ty, bytecode);
+                }
+
+                if params.len() != emc.args.len() + 1 {
+                    return Ok(self.report(Diagnostic::new(
+                        "E0061",
+                        format!(
+                            "Method {} takes {} argument(s) but {} were supplied",
+                            method_name,
+                            params.len() - 1,
+                            emc.args.len()
+                        ),
+                    ).with_primary(
+                        self.span_of(emc),
+                        &format!("expected {} argument(s)", params.len() - 1),
+                    )));
+                }
+
+                let mut arg_tys = vec![self_ty];
+                arg_tys.extend(self.convert_args_to_bytecode(
+                    &emc.args,
+                    &params[1..],
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                )?);
+
+                match call {
+                    Bytecode::Call(method_id) if is_generic => {
+                        if !self.check_trait_bounds(&params, &mut arg_tys, emc) {
+                            return Ok(builtin_type::ERROR);
+                        }
+
+                        let (instance_definition_id, return_ty) =
+                            self.instantiate_generic_fn(method_id, &arg_tys)?;
+
+                        bytecode.push(Bytecode::Call(instance_definition_id));
+
+                        Ok(return_ty)
+                    }
+                    _ => {
+                        bytecode.push(call);
+
+                        Ok(return_ty)
+                    }
+                }
+            }
+            Expr::Field(ef) => {
+                let definition_id = self.convert_expr_to_bytecode(
+                    &*ef.base,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                )?;
+                let definition_id = self.auto_deref(definition_id, bytecode);
+
+                let (field_name, field_ty) = self.field_type(ef, definition_id, current_scope_id);
+                bytecode.push(Bytecode::Dot(field_name));
+                Ok(field_ty)
+            }
+            Expr::Macro(em) => {
+                if em.mac.path.segments[0].ident.as_ref() == "println" {
+                    let tokens = debug_call_tokens(&em.mac);
+                    let result: Expr = syn::parse2(tokens.into_iter().collect()).map_err(|e| {
+                        Diagnostic::uncoded(format!("couldn't parse println! arguments: {}", e))
+                            .with_primary(self.span_of(&em.mac), "in this macro")
+                    })?;
+                    self.convert_expr_to_bytecode(
+                        &result,
+                        expected_return_type,
+                        bytecode,
+                        current_scope_id,
+                        var_stack,
+                    )
+                } else {
+                    Ok(self.report(self.unsupported(&em.mac, "Macros other than `println!` are not yet supported")))
+                }
+            }
+            Expr::Unsafe(eu) => self.convert_block_to_bytecode(
+                &eu.block,
+                expected_return_type,
+                bytecode,
+                Some(current_scope_id),
+                var_stack,
+            ),
+            _ => Ok(self.report(self.unsupported(expr, "This kind of expression is not yet supported"))),
+        }
+    }
+
+    /// Converts an expression whose type is known from where it's used, eg) from a type annotation or the
+    /// parameter it's given to.  Numbers without a type take that type rather than falling back to i32 or f64,
+    /// including those in tuples, and pointers are coerced into the trait object it may be
+    /// Converts a literal, checking that an integer fits in its type.  A negated literal is checked as negative,
+    /// as it may only fit in its type once it is, like `-128i8`
+    fn convert_lit_to_bytecode(&mut self, lit: &Lit, negated: bool, bytecode: &mut Vec<Bytecode>) -> DefinitionId {
+        let (val, ty) = match *lit {
+            Lit::Int(ref li) => {
+                let ty = match li.suffix() {
+                    IntSuffix::U64 => builtin_type::U64,
+                    IntSuffix::U32 => builtin_type::U32,
+                    IntSuffix::I64 => builtin_type::I64,
+                    IntSuffix::I32 => builtin_type::I32,
+                    IntSuffix::U8 => builtin_type::U8,
+                    IntSuffix::U16 => builtin_type::U16,
+                    IntSuffix::U128 => builtin_type::U128,
+                    IntSuffix::Usize => builtin_type::USIZE,
+                    IntSuffix::I8 => builtin_type::I8,
+                    IntSuffix::I16 => builtin_type::I16,
+                    IntSuffix::I128 => builtin_type::I128,
+                    IntSuffix::Isize => builtin_type::ISIZE,
+                    IntSuffix::None => builtin_type::UNKNOWN_INT,
+                };
+                (u128::from(li.value()), ty)
+            }
+            // Integers too big for a u64 are left for us to parse
+            Lit::Verbatim(ref lv) => match wide_int_literal(&lv.token.to_string()) {
+                Some((Some(val), ty)) => (val, ty),
+                Some((None, _)) => return self.report_literal_too_large(lit),
+                None => return self.report(self.unsupported(lit, "This kind of literal is not yet supported")),
+            },
+            Lit::Float(ref lf) => {
+                let (code, ty) = match lf.suffix() {
+                    FloatSuffix::F32 => (Bytecode::PushF32(lf.value() as f32), builtin_type::F32),
+                    FloatSuffix::F64 => (Bytecode::PushF64(lf.value()), builtin_type::F64),
+                    FloatSuffix::None => (Bytecode::PushUnknownFloat(lf.value()), builtin_type::UNKNOWN_FLOAT),
+                };
+                bytecode.push(code);
+                return ty;
+            }
+            Lit::Bool(ref lb) => {
+                bytecode.push(Bytecode::PushBool(lb.value));
+                return builtin_type::BOOL;
+            }
+            _ => return self.report(self.unsupported(lit, "This kind of literal is not yet supported")),
+        };
+
+        if ty == builtin_type::UNKNOWN_INT {
+            // Without a type, the literal has to fit in an i128 until it's given one
+            if val > i128::MAX as u128 + u128::from(negated) {
+                return self.report_literal_too_large(lit);
+            }
+        } else if !int_literal_fits(val, negated, ty) {
+            return self.report(self.literal_out_of_range(lit, val, negated, ty));
+        }
+
+        bytecode.push(int_literal_code(val, ty));
+        ty
+    }
+
+    fn report_literal_too_large(&mut self, lit: &Lit) -> DefinitionId {
+        self.report(
+            Diagnostic::new("E0080", "Integer literal is too large".to_string())
+                .with_primary(self.span_of(lit), "too large for its type"),
+        )
+    }
+
+    /// An integer literal that doesn't fit in the type it's given, like rustc's `overflowing_literals`
+    fn literal_out_of_range<T: Spanned>(&self, lit: &T, val: u128, negated: bool, ty: DefinitionId) -> Diagnostic {
+        let (min, max) = int_range(ty);
+        let name = self.printable_name(ty);
+        Diagnostic::new("E0080", format!("Literal out of range for {}", name))
+            .with_primary(self.span_of(lit), "literal out of range")
+            .with_note(&format!(
+                "the literal `{}{}` does not fit into the type `{}` whose range is `{}..={}`",
+                if negated { "-" } else { "" },
+                val,
+                name,
+                min,
+                max
+            ))
+    }
+
+    /// Reports the integer literals in a value that nothing gives a type, which are i32s as in Rust, if they don't
+    /// fit in one.  The value is held with the type of each literal, so it has to fit for every backend to agree
+    fn check_fallback_literals(&mut self, expr: &Expr, ty: DefinitionId) {
+        if ty == builtin_type::UNKNOWN_INT {
+            if let Some((val, negated)) = unsuffixed_int_literal(expr) {
+                if !int_literal_fits(val, negated, builtin_type::I32) {
+                    let diagnostic = self.literal_out_of_range(expr, val, negated, builtin_type::I32).with_note(
+                        "an integer literal is an i32 unless it has a suffix or its type is known where it's used",
+                    );
+                    self.report(diagnostic);
+                }
+                return;
+            }
+        }
+
+        match *expr {
+            Expr::Paren(ref ep) => self.check_fallback_literals(&ep.expr, ty),
+            Expr::Unary(ref eu) if ty == builtin_type::UNKNOWN_INT => self.check_fallback_literals(&eu.expr, ty),
+            Expr::Binary(ref eb) if ty == builtin_type::UNKNOWN_INT => {
+                self.check_fallback_literals(&eb.left, ty);
+                self.check_fallback_literals(&eb.right, ty);
+            }
+            _ => {}
+        }
+    }
+
+    pub(crate) fn convert_expr_expecting(
+        &mut self,
+        expr: &Expr,
+        expected_ty: DefinitionId,
+        expected_return_type: DefinitionId,
+        bytecode: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<DefinitionId, Diagnostic> {
+        // A literal without a suffix is pushed as the integer type expected of it, so it has to fit in it
+        if let Some((val, negated)) = unsuffixed_int_literal(expr) {
+            if expected_ty != builtin_type::UNKNOWN_INT && int_bounds(expected_ty).is_some() {
+                if negated && val > 0 && matches!(builtin_type::int_layout(expected_ty), Some((_, false))) {
+                    return Ok(self.report(Diagnostic::new(
+                        "E0600",
+                        format!("Can't negate value of type {}", self.printable_name(expected_ty)),
+                    ).with_primary(self.span_of(expr), "cannot apply unary operator `-`")));
+                }
+                if !int_literal_fits(val, negated, expected_ty) {
+                    return Ok(self.report(self.literal_out_of_range(expr, val, negated, expected_ty)));
+                }
+                let code = int_literal_code(val, expected_ty);
+                bytecode.push(if negated { negated_literal(&code).unwrap_or(code) } else { code });
+                return Ok(expected_ty);
+            }
+        }
+
+        let ty = match expr {
+            Expr::Paren(ep) => {
+                return self.convert_expr_expecting(
+                    &ep.expr,
+                    expected_ty,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                )
+            }
+            Expr::Tuple(et) => self.convert_tuple_to_bytecode(
+                et,
+                expected_ty,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                var_stack,
+            )?,
+            Expr::Array(ea) => self.convert_array_to_bytecode(
+                ea,
+                expected_ty,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                var_stack,
+            )?,
+            Expr::Repeat(er) => self.convert_repeat_to_bytecode(
+                er,
+                expected_ty,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                var_stack,
+            )?,
+            Expr::If(ei) => self.convert_if_to_bytecode(
+                ei,
+                expected_ty,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                var_stack,
+            )?,
+            Expr::Match(em) => self.convert_match_to_bytecode(
+                em,
+                expected_ty,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                var_stack,
+            )?,
+            Expr::Block(eb) => self.convert_block_expecting(
+                &eb.block,
+                expected_ty,
+                expected_return_type,
+                bytecode,
+                Some(current_scope_id),
+                var_stack,
+            )?,
+            Expr::Struct(es) => self.convert_struct_to_bytecode(
+                es,
+                expected_ty,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                var_stack,
+            )?,
+            Expr::Binary(eb) if is_arithmetic(&eb.op) => self.convert_arithmetic_to_bytecode(
+                eb,
+                expected_ty,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                var_stack,
+            )?,
+            _ => self.convert_expr_to_bytecode(expr, expected_return_type, bytecode, current_scope_id, var_stack)?,
+        };
+
+        match ty {
+            builtin_type::UNKNOWN_INT if int_bounds(expected_ty).is_some() => {
+                bytecode.push(Bytecode::As(expected_ty));
+                Ok(expected_ty)
+            }
+            builtin_type::UNKNOWN_FLOAT if builtin_type::is_float(expected_ty) => {
+                bytecode.push(Bytecode::As(expected_ty));
+                Ok(expected_ty)
+            }
+            _ => self.coerce_to_dyn(ty, expected_ty, bytecode, expr),
+        }
+    }
+
+    /// Converts a struct built with named fields.  A generic struct takes the type that's expected of it, as an
+    /// annotated `let` does
+    fn convert_struct_to_bytecode(
+        &mut self,
+        es: &ExprStruct,
+        expected_ty: DefinitionId,
+        expected_return_type: DefinitionId,
+        bytecode: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<DefinitionId, Diagnostic> {
+        let definition_id = match self.process_path(&es.path, current_scope_id)? {
+            Some(definition_id) => definition_id,
+            None => {
+                return Ok(self.report(Diagnostic::new(
+                    "E0422",
+                    format!("Can't find struct {}", self.printable_path(&es.path)),
+                ).with_primary(self.span_of(&es.path), "not found in this scope")))
+            }
+        };
+
+        let struct_fields = if let Definition::Struct(ref st) =
+            self.definitions[definition_id]
+        {
+            st.fields.clone()
+        } else if self.has_named_fields(definition_id) {
+            return Ok(builtin_type::ERROR);
+        } else {
+            return Ok(self.report(Diagnostic::new(
+                "E0574",
+                format!("Expected struct, found {}", self.printable_path(&es.path)),
+            ).with_primary(self.span_of(&es.path), "not a struct")));
+        };
+
+        let mut fields = vec![];
+        for field in &es.fields {
+            let name = member_name(&field.member);
+            if !struct_fields.iter().any(|x| x.0 == name) {
+                self.report(Diagnostic::new(
+                    "E0560",
+                    format!(
+                        "Struct {} has no field named {}",
+                        self.printable_path(&es.path),
+                        name
+                    ),
+                ).with_primary(self.span_of(&field.member), "unknown field"));
+                continue;
+            }
+            let span = self.span_of(&field.member);
+            self.check_field_visible("E0451", definition_id, &name, span, current_scope_id);
+            fields.push((name, &field.expr));
+        }
+
+        let missing_fields: Vec<String> = struct_fields
+            .iter()
+            .filter(|x| !fields.iter().any(|y| y.0 == x.0))
+            .map(|x| format!("`{}`", x.0))
+            .collect();
+        if !missing_fields.is_empty() {
+            self.report(Diagnostic::new(
+                "E0063",
+                format!(
+                    "Missing fields {} in initializer of {}",
+                    missing_fields.join(", "),
+                    self.printable_path(&es.path)
+                ),
+            ).with_primary(self.span_of(&es.path), "missing fields"));
+        }
+
+        // The values are given in the order the struct keeps its fields
+        fields.sort_by_key(|x| struct_fields.iter().position(|y| y.0 == x.0));
+
+        // The types a generic struct is given, or is expected to have, decide the types of its values.  Otherwise
+        // they're inferred from its values
+        let known_ty_args = self.known_struct_ty_args(definition_id, &es.path, expected_ty, current_scope_id)?;
+        let known_fields = match self.definitions[definition_id] {
+            Definition::Struct(ref st) if st.is_generic() && known_ty_args.len() == st.ty_params.len() => {
+                let ty_args = known_ty_args.iter().map(|x| x.1).collect();
+                let instance_id = self.struct_instance(definition_id, ty_args);
+                match self.definitions[instance_id] {
+                    Definition::Struct(ref instance) => instance.fields.clone(),
+                    _ => struct_fields.clone(),
+                }
+            }
+            _ => struct_fields.clone(),
+        };
+
+        let mut values = vec![];
+        let mut field_tys = vec![];
+        for field in &fields {
+            let expected_field_ty = known_fields.iter().find(|x| x.0 == field.0).unwrap().1;
+            let field_ty = self.convert_expr_expecting(
+                field.1,
+                expected_field_ty,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                var_stack,
+            )?;
+
+            let generic_field_ty = struct_fields.iter().find(|x| x.0 == field.0).unwrap().1;
+            values.push((generic_field_ty, field_ty));
+            field_tys.push(field_ty);
+        }
+
+        // A generic struct is built as the instance for the types of its values
+        let struct_id = self.infer_struct_instance(definition_id, &known_ty_args, &values, es);
+        let struct_fields = match self.definitions[struct_id] {
+            Definition::Struct(ref st) => st.fields.clone(),
+            _ => return Ok(builtin_type::ERROR),
+        };
+
+        for (field, field_ty) in fields.into_iter().zip(field_tys) {
+            let expected_ty = struct_fields.iter().find(|x| x.0 == field.0).unwrap().1;
+            if !self.assignment_compatible(expected_ty, field_ty) {
+                self.report(Diagnostic::new(
+                    "E0308",
+                    format!(
+                        "Field {} expects {}, found {}",
+                        field.0,
+                        self.printable_name(expected_ty),
+                        self.printable_name(field_ty)
+                    ),
+                ).with_primary(
+                    self.span_of(field.1),
+                    &format!("expected {}", self.printable_name(expected_ty)),
+                ));
+            }
+        }
+
+        bytecode.push(Bytecode::Call(struct_id));
+        Ok(struct_id)
+    }
+
+    /// Converts a match.  Each arm tests the value being matched in turn.  The arms become a chain of ifs, each one
+    /// in the else of the one before it
+    fn convert_match_to_bytecode(
+        &mut self,
+        em: &ExprMatch,
+        expected_ty: DefinitionId,
+        expected_return_type: DefinitionId,
+        bytecode: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<DefinitionId, Diagnostic> {
+        let (access, scrutinee_ty) = self.convert_scrutinee_to_bytecode(
+            &em.expr,
+            expected_return_type,
+            bytecode,
+            current_scope_id,
+            var_stack,
+        )?;
+
+        let mut match_ty: Option<(DefinitionId, &Expr)> = None;
+        let mut open_ifs = vec![];
+        let mut placeholders = vec![];
+        let mut has_catch_all = false;
+        let mut arm_patterns = vec![];
+
+        for arm in &em.arms {
+            if has_catch_all {
+                // Nothing is left for this arm to match, which checking the patterns will report
+                arm_patterns.push((Pattern::Wild, self.span_of(&arm.pats)));
+                continue;
+            }
+            if arm.pats.len() != 1 {
+                return Ok(self.report(self.unsupported(&arm.pats, "Alternative patterns are not yet supported")));
+            }
+            if let Some((_, ref guard)) = arm.guard {
+                return Ok(self.report(self.unsupported(guard, "Match guards are not yet supported")));
+            }
+
+            let mut arm_var_stack = var_stack.clone();
+            let mut bindings = vec![];
+            let (test, pattern) = self.convert_pattern_to_bytecode(
+                &arm.pats[0],
+                scrutinee_ty,
+                &access,
+                &mut bindings,
+                current_scope_id,
+                &mut arm_var_stack,
+            )?;
+            arm_patterns.push((pattern, self.span_of(&arm.pats[0])));
+
+            let if_pos = match test {
+                Some(test) => {
+                    bytecode.extend(test);
+                    bytecode.push(Bytecode::If(0, builtin_type::VOID));
+                    Some(bytecode.len() - 1)
+                }
+                None => None,
+            };
+
+            bytecode.extend(bindings);
+            let arm_ty = self.convert_expr_expecting(
+                &arm.body,
+                expected_ty,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                &mut arm_var_stack,
+            )?;
+            var_stack.leave_block(arm_var_stack);
+            placeholders.extend(diverged_placeholder(arm_ty, bytecode));
+
+            // Arms giving numbers without a type take the type of the other arms, as do arms that never
+            // finish
+            match match_ty {
+                None => match_ty = Some((arm_ty, &arm.body)),
+                Some((ty, _))
+                    if ty == builtin_type::ERROR
+                        || ty == builtin_type::NEVER
+                        || ((ty == builtin_type::UNKNOWN_INT || ty == builtin_type::UNKNOWN_FLOAT)
+                            && self.operator_compatible(ty, arm_ty)) =>
+                {
+                    match_ty = Some((arm_ty, &arm.body))
+                }
+                Some((ty, first_body)) => {
+                    if !self.operator_compatible(ty, arm_ty) {
+                        self.report(Diagnostic::new(
+                            "E0308",
+                            "Match arms have incompatible types".to_string(),
+                        ).with_primary(
+                            self.span_of(&arm.body),
+                            &format!(
+                                "expected {}, found {}",
+                                self.printable_name(ty),
+                                self.printable_name(arm_ty)
+                            ),
+                        )
+                            .with_secondary(
+                                self.span_of(first_body),
+                                &format!("this arm has type {}", self.printable_name(ty)),
+                            ));
+                    }
+                }
+            }
+
+            if let Some(if_pos) = if_pos {
+                open_ifs.push((if_pos, bytecode.len()));
+                bytecode.push(Bytecode::Else(0, builtin_type::VOID));
+            } else {
+                has_catch_all = true;
+            }
+        }
+
+        self.check_match_patterns(scrutinee_ty, &arm_patterns, self.span_of(&em.expr));
+
+        let match_ty = match match_ty {
+            Some((ty, _)) => ty,
+            None => builtin_type::VOID,
+        };
+        fill_placeholders(&placeholders, match_ty, bytecode);
+
+        // Checking the patterns has made sure one of the arms matches, but the last if still needs an else
+        if !has_catch_all {
+            bytecode.push(Bytecode::Unreachable(pushed_type(match_ty)));
+        }
+
+        // Close the chain of ifs, now that we know where each ends
+        for (if_pos, else_pos) in open_ifs.into_iter().rev() {
+            let end_if_pos = bytecode.len();
+            bytecode.push(Bytecode::EndIf(pushed_type(match_ty)));
+            bytecode[if_pos] = Bytecode::If(else_pos - if_pos + 1, pushed_type(match_ty));
+            bytecode[else_pos] = Bytecode::Else(end_if_pos - else_pos, pushed_type(match_ty));
+        }
+
+        Ok(match_ty)
+    }
+
+    /// Converts an `if`, whose blocks are expected to have the given type
+    fn convert_if_to_bytecode(
+        &mut self,
+        ei: &ExprIf,
+        expected_ty: DefinitionId,
+        expected_return_type: DefinitionId,
+        bytecode: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<DefinitionId, Diagnostic> {
+        let cond_type = self.convert_expr_to_bytecode(
+            &*ei.cond,
+            expected_return_type,
+            bytecode,
+            current_scope_id,
+            var_stack,
+        )?;
+
+        match cond_type {
+            builtin_type::BOOL | builtin_type::ERROR => {}
+            _ => {
+                self.report(Diagnostic::new(
+                    "E0308",
+                    "If condition needs to be boolean".to_string(),
+                ).with_primary(
+                    self.span_of(&*ei.cond),
+                    &format!("expected bool, found {}", self.printable_name(cond_type)),
+                ));
+            }
+        }
+
+        bytecode.push(Bytecode::If(0, builtin_type::VOID));
+        let before_then_block_len = bytecode.len();
+
+        let then_ty = self.convert_block_expecting(
+            &ei.then_branch,
+            expected_ty,
+            expected_return_type,
+            bytecode,
+            Some(current_scope_id),
+            var_stack,
+        )?;
+        let mut placeholders: Vec<usize> = diverged_placeholder(then_ty, bytecode).into_iter().collect();
+        let after_then_block_len = bytecode.len();
+
+        let mut if_ty = pushed_type(then_ty);
+        if let Some(ref else_branch) = ei.else_branch {
+            let then_span = self.span_of(&ei.then_branch);
+            let (else_if_ty, else_placeholder) = self.convert_else_to_bytecode(
+                &else_branch.1,
+                (then_ty, then_span),
+                (expected_ty, expected_return_type),
+                bytecode,
+                current_scope_id,
+                var_stack,
+            )?;
+            if_ty = else_if_ty;
+            placeholders.extend(else_placeholder);
+        }
+        fill_placeholders(&placeholders, if_ty, bytecode);
+        bytecode.push(Bytecode::EndIf(pushed_type(if_ty)));
+
+        // Patch the original offset to the correct offset
+        bytecode[before_then_block_len - 1] = Bytecode::If(
+            after_then_block_len - before_then_block_len + 2,
+            pushed_type(if_ty),
+        );
+
+        Ok(if_ty)
+    }
+
+    /// Converts a tuple.  Its elements are converted expecting the types of the expected tuple, if there is one
+    fn convert_tuple_to_bytecode(
+        &mut self,
+        et: &ExprTuple,
+        expected_ty: DefinitionId,
+        expected_return_type: DefinitionId,
+        bytecode: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<DefinitionId, Diagnostic> {
+        // `()` is the unit value, which is nothing at all
+        if et.elems.is_empty() {
+            return Ok(builtin_type::VOID);
+        }
+
+        let expected_element_tys = match self.definitions[expected_ty] {
+            Definition::Tuple(ref element_tys) if element_tys.len() == et.elems.len() => element_tys.clone(),
+            _ => vec![builtin_type::UNKNOWN; et.elems.len()],
+        };
+
+        let mut element_tys = vec![];
+        for (elem, expected_element_ty) in et.elems.iter().zip(expected_element_tys) {
+            let element_ty = self.convert_expr_expecting(
+                elem,
+                expected_element_ty,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                var_stack,
+            )?;
+            match element_ty {
+                builtin_type::ERROR => return Ok(builtin_type::ERROR),
+                // Nothing else decides the type of a number held in a tuple, so it falls back to i32 or f64
+                _ => {
+                    self.check_fallback_literals(elem, element_ty);
+                    element_tys.push(builtin_type::fallback(element_ty));
+                }
+            }
+        }
+
+        let tuple_ty = self.tuple_type(element_tys);
+        bytecode.push(Bytecode::Call(tuple_ty));
+        Ok(tuple_ty)
+    }
+
+    /// Converts an array literal, whose elements are expected to have the element type of the given type
+    fn convert_array_to_bytecode(
+        &mut self,
+        ea: &ExprArray,
+        expected_ty: DefinitionId,
+        expected_return_type: DefinitionId,
+        bytecode: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<DefinitionId, Diagnostic> {
+        let expected_element_ty = self.expected_element_type(expected_ty);
+        let mut element_ty = builtin_type::UNKNOWN_INT;
+        let mut first_elem = None;
+        for elem in &ea.elems {
+            let ty = self.convert_expr_expecting(
+                elem,
+                expected_element_ty,
+                expected_return_type,
+                bytecode,
+                current_scope_id,
+                var_stack,
+            )?;
+
+            match first_elem {
+                None => {
+                    element_ty = ty;
+                    first_elem = Some(elem);
+                }
+                Some(first_elem) => {
+                    if !self.operator_compatible(element_ty, ty) {
+                        self.report(Diagnostic::new(
+                            "E0308",
+                            format!(
+                                "Array elements have incompatible types: expected {}, found {}",
+                                self.printable_name(element_ty),
+                                self.printable_name(ty)
+                            ),
+                        ).with_primary(
+                            self.span_of(elem),
+                            &format!("expected {}", self.printable_name(element_ty)),
+                        ).with_secondary(
+                            self.span_of(first_elem),
+                            &format!("first element has type {}", self.printable_name(element_ty)),
+                        ));
+                        return Ok(builtin_type::ERROR);
+                    }
+                    element_ty = self.tighter_of_types(element_ty, ty);
+                }
+            }
+        }
+
+        if first_elem.is_none() {
+            if expected_element_ty != builtin_type::UNKNOWN {
+                let array_ty = self.array_type(expected_element_ty, 0);
+                bytecode.push(Bytecode::Call(array_ty));
+                return Ok(array_ty);
+            }
+            return Ok(self.report(Diagnostic::new(
+                "E0282",
+                "Can not infer the type of the elements of an empty array".to_string(),
+            ).with_primary(self.span_of(ea), "type annotations needed")));
+        }
+        if element_ty == builtin_type::ERROR {
+            return Ok(builtin_type::ERROR);
+        }
+
+        // Nothing else decides the type of number elements, so they fall back to i32 or f64
+        for elem in &ea.elems {
+            self.check_fallback_literals(elem, element_ty);
+        }
+        let element_ty = builtin_type::fallback(element_ty);
+        let array_ty = self.array_type(element_ty, ea.elems.len());
+        bytecode.push(Bytecode::Call(array_ty));
+        Ok(array_ty)
+    }
+
+    /// Converts an array made by repeating one value, which is expected to have the element type of the given type
+    fn convert_repeat_to_bytecode(
+        &mut self,
+        er: &ExprRepeat,
+        expected_ty: DefinitionId,
+        expected_return_type: DefinitionId,
+        bytecode: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<DefinitionId, Diagnostic> {
+        let expected_element_ty = self.expected_element_type(expected_ty);
+        let element_ty = self.convert_expr_expecting(
+            &er.expr,
+            expected_element_ty,
+            expected_return_type,
+            bytecode,
+            current_scope_id,
+            var_stack,
+        )?;
+        let len = self.array_len(&er.len);
+        if element_ty == builtin_type::ERROR || len.is_none() {
+            return Ok(builtin_type::ERROR);
+        }
+
+        self.check_fallback_literals(&er.expr, element_ty);
+        let element_ty = builtin_type::fallback(element_ty);
+        let array_ty = self.array_type(element_ty, len.unwrap());
+        bytecode.push(Bytecode::ArrayRepeat(array_ty));
+        Ok(array_ty)
+    }
+
+    /// The type the elements of an array literal should have when the array is expected to have the given type
+    fn expected_element_type(&self, expected_ty: DefinitionId) -> DefinitionId {
+        match self.definitions[expected_ty] {
+            Definition::Array(element_ty, _) => element_ty,
+            _ => builtin_type::UNKNOWN,
+        }
+    }
+
+    pub(crate) fn resolve_type(
+        &mut self,
+        tp: &Type,
+        current_scope_id: ScopeId,
+    ) -> Result<DefinitionId, Diagnostic> {
+        match *tp {
+            Type::Path(ref tp) => match builtin_type::named(tp.path.segments[0].ident.as_ref()) {
+                Some(builtin_ty) => Ok(builtin_ty),
+                None if tp.path.segments.len() == 1 && tp.path.segments[0].ident == "Box" => {
+                    let boxed_ty = match tp.path.segments[0].arguments {
+                        PathArguments::AngleBracketed(ref generic_args) => {
+                            match generic_args.args.iter().next() {
+                                Some(GenericArgument::Type(ref ty)) => {
+                                    self.resolve_type(ty, current_scope_id)?
+                                }
+                                _ => return Ok(self.report(box_missing_type(self.span_of(tp)))),
+                            }
+                        }
+                        _ => return Ok(self.report(box_missing_type(self.span_of(tp)))),
+                    };
+                    Ok(self.box_type(boxed_ty))
+                }
+                None => {
+                    if let Some(definition_id) = self.process_path(&tp.path, current_scope_id)? {
+                        if let Definition::Struct(_) = self.definitions[definition_id] {
+                            self.resolve_struct_type(definition_id, tp, current_scope_id)
+                        } else if let Definition::Enum(_) = self.definitions[definition_id] {
+                            Ok(definition_id)
+                        } else if let Definition::TypeVariable(..) = self.definitions[definition_id] {
+                            Ok(definition_id)
+                        } else {
+                            Ok(self.report(Diagnostic::new(
+                                "E0573",
+                                format!("Expected type, found {}", self.printable_path(&tp.path)),
+                            ).with_primary(self.span_of(tp), "not a type")))
+                        }
+                    } else {
+                        Ok(self.report(Diagnostic::new(
+                            "E0412",
+                            format!("Could not find type {}", self.printable_path(&tp.path)),
+                        ).with_primary(self.span_of(tp), "not found in this scope")))
+                    }
+                }
+            },
+            Type::Tuple(ref tt) => {
+                if tt.elems.is_empty() {
+                    return Ok(builtin_type::VOID);
+                }
+                let mut element_tys = vec![];
+                for elem in &tt.elems {
+                    element_tys.push(self.resolve_type(elem, current_scope_id)?);
+                }
+                Ok(self.tuple_type(element_tys))
+            }
+            Type::Paren(ref tp) => self.resolve_type(&tp.elem, current_scope_id),
+            Type::BareFn(ref tbf) => {
+                let mut param_tys = vec![];
+                for input in &tbf.inputs {
+                    param_tys.push(self.resolve_type(&input.ty, current_scope_id)?);
+                }
+                let return_ty = match tbf.output {
+                    ReturnType::Default => builtin_type::VOID,
+                    ReturnType::Type(_, ref ty) => self.resolve_type(ty, current_scope_id)?,
+                };
+                Ok(self.fn_ptr_type(param_tys, return_ty))
+            }
+            Type::Array(ref ta) => {
+                let element_ty = self.resolve_type(&ta.elem, current_scope_id)?;
+                match self.array_len(&ta.len) {
+                    Some(len) => Ok(self.array_type(element_ty, len)),
+                    None => Ok(builtin_type::ERROR),
+                }
+            }
+            Type::Ptr(_) => {
+                //TODO: FIXME: Currently we only support void pointers, so we assume that's what it is
+                Ok(builtin_type::VOID_PTR)
+            }
+            Type::Reference(ref tr) => {
+                let referred_ty = self.resolve_type(&tr.elem, current_scope_id)?;
+                Ok(self.ref_type(referred_ty, tr.mutability.is_some()))
+            }
+            Type::TraitObject(ref tto) => {
+                let trait_ids = self.resolve_bounds(&tto.bounds, current_scope_id);
+                match trait_ids.len() {
+                    0 => Ok(builtin_type::ERROR), // the trait couldn't be found, which was reported
+                    1 => self.dyn_type(trait_ids[0], tto),
+                    _ => Ok(self.report(self.unsupported(
+                        tto,
+                        "Trait objects of more than one trait are not yet supported",
+                    ))),
+                }
+            }
+            _ => Ok(self.report(self.unsupported(tp, "This kind of type is not yet supported"))),
+        }
+    }
+
+    /// Finds the length given for an array type or repeat expression, reporting an error if it isn't an integer
+    /// literal
+    fn array_len(&mut self, len: &Expr) -> Option<usize> {
+        if let Expr::Lit(el) = len {
+            if let Lit::Int(ref li) = el.lit {
+                return Some(li.value() as usize);
+            }
+        }
+
+        self.report(Diagnostic::new(
+            "E0435",
+            "Array lengths must be integer literals".to_string(),
+        ).with_primary(self.span_of(len), "not an integer literal"));
+        None
+    }
+
+    /// Gets the trait object type for the trait, checking that the trait can be used as one
+    fn dyn_type<T: Spanned>(
+        &mut self,
+        trait_id: DefinitionId,
+        ty_node: &T,
+    ) -> Result<DefinitionId, Diagnostic> {
+        for (definition_id, definition) in self.definitions.iter().enumerate() {
+            if let Definition::DynTrait(ref dyn_trait) = *definition {
+                if dyn_trait.trait_id == trait_id {
+                    return Ok(definition_id);
+                }
+            }
+        }
+
+        let trait_def = match self.definitions[trait_id] {
+            Definition::Trait(ref trait_def) => trait_def.clone(),
+            _ => unimplemented!("Trait object of a definition that isn't a trait"),
+        };
+
+        let violations = object_safety_violations(&trait_def);
+        if !violations.is_empty() {
+            let mut diagnostic = Diagnostic::new(
+                "E0038",
+                format!("The trait `{}` cannot be made into an object", trait_def.name),
+            ).with_primary(
+                self.span_of(ty_node),
+                "the trait cannot be made into an object",
+            );
+            for violation in violations {
+                diagnostic = diagnostic.with_note(&violation);
+            }
+            return Ok(self.report(diagnostic));
+        }
+
+        // Add the type before resolving the method signatures, as they may refer to it
+        self.definitions.push(Definition::DynTrait(DynTrait {
+            trait_id,
+            methods: vec![],
+        }));
+        let dyn_ty = self.definitions.len() - 1;
+
+        let mut methods = vec![];
+        for method in &trait_def.methods {
+            match self.trait_method_signature(trait_id, &method.name, dyn_ty)? {
+                Some(signature) => methods.push(signature),
+                None => unimplemented!("Trait method {} went missing", method.name),
+            }
+        }
+        if let Definition::DynTrait(ref mut dyn_trait) = self.definitions[dyn_ty] {
+            dyn_trait.methods = methods;
+        }
+
+        Ok(dyn_ty)
+    }
+
+    /// The struct type named by the path.  A generic struct is named with the types it's instantiated with
+    fn resolve_struct_type(
+        &mut self,
+        struct_id: DefinitionId,
+        tp: &syn::TypePath,
+        current_scope_id: ScopeId,
+    ) -> Result<DefinitionId, Diagnostic> {
+        let mut ty_args = vec![];
+        if let Some(segment) = tp.path.segments.iter().last() {
+            if let PathArguments::AngleBracketed(ref generic_args) = segment.arguments {
+                for generic_arg in &generic_args.args {
+                    if let GenericArgument::Type(ref ty) = *generic_arg {
+                        ty_args.push(self.resolve_type(ty, current_scope_id)?);
+                    }
+                }
+            }
+        }
+
+        let num_ty_params = match self.definitions[struct_id] {
+            Definition::Struct(ref st) => st.ty_params.len(),
+            _ => 0,
+        };
+        if ty_args.len() != num_ty_params {
+            return Ok(self.report(Diagnostic::new(
+                "E0107",
+                format!(
+                    "Struct {} takes {} type argument(s) but {} were supplied",
+                    self.printable_path(&tp.path),
+                    num_ty_params,
+                    ty_args.len()
+                ),
+            ).with_primary(
+                self.span_of(tp),
+                &format!("expected {} type argument(s)", num_ty_params),
+            )));
+        }
+
+        if num_ty_params == 0 {
+            Ok(struct_id)
+        } else {
+            Ok(self.struct_instance(struct_id, ty_args))
+        }
+    }
+
+    /// The types already known for the type parameters of a generic struct being built, given with turbofish, like
+    /// `Pair::<u8> { .. }`, or from the instance of it that's expected
+    fn known_struct_ty_args(
+        &mut self,
+        struct_id: DefinitionId,
+        path: &syn::Path,
+        expected_ty: DefinitionId,
+        current_scope_id: ScopeId,
+    ) -> Result<Vec<(DefinitionId, DefinitionId)>, Diagnostic> {
+        let ty_params = match self.definitions[struct_id] {
+            Definition::Struct(ref st) if st.is_generic() => st.ty_params.clone(),
+            _ => return Ok(vec![]),
+        };
+
+        if let Some(segment) = path.segments.iter().last() {
+            if let PathArguments::AngleBracketed(ref generic_args) = segment.arguments {
+                let mut ty_args = vec![];
+                for generic_arg in &generic_args.args {
+                    if let GenericArgument::Type(ref ty) = *generic_arg {
+                        ty_args.push(self.resolve_type(ty, current_scope_id)?);
+                    }
+                }
+                if ty_args.len() != ty_params.len() {
+                    self.report(Diagnostic::new(
+                        "E0107",
+                        format!(
+                            "Struct {} takes {} type argument(s) but {} were supplied",
+                            self.printable_path(path),
+                            ty_params.len(),
+                            ty_args.len()
+                        ),
+                    ).with_primary(
+                        self.span_of(segment),
+                        &format!("expected {} type argument(s)", ty_params.len()),
+                    ));
+                    return Ok(ty_params.into_iter().map(|ty_param| (ty_param, builtin_type::ERROR)).collect());
+                }
+                return Ok(ty_params.into_iter().zip(ty_args).collect());
+            }
+        }
+
+        // An expected type like the `W<T>` of a param of a generic function doesn't know what its type variables
+        // stand for, so those are left to the values of the fields
+        match self.definitions[expected_ty] {
+            Definition::Struct(ref st) => match st.instance_of {
+                Some((generic_id, ref ty_args)) if generic_id == struct_id => Ok(ty_params
+                    .into_iter()
+                    .zip(ty_args.iter().cloned())
+                    .filter(|(_, ty_arg)| !matches!(self.definitions[*ty_arg], Definition::TypeVariable(..)))
+                    .collect()),
+                _ => Ok(vec![]),
+            },
+            _ => Ok(vec![]),
+        }
+    }
+
+    /// Finds the instance of a generic struct being built, from the types of the values given for its fields.
+    /// Each value is given with the type of the field it's for, after any types already known for its type
+    /// parameters.  Any other struct is its own type
+    fn infer_struct_instance<T: Spanned>(
+        &mut self,
+        struct_id: DefinitionId,
+        known_ty_args: &[(DefinitionId, DefinitionId)],
+        values: &[(DefinitionId, DefinitionId)],
+        node: &T,
+    ) -> DefinitionId {
+        let (name, ty_params) = match self.definitions[struct_id] {
+            Definition::Struct(ref st) if st.is_generic() => (st.name.clone(), st.ty_params.clone()),
+            _ => return struct_id,
+        };
+
+        let mut unification = known_ty_args.to_vec();
+        for (field_ty, value_ty) in values {
+            self.unify(*field_ty, *value_ty, &mut unification);
+        }
+
+        // Nothing else decides the type of a number given for a type parameter, so it falls back to i32 or f64
+        let mut ty_args = vec![];
+        for ty_param in ty_params {
+            match unification.iter().find(|x| x.0 == ty_param) {
+                Some(&(_, builtin_type::ERROR)) => return builtin_type::ERROR,
+                Some(&(_, ty_arg)) => ty_args.push(builtin_type::fallback(ty_arg)),
+                None => {
+                    return self.report(Diagnostic::new("E0282", "Type annotations needed".to_string()).with_primary(
+                        self.span_of(node),
+                        &format!("cannot infer the type parameters of `{}`", name),
+                    ))
+                }
+            }
+        }
+        self.struct_instance(struct_id, ty_args)
+    }
+
+    /// Coerces a pointer into a trait object if that's what's expected, eg) `Box<Square>` into `Box<dyn Shape>`
+    /// Returns the type of the value left on the stack
+    fn coerce_to_dyn<T: Spanned>(
+        &mut self,
+        from_ty: DefinitionId,
+        to_ty: DefinitionId,
+        bytecode: &mut Vec<Bytecode>,
+        expr: &T,
+    ) -> Result<DefinitionId, Diagnostic> {
+        let (from_pointee, to_pointee) = match (&self.definitions[from_ty], &self.definitions[to_ty]) {
+            (Definition::Ref(from_pointee, from_mutable), Definition::Ref(to_pointee, to_mutable))
+                if *from_mutable || !*to_mutable =>
+            {
+                (*from_pointee, *to_pointee)
+            }
+            (Definition::Box(from_pointee), Definition::Box(to_pointee)) => {
+                (*from_pointee, *to_pointee)
+            }
+            _ => return Ok(from_ty),
+        };
+
+        let trait_id = match self.definitions[to_pointee] {
+            Definition::DynTrait(ref dyn_trait) if from_pointee != to_pointee => dyn_trait.trait_id,
+            _ => return Ok(from_ty),
+        };
+
+        if !self.implements_trait(from_pointee, trait_id) {
+            let diagnostic = self.unsatisfied_bound(from_pointee, trait_id, expr);
+            return Ok(self.report(diagnostic));
+        }
+        if self.has_type_variable(from_pointee) {
+            return Ok(self.report(self.unsupported(expr, "Trait objects of type parameters are not yet supported")));
+        }
+
+        let vtable_id = self.vtable(to_pointee, from_pointee)?;
+        bytecode.push(Bytecode::MakeDyn(vtable_id));
+
+        Ok(to_ty)
+    }
+
+    /// Converts the else branch of an `if` or `if let`, checking that it has the same type as the then block.
+    /// Returns the type of the whole `if`, which is the else branch's if the then block gave an integer without a
+    /// type
+    fn convert_else_to_bytecode(
+        &mut self,
+        else_branch: &Expr,
+        (then_ty, then_span): (DefinitionId, SourceSpan),
+        (expected_ty, expected_return_type): (DefinitionId, DefinitionId),
+        bytecode: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<(DefinitionId, Option<usize>), Diagnostic> {
+        let else_pos = bytecode.len();
+        bytecode.push(Bytecode::Else(0, builtin_type::VOID));
+
+        let (else_ty, else_span) = match else_branch {
+            Expr::Block(eb) => (
+                self.convert_block_expecting(
+                    &eb.block,
+                    expected_ty,
+                    expected_return_type,
+                    bytecode,
+                    Some(current_scope_id),
+                    var_stack,
+                )?,
+                self.span_of(&eb.block),
+            ),
+            // `else if` and `else if let` are ifs of their own
+            Expr::If(_) | Expr::IfLet(_) => (
+                self.convert_expr_expecting(
+                    else_branch,
+                    expected_ty,
+                    expected_return_type,
+                    bytecode,
+                    current_scope_id,
+                    var_stack,
+                )?,
+                self.span_of(else_branch),
+            ),
+            _ => unimplemented!("Unsupported else block"),
+        };
+
+        if !self.operator_compatible(then_ty, else_ty) {
+            self.report(Diagnostic::new(
+                "E0308",
+                "If then/else blocks have mismatching types".to_string(),
+            ).with_primary(
+                else_span,
+                &format!(
+                    "expected {}, found {}",
+                    self.printable_name(then_ty),
+                    self.printable_name(else_ty)
+                ),
+            )
+                .with_secondary(
+                    then_span,
+                    &format!("this block has type {}", self.printable_name(then_ty)),
+                ));
+        }
+        let placeholder = diverged_placeholder(else_ty, bytecode);
+        let if_ty = match then_ty {
+            builtin_type::UNKNOWN_INT | builtin_type::UNKNOWN_FLOAT | builtin_type::ERROR | builtin_type::NEVER => {
+                else_ty
+            }
+            _ => then_ty,
+        };
+        bytecode[else_pos] = Bytecode::Else(bytecode.len() - else_pos, pushed_type(if_ty));
+
+        Ok((if_ty, placeholder))
+    }
+
+    /// Converts the pattern of a `let` that destructures its value, which must always match
+    /// `access` is the bytecode that pushes the value being destructured
+    fn convert_local_pattern(
+        &mut self,
+        pat: &Pat,
+        ty: DefinitionId,
+        access: &[Bytecode],
+        bytecode: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<(), Diagnostic> {
+        let (_, pattern) =
+            self.convert_pattern_to_bytecode(pat, ty, access, bytecode, current_scope_id, var_stack)?;
+        self.check_irrefutable(&pattern, ty, self.span_of(pat), "local binding");
+
+        Ok(())
+    }
+
+    /// Converts the value being matched by a `match`, `if let` or `while let`.  Returns the bytecode that pushes
+    /// the value, for the patterns to test, along with its type
+    /// A variable is matched where it is, so that `ref` bindings refer into it.  Anything else is put in a
+    /// temporary first.  Either way, matching through a reference matches what it refers to
+    fn convert_scrutinee_to_bytecode(
+        &mut self,
+        expr: &Expr,
+        expected_return_type: DefinitionId,
+        bytecode: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<(Vec<Bytecode>, DefinitionId), Diagnostic> {
+        if let Expr::Path(ep) = expr {
+            if ep.path.segments.len() == 1 && ep.path.leading_colon.is_none() {
+                if let Some(var_id) = var_stack.find_var(ep.path.segments[0].ident.as_ref()) {
+                    let span_id = var_stack.add_span(self.span_of(ep));
+                    let mut access = vec![Bytecode::Var(var_id, span_id)];
+                    let ty = self.auto_deref(var_stack.vars[var_id].ty, &mut access);
+                    return Ok((access, ty));
+                }
+            }
+        }
+
+        let ty = self.convert_expr_to_bytecode(
+            expr,
+            expected_return_type,
+            bytecode,
+            current_scope_id,
+            var_stack,
+        )?;
+        let ty = self.auto_deref(ty, bytecode);
+        let var_id = var_stack.add_var(String::new(), ty, true);
+        let span_id = var_stack.add_span(self.span_of(expr));
+        bytecode.push(Bytecode::VarDecl(var_id));
+
+        Ok((vec![Bytecode::Var(var_id, span_id)], ty))
+    }
+
+    /// Converts a pattern in a match arm.  The bytecode that binds its variables goes into `bindings`, and the
+    /// test for whether it matches is returned, along with the pattern's shape for checking the match's arms.
+    /// Patterns that always match, like `_`, have no test
+    /// `access` is the bytecode that pushes the value being matched
+    /// Resolves the path of a pattern matching a value of the given type, where `Some` and `None` are always
+    /// available for an `Option`
+    fn resolve_pattern_path(
+        &mut self,
+        path: &syn::Path,
+        ty: DefinitionId,
+        current_scope_id: ScopeId,
+    ) -> Result<Option<DefinitionId>, Diagnostic> {
+        match self.option_variant(path, ty) {
+            Some(variant_id) => Ok(Some(variant_id)),
+            None => self.process_path(path, current_scope_id),
+        }
+    }
+
+    fn convert_pattern_to_bytecode(
+        &mut self,
+        pat: &Pat,
+        ty: DefinitionId,
+        access: &[Bytecode],
+        bindings: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<(Option<Vec<Bytecode>>, Pattern), Diagnostic> {
+        match unparenthesized(pat) {
+            Pat::Wild(_) => Ok((None, Pattern::Wild)),
+            Pat::Ident(pi) => {
+                if pi.subpat.is_some() {
+                    self.report(self.unsupported(pat, "Bindings with subpatterns are not yet supported"));
+                    return Ok(failed_pattern());
+                }
+
+                // A lone name may be a unit variant or unit struct that's in scope rather than a new binding
+                let ident = pi.ident.to_string();
+                if let Some((definition_id, _)) = self.get_defn(&ident, current_scope_id) {
+                    if self.unit_variant_enum(definition_id).is_some() {
+                        return Ok(self.variant_test(definition_id, ty, access, vec![], pat));
+                    }
+                    if self.is_unit_struct(definition_id) {
+                        return Ok(self.unit_struct_pattern(definition_id, ty, pat));
+                    }
+                }
+
+                // A `ref` binding refers to the matched value where it is, rather than copying it
+                let var_ty = if pi.by_ref.is_some() {
+                    let kind = match pi.mutability {
+                        Some(_) => {
+                            self.check_access_mutable(access, self.span_of(pi), var_stack);
+                            BorrowKind::Mutable
+                        }
+                        None => BorrowKind::Shared,
+                    };
+                    let span_id = var_stack.add_span(self.span_of(pi));
+                    bindings.extend(place_of(access));
+                    bindings.push(Bytecode::Ref(kind, span_id));
+                    self.ref_type(ty, pi.mutability.is_some())
+                } else {
+                    bindings.extend_from_slice(access);
+                    ty
+                };
+                let var_id = var_stack.add_var(ident, var_ty, pi.by_ref.is_none() && pi.mutability.is_some());
+                bindings.push(Bytecode::VarDecl(var_id));
+                Ok((None, Pattern::Wild))
+            }
+            Pat::Lit(pl) => {
+                let mut test = access.to_vec();
+                let lit_ty = self.convert_expr_expecting(
+                    &pl.expr,
+                    ty,
+                    builtin_type::UNKNOWN,
+                    &mut test,
+                    current_scope_id,
+                    var_stack,
+                )?;
+
+                if !self.operator_compatible(ty, lit_ty) {
+                    self.pattern_mismatch(ty, lit_ty, pat);
+                    return Ok(failed_pattern());
+                }
+                test.push(Bytecode::Eq);
+
+                let pattern = match literal_constructor(&pl.expr, ty) {
+                    Some(constructor) => Pattern::Constructor(constructor, vec![]),
+                    None => Pattern::Error,
+                };
+                Ok((Some(test), pattern))
+            }
+            Pat::Range(pr) => {
+                let (lo, hi) = match (literal_constructor(&pr.lo, ty), literal_constructor(&pr.hi, ty)) {
+                    (
+                        Some(Constructor::IntRange(lo, _)),
+                        Some(Constructor::IntRange(hi, _)),
+                    ) => (lo, hi),
+                    _ => {
+                        self.report(self.unsupported(pat, "Range patterns must be between integer literals"));
+                        return Ok(failed_pattern());
+                    }
+                };
+
+                // The value is in the range if it's not below the bottom, and not above (or at) the top
+                let mut test = access.to_vec();
+                let lo_ty = self.convert_expr_expecting(
+                    &pr.lo,
+                    ty,
+                    builtin_type::UNKNOWN,
+                    &mut test,
+                    current_scope_id,
+                    var_stack,
+                )?;
+                test.push(Bytecode::Lt);
+                test.push(Bytecode::PushBool(false));
+                test.push(Bytecode::Eq);
+
+                let mut hi_test = vec![];
+                let hi_ty = self.convert_expr_expecting(
+                    &pr.hi,
+                    ty,
+                    builtin_type::UNKNOWN,
+                    &mut hi_test,
+                    current_scope_id,
+                    var_stack,
+                )?;
+                let hi = match pr.limits {
+                    RangeLimits::Closed(_) => {
+                        hi_test.extend_from_slice(access);
+                        hi_test.push(Bytecode::Lt);
+                        hi_test.push(Bytecode::PushBool(false));
+                        hi_test.push(Bytecode::Eq);
+                        Some(hi)
+                    }
+                    RangeLimits::HalfOpen(_) => {
+                        let mut below_hi = access.to_vec();
+                        below_hi.append(&mut hi_test);
+                        below_hi.push(Bytecode::Lt);
+                        hi_test = below_hi;
+                        // Nothing is below the smallest value, so a range up to it is as empty as one that's reversed
+                        hi.checked_sub(1)
+                    }
+                };
+                push_and(&mut test, hi_test);
+
+                for bound_ty in [lo_ty, hi_ty].iter() {
+                    if !self.operator_compatible(ty, *bound_ty) {
+                        self.pattern_mismatch(ty, *bound_ty, pat);
+                        return Ok(failed_pattern());
+                    }
+                }
+                let hi = match hi {
+                    Some(hi) if lo <= hi => hi,
+                    _ => {
+                        self.report(Diagnostic::new(
+                            "E0030",
+                            "Lower range bound must be less than or equal to upper".to_string(),
+                        ).with_primary(self.span_of(pat), "lower bound larger than upper bound"));
+                        return Ok(failed_pattern());
+                    }
+                };
+
+                Ok((
+                    Some(test),
+                    Pattern::Constructor(Constructor::IntRange(lo, hi), vec![]),
+                ))
+            }
+            Pat::Path(pp) => match self.resolve_pattern_path(&pp.path, ty, current_scope_id)? {
+                Some(definition_id) if self.is_unit_struct(definition_id) => {
+                    Ok(self.unit_struct_pattern(definition_id, ty, pat))
+                }
+                Some(definition_id) if self.unit_variant_enum(definition_id).is_some() => {
+                    Ok(self.variant_test(definition_id, ty, access, vec![], pat))
+                }
+                Some(definition_id) => {
+                    if let Definition::Variant(_) = self.definitions[definition_id] {
+                        self.report(Diagnostic::new(
+                            "E0532",
+                            format!(
+                                "Expected unit variant, found tuple variant `{}`",
+                                self.printable_path(&pp.path)
+                            ),
+                        ).with_primary(self.span_of(pat), "not a unit variant"));
+                    } else {
+                        self.unresolved_pattern(&pp.path, pat);
+                    }
+                    Ok(failed_pattern())
+                }
+                None => {
+                    self.unresolved_pattern(&pp.path, pat);
+                    Ok(failed_pattern())
+                }
+            },
+            Pat::Struct(ps) => {
+                let struct_id = match self.process_path(&ps.path, current_scope_id)? {
+                    Some(definition_id) => match self.definitions[definition_id] {
+                        Definition::Struct(_) => self.matched_instance(definition_id, ty),
+                        // The names are still bound, so that their uses aren't reported as well
+                        _ if self.has_named_fields(definition_id) => {
+                            let mut names = vec![];
+                            pattern_names(pat, &mut names);
+                            for name in names {
+                                let var_id = var_stack.add_var(name, builtin_type::ERROR, false);
+                                bindings.push(Bytecode::VarDeclUninit(var_id));
+                            }
+                            return Ok(failed_pattern());
+                        }
+                        _ => {
+                            self.report(Diagnostic::new(
+                                "E0574",
+                                format!("Expected struct, found {}", self.printable_path(&ps.path)),
+                            ).with_primary(self.span_of(&ps.path), "not a struct"));
+                            return Ok(failed_pattern());
+                        }
+                    },
+                    None => {
+                        self.report(Diagnostic::new(
+                            "E0422",
+                            format!("Can not find struct `{}`", self.printable_path(&ps.path)),
+                        ).with_primary(self.span_of(&ps.path), "not found in this scope"));
+                        return Ok(failed_pattern());
+                    }
+                };
+                if struct_id != ty && ty != builtin_type::ERROR {
+                    self.pattern_mismatch(ty, struct_id, pat);
+                    return Ok(failed_pattern());
+                }
+                let fields = match self.definitions[struct_id] {
+                    Definition::Struct(ref struct_def) => struct_def.fields.clone(),
+                    _ => unreachable!(),
+                };
+
+                let mut sub_tests = vec![];
+                let mut subpatterns = vec![Pattern::Wild; fields.len()];
+                let mut mentioned = vec![false; fields.len()];
+                for field_pat in &ps.fields {
+                    let name = member_name(&field_pat.member);
+                    let index = match fields.iter().position(|field| field.0 == name) {
+                        Some(index) => index,
+                        None => {
+                            self.report(Diagnostic::new(
+                                "E0026",
+                                format!(
+                                    "Struct `{}` does not have a field named `{}`",
+                                    self.printable_name(struct_id),
+                                    name
+                                ),
+                            ).with_primary(self.span_of(&field_pat.member), "no such field"));
+                            return Ok(failed_pattern());
+                        }
+                    };
+                    let span = self.span_of(&field_pat.member);
+                    self.check_field_visible("E0451", struct_id, &name, span, current_scope_id);
+
+                    let mut sub_access = access.to_vec();
+                    sub_access.push(Bytecode::Dot(name));
+                    let (sub_test, subpattern) = self.convert_pattern_to_bytecode(
+                        &field_pat.pat,
+                        fields[index].1,
+                        &sub_access,
+                        bindings,
+                        current_scope_id,
+                        var_stack,
+                    )?;
+                    if let Some(sub_test) = sub_test {
+                        sub_tests.push(sub_test);
+                    }
+                    subpatterns[index] = subpattern;
+                    mentioned[index] = true;
+                }
+
+                if ps.dot2_token.is_none() {
+                    let missing: Vec<String> = fields
+                        .iter()
+                        .zip(mentioned)
+                        .filter(|(_, mentioned)| !mentioned)
+                        .map(|(field, _)| format!("`{}`", field.0))
+                        .collect();
+                    if !missing.is_empty() {
+                        self.report(Diagnostic::new(
+                            "E0027",
+                            format!("Pattern does not mention field(s) {}", missing.join(", ")),
+                        ).with_primary(
+                            self.span_of(pat),
+                            "missing fields, or use `..` to ignore them",
+                        ));
+                        return Ok(failed_pattern());
+                    }
+                }
+
+                // A struct always matches its own type, so it only needs testing if its fields do
+                Ok((
+                    all_of(sub_tests),
+                    Pattern::Constructor(Constructor::Struct(struct_id), subpatterns),
+                ))
+            }
+            Pat::TupleStruct(pts) => {
+                let definition_id = match self.resolve_pattern_path(&pts.path, ty, current_scope_id)? {
+                    Some(definition_id) => self.matched_instance(definition_id, ty),
+                    None => {
+                        self.unresolved_pattern(&pts.path, pat);
+                        return Ok(failed_pattern());
+                    }
+                };
+                let (field_tys, is_variant) = match self.definitions[definition_id] {
+                    Definition::Variant(ref variant) => (variant.fields.clone(), true),
+                    Definition::Struct(ref st) if st.kind == StructKind::Tuple => {
+                        (st.fields.iter().map(|field| field.1).collect(), false)
+                    }
+                    _ => {
+                        self.report(Diagnostic::new(
+                            "E0532",
+                            format!(
+                                "Expected tuple struct or tuple variant, found `{}`",
+                                self.printable_path(&pts.path)
+                            ),
+                        ).with_primary(self.span_of(pat), "not a tuple struct or tuple variant"));
+                        return Ok(failed_pattern());
+                    }
+                };
+                if !is_variant && definition_id != ty && ty != builtin_type::ERROR {
+                    self.pattern_mismatch(ty, definition_id, pat);
+                    return Ok(failed_pattern());
+                }
+                if !is_variant {
+                    self.check_constructor_visible(definition_id, &pts.path, current_scope_id);
+                }
+
+                let subpats = match tuple_subpatterns(&pts.pat, field_tys.len()) {
+                    Some(subpats) => subpats,
+                    None => {
+                        self.report(Diagnostic::new(
+                            "E0023",
+                            format!(
+                                "This pattern has {} field(s), but `{}` has {}",
+                                pts.pat.front.len() + pts.pat.back.len(),
+                                self.printable_path(&pts.path),
+                                field_tys.len()
+                            ),
+                        ).with_primary(
+                            self.span_of(pat),
+                            &format!("expected {} field(s)", field_tys.len()),
+                        ));
+                        return Ok(failed_pattern());
+                    }
+                };
+
+                let fields: Vec<(Vec<Bytecode>, DefinitionId)> = field_tys
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, field_ty)| {
+                        let mut field_access = access.to_vec();
+                        if is_variant {
+                            field_access.push(Bytecode::VariantField(definition_id, index));
+                        } else {
+                            field_access.push(Bytecode::Dot(index.to_string()));
+                        }
+                        (field_access, field_ty)
+                    })
+                    .collect();
+                let (sub_tests, subpatterns) = self.convert_subpatterns_to_bytecode(
+                    &subpats,
+                    &fields,
+                    bindings,
+                    current_scope_id,
+                    var_stack,
+                )?;
+
+                if !is_variant {
+                    return Ok((
+                        all_of(sub_tests),
+                        Pattern::Constructor(Constructor::Struct(definition_id), subpatterns),
+                    ));
+                }
+
+                // Each field that needs testing is checked only if the variant, and everything before it, matched
+                let (test, pattern) = self.variant_test(definition_id, ty, access, subpatterns, pat);
+                match test {
+                    Some(test) => {
+                        let mut tests = vec![test];
+                        tests.extend(sub_tests);
+                        Ok((all_of(tests), pattern))
+                    }
+                    None => Ok((test, pattern)),
+                }
+            }
+            Pat::Tuple(pt) => {
+                let element_tys = match self.definitions[ty] {
+                    Definition::Tuple(ref element_tys) => element_tys.clone(),
+                    _ if ty == builtin_type::ERROR => return Ok(failed_pattern()),
+                    _ => {
+                        self.report(Diagnostic::new(
+                            "E0308",
+                            format!(
+                                "Mismatched types in pattern: expected {}, found a tuple of {} element(s)",
+                                self.printable_name(ty),
+                                pt.front.len() + pt.back.len()
+                            ),
+                        ).with_primary(
+                            self.span_of(pat),
+                            &format!("expected {}", self.printable_name(ty)),
+                        ));
+                        return Ok(failed_pattern());
+                    }
+                };
+
+                let subpats = match tuple_subpatterns(pt, element_tys.len()) {
+                    Some(subpats) => subpats,
+                    None => {
+                        self.report(Diagnostic::new(
+                            "E0308",
+                            format!(
+                                "Mismatched types in pattern: expected {}, found a tuple of {} element(s)",
+                                self.printable_name(ty),
+                                pt.front.len() + pt.back.len()
+                            ),
+                        ).with_primary(
+                            self.span_of(pat),
+                            &format!("expected {}", self.printable_name(ty)),
+                        ));
+                        return Ok(failed_pattern());
+                    }
+                };
+
+                let elements: Vec<(Vec<Bytecode>, DefinitionId)> = element_tys
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, element_ty)| {
+                        let mut element_access = access.to_vec();
+                        element_access.push(Bytecode::Dot(index.to_string()));
+                        (element_access, element_ty)
+                    })
+                    .collect();
+                let (sub_tests, subpatterns) = self.convert_subpatterns_to_bytecode(
+                    &subpats,
+                    &elements,
+                    bindings,
+                    current_scope_id,
+                    var_stack,
+                )?;
+
+                // Like a struct, a tuple only needs testing if its elements do
+                Ok((
+                    all_of(sub_tests),
+                    Pattern::Constructor(Constructor::Tuple(ty), subpatterns),
+                ))
+            }
+            _ => {
+                self.report(self.unsupported(pat, "This kind of pattern is not yet supported"));
+                Ok(failed_pattern())
+            }
+        }
+    }
+
+    /// Converts the patterns for each field of a tuple, tuple struct or tuple variant.  `fields` has the bytecode
+    /// that pushes each field, along with its type.  A field without a pattern matches anything
+    /// Returns the tests for the fields that need testing, along with the shape of each field's pattern
+    fn convert_subpatterns_to_bytecode(
+        &mut self,
+        subpats: &[Option<&Pat>],
+        fields: &[(Vec<Bytecode>, DefinitionId)],
+        bindings: &mut Vec<Bytecode>,
+        current_scope_id: ScopeId,
+        var_stack: &mut VarStack,
+    ) -> Result<(Vec<Vec<Bytecode>>, Vec<Pattern>), Diagnostic> {
+        let mut sub_tests = vec![];
+        let mut subpatterns = vec![];
+        for (subpat, (field_access, field_ty)) in subpats.iter().zip(fields) {
+            let (sub_test, subpattern) = match subpat {
+                Some(subpat) => self.convert_pattern_to_bytecode(
+                    subpat,
+                    *field_ty,
+                    field_access,
+                    bindings,
+                    current_scope_id,
+                    var_stack,
+                )?,
+                None => (None, Pattern::Wild),
+            };
+            if let Some(sub_test) = sub_test {
+                sub_tests.push(sub_test);
+            }
+            subpatterns.push(subpattern);
+        }
+
+        Ok((sub_tests, subpatterns))
+    }
+
+    /// A unit struct has only one value, so matching it needs no test
+    fn unit_struct_pattern(
+        &mut self,
+        struct_id: DefinitionId,
+        ty: DefinitionId,
+        pat: &Pat,
+    ) -> (Option<Vec<Bytecode>>, Pattern) {
+        if struct_id != ty && ty != builtin_type::ERROR {
+            self.pattern_mismatch(ty, struct_id, pat);
+            return failed_pattern();
+        }
+        (None, Pattern::Constructor(Constructor::Struct(struct_id), vec![]))
+    }
+
+    /// The test for whether the value being matched is the given variant
+    fn variant_test(
+        &mut self,
+        variant_id: DefinitionId,
+        ty: DefinitionId,
+        access: &[Bytecode],
+        subpatterns: Vec<Pattern>,
+        pat: &Pat,
+    ) -> (Option<Vec<Bytecode>>, Pattern) {
+        let enum_id = match self.definitions[variant_id] {
+            Definition::Variant(ref variant) => variant.enum_id,
+            _ => unreachable!(),
+        };
+        if enum_id != ty && ty != builtin_type::ERROR {
+            self.pattern_mismatch(ty, enum_id, pat);
+            return failed_pattern();
+        }
+
+        let mut test = access.to_vec();
+        test.push(Bytecode::IsVariant(variant_id));
+        (
+            Some(test),
+            Pattern::Constructor(Constructor::Variant(variant_id), subpatterns),
+        )
+    }
+
+    /// Reports a pattern that can't match the type of the value being matched
+    fn pattern_mismatch(&mut self, expected: DefinitionId, found: DefinitionId, pat: &Pat) {
+        self.report(Diagnostic::new(
+            "E0308",
+            format!(
+                "Mismatched types in pattern: expected {}, found {}",
+                self.printable_name(expected),
+                self.printable_name(found)
+            ),
+        ).with_primary(
+            self.span_of(pat),
+            &format!("expected {}", self.printable_name(expected)),
+        ));
+    }
+
+    /// Reports a path in a pattern that doesn't name a variant
+    fn unresolved_pattern(&mut self, path: &syn::Path, pat: &Pat) {
+        self.report(Diagnostic::new(
+            "E0531",
+            format!("Can not find variant `{}`", self.printable_path(path)),
+        ).with_primary(self.span_of(pat), "not found in this scope"));
+    }
+
+    /// Checks if the definition is a struct declared without fields, like `struct Foo;`
+    fn is_unit_struct(&self, definition_id: DefinitionId) -> bool {
+        match self.definitions[definition_id] {
+            Definition::Struct(ref st) => st.kind == StructKind::Unit,
+            _ => false,
+        }
+    }
+
+    /// If the definition is a variant without fields, returns the enum it belongs to
+    fn unit_variant_enum(&self, definition_id: DefinitionId) -> Option<DefinitionId> {
+        match self.definitions[definition_id] {
+            Definition::Variant(ref variant) if variant.fields.is_empty() => Some(variant.enum_id),
+            _ => None,
+        }
+    }
+
+    /// Formats a path as it appeared in the source, eg) `foo::bar`
+    pub(crate) fn printable_path(&self, path: &syn::Path) -> String {
+        let segments: Vec<String> = path.segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect();
+        format!(
+            "{}{}",
+            if path.leading_colon.is_some() {
+                "::"
+            } else {
+                ""
+            },
+            segments.join("::")
+        )
+    }
+}
+
+/// Gathers the names of the variables a pattern binds
+fn pattern_names(pat: &Pat, names: &mut Vec<String>) {
+    let tuple_names = |pat_tuple: &PatTuple, names: &mut Vec<String>| {
+        for pat in pat_tuple.front.iter().chain(pat_tuple.back.iter()) {
+            pattern_names(pat, names);
+        }
+    };
+    match pat {
+        Pat::Ident(pi) => {
+            names.push(pi.ident.to_string());
+            if let Some((_, ref subpat)) = pi.subpat {
+                pattern_names(subpat, names);
+            }
+        }
+        Pat::Tuple(pt) => tuple_names(pt, names),
+        Pat::TupleStruct(pts) => tuple_names(&pts.pat, names),
+        Pat::Struct(ps) => for field in &ps.fields {
+            pattern_names(&field.pat, names);
+        },
+        Pat::Ref(pr) => pattern_names(&pr.pat, names),
+        Pat::Box(pb) => pattern_names(&pb.pat, names),
+        _ => {}
+    }
+}
+
+/// The error for a `Box` that doesn't say the type it holds
+fn box_missing_type(span: SourceSpan) -> Diagnostic {
+    Diagnostic::new("E0107", "Box takes 1 type argument but 0 were supplied".to_string())
+        .with_primary(span, "expected 1 type argument")
+}
+
+/// The bytecode and shape of a pattern that had an error.  The arm it's in is kept, but will never match
+fn failed_pattern() -> (Option<Vec<Bytecode>>, Pattern) {
+    (Some(vec![Bytecode::PushBool(false)]), Pattern::Error)
+}
+
+/// The bytecode for the place that the bytecode given reads its value from, so that a reference can be taken to it
+fn place_of(access: &[Bytecode]) -> Vec<Bytecode> {
+    access
+        .iter()
+        .map(|code| match code {
+            Bytecode::Var(var_id, span_id) => Bytecode::LValueVar(*var_id, *span_id),
+            Bytecode::Dot(field) => Bytecode::LValueDot(field.clone()),
+            Bytecode::VariantField(variant_id, index) => {
+                Bytecode::LValueVariantField(*variant_id, *index)
+            }
+            code => code.clone(),
+        })
+        .collect()
+}
+
+/// Joins the tests so that they must all pass, each being run only if the ones before it passed
+/// Returns None if there's nothing to test
+fn all_of(tests: Vec<Vec<Bytecode>>) -> Option<Vec<Bytecode>> {
+    let mut tests = tests.into_iter();
+    let mut test = tests.next()?;
+    for next_test in tests {
+        push_and(&mut test, next_test);
+    }
+    Some(test)
+}
+
+/// Lines up the subpatterns of a tuple-like pattern with the fields they match.  A `..` stands in for the
+/// fields between the ones before and after it.  Returns None if the pattern has the wrong number of fields
+fn tuple_subpatterns(pat_tuple: &PatTuple, num_fields: usize) -> Option<Vec<Option<&Pat>>> {
+    let num_mentioned = pat_tuple.front.len() + pat_tuple.back.len();
+    if num_mentioned > num_fields || (pat_tuple.dot2_token.is_none() && num_mentioned < num_fields) {
+        return None;
+    }
+
+    let mut subpats: Vec<Option<&Pat>> = pat_tuple.front.iter().map(Some).collect();
+    subpats.extend((num_mentioned..num_fields).map(|_| None));
+    subpats.extend(pat_tuple.back.iter().map(Some));
+    Some(subpats)
+}
+
+/// The pattern inside any parentheses around it, eg) `x` for `(x)`.  Only a trailing comma makes a tuple of one
+/// element, eg) `(x,)`
+fn unparenthesized(pat: &Pat) -> &Pat {
+    match pat {
+        Pat::Tuple(pt) if pt.front.len() == 1 && !pt.front.trailing_punct() && pt.dot2_token.is_none() => {
+            unparenthesized(&pt.front[0])
+        }
+        _ => pat,
+    }
+}
+
+/// Checks if the unary expression is a dereference, eg) `*x`
+fn is_deref(eu: &syn::ExprUnary) -> bool {
+    match eu.op {
+        UnOp::Deref(_) => true,
+        _ => false,
+    }
+}
+
+/// Checks if the expression names a variable and nothing more, eg) `x` or `(x)`
+fn is_var_path(expr: &Expr) -> bool {
+    match expr {
+        Expr::Path(ep) => ep.path.segments.len() == 1,
+        Expr::Paren(ep) => is_var_path(&ep.expr),
+        _ => false,
+    }
+}
+
+/// How a place is written in error messages, eg) `p.x`, `a[..]` or `*r`
+/// The variable a place is in, if it's in one, eg) `grid` for `grid.cells[0]`
+fn place_root(expr: &Expr) -> Option<String> {
+    match expr {
+        Expr::Path(ep) if ep.path.segments.len() == 1 => Some(ep.path.segments[0].ident.to_string()),
+        Expr::Field(ef) => place_root(&ef.base),
+        Expr::Index(ei) => place_root(&ei.expr),
+        Expr::Unary(eu) if is_deref(eu) => place_root(&eu.expr),
+        Expr::Paren(ep) => place_root(&ep.expr),
+        _ => None,
+    }
+}
+
+fn place_name(expr: &Expr) -> String {
+    match expr {
+        Expr::Path(ep) => ep.path.segments[0].ident.to_string(),
+        Expr::Field(ef) => format!("{}.{}", place_name(&ef.base), member_name(&ef.member)),
+        Expr::Index(ei) => format!("{}[..]", place_name(&ei.expr)),
+        Expr::Unary(eu) if is_deref(eu) => format!("*{}", place_name(&eu.expr)),
+        Expr::Paren(ep) => place_name(&ep.expr),
+        _ => "value".to_string(),
+    }
+}
+
+/// The name of a field, with unnamed fields like `.0` named after their position
+fn member_name(member: &Member) -> String {
+    match member {
+        Member::Named(ident) => ident.to_string(),
+        Member::Unnamed(index) => index.index.to_string(),
+    }
+}
+
+/// The value and type of an integer literal too big for a u64, if the text is one.  Only the 128-bit types can hold
+/// it, or an integer without a type yet, and the value is missing if it's too big even for those
+fn wide_int_literal(text: &str) -> Option<(Option<u128>, DefinitionId)> {
+    let text = text.replace('_', "");
+    let (digits, ty) = if text.ends_with("u128") {
+        (&text[..text.len() - 4], builtin_type::U128)
+    } else if text.ends_with("i128") {
+        (&text[..text.len() - 4], builtin_type::I128)
+    } else {
+        (&text[..], builtin_type::UNKNOWN_INT)
+    };
+    match digits.parse() {
+        Ok(val) => Some((Some(val), ty)),
+        Err(_) if !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()) => Some((None, ty)),
+        Err(_) => None,
+    }
+}
+
+/// The magnitude of an integer literal without a suffix, and whether it's negated
+fn unsuffixed_int_literal(expr: &Expr) -> Option<(u128, bool)> {
+    match *expr {
+        Expr::Lit(ref el) => match el.lit {
+            Lit::Int(ref li) => match li.suffix() {
+                IntSuffix::None => Some((u128::from(li.value()), false)),
+                _ => None,
+            },
+            Lit::Verbatim(ref lv) => match wide_int_literal(&lv.token.to_string()) {
+                Some((Some(val), builtin_type::UNKNOWN_INT)) => Some((val, false)),
+                _ => None,
+            },
+            _ => None,
+        },
+        Expr::Unary(ref eu) => match eu.op {
+            UnOp::Neg(_) => match unsuffixed_int_literal(&eu.expr) {
+                Some((val, false)) => Some((val, true)),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Whether the operator gives a number computed from its operands, rather than comparing them
+fn is_arithmetic(op: &BinOp) -> bool {
+    matches!(
+        op,
+        BinOp::Add(_)
+            | BinOp::Sub(_)
+            | BinOp::Mul(_)
+            | BinOp::Div(_)
+            | BinOp::Rem(_)
+            | BinOp::BitAnd(_)
+            | BinOp::BitOr(_)
+            | BinOp::BitXor(_)
+            | BinOp::Shl(_)
+            | BinOp::Shr(_)
+    )
+}
+
+/// The push of an integer literal as the given type.  It's read with `as`, so one at the minimum of a signed type
+/// wraps to it, ready to be negated
+fn int_literal_code(val: u128, ty: DefinitionId) -> Bytecode {
+    match ty {
+        builtin_type::U64 => Bytecode::PushU64(val as u64),
+        builtin_type::U32 => Bytecode::PushU32(val as u32),
+        builtin_type::I64 => Bytecode::PushI64(val as i64),
+        builtin_type::I32 => Bytecode::PushI32(val as i32),
+        builtin_type::U8 => Bytecode::PushU8(val as u8),
+        builtin_type::U16 => Bytecode::PushU16(val as u16),
+        builtin_type::U128 => Bytecode::PushU128(val),
+        builtin_type::USIZE => Bytecode::PushUsize(val as usize),
+        builtin_type::I8 => Bytecode::PushI8(val as i8),
+        builtin_type::I16 => Bytecode::PushI16(val as i16),
+        builtin_type::I128 => Bytecode::PushI128(val as i128),
+        builtin_type::ISIZE => Bytecode::PushIsize(val as isize),
+        _ => Bytecode::PushUnknownInt(val as i128),
+    }
+}
+
+/// The smallest and largest values of an integer type
+fn int_range(ty: DefinitionId) -> (i128, u128) {
+    match builtin_type::int_layout(ty) {
+        Some((bits, true)) => (-1 << (bits - 1), (1 << (bits - 1)) - 1),
+        Some((bits, false)) => (0, u128::MAX >> (128 - bits)),
+        None => (0, 0),
+    }
+}
+
+/// Whether an integer literal of the given magnitude fits in the type.  A negated literal in an unsigned type is
+/// left to be reported as a negation that isn't allowed
+fn int_literal_fits(val: u128, negated: bool, ty: DefinitionId) -> bool {
+    let (min, max) = int_range(ty);
+    match negated {
+        true if min < 0 => val <= min.unsigned_abs(),
+        _ => val <= max,
+    }
+}
+
+/// The type of the value an expression leaves on the stack.  One that never finishes leaves nothing
+fn pushed_type(ty: DefinitionId) -> DefinitionId {
+    match ty {
+        builtin_type::NEVER => builtin_type::VOID,
+        _ => ty,
+    }
+}
+
+/// Keeps room after a branch that never finishes, which leaves nothing on the stack, for a value of the type of the
+/// whole if or match.  The room is filled by `fill_placeholders` once that type is known
+fn diverged_placeholder(branch_ty: DefinitionId, bytecode: &mut Vec<Bytecode>) -> Option<usize> {
+    if branch_ty != builtin_type::NEVER {
+        return None;
+    }
+    bytecode.push(Bytecode::Unreachable(builtin_type::VOID));
+    Some(bytecode.len() - 1)
+}
+
+/// Fills the room kept after branches that never finish with a made-up value of the given type, which is never used
+fn fill_placeholders(placeholders: &[usize], ty: DefinitionId, bytecode: &mut [Bytecode]) {
+    for pos in placeholders {
+        bytecode[*pos] = Bytecode::Unreachable(pushed_type(ty));
+    }
+}
+
+/// The push of a signed integer literal with its value negated.  A literal is read as its type with `as`, so the
+/// value at the minimum of the type is already negative, and negating it wraps back to itself
+fn negated_literal(code: &Bytecode) -> Option<Bytecode> {
+    match *code {
+        Bytecode::PushI8(val) => Some(Bytecode::PushI8(val.wrapping_neg())),
+        Bytecode::PushI16(val) => Some(Bytecode::PushI16(val.wrapping_neg())),
+        Bytecode::PushI32(val) => Some(Bytecode::PushI32(val.wrapping_neg())),
+        Bytecode::PushI64(val) => Some(Bytecode::PushI64(val.wrapping_neg())),
+        Bytecode::PushI128(val) => Some(Bytecode::PushI128(val.wrapping_neg())),
+        Bytecode::PushIsize(val) => Some(Bytecode::PushIsize(val.wrapping_neg())),
+        Bytecode::PushUnknownInt(val) => Some(Bytecode::PushUnknownInt(val.wrapping_neg())),
+        _ => None,
+    }
+}
+
+/// Declares a param of a function or closure.  One given as a pattern is held in a variable of its own, to be
+/// destructured once all of the params are known
+fn declare_param<'p>(
+    pat: &'p Pat,
+    ty: DefinitionId,
+    params: &mut Vec<Param>,
+    pattern_params: &mut Vec<(&'p Pat, usize, DefinitionId)>,
+    var_stack: &mut VarStack,
+) {
+    match unparenthesized(pat) {
+        Pat::Ident(ref pi) if pi.by_ref.is_none() && pi.subpat.is_none() => {
+            let ident = pi.ident.to_string();
+            let mutable = pi.mutability.is_some();
+            let var_id = var_stack.add_var(ident.clone(), ty, mutable);
+            params.push(Param::new(ident, var_id, ty, mutable));
+        }
+        _ => {
+            // Like any temporary, the value being destructured can be borrowed mutably
+            let var_id = var_stack.add_var(String::new(), ty, true);
+            params.push(Param::unnamed(params.len(), var_id, ty));
+            pattern_params.push((pat, var_id, ty));
+        }
+    }
+}
+
+/// A range being gone through by a `for` loop
+struct RangeIter<'a> {
+    range: &'a syn::ExprRange,
+    inclusive: bool,
+    rev: bool,
+    step: Option<&'a Expr>,
+}
+
+/// Finds the range a `for` loop goes through, which may be reversed with `.rev()` and then stepped through with
+/// `.step_by(k)`.  Anything else isn't yet supported as the iterator of a `for` loop
+fn range_iter<'a>(expr: &'a Expr) -> Option<RangeIter<'a>> {
+    match expr {
+        Expr::Paren(ep) => range_iter(&ep.expr),
+        Expr::Range(er) => Some(RangeIter {
+            range: er,
+            inclusive: match er.limits {
+                RangeLimits::Closed(_) => true,
+                RangeLimits::HalfOpen(_) => false,
+            },
+            rev: false,
+            step: None,
+        }),
+        Expr::MethodCall(emc) if emc.method == "rev" && emc.args.is_empty() => match range_iter(&emc.receiver) {
+            Some(ref iter) if iter.rev || iter.step.is_some() => None,
+            Some(iter) => Some(RangeIter { rev: true, ..iter }),
+            None => None,
+        },
+        Expr::MethodCall(emc) if emc.method == "step_by" && emc.args.len() == 1 => {
+            match range_iter(&emc.receiver) {
+                Some(ref iter) if iter.step.is_some() => None,
+                Some(iter) => Some(RangeIter {
+                    step: Some(&emc.args[0]),
+                    ..iter
+                }),
+                None => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Points the `break`s and `continue`s that leave a loop, from its `BeginWhile` to its `EndWhile`, at its end and
+/// its start.  Those leaving loops inside it are told apart by how many loops out they go
+fn patch_loop_exits(loop_bytecode: &mut [Bytecode]) {
+    let end = loop_bytecode.len() - 1;
+    let mut depth = 0;
+    for (pos, code) in loop_bytecode.iter_mut().enumerate().take(end).skip(1) {
+        match *code {
+            Bytecode::BeginWhile(_) | Bytecode::ForNext(_, _, _) => depth += 1,
+            Bytecode::EndWhile(_, _) => depth -= 1,
+            Bytecode::Break(_, loops_out, ty) if loops_out == depth => *code = Bytecode::Break(end + 1 - pos, loops_out, ty),
+            Bytecode::Continue(_, loops_out) if loops_out == depth => *code = Bytecode::Continue(pos, loops_out),
+            _ => {}
+        }
+    }
+}
+
+/// Whether a `loop` can be left other than by returning, by a `break` out of it or by its condition if it's a `while`
+fn loop_exits(loop_bytecode: &[Bytecode]) -> bool {
+    if let Some(Bytecode::BeginWhile(_)) = loop_bytecode.first() {
+        let mut depth = 0;
+        for code in &loop_bytecode[1..loop_bytecode.len() - 1] {
+            match *code {
+                Bytecode::BeginWhile(_) | Bytecode::ForNext(_, _, _) => depth += 1,
+                Bytecode::EndWhile(_, _) => depth -= 1,
+                Bytecode::WhileCond(_) if depth == 0 => return true,
+                Bytecode::Break(_, loops_out, _) if loops_out == depth => return true,
+                _ => {}
+            }
+        }
+        return false;
+    }
+    true
+}
+
+/// Adds a test that's only run if the test before it passed, so that both must pass
+fn push_and(test: &mut Vec<Bytecode>, next_test: Vec<Bytecode>) {
+    test.push(Bytecode::If(next_test.len() + 2, builtin_type::BOOL));
+    test.extend(next_test);
+    test.push(Bytecode::Else(2, builtin_type::BOOL));
+    test.push(Bytecode::PushBool(false));
+    test.push(Bytecode::EndIf(builtin_type::BOOL));
+}
+
+/// The value of a literal in a pattern on the given type, as the constructor that builds it
+fn literal_constructor(expr: &Expr, ty: DefinitionId) -> Option<Constructor> {
+    if let Expr::Lit(ref el) = *expr {
+        if let Lit::Bool(ref lb) = el.lit {
+            return Some(Constructor::Bool(lb.value));
+        }
+    }
+    let (magnitude, negated) = int_literal_magnitude(expr)?;
+    let value = int_pattern_value(magnitude, negated, ty);
+    Some(Constructor::IntRange(value, value))
+}
+
+/// The magnitude of an integer literal, with or without a suffix, and whether it's negated
+fn int_literal_magnitude(expr: &Expr) -> Option<(u128, bool)> {
+    match *expr {
+        Expr::Lit(ref el) => match el.lit {
+            Lit::Int(ref li) => Some((u128::from(li.value()), false)),
+            Lit::Verbatim(ref lv) => match wide_int_literal(&lv.token.to_string()) {
+                Some((Some(val), _)) => Some((val, false)),
+                _ => None,
+            },
+            _ => None,
+        },
+        Expr::Unary(ref eu) => match eu.op {
+            UnOp::Neg(_) => match int_literal_magnitude(&eu.expr) {
+                Some((val, false)) => Some((val, true)),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The reasons the trait can't be used as a trait object, if there are any
+fn object_safety_violations(trait_def: &Trait) -> Vec<String> {
+    let mut violations = vec![];
+
+    for method in &trait_def.methods {
+        match method.decl.inputs.iter().next() {
+            Some(FnArg::SelfRef(_)) => {}
+            Some(FnArg::SelfValue(_)) => {
+                violations.push(format!("method `{}` takes `self` by value", method.name))
+            }
+            _ => violations.push(format!(
+                "associated function `{}` has no `self` parameter",
+                method.name
+            )),
+        }
+
+        if !method.decl.generics.params.is_empty() {
+            violations.push(format!(
+                "method `{}` has generic type parameters",
+                method.name
+            ));
+        }
+
+        let params_mention_self = method.decl.inputs.iter().skip(1).any(|input| match input {
+            FnArg::Captured(ref capture) => type_mentions_self(&capture.ty),
+            _ => false,
+        });
+        let return_mentions_self = match method.decl.output {
+            ReturnType::Type(_, ref ty) => type_mentions_self(ty),
+            ReturnType::Default => false,
+        };
+        if params_mention_self || return_mentions_self {
+            violations.push(format!(
+                "method `{}` references the `Self` type in its parameters or return type",
+                method.name
+            ));
+        }
+    }
+
+    violations
+}
+
+/// Checks if the type refers to `Self`, eg) `Self` or `&Box<Self>`
+fn type_mentions_self(ty: &Type) -> bool {
+    match *ty {
+        Type::Path(ref tp) => tp.path.segments.iter().any(|segment| {
+            segment.ident == "Self" || match segment.arguments {
+                PathArguments::AngleBracketed(ref generic_args) => {
+                    generic_args.args.iter().any(|arg| match arg {
+                        GenericArgument::Type(ref ty) => type_mentions_self(ty),
+                        _ => false,
+                    })
+                }
+                _ => false,
+            }
+        }),
+        Type::Reference(ref tr) => type_mentions_self(&tr.elem),
+        Type::Paren(ref tp) => type_mentions_self(&tp.elem),
+        _ => false,
     }
 }
@@ -0,0 +1,1013 @@
+use bytecode::diagnostic::Diagnostic;
+use bytecode::engine::{BorrowKind, Bytecode, BytecodeEngine, Closure, Definition, DefinitionId, Fun, SpanId};
+use bytecode::typecheck::builtin_type;
+use std::collections::HashSet;
+
+type VarId = usize;
+type LoanId = usize;
+
+/// A step from a variable to the part of it that's borrowed
+#[derive(Debug, Clone, PartialEq)]
+enum Projection {
+    Field(String),
+    VariantField(DefinitionId, usize),
+    /// Any element of an array, as which one isn't known until the function runs
+    Index,
+    /// The inside of a Box
+    Deref,
+}
+
+/// A reference taken to (part of) a variable of the function being checked
+#[derive(Debug, Clone)]
+struct Loan {
+    var_id: VarId,
+    path: Vec<Projection>,
+    kind: BorrowKind,
+    span_id: SpanId,
+    /// The bytecode that made it.  Going around a loop again makes the same loan, not a new one
+    at: usize,
+}
+
+/// What the checker knows about a value or place on the stack
+#[derive(Debug, Clone, Default)]
+struct Operand {
+    ty: Option<DefinitionId>,
+    /// The loans kept alive by this value, if it is or holds a reference.  For a place reached through a
+    /// reference, these are the loans kept alive by that reference
+    loans: Vec<LoanId>,
+    /// The variables this value was read from, which are moved if the value is used up and isn't Copy
+    sources: Vec<(VarId, SpanId)>,
+    /// If this is a place (an lvalue) rather than a value
+    is_place: bool,
+    /// If this is a place owned by the function: the variable it's in, where it's used, and the path from it
+    root: Option<(VarId, SpanId)>,
+    path: Vec<Projection>,
+}
+
+impl Operand {
+    fn value(ty: Option<DefinitionId>, loans: Vec<LoanId>) -> Operand {
+        Operand {
+            ty,
+            loans,
+            ..Operand::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct State {
+    stack: Vec<Operand>,
+    /// The loans kept alive by the value in each variable
+    holds: Vec<Vec<LoanId>>,
+    /// Where each variable was moved, if it has been
+    moved: Vec<Option<SpanId>>,
+    /// If each variable may have been given a value, which only happens once for variables that aren't `mut`
+    assigned: Vec<bool>,
+    /// If this point can't be reached, as a `break`, `continue` or `return` left before it
+    diverged: bool,
+}
+
+impl State {
+    fn pop(&mut self) -> Operand {
+        self.stack.pop().unwrap_or_default()
+    }
+
+    fn pop_n(&mut self, n: usize) -> Vec<Operand> {
+        let at = self.stack.len().saturating_sub(n);
+        self.stack.split_off(at)
+    }
+
+    /// Combines the state at the end of one branch with the state at the end of the other
+    fn merge(&mut self, other: &State) {
+        // A branch that has left doesn't bring anything to where the branches join
+        if other.diverged {
+            return;
+        }
+        if self.diverged {
+            *self = other.clone();
+            return;
+        }
+        for (operand, other_operand) in self.stack.iter_mut().zip(other.stack.iter()) {
+            union(&mut operand.loans, &other_operand.loans);
+        }
+        for (holds, other_holds) in self.holds.iter_mut().zip(other.holds.iter()) {
+            union(holds, other_holds);
+        }
+        for (moved, other_moved) in self.moved.iter_mut().zip(other.moved.iter()) {
+            if moved.is_none() {
+                *moved = *other_moved;
+            }
+        }
+        for (assigned, other_assigned) in self.assigned.iter_mut().zip(other.assigned.iter()) {
+            *assigned |= *other_assigned;
+        }
+    }
+
+    /// Checks if merging in another state changed this one from what it was before.  As merging only ever adds
+    /// loans, moves and assignments, it's enough to count them
+    fn grew_from(&self, before: &State) -> bool {
+        let loans = |state: &State| {
+            state.stack.iter().map(|operand| operand.loans.len()).sum::<usize>()
+                + state.holds.iter().map(|holds| holds.len()).sum::<usize>()
+        };
+        let moves = |state: &State| state.moved.iter().filter(|moved| moved.is_some()).count();
+        let assignments = |state: &State| state.assigned.iter().filter(|assigned| **assigned).count();
+
+        loans(self) != loans(before) || moves(self) != moves(before) || assignments(self) != assignments(before)
+    }
+}
+
+/// A loop being checked
+struct LoopState {
+    /// The `BeginWhile` or `ForNext` that each time around the loop starts at
+    start: usize,
+    /// The state at the start of each time around the loop, which includes what the times before left behind
+    entry: State,
+    /// The states the loop can be left in, from its condition being false or a `break`
+    exits: Vec<State>,
+    /// The states that go around the loop again from a `continue`
+    continues: Vec<State>,
+}
+
+fn union(loans: &mut Vec<LoanId>, other: &[LoanId]) {
+    for loan_id in other {
+        if !loans.contains(loan_id) {
+            loans.push(*loan_id);
+        }
+    }
+}
+
+/// Checks if borrowing one path conflicts with borrowing the other, which happens when one contains the other
+fn overlaps(path: &[Projection], other: &[Projection]) -> bool {
+    path.iter().zip(other.iter()).all(|(x, y)| x == y)
+}
+
+/// The borrow checker.  It walks the bytecode in order, following both branches of each `if` and joining them
+/// back up afterwards.  A loop is walked again, starting from what the last time around left behind, until that no
+/// longer changes, so that moves and borrows made late in the loop are seen by the code before them.
+/// A loan is live while the value holding it is on the stack, or while it's held by a variable that's used again
+/// later.  Variables used in a loop are treated as used until the end of the loop
+struct BorrowChecker<'a> {
+    engine: &'a BytecodeEngine,
+    fun: &'a Fun,
+    loans: Vec<Loan>,
+    /// The last bytecode using each variable
+    last_use: Vec<usize>,
+    /// The bytecode declaring each variable
+    declared_at: Vec<usize>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> BorrowChecker<'a> {
+    fn new(engine: &'a BytecodeEngine, fun: &'a Fun) -> BorrowChecker<'a> {
+        let mut last_use = vec![0; fun.vars.len()];
+        let mut declared_at = vec![0; fun.vars.len()];
+        let mut loop_starts = vec![];
+
+        for (idx, code) in fun.bytecode.iter().enumerate() {
+            match code {
+                Bytecode::Var(var_id, _) | Bytecode::LValueVar(var_id, _) => last_use[*var_id] = idx,
+                Bytecode::MakeClosure(closure_ty, _) => {
+                    if let Definition::Closure(ref closure) = engine.definitions[*closure_ty] {
+                        for capture in &closure.captures {
+                            last_use[capture.var_id] = idx;
+                        }
+                    }
+                }
+                Bytecode::VarDecl(var_id) | Bytecode::VarDeclUninit(var_id) => declared_at[*var_id] = idx,
+                Bytecode::BeginWhile(_) | Bytecode::ForNext(_, _, _) => loop_starts.push(idx),
+                Bytecode::EndWhile(_, _) => {
+                    let start = loop_starts.pop().unwrap_or(0);
+                    for (var_id, last) in last_use.iter_mut().enumerate() {
+                        // The next time around the loop will use it again
+                        if declared_at[var_id] < start && *last >= start {
+                            *last = idx;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        BorrowChecker {
+            engine,
+            fun,
+            loans: vec![],
+            last_use,
+            declared_at,
+            diagnostics: vec![],
+        }
+    }
+
+    fn check(&mut self) {
+        let fun = self.fun;
+        let engine = self.engine;
+        let definitions = &engine.definitions;
+        let mut state = State {
+            stack: vec![],
+            holds: vec![vec![]; fun.vars.len()],
+            moved: vec![None; fun.vars.len()],
+            // Parameters come in with their values
+            assigned: vec![true; fun.vars.len()],
+            diverged: false,
+        };
+        // The state before each `if` being checked, and the state at the end of its then block once it's reached
+        let mut branches: Vec<(State, Option<State>)> = vec![];
+        let mut loops: Vec<LoopState> = vec![];
+
+        let mut idx = 0;
+        while idx < fun.bytecode.len() {
+            let code = &fun.bytecode[idx];
+            match code {
+                Bytecode::ReturnLastStackValue => {
+                    let operand = state.pop();
+                    self.consume(&mut state, &operand);
+                    if self.contains_ref(fun.return_ty, &[], &mut HashSet::new()) {
+                        if let Some(loan_id) = operand.loans.first() {
+                            self.report_returned_loan(*loan_id);
+                        }
+                    }
+                    state.diverged = true;
+                }
+                Bytecode::ReturnVoid => state.diverged = true,
+                Bytecode::PushU64(_)
+                | Bytecode::PushU32(_)
+                | Bytecode::PushI64(_)
+                | Bytecode::PushI32(_)
+                | Bytecode::PushU8(_)
+                | Bytecode::PushU16(_)
+                | Bytecode::PushU128(_)
+                | Bytecode::PushUsize(_)
+                | Bytecode::PushI8(_)
+                | Bytecode::PushI16(_)
+                | Bytecode::PushI128(_)
+                | Bytecode::PushIsize(_)
+                | Bytecode::PushUnknownInt(_)
+                | Bytecode::PushF32(_)
+                | Bytecode::PushF64(_)
+                | Bytecode::PushUnknownFloat(_)
+                | Bytecode::PushBool(_)
+                | Bytecode::PushFn(_)
+                | Bytecode::PushRawPtr(_) => state.stack.push(Operand::default()),
+                Bytecode::As(ty) => {
+                    let operand = state.pop();
+                    state.stack.push(self.value(Some(*ty), operand.loans));
+                }
+                Bytecode::Add(_)
+                | Bytecode::Sub(_)
+                | Bytecode::Mul(_)
+                | Bytecode::Div(_)
+                | Bytecode::Rem(_)
+                | Bytecode::BitAnd
+                | Bytecode::BitOr
+                | Bytecode::BitXor
+                | Bytecode::Shl(_)
+                | Bytecode::Shr(_)
+                | Bytecode::Lt
+                | Bytecode::Le
+                | Bytecode::Gt
+                | Bytecode::Ge
+                | Bytecode::Eq
+                | Bytecode::Ne => {
+                    state.pop_n(2);
+                    state.stack.push(Operand::default());
+                }
+                Bytecode::OverflowingOp(op, _, _) => {
+                    // Negating, as `wrapping_neg` does, takes only the one operand
+                    state.pop_n(if matches!(**op, Bytecode::Neg(_)) { 1 } else { 2 });
+                    state.stack.push(Operand::default());
+                }
+                Bytecode::Neg(_) | Bytecode::Not(_) | Bytecode::Cast(_, _) | Bytecode::IsVariant(_) => {
+                    state.pop();
+                    state.stack.push(Operand::default());
+                }
+                Bytecode::Dot(field) => {
+                    let operand = state.pop();
+                    let ty = operand.ty.and_then(|ty| self.field_type(ty, field));
+                    state.stack.push(self.value(ty, operand.loans));
+                }
+                Bytecode::VariantField(variant_id, field_idx) => {
+                    let operand = state.pop();
+                    let ty = variant_field_type(definitions, *variant_id, *field_idx);
+                    state.stack.push(self.value(ty, operand.loans));
+                }
+                Bytecode::Index(array_ty) => {
+                    state.pop();
+                    let operand = state.pop();
+                    let ty = match definitions[*array_ty] {
+                        Definition::Array(element_ty, _) => Some(element_ty),
+                        _ => None,
+                    };
+                    state.stack.push(self.value(ty, operand.loans));
+                }
+                Bytecode::Ref(kind, span_id) => {
+                    let place = state.pop();
+                    let loans = self.borrow(&mut state, place, *kind, *span_id, idx);
+                    state.stack.push(Operand::value(None, loans));
+                }
+                Bytecode::Deref => {
+                    let operand = state.pop();
+                    let deref = self.deref(&state, operand);
+                    state.stack.push(deref);
+                }
+                Bytecode::VarDecl(var_id) => {
+                    let operand = state.pop();
+                    // Unnamed variables hold values being destructured, which only moves the parts that are bound
+                    if !fun.vars[*var_id].ident.is_empty() {
+                        self.consume(&mut state, &operand);
+                    }
+                    state.holds[*var_id] = operand.loans;
+                    state.moved[*var_id] = None;
+                    state.assigned[*var_id] = true;
+                }
+                Bytecode::VarDeclUninit(var_id) => {
+                    state.holds[*var_id] = vec![];
+                    state.moved[*var_id] = None;
+                    state.assigned[*var_id] = false;
+                }
+                Bytecode::Var(var_id, span_id) => {
+                    self.check_not_moved(&mut state, *var_id, *span_id, "used");
+                    let path = read_path(&fun.bytecode[idx + 1..]);
+                    self.check_not_mutably_borrowed(&state, *var_id, &path, *span_id, idx);
+                    let ty = fun.vars[*var_id].ty;
+                    let mut operand = self.value(Some(ty), state.holds[*var_id].clone());
+                    if !self.is_copy(ty) {
+                        operand.sources.push((*var_id, *span_id));
+                    }
+                    state.stack.push(operand);
+                }
+                Bytecode::LValueVar(var_id, span_id) => state.stack.push(Operand {
+                    ty: Some(fun.vars[*var_id].ty),
+                    is_place: true,
+                    root: Some((*var_id, *span_id)),
+                    ..Operand::default()
+                }),
+                Bytecode::LValueDot(field) => {
+                    let mut place = state.pop();
+                    place.ty = place.ty.and_then(|ty| self.field_type(ty, field));
+                    place.path.push(Projection::Field(field.clone()));
+                    state.stack.push(place);
+                }
+                Bytecode::LValueVariantField(variant_id, field_idx) => {
+                    let mut place = state.pop();
+                    place.ty = variant_field_type(definitions, *variant_id, *field_idx);
+                    place.path.push(Projection::VariantField(*variant_id, *field_idx));
+                    state.stack.push(place);
+                }
+                Bytecode::LValueIndex(array_ty) => {
+                    state.pop();
+                    let mut place = state.pop();
+                    place.ty = match definitions[*array_ty] {
+                        Definition::Array(element_ty, _) => Some(element_ty),
+                        _ => None,
+                    };
+                    place.path.push(Projection::Index);
+                    state.stack.push(place);
+                }
+                Bytecode::Assign => {
+                    let place = state.pop();
+                    let operand = state.pop();
+                    self.consume(&mut state, &operand);
+                    if let Some((var_id, span_id)) = place.root {
+                        self.check_not_borrowed(&state, var_id, &place.path, span_id, idx);
+                        if place.path.is_empty() {
+                            if state.assigned[var_id] && !fun.vars[var_id].mutable {
+                                self.report_assigned_twice(var_id, span_id);
+                            }
+                            // Assigning a whole new value gives the variable back any value it lost
+                            state.holds[var_id] = operand.loans;
+                            state.moved[var_id] = None;
+                            state.assigned[var_id] = true;
+                        } else {
+                            self.check_not_moved(&mut state, var_id, span_id, "assigned to");
+                            union(&mut state.holds[var_id], &operand.loans);
+                        }
+                    }
+                }
+                Bytecode::CompoundAssign(_, _) => {
+                    // The place keeps its value, updated, so this is a use of it as well as an assignment
+                    let place = state.pop();
+                    state.pop();
+                    if let Some((var_id, span_id)) = place.root {
+                        self.check_not_borrowed(&state, var_id, &place.path, span_id, idx);
+                        if place.path.is_empty() && !fun.vars[var_id].mutable {
+                            self.report_assigned_twice(var_id, span_id);
+                        }
+                        self.check_not_moved(&mut state, var_id, span_id, "assigned to");
+                    }
+                }
+                Bytecode::Call(definition_id) => {
+                    let (num_args, return_ty, unification) = match definitions[*definition_id] {
+                        Definition::Fun(ref target_fun) => {
+                            (target_fun.params.len(), Some(target_fun.return_ty), &[][..])
+                        }
+                        Definition::InstantiatedFun(fn_id, ref unification) => match definitions[fn_id] {
+                            // The return type may use type parameters, which stand for the types they're
+                            // instantiated with
+                            Definition::Fun(ref target_fun) => {
+                                (target_fun.params.len(), Some(target_fun.return_ty), &unification[..])
+                            }
+                            _ => (0, None, &[][..]),
+                        },
+                        Definition::Struct(ref st) => (st.fields.len(), Some(*definition_id), &[][..]),
+                        Definition::Variant(ref variant) => (variant.fields.len(), Some(variant.enum_id), &[][..]),
+                        Definition::Tuple(ref element_tys) => (element_tys.len(), Some(*definition_id), &[][..]),
+                        Definition::Array(_, len) => (len, Some(*definition_id), &[][..]),
+                        _ => (0, None, &[][..]),
+                    };
+                    self.call(&mut state, num_args, return_ty, unification);
+                }
+                Bytecode::CallTraitMethod(_, trait_id, method_name, return_ty) => {
+                    let num_args = match definitions[*trait_id] {
+                        Definition::Trait(ref trait_def) => trait_def
+                            .methods
+                            .iter()
+                            .find(|method| &method.name == method_name)
+                            .map_or(0, |method| method.decl.inputs.len()),
+                        _ => 0,
+                    };
+                    self.call(&mut state, num_args, Some(*return_ty), &[]);
+                }
+                Bytecode::CallDyn(dyn_ty, method_index) => {
+                    let (num_args, return_ty) = match definitions[*dyn_ty] {
+                        Definition::DynTrait(ref dyn_trait) => {
+                            let (ref params, return_ty) = dyn_trait.methods[*method_index];
+                            (params.len(), Some(return_ty))
+                        }
+                        _ => (0, None),
+                    };
+                    self.call(&mut state, num_args, return_ty, &[]);
+                }
+                Bytecode::MakeClosure(closure_ty, span_id) => {
+                    // The closure keeps what it borrows alive for as long as it's around
+                    let mut loans = vec![];
+                    if let Definition::Closure(ref closure) = definitions[*closure_ty] {
+                        for capture in &closure.captures {
+                            let place = Operand {
+                                ty: Some(capture.ty),
+                                is_place: true,
+                                root: Some((capture.var_id, *span_id)),
+                                ..Operand::default()
+                            };
+                            match closure.borrow_kind(capture) {
+                                Some(kind) => {
+                                    let capture_loans = self.borrow(&mut state, place, kind, *span_id, idx);
+                                    union(&mut loans, &capture_loans);
+                                }
+                                None => {
+                                    self.check_not_moved(&mut state, capture.var_id, *span_id, "used");
+                                    if !self.is_copy(capture.ty) {
+                                        state.moved[capture.var_id] = Some(*span_id);
+                                    }
+                                    union(&mut loans, &state.holds[capture.var_id].clone());
+                                }
+                            }
+                        }
+                    }
+                    state.stack.push(Operand::value(Some(*closure_ty), loans));
+                }
+                Bytecode::CallClosure(closure_ty) => {
+                    let (num_params, return_ty) = match definitions[*closure_ty] {
+                        Definition::Closure(Closure {
+                            fun: Some(ref closure_fun),
+                            ..
+                        }) => (closure_fun.params.len(), Some(closure_fun.return_ty)),
+                        _ => (0, None),
+                    };
+                    // The closure is borrowed for the call, under the args
+                    self.call(&mut state, num_params + 1, return_ty, &[]);
+                }
+                Bytecode::CallPtr(fn_ptr_ty) => {
+                    let (num_params, return_ty) = match definitions[*fn_ptr_ty] {
+                        Definition::FnPtr(ref param_tys, return_ty) => (param_tys.len(), Some(return_ty)),
+                        _ => (0, None),
+                    };
+                    // The function pointer is under the args
+                    self.call(&mut state, num_params + 1, return_ty, &[]);
+                }
+                Bytecode::BoxNew(ty) | Bytecode::ArrayRepeat(ty) => {
+                    let operand = state.pop();
+                    self.consume(&mut state, &operand);
+                    state.stack.push(self.value(Some(*ty), operand.loans));
+                }
+                Bytecode::MakeDyn(_) => {
+                    let operand = state.pop();
+                    self.consume(&mut state, &operand);
+                    state.stack.push(Operand::value(None, operand.loans));
+                }
+                Bytecode::If(_, _) => {
+                    state.pop();
+                    branches.push((state.clone(), None));
+                }
+                Bytecode::Else(_, _) => {
+                    if let Some((ref before, ref mut then_state)) = branches.last_mut() {
+                        let else_state = before.clone();
+                        *then_state = Some(::std::mem::replace(&mut state, else_state));
+                    }
+                }
+                Bytecode::EndIf(_) => {
+                    if let Some((before, then_state)) = branches.pop() {
+                        state.merge(&then_state.unwrap_or(before));
+                    }
+                }
+                Bytecode::BeginWhile(_) => enter_loop(&mut loops, &state, idx),
+                Bytecode::Continue(_, loops_out) => {
+                    if let Some(target) = loops.iter_mut().rev().nth(*loops_out) {
+                        target.continues.push(state.clone());
+                    }
+                    state.diverged = true;
+                }
+                Bytecode::BeginFor(_, _) => {
+                    for _ in 0..3 {
+                        let operand = state.pop();
+                        self.consume(&mut state, &operand);
+                    }
+                }
+                Bytecode::ForNext(_, _, _) => {
+                    enter_loop(&mut loops, &state, idx);
+                    // The loop is left from here once the range is finished
+                    if let Some(current) = loops.last_mut() {
+                        current.exits.push(state.clone());
+                    }
+                    state.stack.push(Operand::default());
+                }
+                Bytecode::EndWhile(_, ty) => {
+                    if let Some(mut current) = loops.pop() {
+                        let mut back = state.clone();
+                        for continued in &current.continues {
+                            back.merge(continued);
+                        }
+                        // The variables declared in the loop are declared again before they're next used
+                        for var_id in 0..self.fun.vars.len() {
+                            if self.declared_at[var_id] > current.start {
+                                back.holds[var_id] = vec![];
+                                back.moved[var_id] = None;
+                            }
+                        }
+
+                        let mut entry = current.entry.clone();
+                        entry.merge(&back);
+                        if entry.grew_from(&current.entry) {
+                            // Go around again, with what this time left behind
+                            current.entry = entry.clone();
+                            state = entry;
+                            idx = current.start;
+                            loops.push(current);
+                            continue;
+                        }
+
+                        state.diverged = true;
+                        for exit in &current.exits {
+                            state.merge(exit);
+                        }
+                    }
+                    // The value given by a `break` comes out of the end of the loop
+                    if *ty != builtin_type::VOID {
+                        state.stack.push(self.value(Some(*ty), vec![]));
+                    }
+                }
+                Bytecode::Break(_, loops_out, ty) => {
+                    if *ty != builtin_type::VOID {
+                        let operand = state.pop();
+                        self.consume(&mut state, &operand);
+                    }
+                    if let Some(target) = loops.iter_mut().rev().nth(*loops_out) {
+                        target.exits.push(state.clone());
+                    }
+                    state.diverged = true;
+                }
+                Bytecode::WhileCond(_) => {
+                    state.pop();
+                    // The loop is left from here once the condition is false
+                    if let Some(current) = loops.last_mut() {
+                        current.exits.push(state.clone());
+                    }
+                }
+                Bytecode::DebugPrint(_) => {
+                    state.pop();
+                }
+                Bytecode::Pop => {
+                    let operand = state.pop();
+                    self.consume(&mut state, &operand);
+                }
+                Bytecode::Unreachable(ty) => {
+                    // The if this is in still expects a result
+                    if *ty != builtin_type::VOID {
+                        state.stack.push(Operand::default());
+                    }
+                }
+            }
+            idx += 1;
+        }
+    }
+
+    /// A value of the given type.  Only values that can hold references keep loans alive
+    fn value(&self, ty: Option<DefinitionId>, loans: Vec<LoanId>) -> Operand {
+        match ty {
+            Some(ty) if !self.contains_ref(ty, &[], &mut HashSet::new()) => Operand::value(Some(ty), vec![]),
+            _ => Operand::value(ty, loans),
+        }
+    }
+
+    /// Calls with the arguments on top of the stack, which are used up.  The return type may use type parameters,
+    /// which are substituted by the given unification
+    fn call(
+        &mut self,
+        state: &mut State,
+        num_args: usize,
+        return_ty: Option<DefinitionId>,
+        unification: &[(DefinitionId, DefinitionId)],
+    ) {
+        let mut loans = vec![];
+        for arg in state.pop_n(num_args) {
+            self.consume(state, &arg);
+            union(&mut loans, &arg.loans);
+        }
+        if return_ty.is_some_and(|ty| !self.contains_ref(ty, unification, &mut HashSet::new())) {
+            loans = vec![];
+        }
+        // A return type using type parameters isn't the type of the value, so it's only used for what it holds
+        let ty = return_ty.filter(|_| unification.is_empty());
+        state.stack.push(Operand::value(ty, loans));
+    }
+
+    /// Uses up a value, moving the variables it was read from
+    fn consume(&mut self, state: &mut State, operand: &Operand) {
+        for (var_id, span_id) in &operand.sources {
+            state.moved[*var_id] = Some(*span_id);
+        }
+    }
+
+    fn deref(&self, state: &State, operand: Operand) -> Operand {
+        let definitions = &self.engine.definitions;
+        let pointee = operand.ty.and_then(|ty| match definitions[ty] {
+            Definition::Ref(referred_ty, _) | Definition::Box(referred_ty) => Some(referred_ty),
+            _ => None,
+        });
+        let is_box = matches!(operand.ty.map(|ty| &definitions[ty]), Some(Definition::Box(_)));
+
+        if !operand.is_place {
+            return self.value(pointee, operand.loans);
+        }
+
+        match operand.root {
+            // The inside of a Box is still owned by the variable
+            Some(_) if is_box => {
+                let mut place = operand;
+                place.ty = pointee;
+                place.path.push(Projection::Deref);
+                place
+            }
+            // Otherwise it's somewhere else, kept alive by the reference in the place
+            _ => {
+                let mut loans = operand.loans;
+                if let Some((var_id, _)) = operand.root {
+                    union(&mut loans, &state.holds[var_id]);
+                }
+                Operand {
+                    ty: pointee,
+                    loans,
+                    is_place: true,
+                    ..Operand::default()
+                }
+            }
+        }
+    }
+
+    /// Takes a reference to the place, checking it against the other loans still in use.
+    /// Returns the loans the reference keeps alive
+    fn borrow(&mut self, state: &mut State, place: Operand, kind: BorrowKind, span_id: SpanId, idx: usize) -> Vec<LoanId> {
+        let (var_id, use_span_id) = match place.root {
+            Some(root) => root,
+            // Borrowing through a reference, which keeps the same things alive
+            None => return place.loans,
+        };
+        self.check_not_moved(state, var_id, use_span_id, "borrowed");
+
+        let live_loans = self.live_loans(state, idx);
+        let conflict = live_loans.into_iter().find(|loan_id| {
+            let loan = &self.loans[*loan_id];
+            loan.var_id == var_id && overlaps(&loan.path, &place.path) && conflicts(loan.kind, kind)
+        });
+        if let Some(loan_id) = conflict {
+            self.report_conflict(loan_id, &place.path, kind, span_id);
+        }
+
+        let existing = self.loans.iter().position(|loan| {
+            loan.at == idx && loan.var_id == var_id && loan.path == place.path && loan.kind == kind
+        });
+        match existing {
+            Some(loan_id) => vec![loan_id],
+            None => {
+                self.loans.push(Loan {
+                    var_id,
+                    path: place.path,
+                    kind,
+                    span_id,
+                    at: idx,
+                });
+                vec![self.loans.len() - 1]
+            }
+        }
+    }
+
+    /// Checks that nothing borrows the place that's being assigned to, as the borrow would see it change
+    fn check_not_borrowed(&mut self, state: &State, var_id: VarId, path: &[Projection], span_id: SpanId, idx: usize) {
+        let live_loans = self.live_loans(state, idx);
+        let borrowed = live_loans.into_iter().find(|loan_id| {
+            let loan = &self.loans[*loan_id];
+            loan.var_id == var_id && overlaps(&loan.path, path)
+        });
+        if let Some(loan_id) = borrowed {
+            let loan = &self.loans[loan_id];
+            let place = self.printable_place(var_id, path);
+            let diagnostic = Diagnostic::new(
+                "E0506",
+                format!("Cannot assign to `{}` because it is borrowed", place),
+            ).with_primary(
+                self.fun.spans[span_id].clone(),
+                &format!("assignment to borrowed `{}` occurs here", place),
+            )
+                .with_secondary(
+                    self.fun.spans[loan.span_id].clone(),
+                    &format!("`{}` is borrowed here", self.printable_place(var_id, &loan.path)),
+                );
+            self.push_diagnostic(diagnostic);
+        }
+    }
+
+    /// Checks that nothing mutably borrows the place being read, as the borrow could be changing it
+    fn check_not_mutably_borrowed(
+        &mut self,
+        state: &State,
+        var_id: VarId,
+        path: &[Projection],
+        span_id: SpanId,
+        idx: usize,
+    ) {
+        let live_loans = self.live_loans(state, idx);
+        let borrowed = live_loans.into_iter().find(|loan_id| {
+            let loan = &self.loans[*loan_id];
+            loan.var_id == var_id && overlaps(&loan.path, path) && conflicts(loan.kind, BorrowKind::Shared)
+        });
+        if let Some(loan_id) = borrowed {
+            let loan = &self.loans[loan_id];
+            let place = self.printable_place(var_id, &loan.path);
+            let diagnostic = Diagnostic::new(
+                "E0503",
+                format!("Cannot use `{}` because it was mutably borrowed", place),
+            ).with_primary(self.fun.spans[span_id].clone(), &format!("use of borrowed `{}`", place))
+                .with_secondary(self.fun.spans[loan.span_id].clone(), &format!("`{}` is borrowed here", place));
+            self.push_diagnostic(diagnostic);
+        }
+    }
+
+    /// The loans that are still going to be used at the given bytecode
+    fn live_loans(&self, state: &State, idx: usize) -> Vec<LoanId> {
+        let mut live = vec![];
+        for operand in &state.stack {
+            union(&mut live, &operand.loans);
+        }
+        for (var_id, holds) in state.holds.iter().enumerate() {
+            if self.last_use[var_id] >= idx {
+                union(&mut live, holds);
+            }
+        }
+        live
+    }
+
+    fn check_not_moved(&mut self, state: &mut State, var_id: VarId, span_id: SpanId, action: &str) {
+        if let Some(moved_span_id) = state.moved[var_id] {
+            let name = &self.fun.vars[var_id].ident;
+            let (used, moved) = (&self.fun.spans[span_id], &self.fun.spans[moved_span_id]);
+            // A move that isn't before the use in the source was made the last time around a loop
+            let moved_label = if (moved.start_line, moved.start_column) >= (used.start_line, used.start_column) {
+                "value moved here, in previous iteration of loop"
+            } else {
+                "value moved here"
+            };
+            let diagnostic = Diagnostic::new("E0382", format!("Use of moved value: `{}`", name))
+                .with_primary(used.clone(), &format!("value {} here after move", action))
+                .with_secondary(moved.clone(), moved_label);
+            self.push_diagnostic(diagnostic);
+            // Only report the first use after each move
+            state.moved[var_id] = None;
+        }
+    }
+
+    /// Records the error, unless it's already been found the last time around a loop
+    fn push_diagnostic(&mut self, diagnostic: Diagnostic) {
+        let primary_span = |diagnostic: &Diagnostic| diagnostic.primary.as_ref().map(|label| label.span.clone());
+        let found = self.diagnostics.iter().any(|found| {
+            found.code == diagnostic.code && primary_span(found) == primary_span(&diagnostic)
+        });
+        if !found {
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    fn report_assigned_twice(&mut self, var_id: VarId, span_id: SpanId) {
+        let name = &self.fun.vars[var_id].ident;
+        self.push_diagnostic(
+            Diagnostic::new(
+                "E0384",
+                format!("Cannot assign twice to immutable variable `{}`", name),
+            ).with_primary(
+                self.fun.spans[span_id].clone(),
+                "cannot assign twice to immutable variable",
+            )
+                .with_note(&format!("consider changing this to be mutable: `mut {}`", name)),
+        );
+    }
+
+    fn report_conflict(&mut self, loan_id: LoanId, path: &[Projection], kind: BorrowKind, span_id: SpanId) {
+        let loan = &self.loans[loan_id];
+        let place = self.printable_place(loan.var_id, path);
+        let diagnostic = match (loan.kind, kind) {
+            (BorrowKind::Shared, _) => Diagnostic::new(
+                "E0502",
+                format!(
+                    "Cannot borrow `{}` as mutable because it is also borrowed as immutable",
+                    place
+                ),
+            ).with_primary(self.fun.spans[span_id].clone(), "mutable borrow occurs here")
+                .with_secondary(self.fun.spans[loan.span_id].clone(), "immutable borrow occurs here"),
+            (_, BorrowKind::Shared) => Diagnostic::new(
+                "E0502",
+                format!(
+                    "Cannot borrow `{}` as immutable because it is also borrowed as mutable",
+                    place
+                ),
+            ).with_primary(self.fun.spans[span_id].clone(), "immutable borrow occurs here")
+                .with_secondary(self.fun.spans[loan.span_id].clone(), "mutable borrow occurs here"),
+            // Borrowing again where the loan was made happens the next time around a loop
+            _ if loan.span_id == span_id => Diagnostic::new(
+                "E0499",
+                format!("Cannot borrow `{}` as mutable more than once at a time", place),
+            ).with_primary(
+                self.fun.spans[span_id].clone(),
+                &format!("`{}` was mutably borrowed here in the previous iteration of the loop", place),
+            ),
+            _ => Diagnostic::new(
+                "E0499",
+                format!("Cannot borrow `{}` as mutable more than once at a time", place),
+            ).with_primary(self.fun.spans[span_id].clone(), "second mutable borrow occurs here")
+                .with_secondary(self.fun.spans[loan.span_id].clone(), "first mutable borrow occurs here"),
+        };
+        self.push_diagnostic(diagnostic);
+    }
+
+    fn report_returned_loan(&mut self, loan_id: LoanId) {
+        let loan = &self.loans[loan_id];
+        let what = if loan.path.is_empty() {
+            "local variable"
+        } else {
+            "local data"
+        };
+        self.push_diagnostic(
+            Diagnostic::new(
+                "E0515",
+                format!(
+                    "Cannot return reference to {} `{}`",
+                    what,
+                    self.printable_place(loan.var_id, &loan.path)
+                ),
+            ).with_primary(
+                self.fun.spans[loan.span_id].clone(),
+                "returns a reference to data owned by the current function",
+            ),
+        );
+    }
+
+    /// The place as it would be written in source, eg) `p.x` or `grid.cells[..]`
+    fn printable_place(&self, var_id: VarId, path: &[Projection]) -> String {
+        let mut place = self.fun.vars[var_id].ident.clone();
+        for projection in path {
+            place = match projection {
+                Projection::Field(field) => format!("{}.{}", place, field),
+                Projection::VariantField(_, field_idx) => format!("{}.{}", place, field_idx),
+                Projection::Index => format!("{}[..]", place),
+                Projection::Deref => format!("*{}", place),
+            };
+        }
+        place
+    }
+
+    fn field_type(&self, ty: DefinitionId, field: &str) -> Option<DefinitionId> {
+        match self.engine.definitions[ty] {
+            Definition::Struct(ref st) => st.fields.iter().find(|x| x.0 == field).map(|x| x.1),
+            Definition::Tuple(ref element_tys) => field
+                .parse::<usize>()
+                .ok()
+                .and_then(|idx| element_tys.get(idx).cloned()),
+            _ => None,
+        }
+    }
+
+    /// Checks if values of the type can be used again after being used up.  Mutable references are counted,
+    /// as passing one along borrows it again rather than moving it
+    fn is_copy(&self, ty: DefinitionId) -> bool {
+        match self.engine.definitions[ty] {
+            Definition::Builtin | Definition::Ref(_, _) | Definition::FnPtr(_, _) => true,
+            Definition::Tuple(ref element_tys) => element_tys.iter().all(|ty| self.is_copy(*ty)),
+            Definition::Array(element_ty, _) => self.is_copy(element_ty),
+            _ => false,
+        }
+    }
+
+    /// Checks if values of the type can hold a reference, and so keep a loan alive.  A type parameter may be
+    /// substituted by the unification, and otherwise could be a reference
+    fn contains_ref(
+        &self,
+        ty: DefinitionId,
+        unification: &[(DefinitionId, DefinitionId)],
+        seen: &mut HashSet<DefinitionId>,
+    ) -> bool {
+        if !seen.insert(ty) {
+            return false;
+        }
+        let contains_ref =
+            |ty: DefinitionId, seen: &mut HashSet<DefinitionId>| self.contains_ref(ty, unification, seen);
+        match self.engine.definitions[ty] {
+            Definition::Builtin => false,
+            Definition::Ref(_, _) => true,
+            Definition::TypeVariable(..) => match unification.iter().find(|x| x.0 == ty) {
+                Some(x) => contains_ref(x.1, seen),
+                None => true,
+            },
+            Definition::Box(boxed_ty) => contains_ref(boxed_ty, seen),
+            Definition::Array(element_ty, _) => contains_ref(element_ty, seen),
+            Definition::Tuple(ref element_tys) => element_tys.iter().any(|ty| contains_ref(*ty, seen)),
+            Definition::Struct(ref st) => st.fields.iter().any(|field| contains_ref(field.1, seen)),
+            Definition::Closure(ref closure) => closure
+                .captures
+                .iter()
+                .any(|capture| !closure.by_move || contains_ref(capture.ty, seen)),
+            Definition::Enum(ref enum_def) => enum_def.variants.iter().any(|variant_id| {
+                match self.engine.definitions[*variant_id] {
+                    Definition::Variant(ref variant) => variant.fields.iter().any(|ty| contains_ref(*ty, seen)),
+                    _ => false,
+                }
+            }),
+            _ => false,
+        }
+    }
+}
+
+/// The fields read from a variable by the bytecode following it, eg) `b` for `s.b`
+fn read_path(following: &[Bytecode]) -> Vec<Projection> {
+    following
+        .iter()
+        .map_while(|code| match code {
+            Bytecode::Dot(field) => Some(Projection::Field(field.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Checks if a new borrow of the given kind can't be made while the loan is in use.  Shared borrows can be made
+/// while a method receiver is only reserved, as the method isn't called until the arguments are done
+fn conflicts(loan_kind: BorrowKind, kind: BorrowKind) -> bool {
+    !matches!(
+        (loan_kind, kind),
+        (BorrowKind::Shared, BorrowKind::Shared) | (BorrowKind::TwoPhase, BorrowKind::Shared)
+    )
+}
+
+/// Starts checking the loop at the given bytecode, unless this is going around it again
+fn enter_loop(loops: &mut Vec<LoopState>, state: &State, idx: usize) {
+    match loops.last_mut() {
+        Some(current) if current.start == idx => {
+            current.exits.clear();
+            current.continues.clear();
+        }
+        _ => loops.push(LoopState {
+            start: idx,
+            entry: state.clone(),
+            exits: vec![],
+            continues: vec![],
+        }),
+    }
+}
+
+fn variant_field_type(definitions: &[Definition], variant_id: DefinitionId, field_idx: usize) -> Option<DefinitionId> {
+    match definitions[variant_id] {
+        Definition::Variant(ref variant) => variant.fields.get(field_idx).cloned(),
+        _ => None,
+    }
+}
+
+impl BytecodeEngine {
+    /// Checks the references and moves in the function's bytecode, reporting borrows that conflict with a
+    /// mutable borrow, uses of values after they're moved, and references to locals that are returned
+    pub(crate) fn check_borrows(&mut self, fun: &Fun) {
+        let diagnostics = {
+            let mut checker = BorrowChecker::new(self, fun);
+            checker.check();
+            checker.diagnostics
+        };
+        for diagnostic in diagnostics {
+            self.report(diagnostic);
+        }
+    }
+}
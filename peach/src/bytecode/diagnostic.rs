@@ -0,0 +1,117 @@
+use std::fmt;
+
+/// A region of a source file.  Lines are 1-based and columns are 0-based, matching proc_macro2's `LineColumn`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceSpan {
+    pub file: String,
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+/// A message attached to a span of source
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: SourceSpan,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new(span: SourceSpan, message: &str) -> Label {
+        Label {
+            span,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// An error, or a warning, found while converting source to bytecode.
+/// The primary label points at the offending code, the secondary labels give context (like the types of operands),
+/// and the notes are printed after the snippet.  The primary label is boxed to keep `Result<_, Diagnostic>` small.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub primary: Option<Box<Label>>,
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    /// Warnings are shown but don't stop the program from being run
+    pub is_warning: bool,
+}
+
+impl Diagnostic {
+    pub fn new(code: &'static str, message: String) -> Diagnostic {
+        Diagnostic {
+            code: Some(code),
+            message,
+            primary: None,
+            secondary: vec![],
+            notes: vec![],
+            is_warning: false,
+        }
+    }
+
+    /// Creates a diagnostic without an error code, eg) for IO and parse errors
+    pub fn uncoded(message: String) -> Diagnostic {
+        Diagnostic {
+            code: None,
+            message,
+            primary: None,
+            secondary: vec![],
+            notes: vec![],
+            is_warning: false,
+        }
+    }
+
+    /// Creates a warning.  Like rustc's lints, these have no code
+    pub fn warning(message: String) -> Diagnostic {
+        Diagnostic {
+            is_warning: true,
+            ..Diagnostic::uncoded(message)
+        }
+    }
+
+    /// The word the diagnostic is introduced with
+    pub fn level(&self) -> &'static str {
+        if self.is_warning {
+            "warning"
+        } else {
+            "error"
+        }
+    }
+
+    pub fn with_primary(mut self, span: SourceSpan, message: &str) -> Diagnostic {
+        self.primary = Some(Box::new(Label::new(span, message)));
+        self
+    }
+
+    pub fn with_secondary(mut self, span: SourceSpan, message: &str) -> Diagnostic {
+        self.secondary.push(Label::new(span, message));
+        self
+    }
+
+    pub fn with_note(mut self, note: &str) -> Diagnostic {
+        self.notes.push(note.to_string());
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "{}[{}]: {}", self.level(), code, self.message)?,
+            None => write!(f, "{}: {}", self.level(), self.message)?,
+        }
+        if let Some(ref primary) = self.primary {
+            write!(
+                f,
+                " ({}:{}:{})",
+                primary.span.file,
+                primary.span.start_line,
+                primary.span.start_column + 1
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -1,9 +1,13 @@
 use std::collections::HashMap;
 
+use bytecode::diagnostic::{Diagnostic, SourceSpan};
 use bytecode::typecheck::builtin_type;
+use proc_macro2::TokenStream;
 use std::os::raw::c_void;
-use syn::{self, Block, FnArg, FnDecl, ForeignItem, ImplItem, Item, ItemImpl, ItemMod, ItemStruct,
-          Pat, ReturnType};
+use syn::spanned::Spanned;
+use syn::synom::Synom;
+use syn::{self, Block, Fields, FnArg, FnDecl, ForeignItem, ImplItem, Item, ItemEnum, ItemImpl,
+          ItemMod, ItemStruct, ItemTrait, Pat, ReturnType, TraitItem};
 
 pub(crate) type ScopeId = usize;
 
@@ -13,6 +17,49 @@ pub type DefinitionId = usize;
 type VarId = usize;
 type Offset = usize;
 
+/// Identifies a span of source in the function's `spans`, so that checks run on the bytecode can report errors
+pub type SpanId = usize;
+
+/// The kind of reference a `Ref` makes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BorrowKind {
+    Shared,
+    Mutable,
+    /// A `&mut` made for a method receiver.  The arguments may still borrow the receiver, as long as they're
+    /// done with it by the time the method is called
+    TwoPhase,
+}
+
+/// The range of integers a `for` loop goes through
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ForRange {
+    /// The type of the integers
+    pub ty: DefinitionId,
+    /// Whether the range includes its end, as `a..=b` does
+    pub inclusive: bool,
+    /// Whether it's gone through from the end, as after `.rev()`
+    pub rev: bool,
+}
+
+/// How an integer method like `wrapping_add` handles overflow
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overflow {
+    /// Wraps around at the bounds of the type
+    Wrapping,
+    /// Gives `None` instead
+    Checked,
+    /// Stops at the bound of the type it went past
+    Saturating,
+}
+
+/// Which Rust build profile to follow.  Arithmetic that overflows panics in a debug build, and wraps around in a
+/// release build
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Profile {
+    Debug,
+    Release,
+}
+
 #[derive(Debug, Clone)]
 pub enum Bytecode {
     ReturnLastStackValue,
@@ -21,33 +68,81 @@ pub enum Bytecode {
     PushU32(u32),
     PushI64(i64),
     PushI32(i32),
-    PushUnknownInt(i32),
+    PushU8(u8),
+    PushU16(u16),
+    PushU128(u128),
+    PushUsize(usize),
+    PushI8(i8),
+    PushI16(i16),
+    PushI128(i128),
+    PushIsize(isize),
+    PushUnknownInt(i128),
+    PushF32(f32),
+    PushF64(f64),
+    PushUnknownFloat(f64),
     PushBool(bool),
     PushRawPtr(*const c_void),
     As(DefinitionId),
-    Add,
-    Sub,
-    Mul,
-    Div,
+    Cast(DefinitionId, DefinitionId), // An `as` cast, from the first type to the second
+    Add(DefinitionId), // Includes the type of the operands
+    Sub(DefinitionId), // Includes the type of the operands
+    Mul(DefinitionId), // Includes the type of the operands
+    Div(DefinitionId), // Includes the type of the operands
+    Rem(DefinitionId), // Includes the type of the operands
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl(DefinitionId), // Includes the type of the value being shifted
+    Shr(DefinitionId), // Includes the type of the value being shifted
     Lt,
-    Neg,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+    Neg(DefinitionId), // Includes the type of the operand
+    Not(DefinitionId), // Includes the type of the operand, which is a bool or an integer
     Dot(String),
+    Ref(BorrowKind, SpanId), // Turns the lvalue on top of the stack into a reference
+    Deref, // Replaces the reference on top of the stack with the value it refers to
     VarDecl(VarId),
     VarDeclUninit(VarId),
-    Var(VarId),
+    Var(VarId, SpanId),
     Assign,
+    CompoundAssign(Box<Bytecode>, DefinitionId), // Applies the operator to the place (of the given type) and the value under it, storing the result in the place
+    OverflowingOp(Box<Bytecode>, Overflow, DefinitionId), // An operator called as a method like `checked_add`, of the result type
     Call(DefinitionId),
+    CallTraitMethod(DefinitionId, DefinitionId, String, DefinitionId), // A method of the trait (second) called on a type variable (first), resolved when instantiated.  Includes the type the method returns
+    CallDyn(DefinitionId, usize), // Calls the method (by its index in the trait) of the trait object (of the given type) under the args
+    MakeClosure(DefinitionId, SpanId), // Makes a closure of the given type, which borrows or moves the variables it captures where it's made
+    CallClosure(DefinitionId), // Calls the closure (of the given type) referred to under the args
+    PushFn(DefinitionId), // Pushes a pointer to the given function
+    CallPtr(DefinitionId), // Calls the function pointer (of the given type) under the args
+    BoxNew(DefinitionId), // Moves the value on top of the stack to the heap, leaving a Box (of the given type) pointing to it
+    MakeDyn(DefinitionId), // Turns the pointer on top of the stack into a trait object, using the given vtable
     If(Offset, DefinitionId), // Offset is number of bytecodes to jump forward if false.  Also includes the type of the result, if this is an expression
     Else(Offset, DefinitionId), // Offset is number of bytecodes to skip (aka jump forward). Also includes the type of the result, if this is an expression
     EndIf(DefinitionId),        //includes the type of the result, if this is an expression
-    BeginWhile,
+    BeginWhile(DefinitionId), // Starts a `while` or `loop`.  Includes the type of the value its `break`s give, if it's an expression
     WhileCond(Offset), // Offset is number of bytecodes to jump forward if false
-    EndWhile(Offset),  // Offset is number of bytecodes to jump backward to return to start of while
+    EndWhile(Offset, DefinitionId), // Offset is number of bytecodes to jump backward to return to start of while.  Also includes the type of the loop
+    Break(Offset, usize, DefinitionId), // Offset is number of bytecodes to jump forward to leave the loop, which is the given number of loops out.  Includes the type of the value it gives, if any
+    Continue(Offset, usize), // Offset is number of bytecodes to jump backward to the start of the loop, which is the given number of loops out
+    BeginFor(VarId, ForRange), // Replaces the start, end and step on the stack with the state of going through the range, kept in the given variable
+    ForNext(Offset, VarId, DefinitionId), // Starts a `for` loop, pushing the next integer (of the given type) of the range in the variable.  Offset is number of bytecodes to jump forward if it's finished
+    IsVariant(DefinitionId), // Replaces the enum value on top of the stack with whether it's the given variant
+    VariantField(DefinitionId, usize), // Replaces the enum value on top of the stack with the given field of the given variant
+    Unreachable(DefinitionId), // Reached if no arm of a match matched, or after a branch that never finishes.  Includes the type of the match or if, if it's an expression
+    ArrayRepeat(DefinitionId), // Replaces the value on top of the stack with an array (of the given type) filled with copies of it
+    Index(DefinitionId), // Replaces the array (of the given type) and the index above it with the element at that index
     DebugPrint(DefinitionId),
+    Pop, // Discards the value on top of the stack, left by an expression used as a statement
 
     //lvalue
-    LValueVar(VarId),
+    LValueVar(VarId, SpanId),
     LValueDot(String),
+    LValueVariantField(DefinitionId, usize),
+    LValueIndex(DefinitionId),
 }
 
 #[derive(Debug, Clone)]
@@ -55,10 +150,22 @@ pub struct Param {
     pub name: String,
     pub(crate) var_id: VarId,
     pub ty: DefinitionId,
+    pub mutable: bool,
 }
 impl Param {
-    pub fn new(name: String, var_id: VarId, ty: DefinitionId) -> Param {
-        Param { name, var_id, ty }
+    pub fn new(name: String, var_id: VarId, ty: DefinitionId, mutable: bool) -> Param {
+        Param {
+            name,
+            var_id,
+            ty,
+            mutable,
+        }
+    }
+
+    /// A parameter given as a pattern rather than a name.  It's named after its position, so that it can
+    /// still be passed in.  Any `mut` belongs to the bindings in the pattern rather than the parameter
+    pub(crate) fn unnamed(index: usize, var_id: VarId, ty: DefinitionId) -> Param {
+        Param::new(format!("__arg{}", index), var_id, ty, false)
     }
 }
 
@@ -66,11 +173,12 @@ impl Param {
 pub struct VarDecl {
     pub ident: String,
     pub ty: DefinitionId,
+    pub mutable: bool,
 }
 
 impl VarDecl {
-    fn new(ident: String, ty: DefinitionId) -> VarDecl {
-        VarDecl { ident, ty }
+    fn new(ident: String, ty: DefinitionId, mutable: bool) -> VarDecl {
+        VarDecl { ident, ty, mutable }
     }
 }
 
@@ -78,6 +186,7 @@ impl VarDecl {
 pub struct VarStack {
     var_stack: Vec<usize>,
     pub(crate) vars: Vec<VarDecl>,
+    pub(crate) spans: Vec<SourceSpan>,
 }
 
 impl VarStack {
@@ -85,16 +194,37 @@ impl VarStack {
         VarStack {
             var_stack: vec![],
             vars: vec![],
+            spans: vec![],
         }
     }
 
-    pub(crate) fn add_var(&mut self, ident: String, definition_id: DefinitionId) -> usize {
-        self.vars.push(VarDecl::new(ident, definition_id));
+    pub(crate) fn add_span(&mut self, span: SourceSpan) -> SpanId {
+        self.spans.push(span);
+        self.spans.len() - 1
+    }
+
+    /// Keeps the variables and spans added in a block after the block's names go out of scope
+    pub(crate) fn leave_block(&mut self, block_var_stack: VarStack) {
+        self.vars = block_var_stack.vars;
+        self.spans = block_var_stack.spans;
+    }
+
+    pub(crate) fn add_var(&mut self, ident: String, definition_id: DefinitionId, mutable: bool) -> usize {
+        self.vars.push(VarDecl::new(ident, definition_id, mutable));
         let pos = self.vars.len() - 1;
         self.var_stack.push(pos);
         pos
     }
 
+    /// The variables that can be named, in the order they were declared
+    pub(crate) fn visible(&self) -> Vec<usize> {
+        self.var_stack
+            .iter()
+            .cloned()
+            .filter(|var_id| !self.vars[*var_id].ident.is_empty())
+            .collect()
+    }
+
     //TODO: this probably should be a Result in the future
     pub fn find_var(&self, ident: &str) -> Option<usize> {
         for var in self.var_stack.iter().rev() {
@@ -113,6 +243,7 @@ pub struct Fun {
     pub return_ty: DefinitionId,
     pub vars: Vec<VarDecl>,
     pub bytecode: Vec<Bytecode>,
+    pub spans: Vec<SourceSpan>,
     pub extern_name: Option<String>,
 }
 
@@ -132,16 +263,185 @@ impl Mod {
     }
 }
 
+/// How a struct is declared, which decides how its values are built and matched
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StructKind {
+    /// eg) `struct Foo { x: i32 }`, built with `Foo { x: 1 }`
+    Named,
+    /// eg) `struct Foo(i32);`, built with `Foo(1)`.  Its fields are named after their positions
+    Tuple,
+    /// eg) `struct Foo;`, which is its only value
+    Unit,
+}
+
 #[derive(Debug, Clone)]
 pub struct Struct {
+    pub name: String,
+    pub kind: StructKind,
     pub fields: Vec<(String, DefinitionId)>,
+    /// Where each field, by name, can be used from
+    pub field_visibility: HashMap<String, Visibility>,
+    /// The type parameters of a generic struct.  Only its instances are used as types
+    pub ty_params: Vec<DefinitionId>,
+    /// The generic struct this is an instance of, and the types it was instantiated with
+    pub instance_of: Option<(DefinitionId, Vec<DefinitionId>)>,
 }
 impl Struct {
-    fn new(fields: Vec<(String, DefinitionId)>) -> Struct {
-        Struct { fields }
+    fn new(
+        name: String,
+        kind: StructKind,
+        fields: Vec<(String, DefinitionId)>,
+        field_visibility: HashMap<String, Visibility>,
+    ) -> Struct {
+        Struct {
+            name,
+            kind,
+            fields,
+            field_visibility,
+            ty_params: vec![],
+            instance_of: None,
+        }
+    }
+
+    pub fn is_generic(&self) -> bool {
+        !self.ty_params.is_empty()
+    }
+}
+
+/// An enum.  Each of its variants is a definition of its own
+#[derive(Debug, Clone)]
+pub struct Enum {
+    pub name: String,
+    pub variants: Vec<DefinitionId>,
+}
+
+/// A variant of an enum, with the types of the values it carries
+#[derive(Debug, Clone)]
+pub struct Variant {
+    pub name: String,
+    pub enum_id: DefinitionId,
+    /// The position of the variant in the enum, which is also its tag
+    pub index: usize,
+    pub fields: Vec<DefinitionId>,
+    /// Named fields aren't supported yet.  A variant with them is kept so the rest of its enum can be used, but it's
+    /// already been reported, so building or matching it only gives an error type
+    pub has_named_fields: bool,
+}
+
+/// An impl block, whose methods are kept in their own scope
+#[derive(Debug, Clone)]
+pub struct Impl {
+    /// The trait being implemented, if this is a trait impl
+    pub trait_id: Option<DefinitionId>,
+    pub scope_id: ScopeId,
+}
+impl Impl {
+    pub fn new(trait_id: Option<DefinitionId>, scope_id: ScopeId) -> Impl {
+        Impl {
+            trait_id,
+            scope_id,
+        }
+    }
+}
+
+/// A method declared by a trait, with its default body if it has one
+#[derive(Debug, Clone)]
+pub struct TraitMethod {
+    pub name: String,
+    pub decl: FnDecl,
+    pub default: Option<Block>,
+}
+
+/// A trait.  Its method signatures are resolved in the scope the trait was declared in
+#[derive(Debug, Clone)]
+pub struct Trait {
+    pub name: String,
+    pub methods: Vec<TraitMethod>,
+    pub scope_id: ScopeId,
+}
+
+/// A trait used as a type, eg) `dyn Shape`.  It's only ever used behind a pointer, as in `&dyn Shape`
+#[derive(Debug, Clone)]
+pub struct DynTrait {
+    pub trait_id: DefinitionId,
+    /// The params and return type of each of the trait's methods, in the order they appear in vtables
+    pub methods: Vec<(Vec<Param>, DefinitionId)>,
+}
+
+/// The methods a type uses to implement a trait, for calling them through a trait object
+#[derive(Debug, Clone)]
+pub struct Vtable {
+    pub dyn_ty: DefinitionId,
+    pub self_ty: DefinitionId,
+    pub methods: Vec<DefinitionId>,
+}
+
+/// A loop being converted, which the `break`s and `continue`s inside it can leave
+#[derive(Debug, Clone)]
+pub(crate) struct Loop {
+    pub label: Option<String>,
+    /// Whether it's a `loop`, the only kind a `break` can give a value from
+    pub gives_value: bool,
+    /// The type of the values the `break`s give, once one has been seen, along with where it was
+    pub break_ty: Option<(DefinitionId, SourceSpan)>,
+}
+
+impl Loop {
+    pub fn new(label: &Option<syn::Label>, gives_value: bool) -> Loop {
+        Loop {
+            label: label.as_ref().map(|label| label.name.to_string()),
+            gives_value,
+            break_ty: None,
+        }
     }
 }
 
+/// A variable captured by a closure from the function it's made in
+#[derive(Debug, Clone)]
+pub struct Capture {
+    /// The variable, in the function the closure is made in
+    pub var_id: VarId,
+    /// The variable the closure's body uses it through
+    pub(crate) inner_var_id: VarId,
+    pub ty: DefinitionId,
+    /// Whether the closure's body changes it, which needs it to be borrowed mutably
+    pub mutated: bool,
+}
+
+/// The type of a closure.  Each closure expression has a type of its own
+#[derive(Debug, Clone)]
+pub struct Closure {
+    /// Whether the variables it captures are moved into it, as with `move`, rather than borrowed
+    pub by_move: bool,
+    pub captures: Vec<Capture>,
+    /// The body, as a function taking the closure's params.  It's converted once the types of the params are known
+    pub fun: Option<Fun>,
+}
+
+impl Closure {
+    /// How the closure borrows the captured variable, or None if it's moved in
+    pub fn borrow_kind(&self, capture: &Capture) -> Option<BorrowKind> {
+        match (self.by_move, capture.mutated) {
+            (true, _) => None,
+            (false, true) => Some(BorrowKind::Mutable),
+            (false, false) => Some(BorrowKind::Shared),
+        }
+    }
+
+    /// Whether calling the closure changes what it captured, so that it has to be borrowed mutably to be called
+    pub fn mutates(&self) -> bool {
+        self.captures.iter().any(|capture| capture.mutated)
+    }
+}
+
+/// A closure whose body is waiting to be converted until it's first called, as its params don't all have types
+pub(crate) struct PendingClosure {
+    pub expr: syn::ExprClosure,
+    pub scope_id: ScopeId,
+    /// The variables in scope where the closure was made, which its body can capture
+    pub var_stack: VarStack,
+}
+
 #[derive(Debug, Clone)]
 pub struct LazyFn {
     pub decl: FnDecl,
@@ -160,21 +460,54 @@ pub enum Definition {
     LazyFn(LazyFn),
     LazyMod(ItemMod),
     LazyStruct(ItemStruct),
+    LazyEnum(ItemEnum),
     LazyImpl(ItemImpl),
 
     //Processed definitions
     Fun(Fun),
     Mod(Mod),
     Struct(Struct),
+    Enum(Enum),
+    Variant(Variant),
+    Impl(Impl),
+    Trait(Trait),
+    DynTrait(DynTrait),
+    Vtable(Vtable),
+    Closure(Closure),
     InstantiatedFun(DefinitionId, Vec<(DefinitionId, DefinitionId)>),
+    Ref(DefinitionId, bool), // The type being referred to, and if the reference is mutable
+    Box(DefinitionId),       // The type of the value on the heap
+    Tuple(Vec<DefinitionId>), // The types of the elements
+    Array(DefinitionId, usize), // The type of the elements, and how many there are
+    FnPtr(Vec<DefinitionId>, DefinitionId), // The types of the params, and the return type
     Builtin,
-    TypeVariable,
+    TypeVariable(String, Vec<DefinitionId>), // The name of the type parameter, and the traits it is bound by
+}
+
+/// The source of a loaded file, kept around so that diagnostics can show snippets
+pub(crate) struct SourceFile {
+    /// The name used when reporting errors, relative to the project root
+    name: String,
+    /// The name proc_macro2 gave this source when it was parsed
+    codemap_name: Option<String>,
+    src: String,
+}
+
+/// Where a definition can be named from
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Visibility {
+    /// Anywhere, eg) `pub` or `pub(crate)`, as there's only ever the one crate
+    Public,
+    /// Only inside the module with the given scope, including the modules inside it, eg) a private item
+    Restricted(ScopeId),
 }
 
 pub struct Scope {
     parent: Option<ScopeId>,
     is_mod: bool,
-    pub(crate) definitions: HashMap<String, DefinitionId>,
+    pub(crate) definitions: HashMap<String, (DefinitionId, Visibility)>,
+    /// Impls declared in this scope that haven't been attached to their type yet
+    pub(crate) impls: Vec<DefinitionId>,
 }
 
 impl Scope {
@@ -183,6 +516,7 @@ impl Scope {
             parent,
             is_mod,
             definitions: HashMap::new(),
+            impls: vec![],
         }
     }
 }
@@ -203,31 +537,66 @@ impl Scope {
 /// bc.process_fn("main", 0);
 /// ```
 /// Processing is done on function granularity.  As definitions are referenced in the function, they too are processed.
+///
+/// Errors found while processing are collected rather than stopping at the first one.  The expression in error is given
+/// the `ERROR` type, which is compatible with every other type, so that one mistake doesn't cause a cascade of errors.
 pub struct BytecodeEngine {
     pub(crate) scopes: Vec<Scope>,
     pub(crate) definitions: Vec<Definition>,
     pub(crate) project_root: Option<::std::path::PathBuf>,
+    pub(crate) source_files: Vec<SourceFile>,
+    pub(crate) diagnostics: Vec<Diagnostic>,
+    /// The warnings found while processing, which unlike the errors don't stop the program from running
+    pub(crate) warnings: Vec<Diagnostic>,
+    /// The impls attached to each type
+    pub(crate) impls: HashMap<DefinitionId, Vec<DefinitionId>>,
+    /// The instantiations of generic functions, by the generic function and the types it was instantiated with
+    instances: HashMap<(DefinitionId, Vec<DefinitionId>), DefinitionId>,
+    /// The instances of generic structs, by the generic struct and the types it was instantiated with
+    struct_instances: HashMap<(DefinitionId, Vec<DefinitionId>), DefinitionId>,
+    /// The `Option` enum holding each type, made the first time it's needed
+    options: HashMap<DefinitionId, DefinitionId>,
+    pub(crate) profile: Profile,
+    /// The loops around the code being converted, innermost last
+    pub(crate) loops: Vec<Loop>,
+    /// The closures made in the function being converted that haven't been called yet, by their type
+    pub(crate) pending_closures: HashMap<DefinitionId, PendingClosure>,
+    /// For each closure body being converted, innermost last, whether it changes each variable it can capture.
+    /// Those are the first variables of its body
+    pub(crate) captures_mutated: Vec<Vec<bool>>,
 }
 
 impl BytecodeEngine {
     pub fn new() -> BytecodeEngine {
         let mut definitions = vec![];
 
-        for _ in 0..(builtin_type::ERROR + 1) {
+        for _ in 0..(builtin_type::NEVER + 1) {
             definitions.push(Definition::Builtin);
         }
 
         BytecodeEngine {
-            scopes: vec![Scope {
-                parent: None,
-                is_mod: true,
-                definitions: HashMap::new(),
-            }],
+            scopes: vec![Scope::new(None, true)],
             definitions,
             project_root: None,
+            source_files: vec![],
+            diagnostics: vec![],
+            warnings: vec![],
+            impls: HashMap::new(),
+            instances: HashMap::new(),
+            struct_instances: HashMap::new(),
+            options: HashMap::new(),
+            profile: Profile::Debug,
+            loops: vec![],
+            pending_closures: HashMap::new(),
+            captures_mutated: vec![],
         }
     }
 
+    /// Sets the build profile that the program will be run or compiled with
+    pub fn set_profile(&mut self, profile: Profile) {
+        self.profile = profile;
+    }
+
     /// Will find the definition id for the given name, by starting at the scope given and working up through the scopes
     /// until the matching definition is found.
     /// Returns the corresponding definition id with the scope it was found in
@@ -253,11 +622,125 @@ impl BytecodeEngine {
         }
 
         Some((
-            self.scopes[current_scope_id].definitions[defn_name],
+            self.scopes[current_scope_id].definitions[defn_name].0,
             current_scope_id,
         ))
     }
 
+    /// The scope of the module that the given scope is in
+    fn module_of(&self, scope_id: ScopeId) -> ScopeId {
+        let mut current_scope_id = scope_id;
+        while !self.scopes[current_scope_id].is_mod {
+            match self.scopes[current_scope_id].parent {
+                Some(parent_id) => current_scope_id = parent_id,
+                None => break,
+            }
+        }
+        current_scope_id
+    }
+
+    /// Works out where a definition declared in the given scope with the given visibility can be named from
+    pub(crate) fn visibility_in(&mut self, vis: &syn::Visibility, scope_id: ScopeId) -> Visibility {
+        let module_scope_id = self.module_of(scope_id);
+        match vis {
+            syn::Visibility::Public(_) | syn::Visibility::Crate(_) => Visibility::Public,
+            syn::Visibility::Inherited => Visibility::Restricted(module_scope_id),
+            syn::Visibility::Restricted(vis_restricted) => {
+                let path = &vis_restricted.path;
+                if path.segments.len() != 1 {
+                    return self.restricted_to_path(vis_restricted);
+                }
+                match path.segments[0].ident.as_ref() {
+                    "crate" => Visibility::Public,
+                    "self" => Visibility::Restricted(module_scope_id),
+                    "super" => match self.scopes[module_scope_id].parent {
+                        Some(parent_id) => Visibility::Restricted(self.module_of(parent_id)),
+                        None => Visibility::Public,
+                    },
+                    _ => self.restricted_to_path(vis_restricted),
+                }
+            }
+        }
+    }
+
+    /// Reports a `pub(in path)`, which isn't supported yet.  The item is treated as public, so that uses of it
+    /// aren't reported as well
+    fn restricted_to_path(&mut self, vis_restricted: &syn::VisRestricted) -> Visibility {
+        self.report(self.unsupported(vis_restricted, "Visibility restricted to a path is not yet supported"));
+        Visibility::Public
+    }
+
+    /// Checks if something with the given visibility can be named from the given scope
+    pub(crate) fn is_visible(&self, visibility: Visibility, from_scope_id: ScopeId) -> bool {
+        match visibility {
+            Visibility::Public => true,
+            Visibility::Restricted(module_scope_id) => {
+                let mut current_scope_id = Some(from_scope_id);
+                while let Some(scope_id) = current_scope_id {
+                    if scope_id == module_scope_id {
+                        return true;
+                    }
+                    current_scope_id = self.scopes[scope_id].parent;
+                }
+                false
+            }
+        }
+    }
+
+    /// Reports an error if the definition with the given name in a module can't be named from the given scope
+    fn check_visible(&mut self, ident: &syn::Ident, scope_id: ScopeId, from_scope_id: ScopeId) {
+        let (definition_id, visibility) = match self.scopes[scope_id].definitions.get(ident.as_ref()) {
+            Some(entry) => *entry,
+            None => return,
+        };
+
+        if !self.is_visible(visibility, from_scope_id) {
+            let kind = match self.definitions[definition_id] {
+                Definition::LazyFn(_) | Definition::Fun(_) => "Function",
+                Definition::LazyMod(_) | Definition::Mod(_) => "Module",
+                Definition::LazyStruct(_) | Definition::Struct(_) => "Struct",
+                Definition::LazyEnum(_) | Definition::Enum(_) => "Enum",
+                Definition::Trait(_) => "Trait",
+                _ => "Item",
+            };
+            self.report(Diagnostic::new(
+                "E0603",
+                format!("{} `{}` is private", kind, ident),
+            ).with_primary(
+                self.span_of(ident),
+                &format!("private {}", kind.to_lowercase()),
+            ));
+        }
+    }
+
+    /// Reports an error if the associated function or method with the given name on a type can't be called from
+    /// the given scope
+    pub(crate) fn check_associated_fn_visible(
+        &mut self,
+        type_id: DefinitionId,
+        ident: &syn::Ident,
+        from_scope_id: ScopeId,
+    ) {
+        let impl_ids = match self.impls.get(&type_id) {
+            Some(impl_ids) => impl_ids.clone(),
+            None => return,
+        };
+
+        for impl_id in impl_ids {
+            if let Definition::Impl(ref imp) = self.definitions[impl_id] {
+                if let Some(&(_, visibility)) = self.scopes[imp.scope_id].definitions.get(ident.as_ref()) {
+                    if !self.is_visible(visibility, from_scope_id) {
+                        self.report(Diagnostic::new(
+                            "E0624",
+                            format!("Method `{}` is private", ident),
+                        ).with_primary(self.span_of(ident), "private method"));
+                    }
+                    return;
+                }
+            }
+        }
+    }
+
     /// Gets the bytecoded function for the given name
     pub fn get_fn(&self, defn_name: &str, scope_id: ScopeId) -> &Fun {
         if let Some((defn_id, _)) = self.get_defn(defn_name, scope_id) {
@@ -283,7 +766,19 @@ impl BytecodeEngine {
     }
 
     /// Loads the file with the given name
-    pub fn load_file(&mut self, fname: &str) {
+    pub fn load_file(&mut self, fname: &str) -> Result<(), Vec<Diagnostic>> {
+        let result = self.parse_source_file(fname).and_then(|syntax_file| {
+            for item in syntax_file.items {
+                self.prepare_item(item, 0)?;
+            }
+            Ok(())
+        });
+        self.take_diagnostics(result)
+    }
+
+    /// Reads and parses the file with the given name (relative to the project root), remembering its source for
+    /// later diagnostics
+    fn parse_source_file(&mut self, fname: &str) -> Result<syn::File, Diagnostic> {
         use std::fs::File;
         use std::io::Read;
         let path = if let Some(ref project_path) = self.project_root {
@@ -296,44 +791,112 @@ impl BytecodeEngine {
             temp_path
         };
 
-        let mut file = File::open(path).expect("Unable to open file");
-
         let mut src = String::new();
-        file.read_to_string(&mut src).expect("Unable to read file");
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut src))
+            .map_err(|e| Diagnostic::uncoded(format!("couldn't read {}: {}", fname, e)))?;
+
+        let syntax_file = syn::parse_file(&src)
+            .map_err(|e| Diagnostic::uncoded(format!("couldn't parse {}: {}", fname, e)))?;
+
+        // Spans only know the name proc_macro2 gave to the parsed source, so remember which file that was
+        let codemap_name = syntax_file
+            .items
+            .first()
+            .map(|item| item.span().source_file().path().to_string());
+
+        self.source_files.push(SourceFile {
+            name: fname.to_string(),
+            codemap_name,
+            src,
+        });
+
+        Ok(syntax_file)
+    }
 
-        let syntax_file = syn::parse_file(&src).expect("Unable to parse file");
+    /// Returns the source text of a loaded file, using the file name given in diagnostics
+    pub fn source_text(&self, fname: &str) -> Option<&str> {
+        self.source_files
+            .iter()
+            .find(|source_file| source_file.name == fname)
+            .map(|source_file| source_file.src.as_str())
+    }
 
-        for item in syntax_file.items {
-            self.prepare_item(item, 0);
+    /// Finds the location in the source of the given syntax node
+    pub(crate) fn span_of<T: Spanned>(&self, node: &T) -> SourceSpan {
+        let span = node.span();
+        let codemap_name = span.source_file().path().to_string();
+        let start = span.start();
+        let end = span.end();
+
+        let file = self.source_files
+            .iter()
+            .find(|source_file| source_file.codemap_name.as_ref() == Some(&codemap_name))
+            .map(|source_file| source_file.name.clone())
+            .unwrap_or(codemap_name);
+
+        SourceSpan {
+            file,
+            start_line: start.line,
+            start_column: start.column,
+            end_line: end.line,
+            end_column: end.column,
         }
     }
 
-    /// Prepares the given item to be processed lazily
-    pub fn prepare_item(&mut self, item: Item, current_scope_id: ScopeId) {
-        use std::fs::File;
-        use std::io::Read;
+    /// Prepares a trait, keeping its method signatures (and any default bodies) to be resolved as they're used
+    fn prepare_trait(&self, item_trait: ItemTrait, scope_id: ScopeId) -> Result<Trait, Diagnostic> {
+        if !item_trait.generics.params.is_empty() {
+            return Err(self.unsupported(&item_trait.generics, "Generic traits are not yet supported"));
+        }
+        if !item_trait.supertraits.is_empty() {
+            return Err(self.unsupported(&item_trait.supertraits, "Supertraits are not yet supported"));
+        }
+
+        let mut methods = vec![];
+        for item in item_trait.items {
+            match item {
+                TraitItem::Method(trait_item_method) => methods.push(TraitMethod {
+                    name: trait_item_method.sig.ident.to_string(),
+                    decl: trait_item_method.sig.decl,
+                    default: trait_item_method.default,
+                }),
+                _ => return Err(self.unsupported(&item, "Trait items other than methods are not yet supported")),
+            }
+        }
+
+        Ok(Trait {
+            name: item_trait.ident.to_string(),
+            methods,
+            scope_id,
+        })
+    }
 
+    /// Prepares the given item to be processed lazily
+    pub fn prepare_item(&mut self, item: Item, current_scope_id: ScopeId) -> Result<(), Diagnostic> {
         match item {
             Item::Fn(item_fn) => {
                 // Adds a function to be processed lazily
                 let fn_name = item_fn.ident.to_string();
+                let visibility = self.visibility_in(&item_fn.vis, current_scope_id);
                 self.definitions.push(Definition::LazyFn(LazyFn::new(
                     *item_fn.decl,
                     *item_fn.block,
                 )));
                 self.scopes[current_scope_id]
                     .definitions
-                    .insert(fn_name, self.definitions.len() - 1);
+                    .insert(fn_name, (self.definitions.len() - 1, visibility));
             }
             Item::ForeignMod(item_fm) => for f in item_fm.items {
                 match f {
                     ForeignItem::Fn(fun) => {
                         let fn_name = fun.ident.to_string();
+                        let visibility = self.visibility_in(&fun.vis, current_scope_id);
 
                         let return_ty = match &fun.decl.output {
                             ReturnType::Default => builtin_type::VOID,
                             ReturnType::Type(_, ref box_ty) => {
-                                self.resolve_type(box_ty, current_scope_id)
+                                self.resolve_type(box_ty, current_scope_id)?
                             }
                         };
 
@@ -344,23 +907,39 @@ impl BytecodeEngine {
                         for input in &fun.decl.inputs {
                             match input {
                                 FnArg::Captured(ref capture) => {
+                                    let ty = self.resolve_type(&capture.ty, current_scope_id)?;
                                     match capture.pat {
                                         Pat::Ident(ref pi) => {
                                             let ident = pi.ident.to_string();
-                                            let ty =
-                                                self.resolve_type(&capture.ty, current_scope_id);
-                                            let var_id = var_stack.add_var(ident.clone(), ty);
-                                            params.push(Param::new(ident, var_id, ty));
+                                            let mutable = pi.mutability.is_some();
+                                            let var_id = var_stack.add_var(ident.clone(), ty, mutable);
+                                            params.push(Param::new(ident, var_id, ty, mutable));
+                                        }
+                                        Pat::Wild(_) => {
+                                            let var_id = var_stack.add_var(String::new(), ty, false);
+                                            params.push(Param::unnamed(params.len(), var_id, ty));
+                                        }
+                                        ref pat => {
+                                            // Foreign functions have no body for a pattern to bind variables in
+                                            self.report(Diagnostic::new(
+                                                "E0130",
+                                                "Patterns aren't allowed in foreign function declarations"
+                                                    .to_string(),
+                                            ).with_primary(
+                                                self.span_of(pat),
+                                                "pattern not allowed in foreign function",
+                                            ));
+                                            let var_id = var_stack.add_var(String::new(), ty, false);
+                                            params.push(Param::unnamed(params.len(), var_id, ty));
                                         }
-                                        _ => unimplemented!(
-                                            "Unsupported pattern type in function parameter"
-                                        ),
                                     };
                                 }
-                                _ => unimplemented!(
-                                    "Function argument of {:?} is not supported",
-                                    input
-                                ),
+                                _ => {
+                                    return Err(self.unsupported(
+                                        input,
+                                        "Foreign function parameters without a name are not yet supported",
+                                    ))
+                                }
                             }
                         }
 
@@ -370,42 +949,63 @@ impl BytecodeEngine {
                             ty_params: vec![],
                             return_ty,
                             vars: vec![],
+                            spans: vec![],
                             extern_name: Some(fn_name.clone()),
                         }));
                         self.scopes[current_scope_id]
                             .definitions
-                            .insert(fn_name, self.definitions.len() - 1);
+                            .insert(fn_name, (self.definitions.len() - 1, visibility));
+                    }
+                    ref f => {
+                        return Err(self.unsupported(f, "Foreign items other than functions are not yet supported"))
                     }
-                    _ => unimplemented!("Unsupported foreign item"),
                 }
             },
             Item::Impl(item_impl) => {
-                //let impl_name = item_impl.sig.ident.to_string();
-                println!("{:#?}", item_impl);
+                // Impls are attached to their type once the type is processed
+                let type_name = match impl_type_name(&item_impl) {
+                    Some(type_name) => type_name,
+                    None => {
+                        return Err(self.unsupported(
+                            &item_impl.self_ty,
+                            "Impls for types that aren't named are not yet supported",
+                        ))
+                    }
+                };
+                self.definitions.push(Definition::LazyImpl(item_impl));
+                let impl_id = self.definitions.len() - 1;
+                self.scopes[current_scope_id].impls.push(impl_id);
+
+                // If the type was already processed (eg, by an earlier line in the repl, or because it's
+                // builtin), attach it now
+                if let Some(definition_id) = builtin_type::named(&type_name) {
+                    self.attach_impls(definition_id, &type_name, current_scope_id)?;
+                } else if let Some((definition_id, _)) = self.get_defn(&type_name, current_scope_id) {
+                    match self.definitions[definition_id] {
+                        Definition::Struct(_) | Definition::Enum(_) => {
+                            self.attach_impls(definition_id, &type_name, current_scope_id)?;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Item::Trait(item_trait) => {
+                let ident = item_trait.ident.to_string();
+                let visibility = self.visibility_in(&item_trait.vis, current_scope_id);
+                let trait_def = self.prepare_trait(item_trait, current_scope_id)?;
+
+                self.definitions.push(Definition::Trait(trait_def));
+                self.scopes[current_scope_id]
+                    .definitions
+                    .insert(ident, (self.definitions.len() - 1, visibility));
             }
             Item::Mod(item_mod) => {
+                let visibility = self.visibility_in(&item_mod.vis, current_scope_id);
                 if item_mod.content.is_none() {
                     //Load the file as a module
-                    let fname = item_mod.ident.as_ref();
-                    let path = if let Some(ref project_path) = self.project_root {
-                        let mut temp_path = project_path.clone();
-                        temp_path.push(fname);
-                        temp_path.set_extension("rs");
-                        temp_path
-                    } else {
-                        let mut temp_path = ::std::path::PathBuf::new();
-                        temp_path.push(fname);
-                        temp_path.set_extension("rs");
-                        temp_path
-                    };
-
-                    let mut file = File::open(path).expect("Unable to open file");
-
-                    let mut src = String::new();
-                    file.read_to_string(&mut src).expect("Unable to read file");
-
-                    let syntax_file = syn::parse_file(&src).expect("Unable to parse file");
-                    self.scopes.push(Scope::new(None, true));
+                    let fname = format!("{}.rs", item_mod.ident);
+                    let syntax_file = self.parse_source_file(&fname)?;
+                    self.scopes.push(Scope::new(Some(current_scope_id), true));
                     let mod_scope_id = self.scopes.len() - 1;
 
                     // Eagerly process the top-most bit of the file as a module
@@ -416,10 +1016,10 @@ impl BytecodeEngine {
 
                     self.scopes[current_scope_id]
                         .definitions
-                        .insert(item_mod.ident.to_string(), self.definitions.len() - 1);
+                        .insert(item_mod.ident.to_string(), (self.definitions.len() - 1, visibility));
 
                     for item in syntax_file.items {
-                        self.prepare_item(item, mod_scope_id);
+                        self.prepare_item(item, mod_scope_id)?;
                     }
                 } else {
                     // Add module to be processed lazily
@@ -427,7 +1027,7 @@ impl BytecodeEngine {
                     self.definitions.push(Definition::LazyMod(item_mod));
                     self.scopes[current_scope_id]
                         .definitions
-                        .insert(mod_name, self.definitions.len() - 1);
+                        .insert(mod_name, (self.definitions.len() - 1, visibility));
                 }
             }
             Item::Use(ref item_use) => {
@@ -446,62 +1046,227 @@ impl BytecodeEngine {
                     }
                 }
 
-                self.process_use_tree(&item_use.tree, current_scope_id, temp_scope_id);
+                let visibility = self.visibility_in(&item_use.vis, current_scope_id);
+                self.process_use_tree(&item_use.tree, current_scope_id, temp_scope_id, visibility)?;
             }
             Item::Struct(item_struct) => {
                 let ident = item_struct.ident.to_string();
+                let visibility = self.visibility_in(&item_struct.vis, current_scope_id);
 
                 self.definitions.push(Definition::LazyStruct(item_struct));
                 self.scopes[current_scope_id]
                     .definitions
-                    .insert(ident, self.definitions.len() - 1);
+                    .insert(ident, (self.definitions.len() - 1, visibility));
             }
-            _ => {
-                unimplemented!("Unknown item type: {:#?}", item);
+            Item::Enum(item_enum) => {
+                let ident = item_enum.ident.to_string();
+                let visibility = self.visibility_in(&item_enum.vis, current_scope_id);
+
+                self.definitions.push(Definition::LazyEnum(item_enum));
+                self.scopes[current_scope_id]
+                    .definitions
+                    .insert(ident, (self.definitions.len() - 1, visibility));
             }
+            _ => return Err(self.unsupported(&item, "This kind of item is not yet supported")),
         }
+
+        Ok(())
     }
 
     /// Begin processing the lazy definitions starting at the given function.
     /// This will continue processing until all necessary definitions have been processed.
-    pub fn process_fn(&mut self, fn_name: &str, scope_id: ScopeId) -> DefinitionId {
+    /// If there were errors, all of them are returned in the order they were found.
+    pub fn process_fn(&mut self, fn_name: &str, scope_id: ScopeId) -> Result<DefinitionId, Vec<Diagnostic>> {
+        let result = self.process_fn_defn(fn_name, scope_id);
+        self.take_diagnostics(result)
+    }
+
+    /// Records an error and carries on, returning the `ERROR` type for the expression in error
+    pub(crate) fn report(&mut self, diagnostic: Diagnostic) -> DefinitionId {
+        self.diagnostics.push(diagnostic);
+        builtin_type::ERROR
+    }
+
+    /// Records a warning, which is shown but doesn't stop the program from being run
+    pub(crate) fn warn(&mut self, diagnostic: Diagnostic) {
+        self.warnings.push(diagnostic);
+    }
+
+    /// Gathers up the warnings found so far
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        self.warnings.drain(..).collect()
+    }
+
+    /// The error for valid Rust that Peach doesn't support yet.  It has rustc's code for a feature that isn't
+    /// available, which is the closest there is
+    pub(crate) fn unsupported<T: Spanned>(&self, node: &T, message: &str) -> Diagnostic {
+        Diagnostic::new("E0658", message.to_string()).with_primary(self.span_of(node), "not supported")
+    }
+
+    /// Gathers up the errors reported during processing, along with the error that stopped processing (if any)
+    fn take_diagnostics<T>(&mut self, result: Result<T, Diagnostic>) -> Result<T, Vec<Diagnostic>> {
+        let mut diagnostics: Vec<Diagnostic> = self.diagnostics.drain(..).collect();
+        match result {
+            Ok(value) => {
+                if diagnostics.is_empty() {
+                    Ok(value)
+                } else {
+                    Err(diagnostics)
+                }
+            }
+            Err(diagnostic) => {
+                diagnostics.push(diagnostic);
+                Err(diagnostics)
+            }
+        }
+    }
+
+    fn process_fn_defn(&mut self, fn_name: &str, scope_id: ScopeId) -> Result<DefinitionId, Diagnostic> {
         if let Some((definition_id, found_scope_id)) = self.get_defn(fn_name, scope_id) {
+            // The function may be converted from inside a loop of another, whose loops it can't leave, and
+            // whose closures it can't call
+            let outer_loops = ::std::mem::take(&mut self.loops);
+            let outer_closures = ::std::mem::take(&mut self.pending_closures);
             let fun = self.convert_fn_to_bytecode(definition_id, found_scope_id);
-            self.definitions[definition_id] = Definition::Fun(fun);
+            self.loops = outer_loops;
+            self.pending_closures = outer_closures;
+            self.definitions[definition_id] = Definition::Fun(fun?);
 
-            definition_id
+            Ok(definition_id)
         } else {
-            unimplemented!("Can not find function {}", fn_name);
+            Err(Diagnostic::new(
+                "E0425",
+                format!("Can not find function `{}`", fn_name),
+            ))
         }
     }
 
-    fn process_struct(&mut self, struct_name: &str, scope_id: ScopeId) -> DefinitionId {
-        if let Some((definition_id, _found_scope_id)) = self.get_defn(struct_name, scope_id) {
-            let fields_in =
+    fn process_struct(&mut self, struct_name: &str, scope_id: ScopeId) -> Result<DefinitionId, Diagnostic> {
+        if let Some((definition_id, found_scope_id)) = self.get_defn(struct_name, scope_id) {
+            let (fields_in, generics) =
                 if let Definition::LazyStruct(ref item_struct) = self.definitions[definition_id] {
-                    item_struct.fields.clone()
+                    (item_struct.fields.clone(), item_struct.generics.clone())
                 } else {
                     unimplemented!("Could not process struct fields");
                 };
+            let (scope_id, ty_params) = self.declare_ty_params(&generics, scope_id);
 
             let mut fields: Vec<(String, DefinitionId)> = vec![];
-            for iter in &fields_in {
-                let field_ty = self.resolve_type(&iter.ty, scope_id);
-                fields.push((iter.ident.unwrap().to_string(), field_ty));
+            let mut field_visibility = HashMap::new();
+            for (index, iter) in fields_in.iter().enumerate() {
+                let field_ty = self.resolve_type(&iter.ty, scope_id)?;
+                let field_name = match iter.ident {
+                    Some(ident) => ident.to_string(),
+                    None => index.to_string(),
+                };
+                field_visibility.insert(field_name.clone(), self.visibility_in(&iter.vis, found_scope_id));
+                fields.push((field_name, field_ty));
             }
 
-            fields.sort();
+            // Named fields are kept in order of their names.  Unnamed fields stay in order of their position
+            let kind = match fields_in {
+                Fields::Named(_) => {
+                    fields.sort();
+                    StructKind::Named
+                }
+                Fields::Unnamed(_) => StructKind::Tuple,
+                Fields::Unit => StructKind::Unit,
+            };
 
-            let s = Struct::new(fields);
+            let mut s = Struct::new(struct_name.to_string(), kind, fields, field_visibility);
+            s.ty_params = ty_params;
             self.definitions[definition_id] = Definition::Struct(s);
 
-            definition_id
+            self.attach_impls(definition_id, struct_name, found_scope_id)?;
+
+            Ok(definition_id)
         } else {
             unimplemented!("Can not find struct {}", struct_name);
         }
     }
 
-    fn process_mod(&mut self, mod_name: &str, scope_id: ScopeId) -> DefinitionId {
+    fn process_enum(&mut self, enum_name: &str, scope_id: ScopeId) -> Result<DefinitionId, Diagnostic> {
+        if let Some((definition_id, found_scope_id)) = self.get_defn(enum_name, scope_id) {
+            let item_enum =
+                if let Definition::LazyEnum(ref item_enum) = self.definitions[definition_id] {
+                    item_enum.clone()
+                } else {
+                    unimplemented!("Could not process enum variants");
+                };
+
+            if !item_enum.generics.params.is_empty() {
+                return Err(self.unsupported(&item_enum.generics, "Generic enums are not yet supported"));
+            }
+
+            // The enum is stored before its variants, so that they can refer to it (eg, through a Box)
+            self.definitions[definition_id] = Definition::Enum(Enum {
+                name: enum_name.to_string(),
+                variants: vec![],
+            });
+
+            let mut variants = vec![];
+            for (index, variant) in item_enum.variants.iter().enumerate() {
+                if let Some((_, ref discriminant)) = variant.discriminant {
+                    return Err(self.unsupported(discriminant, "Explicit enum discriminants are not yet supported"));
+                }
+
+                let mut fields = vec![];
+                match variant.fields {
+                    Fields::Unit => {}
+                    Fields::Unnamed(ref fields_unnamed) => for field in &fields_unnamed.unnamed {
+                        fields.push(self.resolve_type(&field.ty, found_scope_id)?);
+                    },
+                    Fields::Named(ref fields_named) => {
+                        self.report(self.unsupported(
+                            fields_named,
+                            "Enum variants with named fields are not yet supported",
+                        ));
+                        for field in &fields_named.named {
+                            fields.push(self.resolve_type(&field.ty, found_scope_id)?);
+                        }
+                    }
+                }
+
+                self.definitions.push(Definition::Variant(Variant {
+                    name: variant.ident.to_string(),
+                    enum_id: definition_id,
+                    index,
+                    fields,
+                    has_named_fields: matches!(variant.fields, Fields::Named(_)),
+                }));
+                variants.push(self.definitions.len() - 1);
+            }
+
+            if let Definition::Enum(ref mut enum_def) = self.definitions[definition_id] {
+                enum_def.variants = variants;
+            }
+
+            self.attach_impls(definition_id, enum_name, found_scope_id)?;
+
+            Ok(definition_id)
+        } else {
+            unimplemented!("Can not find enum {}", enum_name);
+        }
+    }
+
+    /// Finds the variant with the given name in an enum
+    fn find_variant(&self, enum_id: DefinitionId, variant_name: &str) -> Option<DefinitionId> {
+        if let Definition::Enum(ref enum_def) = self.definitions[enum_id] {
+            enum_def
+                .variants
+                .iter()
+                .find(|variant_id| match self.definitions[**variant_id] {
+                    Definition::Variant(ref variant) => variant.name == variant_name,
+                    _ => false,
+                })
+                .cloned()
+        } else {
+            None
+        }
+    }
+
+    /// Processes the module with the given name, returning None if there is no such module in scope
+    fn process_mod(&mut self, mod_name: &str, scope_id: ScopeId) -> Result<Option<DefinitionId>, Diagnostic> {
         if let Some((definition_id, current_scope_id)) = self.get_defn(mod_name, scope_id) {
             if let Definition::LazyMod(ref item_mod) = self.definitions[definition_id] {
                 self.scopes.push(Scope::new(Some(current_scope_id), true));
@@ -510,7 +1275,7 @@ impl BytecodeEngine {
                 match item_mod.content {
                     //TODO: would be great if we didn't clone here and just reused what we had
                     Some(ref content) => for item in content.1.clone() {
-                        self.prepare_item(item, mod_scope_id);
+                        self.prepare_item(item, mod_scope_id)?;
                     },
                     None => {}
                 }
@@ -519,60 +1284,511 @@ impl BytecodeEngine {
             } else if let Definition::Mod(_) = self.definitions[definition_id] {
 
             } else {
-                unimplemented!("Processing definition that is not a lazy module");
+                return Ok(None);
             }
-            definition_id
+            Ok(Some(definition_id))
         } else {
-            unimplemented!("Can not find mod {}", mod_name);
+            Ok(None)
         }
     }
 
-    fn process_impl(&mut self, impl_name: &str, scope_id: ScopeId) -> DefinitionId {
-        if let Some((definition_id, current_scope_id)) = self.get_defn(impl_name, scope_id) {
-            if let Definition::LazyImpl(item_impl) = self.definitions[definition_id].clone() {
-                self.scopes.push(Scope::new(Some(current_scope_id), true));
-                let impl_scope_id = self.scopes.len() - 1;
-                for item in item_impl.items {
-                    match item {
-                        ImplItem::Method(impl_item_method) => {
-                            // Adds a function to be processed lazily
-                            let fn_name = impl_item_method.sig.ident.to_string();
-                            self.definitions.push(Definition::LazyFn(LazyFn::new(
-                                impl_item_method.sig.decl,
-                                impl_item_method.block,
-                            )));
+    /// Attaches the impls for the named type that were declared in the same scope as the type
+    fn attach_impls(
+        &mut self,
+        type_id: DefinitionId,
+        type_name: &str,
+        scope_id: ScopeId,
+    ) -> Result<(), Diagnostic> {
+        let pending_impls = self.scopes[scope_id].impls.clone();
+        for impl_id in pending_impls {
+            let matches_type = match self.definitions[impl_id] {
+                Definition::LazyImpl(ref item_impl) => impl_type_name(item_impl).is_some_and(|name| name == type_name),
+                _ => false,
+            };
+
+            if matches_type {
+                self.scopes[scope_id].impls.retain(|x| *x != impl_id);
+                self.process_impl(impl_id, type_id, scope_id)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Processes an impl for the given type, making its methods available to be processed lazily
+    fn process_impl(
+        &mut self,
+        impl_id: DefinitionId,
+        type_id: DefinitionId,
+        scope_id: ScopeId,
+    ) -> Result<(), Diagnostic> {
+        if let Definition::LazyImpl(item_impl) = self.definitions[impl_id].clone() {
+            if !item_impl.generics.params.is_empty() {
+                return Err(self.unsupported(&item_impl.generics, "Generic impls are not yet supported"));
+            }
+            // An impl naming an instance of a generic struct, like `impl W<i32>`, is for that instance alone
+            let type_id = match self.definitions[type_id] {
+                Definition::Struct(ref st) if st.is_generic() => self.resolve_type(&item_impl.self_ty, scope_id)?,
+                _ => type_id,
+            };
+
+            // Methods can see everything in the scope the impl is in
+            self.scopes.push(Scope::new(Some(scope_id), false));
+            let impl_scope_id = self.scopes.len() - 1;
+            self.scopes[impl_scope_id]
+                .definitions
+                .insert("Self".to_string(), (type_id, Visibility::Public));
+
+            let trait_id = match item_impl.trait_ {
+                Some((_, ref path, _)) => Some(self.resolve_trait(path, scope_id)?),
+                None => None,
+            };
+            let trait_def = match trait_id {
+                Some(trait_id) => match self.definitions[trait_id] {
+                    Definition::Trait(ref trait_def) => Some(trait_def.clone()),
+                    _ => None,
+                },
+                None => None,
+            };
+
+            for item in &item_impl.items {
+                match item {
+                    ImplItem::Method(impl_item_method) => {
+                        let fn_name = impl_item_method.sig.ident.to_string();
+
+                        if let Some(ref trait_def) = trait_def {
+                            if !trait_def.methods.iter().any(|method| method.name == fn_name) {
+                                self.report(Diagnostic::new(
+                                    "E0407",
+                                    format!(
+                                        "Method `{}` is not a member of trait `{}`",
+                                        fn_name, trait_def.name
+                                    ),
+                                ).with_primary(
+                                    self.span_of(&impl_item_method.sig.ident),
+                                    &format!("not a member of trait `{}`", trait_def.name),
+                                ));
+                                continue;
+                            }
+                        }
+
+                        // The methods of a trait impl are as visible as the trait
+                        let visibility = match trait_def {
+                            Some(_) => Visibility::Public,
+                            None => self.visibility_in(&impl_item_method.vis, scope_id),
+                        };
+
+                        // Adds a function to be processed lazily
+                        self.definitions.push(Definition::LazyFn(LazyFn::new(
+                            impl_item_method.sig.decl.clone(),
+                            impl_item_method.block.clone(),
+                        )));
+                        self.scopes[impl_scope_id]
+                            .definitions
+                            .insert(fn_name, (self.definitions.len() - 1, visibility));
+                    }
+                    _ => return Err(self.unsupported(item, "Impl items other than methods are not yet supported")),
+                }
+            }
+
+            if let Some(trait_def) = trait_def {
+                // Methods the impl doesn't provide fall back to the trait's default, which is then checked
+                // as if it were written in this impl
+                let mut missing = vec![];
+                for method in trait_def.methods {
+                    if self.scopes[impl_scope_id]
+                        .definitions
+                        .contains_key(&method.name)
+                    {
+                        continue;
+                    }
+                    match method.default {
+                        Some(block) => {
+                            self.definitions
+                                .push(Definition::LazyFn(LazyFn::new(method.decl, block)));
                             self.scopes[impl_scope_id]
                                 .definitions
-                                .insert(fn_name, self.definitions.len() - 1);
+                                .insert(method.name, (self.definitions.len() - 1, Visibility::Public));
                         }
-                        _ => unimplemented!("Unsupport item type when processing impl"),
+                        None => missing.push(format!("`{}`", method.name)),
                     }
                 }
-            } else {
-                unimplemented!("Processing definition that is not a lazy impl");
+
+                if !missing.is_empty() {
+                    let missing = missing.join(", ");
+                    self.report(Diagnostic::new(
+                        "E0046",
+                        format!("Not all trait items implemented, missing: {}", missing),
+                    ).with_primary(
+                        self.span_of(&item_impl.self_ty),
+                        &format!("missing {} in implementation", missing),
+                    ));
+                }
             }
-            definition_id
+
+            self.definitions[impl_id] =
+                Definition::Impl(Impl::new(trait_id, impl_scope_id));
+            self.impls
+                .entry(type_id)
+                .or_default()
+                .push(impl_id);
+
+            Ok(())
         } else {
-            unimplemented!("Could not find impl {}", impl_name);
+            unimplemented!("Processing definition that is not a lazy impl");
         }
     }
 
-    fn process_defn(&mut self, name: &str, scope_id: ScopeId) -> Option<DefinitionId> {
+    /// Finds the associated function or method with the given name on a type, processing it if necessary.
+    /// One from an inherent impl is preferred over those of trait impls, and it's an error for more than one trait
+    /// impl to have it
+    pub(crate) fn process_associated_fn(
+        &mut self,
+        type_id: DefinitionId,
+        fn_ident: &syn::Ident,
+    ) -> Result<Option<DefinitionId>, Diagnostic> {
+        let impl_ids = match self.impls.get(&type_id) {
+            Some(impl_ids) => impl_ids.clone(),
+            None => return Ok(None),
+        };
+
+        let fn_name = fn_ident.to_string();
+        let mut trait_impls = vec![];
+        for impl_id in impl_ids {
+            if let Definition::Impl(ref imp) = self.definitions[impl_id] {
+                let impl_scope_id = imp.scope_id;
+                if self.scopes[impl_scope_id].definitions.contains_key(&fn_name) {
+                    match imp.trait_id {
+                        None => return self.process_defn(&fn_name, impl_scope_id),
+                        Some(trait_id) => trait_impls.push((trait_id, impl_scope_id)),
+                    }
+                }
+            }
+        }
+
+        match trait_impls.len() {
+            0 => Ok(None),
+            1 => self.process_defn(&fn_name, trait_impls[0].1),
+            _ => {
+                let trait_ids: Vec<DefinitionId> = trait_impls.iter().map(|x| x.0).collect();
+                Err(self.ambiguous_method(fn_ident, type_id, &trait_ids))
+            }
+        }
+    }
+
+    /// The error for a method name that more than one of the traits a type has is offering
+    pub(crate) fn ambiguous_method(
+        &self,
+        fn_ident: &syn::Ident,
+        type_id: DefinitionId,
+        trait_ids: &[DefinitionId],
+    ) -> Diagnostic {
+        let mut diagnostic = Diagnostic::new("E0034", "multiple applicable items in scope".to_string())
+            .with_primary(self.span_of(fn_ident), &format!("multiple `{}` found", fn_ident));
+        for (index, trait_id) in trait_ids.iter().enumerate() {
+            diagnostic = diagnostic.with_note(&format!(
+                "candidate #{} is defined in the trait `{}` for the type `{}`",
+                index + 1,
+                self.printable_name(*trait_id),
+                self.printable_name(type_id)
+            ));
+        }
+        diagnostic
+    }
+
+    /// Finds the trait the path refers to
+    pub(crate) fn resolve_trait(
+        &mut self,
+        path: &syn::Path,
+        scope_id: ScopeId,
+    ) -> Result<DefinitionId, Diagnostic> {
+        match self.process_path(path, scope_id)? {
+            Some(definition_id) => {
+                if let Definition::Trait(_) = self.definitions[definition_id] {
+                    Ok(definition_id)
+                } else {
+                    Err(Diagnostic::new(
+                        "E0404",
+                        format!("Expected trait, found {}", self.printable_path(path)),
+                    ).with_primary(self.span_of(path), "not a trait"))
+                }
+            }
+            None => Err(Diagnostic::new(
+                "E0405",
+                format!("Can not find trait `{}`", self.printable_path(path)),
+            ).with_primary(self.span_of(path), "not found in this scope")),
+        }
+    }
+
+    /// Finds the method of the given trait in the type's implementation of it, processing it if necessary
+    pub(crate) fn process_trait_method(
+        &mut self,
+        type_id: DefinitionId,
+        trait_id: DefinitionId,
+        fn_name: &str,
+    ) -> Result<Option<DefinitionId>, Diagnostic> {
+        let impl_ids = match self.impls.get(&type_id) {
+            Some(impl_ids) => impl_ids.clone(),
+            None => return Ok(None),
+        };
+
+        for impl_id in impl_ids {
+            if let Definition::Impl(ref imp) = self.definitions[impl_id] {
+                if imp.trait_id == Some(trait_id)
+                    && self.scopes[imp.scope_id].definitions.contains_key(fn_name)
+                {
+                    let impl_scope_id = imp.scope_id;
+                    return self.process_defn(fn_name, impl_scope_id);
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Checks if the type implements the trait.  A type variable implements the traits it's bound by
+    pub(crate) fn implements_trait(&self, type_id: DefinitionId, trait_id: DefinitionId) -> bool {
+        if type_id == builtin_type::ERROR {
+            return true;
+        }
+        if let Definition::TypeVariable(_, ref bounds) = self.definitions[type_id] {
+            return bounds.contains(&trait_id);
+        }
+        match self.impls.get(&type_id) {
+            Some(impl_ids) => impl_ids.iter().any(|impl_id| match self.definitions[*impl_id] {
+                Definition::Impl(ref imp) => imp.trait_id == Some(trait_id),
+                _ => false,
+            }),
+            None => false,
+        }
+    }
+
+    /// Gets the reference type to the given type, creating it if it hasn't been used before
+    pub(crate) fn ref_type(&mut self, ty: DefinitionId, mutable: bool) -> DefinitionId {
+        for (definition_id, definition) in self.definitions.iter().enumerate() {
+            if let Definition::Ref(referred_ty, referred_mutable) = *definition {
+                if referred_ty == ty && referred_mutable == mutable {
+                    return definition_id;
+                }
+            }
+        }
+
+        self.definitions.push(Definition::Ref(ty, mutable));
+        self.definitions.len() - 1
+    }
+
+    /// Gets the Box type holding the given type, creating it if it hasn't been used before
+    pub(crate) fn box_type(&mut self, ty: DefinitionId) -> DefinitionId {
+        for (definition_id, definition) in self.definitions.iter().enumerate() {
+            if let Definition::Box(boxed_ty) = *definition {
+                if boxed_ty == ty {
+                    return definition_id;
+                }
+            }
+        }
+
+        self.definitions.push(Definition::Box(ty));
+        self.definitions.len() - 1
+    }
+
+    /// Gets the tuple type with the given element types, creating it if it hasn't been used before
+    pub(crate) fn tuple_type(&mut self, element_tys: Vec<DefinitionId>) -> DefinitionId {
+        for (definition_id, definition) in self.definitions.iter().enumerate() {
+            if let Definition::Tuple(ref tys) = *definition {
+                if *tys == element_tys {
+                    return definition_id;
+                }
+            }
+        }
+
+        self.definitions.push(Definition::Tuple(element_tys));
+        self.definitions.len() - 1
+    }
+
+    /// Gets the type of arrays of the given length holding the given type, creating it if it hasn't been used before
+    pub(crate) fn array_type(&mut self, element_ty: DefinitionId, len: usize) -> DefinitionId {
+        for (definition_id, definition) in self.definitions.iter().enumerate() {
+            if let Definition::Array(array_element_ty, array_len) = *definition {
+                if array_element_ty == element_ty && array_len == len {
+                    return definition_id;
+                }
+            }
+        }
+
+        self.definitions.push(Definition::Array(element_ty, len));
+        self.definitions.len() - 1
+    }
+
+    /// Gets the type of pointers to functions with the given signature, creating it if it hasn't been used before
+    pub(crate) fn fn_ptr_type(&mut self, param_tys: Vec<DefinitionId>, return_ty: DefinitionId) -> DefinitionId {
+        for (definition_id, definition) in self.definitions.iter().enumerate() {
+            if let Definition::FnPtr(ref tys, ty) = *definition {
+                if *tys == param_tys && ty == return_ty {
+                    return definition_id;
+                }
+            }
+        }
+
+        self.definitions.push(Definition::FnPtr(param_tys, return_ty));
+        self.definitions.len() - 1
+    }
+
+    /// Gets the `Option` enum holding the given type, creating it if it hasn't been used before.  There are no
+    /// generic enums, so each type gets its own, with `None` as the first variant and `Some` as the second
+    pub(crate) fn option_type(&mut self, ty: DefinitionId) -> DefinitionId {
+        if let Some(option_id) = self.options.get(&ty) {
+            return *option_id;
+        }
+
+        let option_id = self.definitions.len();
+        self.definitions.push(Definition::Enum(Enum {
+            name: format!("Option<{}>", self.printable_name(ty)),
+            variants: vec![option_id + 1, option_id + 2],
+        }));
+        for (index, (name, fields)) in vec![("None", vec![]), ("Some", vec![ty])].into_iter().enumerate() {
+            self.definitions.push(Definition::Variant(Variant {
+                name: name.to_string(),
+                enum_id: option_id,
+                index,
+                fields,
+                has_named_fields: false,
+            }));
+        }
+        self.options.insert(ty, option_id);
+        option_id
+    }
+
+    /// Gets the instance of the generic struct for the given types, creating it if it hasn't been used before.  Each
+    /// instance is a struct of its own, with the types substituted into its fields
+    pub(crate) fn struct_instance(&mut self, generic_id: DefinitionId, ty_args: Vec<DefinitionId>) -> DefinitionId {
+        let key = (generic_id, ty_args.clone());
+        if let Some(instance_id) = self.struct_instances.get(&key) {
+            return *instance_id;
+        }
+
+        let generic = match self.definitions[generic_id] {
+            Definition::Struct(ref st) => st.clone(),
+            _ => unimplemented!("Instantiation of non-struct"),
+        };
+        let arg_names: Vec<String> = ty_args.iter().map(|ty| self.printable_name(*ty)).collect();
+        let unification: Vec<(DefinitionId, DefinitionId)> =
+            generic.ty_params.iter().cloned().zip(ty_args.iter().cloned()).collect();
+
+        // Record the instance before substituting its fields, so that fields referring back to it find it
+        let instance_id = self.definitions.len();
+        self.definitions.push(Definition::Struct(Struct {
+            name: format!("{}<{}>", generic.name, arg_names.join(", ")),
+            fields: vec![],
+            ty_params: vec![],
+            instance_of: Some((generic_id, ty_args)),
+            ..generic.clone()
+        }));
+        self.struct_instances.insert(key, instance_id);
+
+        let fields = generic
+            .fields
+            .iter()
+            .map(|(name, ty)| (name.clone(), self.substitute_type(*ty, &unification)))
+            .collect();
+        if let Definition::Struct(ref mut st) = self.definitions[instance_id] {
+            st.fields = fields;
+        }
+        instance_id
+    }
+
+    /// The struct a pattern naming the given struct matches.  A generic struct is named without its type arguments,
+    /// so it stands for the instance being matched
+    pub(crate) fn matched_instance(&self, struct_id: DefinitionId, ty: DefinitionId) -> DefinitionId {
+        match self.definitions[ty] {
+            Definition::Struct(Struct {
+                instance_of: Some((generic_id, _)),
+                ..
+            }) if generic_id == struct_id => ty,
+            _ => struct_id,
+        }
+    }
+
+    /// Finds the `Some` or `None` variant named by a pattern matching an `Option`.  As in Rust, they can be used
+    /// without being imported
+    pub(crate) fn option_variant(&self, path: &syn::Path, ty: DefinitionId) -> Option<DefinitionId> {
+        if path.segments.len() != 1 || !self.options.values().any(|option_id| *option_id == ty) {
+            return None;
+        }
+        self.find_variant(ty, path.segments[0].ident.as_ref())
+    }
+
+    /// Gets the vtable for calling the methods of the trait object type on the given type, creating it if it
+    /// hasn't been used before
+    pub(crate) fn vtable(
+        &mut self,
+        dyn_ty: DefinitionId,
+        self_ty: DefinitionId,
+    ) -> Result<DefinitionId, Diagnostic> {
+        for (definition_id, definition) in self.definitions.iter().enumerate() {
+            if let Definition::Vtable(ref vtable) = *definition {
+                if vtable.dyn_ty == dyn_ty && vtable.self_ty == self_ty {
+                    return Ok(definition_id);
+                }
+            }
+        }
+
+        let trait_id = match self.definitions[dyn_ty] {
+            Definition::DynTrait(ref dyn_trait) => dyn_trait.trait_id,
+            _ => unimplemented!("Vtable for a type that isn't a trait object"),
+        };
+        let method_names: Vec<String> = match self.definitions[trait_id] {
+            Definition::Trait(ref trait_def) => trait_def
+                .methods
+                .iter()
+                .map(|method| method.name.clone())
+                .collect(),
+            _ => unimplemented!("Trait object of a definition that isn't a trait"),
+        };
+
+        let mut methods = vec![];
+        for method_name in method_names {
+            // A method missing from the impl has already been reported, so its entry is never used
+            let method_id = self.process_trait_method(self_ty, trait_id, &method_name)?
+                .unwrap_or(builtin_type::ERROR);
+            methods.push(method_id);
+        }
+
+        self.definitions.push(Definition::Vtable(Vtable {
+            dyn_ty,
+            self_ty,
+            methods,
+        }));
+        Ok(self.definitions.len() - 1)
+    }
+
+    fn process_defn(&mut self, name: &str, scope_id: ScopeId) -> Result<Option<DefinitionId>, Diagnostic> {
         if let Some((definition_id, scope_id)) = self.get_defn(name, scope_id) {
             match self.definitions[definition_id] {
-                Definition::LazyFn(_) => Some(self.process_fn(name, scope_id)),
-                Definition::LazyMod(_) => Some(self.process_mod(name, scope_id)),
-                Definition::LazyStruct(_) => Some(self.process_struct(name, scope_id)),
-                Definition::LazyImpl(_) => Some(self.process_impl(name, scope_id)),
-                Definition::Fun(_) => Some(definition_id),
-                Definition::Struct(_) => Some(definition_id),
-                Definition::Mod(_) => Some(definition_id),
-                Definition::Builtin => Some(definition_id),
-                Definition::TypeVariable => Some(definition_id),
-                Definition::InstantiatedFun(_, _) => Some(definition_id),
+                Definition::LazyFn(_) => Ok(Some(self.process_fn_defn(name, scope_id)?)),
+                Definition::LazyMod(_) => self.process_mod(name, scope_id),
+                Definition::LazyStruct(_) => Ok(Some(self.process_struct(name, scope_id)?)),
+                Definition::LazyEnum(_) => Ok(Some(self.process_enum(name, scope_id)?)),
+                Definition::LazyImpl(_) | Definition::Impl(_) => Ok(None), // impls aren't named
+                Definition::Fun(_) => Ok(Some(definition_id)),
+                Definition::Struct(_) => Ok(Some(definition_id)),
+                Definition::Enum(_) => Ok(Some(definition_id)),
+                Definition::Variant(_) => Ok(Some(definition_id)),
+                Definition::Mod(_) => Ok(Some(definition_id)),
+                Definition::Builtin => Ok(Some(definition_id)),
+                Definition::Trait(_) => Ok(Some(definition_id)),
+                Definition::TypeVariable(..) => Ok(Some(definition_id)),
+                Definition::InstantiatedFun(_, _) => Ok(Some(definition_id)),
+                Definition::Ref(_, _) => Ok(Some(definition_id)),
+                Definition::Box(_) => Ok(Some(definition_id)),
+                Definition::Tuple(_) => Ok(Some(definition_id)),
+                Definition::Array(_, _) => Ok(Some(definition_id)),
+                Definition::FnPtr(_, _) => Ok(Some(definition_id)),
+                Definition::DynTrait(_) => Ok(Some(definition_id)),
+                Definition::Vtable(_) => Ok(None), // vtables aren't named
+                Definition::Closure(_) => Ok(None), // nor are the types of closures
             }
         } else {
-            None
+            Ok(None)
         }
     }
 
@@ -582,7 +1798,7 @@ impl BytecodeEngine {
         &mut self,
         path: &syn::Path,
         current_scope_id: ScopeId,
-    ) -> Option<DefinitionId> {
+    ) -> Result<Option<DefinitionId>, Diagnostic> {
         let mut mod_scope_id = current_scope_id;
         if path.leading_colon.is_some() {
             loop {
@@ -597,90 +1813,169 @@ impl BytecodeEngine {
         let num_segments = path.segments.len();
 
         for current_segment in 0..(num_segments - 1) {
-            let ident = path.segments[current_segment].ident.as_ref();
-            let definition_id = self.process_mod(ident, mod_scope_id);
-            if let Definition::Mod(ref module) = self.definitions[definition_id] {
-                mod_scope_id = module.scope_id;
-            } else {
-                unimplemented!("Failure to process module");
+            let ident = &path.segments[current_segment].ident;
+            self.check_visible(ident, mod_scope_id, current_scope_id);
+            match self.process_mod(ident.as_ref(), mod_scope_id)? {
+                Some(definition_id) => {
+                    if let Definition::Mod(ref module) = self.definitions[definition_id] {
+                        mod_scope_id = module.scope_id;
+                    }
+                }
+                None => {
+                    // A path like `Foo::bar` refers to a variant or an associated function of the type `Foo`
+                    if current_segment == num_segments - 2 {
+                        if let Some(definition_id) =
+                            self.process_defn(ident.as_ref(), mod_scope_id)?
+                        {
+                            let fn_ident = &path.segments[num_segments - 1].ident;
+                            let name = fn_ident.to_string();
+                            match self.definitions[definition_id] {
+                                Definition::Enum(_) => {
+                                    if let Some(variant_id) = self.find_variant(definition_id, &name) {
+                                        return Ok(Some(variant_id));
+                                    }
+                                    self.check_associated_fn_visible(definition_id, fn_ident, current_scope_id);
+                                    return self.process_associated_fn(definition_id, fn_ident);
+                                }
+                                Definition::Struct(_) => {
+                                    self.check_associated_fn_visible(definition_id, fn_ident, current_scope_id);
+                                    return self.process_associated_fn(definition_id, fn_ident);
+                                }
+                                _ => {}
+                            }
+                        }
+                    }
+                    return Err(self.unresolved_mod(ident));
+                }
             }
         }
 
         // from there, look in this scpoe for the name
         let num_segments = path.segments.len();
+        self.check_visible(&path.segments[num_segments - 1].ident, mod_scope_id, current_scope_id);
         let ident = path.segments[num_segments - 1].ident.to_string();
 
         // lastly, make sure we've processed the definition before we return
         self.process_defn(&ident, mod_scope_id)
     }
 
+    fn unresolved_mod(&self, ident: &syn::Ident) -> Diagnostic {
+        Diagnostic::new(
+            "E0433",
+            format!("failed to resolve. Can not find mod `{}`", ident),
+        ).with_primary(
+            self.span_of(ident),
+            &format!("use of undeclared module `{}`", ident),
+        )
+    }
+
+    fn unresolved_import(&self, ident: &syn::Ident) -> Diagnostic {
+        Diagnostic::new(
+            "E0432",
+            format!("unresolved import. Could not find `{}`", ident),
+        ).with_primary(self.span_of(ident), "no such definition")
+    }
+
+    /// Brings the definitions named by a `use` into the original scope, with the visibility given to the `use`
     fn process_use_tree(
         &mut self,
         use_tree: &syn::UseTree,
         original_scope_id: ScopeId,
         current_scope_id: ScopeId,
-    ) {
+        visibility: Visibility,
+    ) -> Result<(), Diagnostic> {
         match use_tree {
             syn::UseTree::Name(ref use_name) => {
-                let definition_id = self.process_defn(use_name.ident.as_ref(), current_scope_id);
+                self.check_visible(&use_name.ident, current_scope_id, original_scope_id);
+                let definition_id = self.process_defn(use_name.ident.as_ref(), current_scope_id)?;
 
-                if definition_id.is_none() {
-                    unimplemented!(
-                        "Could not process the definition for {}",
-                        use_name.ident.as_ref()
-                    );
+                match definition_id {
+                    Some(definition_id) => {
+                        self.scopes[original_scope_id]
+                            .definitions
+                            .insert(use_name.ident.to_string(), (definition_id, visibility));
+                    }
+                    None => return Err(self.unresolved_import(&use_name.ident)),
                 }
-
-                self.scopes[original_scope_id]
-                    .definitions
-                    .insert(use_name.ident.to_string(), definition_id.unwrap());
             }
             syn::UseTree::Path(ref use_path) => {
-                let definition_id = self.process_mod(use_path.ident.as_ref(), current_scope_id);
-                if let Definition::Mod(ref module) = self.definitions[definition_id] {
-                    self.process_use_tree(&*use_path.tree, original_scope_id, module.scope_id);
-                } else {
-                    unimplemented!("Expected module in use path");
+                self.check_visible(&use_path.ident, current_scope_id, original_scope_id);
+                match self.process_mod(use_path.ident.as_ref(), current_scope_id)? {
+                    Some(definition_id) => {
+                        if let Definition::Mod(ref module) = self.definitions[definition_id] {
+                            let module_scope_id = module.scope_id;
+                            self.process_use_tree(
+                                &*use_path.tree,
+                                original_scope_id,
+                                module_scope_id,
+                                visibility,
+                            )?;
+                        }
+                    }
+                    None => return Err(self.unresolved_mod(&use_path.ident)),
                 }
             }
             syn::UseTree::Group(ref use_group) => {
                 for tree in &use_group.items {
-                    self.process_use_tree(tree, original_scope_id, current_scope_id);
+                    self.process_use_tree(tree, original_scope_id, current_scope_id, visibility)?;
                 }
             }
             syn::UseTree::Glob(_) => {
+                // Only what can be named from where the `use` is gets imported
                 let mut defn_names = vec![];
-                for defn_name in self.scopes[current_scope_id].definitions.keys() {
-                    defn_names.push(defn_name.clone());
+                for (defn_name, (_, defn_visibility)) in &self.scopes[current_scope_id].definitions {
+                    if self.is_visible(*defn_visibility, original_scope_id) {
+                        defn_names.push(defn_name.clone());
+                    }
                 }
 
                 for defn_name in defn_names {
-                    let definition_id = self.process_defn(&defn_name, current_scope_id);
-
-                    if definition_id.is_none() {
-                        unimplemented!("Could not process the definition for {}", defn_name);
+                    if let Some(definition_id) = self.process_defn(&defn_name, current_scope_id)? {
+                        self.scopes[original_scope_id]
+                            .definitions
+                            .insert(defn_name, (definition_id, visibility));
                     }
-
-                    self.scopes[original_scope_id]
-                        .definitions
-                        .insert(defn_name, definition_id.unwrap());
                 }
             }
             syn::UseTree::Rename(ref use_rename) => {
-                let definition_id = self.process_defn(use_rename.ident.as_ref(), current_scope_id);
+                self.check_visible(&use_rename.ident, current_scope_id, original_scope_id);
+                let definition_id =
+                    self.process_defn(use_rename.ident.as_ref(), current_scope_id)?;
 
-                if definition_id.is_none() {
-                    unimplemented!(
-                        "Could not process the definition for {}",
-                        use_rename.ident.as_ref()
-                    );
+                match definition_id {
+                    Some(definition_id) => {
+                        self.scopes[original_scope_id]
+                            .definitions
+                            .insert(use_rename.rename.to_string(), (definition_id, visibility));
+                    }
+                    None => return Err(self.unresolved_import(&use_rename.ident)),
                 }
-
-                self.scopes[original_scope_id]
-                    .definitions
-                    .insert(use_rename.rename.to_string(), definition_id.unwrap());
             }
         }
+
+        Ok(())
+    }
+
+    /// Parses a line of repl input, remembering its source as `<repl>` so that diagnostics can show it
+    fn parse_repl_str<T: Synom>(&mut self, src: &str) -> Result<T, Diagnostic> {
+        let tokens: TokenStream = src.parse()
+            .map_err(|_| Diagnostic::uncoded("error while lexing input string".to_string()))?;
+
+        let codemap_name = tokens
+            .clone()
+            .into_iter()
+            .next()
+            .map(|tt| tt.span().source_file().path().to_string());
+
+        self.source_files
+            .retain(|source_file| source_file.name != "<repl>");
+        self.source_files.push(SourceFile {
+            name: "<repl>".to_string(),
+            codemap_name,
+            src: src.to_string(),
+        });
+
+        syn::parse2(tokens).map_err(|e| Diagnostic::uncoded(e.to_string()))
     }
 
     /// immediately process a string into bytecode, treating it as an expression
@@ -690,19 +1985,17 @@ impl BytecodeEngine {
         expr_str: &str,
         bytecode: &mut Vec<Bytecode>,
         var_stack: &mut VarStack,
-    ) -> Result<DefinitionId, String> {
-        match syn::parse_str::<syn::Expr>(expr_str) {
-            Ok(expr) => {
-                Ok(self.convert_expr_to_bytecode(
-                    &expr,
-                    builtin_type::UNKNOWN,
-                    bytecode,
-                    0, // hardwire repl scope to 0
-                    var_stack,
-                ))
-            }
-            Err(e) => Err(e.to_string()),
-        }
+    ) -> Result<DefinitionId, Vec<Diagnostic>> {
+        let result = self.parse_repl_str::<syn::Expr>(expr_str).and_then(|expr| {
+            self.convert_expr_to_bytecode(
+                &expr,
+                builtin_type::UNKNOWN,
+                bytecode,
+                0, // hardwire repl scope to 0
+                var_stack,
+            )
+        });
+        self.take_diagnostics(result)
     }
 
     /// immediately process a string into bytecode, treating it as a statement
@@ -713,82 +2006,302 @@ impl BytecodeEngine {
         expr_str: &str,
         bytecode: &mut Vec<Bytecode>,
         var_stack: &mut VarStack,
-    ) -> Result<(), String> {
-        match syn::parse_str::<syn::Stmt>(expr_str) {
-            Ok(stmt) => {
-                match stmt {
-                    syn::Stmt::Item(item) => {
-                        self.prepare_item(item, 0);
-                        Ok(())
-                    }
-                    _ => {
-                        self.convert_stmt_to_bytecode(
-                            &stmt,
-                            builtin_type::UNKNOWN,
-                            bytecode,
-                            0, // hardwire repl scope to 0
-                            var_stack,
-                        );
-                        Ok(())
-                    }
-                }
+    ) -> Result<(), Vec<Diagnostic>> {
+        let result = self.parse_repl_str::<syn::Stmt>(expr_str).and_then(|stmt| match stmt {
+            syn::Stmt::Item(item) => self.prepare_item(item, 0),
+            _ => {
+                self.convert_stmt_to_bytecode(
+                    &stmt,
+                    builtin_type::UNKNOWN,
+                    bytecode,
+                    0, // hardwire repl scope to 0
+                    var_stack,
+                )?;
+                Ok(())
             }
-            Err(e) => Err(e.to_string()),
-        }
+        });
+        self.take_diagnostics(result)
     }
 
+    /// Instantiates a generic function for the types of the arguments it's called with.
+    /// Returns the instance and the type it returns.
+    ///
+    /// Once the types are all concrete, the instance gets its own copy of the function's bytecode with the trait
+    /// methods it calls resolved to the ones implemented for those types.  This way, calls to trait methods are
+    /// direct calls, with no lookup needed when the function runs.
     pub fn instantiate_generic_fn(
         &mut self,
         target_fn_id: DefinitionId,
-        scope_id: ScopeId,
-        arg_tys: &Vec<DefinitionId>,
-    ) -> (DefinitionId, DefinitionId) {
+        arg_tys: &[DefinitionId],
+    ) -> Result<(DefinitionId, DefinitionId), Diagnostic> {
         // Simple unification
 
         if let Definition::Fun(ref fun) = self.definitions[target_fn_id] {
-            //let mut instantiated_param_tys = vec![];
             let mut unification = vec![];
-            let mut return_ty = fun.return_ty;
-            let mut instance_name = "inst".to_string();
-
-            let mut arg_iter = arg_tys.iter();
-            for param in &fun.params {
-                let arg = arg_iter.next();
-                match self.definitions[param.ty] {
-                    Definition::TypeVariable => match arg {
-                        Some(arg) => {
-                            unification.push((param.ty, *arg));
-                            instance_name += &format!("${}", arg);
-                            if return_ty == param.ty {
-                                return_ty = *arg;
-                            }
+
+            if fun.params.len() > arg_tys.len() {
+                unimplemented!("Mismatched params and args");
+            }
+            for (param, arg_ty) in fun.params.iter().zip(arg_tys) {
+                self.unify(param.ty, *arg_ty, &mut unification);
+            }
+            let return_ty = fun.return_ty;
+            let return_ty = self.substitute_type(return_ty, &unification);
+
+            let instance_definition_id = self.instantiate_with(target_fn_id, unification)?;
+            Ok((instance_definition_id, return_ty))
+        } else {
+            unimplemented!("Instantiation of non-function")
+        }
+    }
+
+    /// Gets the instance of the generic function for the given substitutions of its type variables, creating it
+    /// if this is the first time it's been used
+    fn instantiate_with(
+        &mut self,
+        generic_fn_id: DefinitionId,
+        unification: Vec<(DefinitionId, DefinitionId)>,
+    ) -> Result<DefinitionId, Diagnostic> {
+        let key = (
+            generic_fn_id,
+            unification.iter().map(|x| x.1).collect::<Vec<_>>(),
+        );
+        if let Some(instance_definition_id) = self.instances.get(&key) {
+            return Ok(*instance_definition_id);
+        }
+
+        let is_concrete = !unification
+            .iter()
+            .any(|x| self.has_type_variable(x.1));
+
+        // Record the instance before specializing it, so that recursive calls find it
+        self.definitions.push(Definition::InstantiatedFun(
+            generic_fn_id,
+            unification.clone(),
+        ));
+        let instance_definition_id = self.definitions.len() - 1;
+        self.instances.insert(key, instance_definition_id);
+
+        // An instance that still uses type variables (from the generic function it's called in) is only a
+        // placeholder.  It's instantiated again once those are known
+        if is_concrete {
+            let mut specialized_fun = if let Definition::Fun(ref fun) = self.definitions[generic_fn_id] {
+                fun.clone()
+            } else {
+                unimplemented!("Instantiation of non-function")
+            };
+            specialized_fun.bytecode = self.specialize_bytecode(&specialized_fun.bytecode, &unification)?;
+
+            // The instances of generic structs that use the type variables stand for the instances they're
+            // substituted to, which C needs to know when it writes their types
+            let mut unification = unification;
+            let mut placeholders: Vec<DefinitionId> = self
+                .struct_instances
+                .iter()
+                .filter(|(key, _)| key.1.iter().any(|ty_arg| self.has_type_variable(*ty_arg)))
+                .map(|(_, instance_id)| *instance_id)
+                .collect();
+            placeholders.sort_unstable();
+            for placeholder_id in placeholders {
+                let instance_id = self.substitute_type(placeholder_id, &unification);
+                if instance_id != placeholder_id {
+                    unification.push((placeholder_id, instance_id));
+                }
+            }
+
+            self.definitions.push(Definition::Fun(specialized_fun));
+            let specialized_fn_id = self.definitions.len() - 1;
+            self.definitions[instance_definition_id] =
+                Definition::InstantiatedFun(specialized_fn_id, unification);
+        }
+
+        Ok(instance_definition_id)
+    }
+
+    /// Resolves the trait method calls and the calls to other generic functions in a generic function's bytecode,
+    /// now that the types of its type variables are known
+    fn specialize_bytecode(
+        &mut self,
+        bytecode: &[Bytecode],
+        unification: &[(DefinitionId, DefinitionId)],
+    ) -> Result<Vec<Bytecode>, Diagnostic> {
+        let mut specialized = Vec::with_capacity(bytecode.len());
+
+        for code in bytecode {
+            let specialized_code = match code {
+                Bytecode::CallTraitMethod(ty, trait_id, method_name, _) => {
+                    let concrete_ty = self.substitute_type(*ty, unification);
+                    match self.process_trait_method(concrete_ty, *trait_id, method_name)? {
+                        Some(method_id) => Bytecode::Call(method_id),
+                        // The impl is missing the method, which has already been reported
+                        None => code.clone(),
+                    }
+                }
+                // Building a generic struct builds the instance the type variables are substituted to
+                Bytecode::Call(definition_id)
+                    if matches!(self.definitions[*definition_id], Definition::Struct(_))
+                        && self.has_type_variable(*definition_id) =>
+                {
+                    Bytecode::Call(self.substitute_type(*definition_id, unification))
+                }
+                Bytecode::Call(definition_id) => {
+                    let placeholder = match self.definitions[*definition_id] {
+                        Definition::InstantiatedFun(generic_fn_id, ref instantiations)
+                            if instantiations.iter().any(|x| self.has_type_variable(x.1)) =>
+                        {
+                            Some((generic_fn_id, instantiations.clone()))
                         }
-                        None => {
-                            unimplemented!("Mismatched params and args");
+                        _ => None,
+                    };
+
+                    match placeholder {
+                        Some((generic_fn_id, instantiations)) => {
+                            let unification = instantiations
+                                .iter()
+                                .map(|x| (x.0, self.substitute_type(x.1, unification)))
+                                .collect();
+                            Bytecode::Call(self.instantiate_with(generic_fn_id, unification)?)
                         }
-                    },
-                    _ => {}
+                        None => code.clone(),
+                    }
                 }
-            }
-            instance_name += &format!("%{}", return_ty);
+                _ => code.clone(),
+            };
+            specialized.push(specialized_code);
+        }
 
-            match self.scopes[scope_id].definitions.get(&instance_name) {
-                Some(def_id) => (*def_id, return_ty),
-                None => {
-                    self.definitions
-                        .push(Definition::InstantiatedFun(target_fn_id, unification));
+        Ok(specialized)
+    }
 
-                    let instance_definition_id = self.definitions.len() - 1;
+    /// Checks if the type is, or refers to, a type variable
+    pub(crate) fn has_type_variable(&self, ty: DefinitionId) -> bool {
+        match self.definitions[ty] {
+            Definition::TypeVariable(..) => true,
+            Definition::Ref(referred_ty, _)
+            | Definition::Box(referred_ty)
+            | Definition::Array(referred_ty, _) => self.has_type_variable(referred_ty),
+            Definition::Tuple(ref element_tys) => {
+                element_tys.iter().any(|ty| self.has_type_variable(*ty))
+            }
+            Definition::FnPtr(ref param_tys, return_ty) => {
+                param_tys.iter().any(|ty| self.has_type_variable(*ty)) || self.has_type_variable(return_ty)
+            }
+            Definition::Struct(ref st) => match st.instance_of {
+                Some((_, ref ty_args)) => ty_args.iter().any(|ty| self.has_type_variable(*ty)),
+                None => st.is_generic(),
+            },
+            _ => false,
+        }
+    }
 
-                    self.scopes[scope_id]
-                        .definitions
-                        .insert(instance_name, instance_definition_id);
+    /// Replaces the type variables in the type with the types they've been instantiated with
+    fn substitute_type(
+        &mut self,
+        ty: DefinitionId,
+        unification: &[(DefinitionId, DefinitionId)],
+    ) -> DefinitionId {
+        match self.definitions[ty] {
+            Definition::TypeVariable(..) => match unification.iter().find(|x| x.0 == ty) {
+                Some(x) => x.1,
+                None => ty,
+            },
+            Definition::Ref(referred_ty, mutable) => {
+                let referred_ty = self.substitute_type(referred_ty, unification);
+                self.ref_type(referred_ty, mutable)
+            }
+            Definition::Box(boxed_ty) => {
+                let boxed_ty = self.substitute_type(boxed_ty, unification);
+                self.box_type(boxed_ty)
+            }
+            Definition::Tuple(ref element_tys) => {
+                let element_tys = element_tys.clone();
+                let element_tys = element_tys
+                    .into_iter()
+                    .map(|element_ty| self.substitute_type(element_ty, unification))
+                    .collect();
+                self.tuple_type(element_tys)
+            }
+            Definition::Array(element_ty, len) => {
+                let element_ty = self.substitute_type(element_ty, unification);
+                self.array_type(element_ty, len)
+            }
+            Definition::FnPtr(ref param_tys, return_ty) => {
+                let param_tys = param_tys.clone();
+                let param_tys = param_tys
+                    .into_iter()
+                    .map(|param_ty| self.substitute_type(param_ty, unification))
+                    .collect();
+                let return_ty = self.substitute_type(return_ty, unification);
+                self.fn_ptr_type(param_tys, return_ty)
+            }
+            Definition::Struct(Struct {
+                instance_of: Some((generic_id, ref ty_args)),
+                ..
+            }) => {
+                let ty_args = ty_args.clone();
+                let ty_args = ty_args
+                    .into_iter()
+                    .map(|ty_arg| self.substitute_type(ty_arg, unification))
+                    .collect();
+                self.struct_instance(generic_id, ty_args)
+            }
+            _ => ty,
+        }
+    }
 
-                    (instance_definition_id, return_ty)
+    /// Matches a type that may use type variables against the type of a value, recording what each type variable
+    /// stands for where it's first found
+    pub(crate) fn unify(
+        &self,
+        ty: DefinitionId,
+        value_ty: DefinitionId,
+        unification: &mut Vec<(DefinitionId, DefinitionId)>,
+    ) {
+        match (&self.definitions[ty], &self.definitions[value_ty]) {
+            (Definition::TypeVariable(..), _) => {
+                if !unification.iter().any(|x| x.0 == ty) {
+                    unification.push((ty, value_ty));
                 }
             }
-        } else {
-            unimplemented!("Instantiation of non-function")
+            (Definition::Ref(referred_ty, _), Definition::Ref(value_referred_ty, _))
+            | (Definition::Box(referred_ty), Definition::Box(value_referred_ty))
+            | (Definition::Array(referred_ty, _), Definition::Array(value_referred_ty, _)) => {
+                self.unify(*referred_ty, *value_referred_ty, unification)
+            }
+            (Definition::Tuple(ref element_tys), Definition::Tuple(ref value_element_tys)) => {
+                for (element_ty, value_element_ty) in element_tys.iter().zip(value_element_tys) {
+                    self.unify(*element_ty, *value_element_ty, unification);
+                }
+            }
+            (Definition::FnPtr(ref param_tys, return_ty), Definition::FnPtr(ref value_param_tys, value_return_ty)) => {
+                for (param_ty, value_param_ty) in param_tys.iter().zip(value_param_tys) {
+                    self.unify(*param_ty, *value_param_ty, unification);
+                }
+                self.unify(*return_ty, *value_return_ty, unification);
+            }
+            (Definition::Struct(ref st), Definition::Struct(ref value_st)) => {
+                if let (Some((generic_id, ref ty_args)), Some((value_generic_id, ref value_ty_args))) =
+                    (&st.instance_of, &value_st.instance_of)
+                {
+                    if generic_id == value_generic_id {
+                        for (ty_arg, value_ty_arg) in ty_args.iter().zip(value_ty_args) {
+                            self.unify(*ty_arg, *value_ty_arg, unification);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The name of the type an impl is for, eg) `Foo` in `impl Foo { }`, if it's for a named type
+fn impl_type_name(item_impl: &ItemImpl) -> Option<String> {
+    match *item_impl.self_ty {
+        syn::Type::Path(ref type_path) => {
+            let num_segments = type_path.path.segments.len();
+            Some(type_path.path.segments[num_segments - 1].ident.to_string())
         }
+        _ => None,
     }
 }
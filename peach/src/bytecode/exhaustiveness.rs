@@ -0,0 +1,516 @@
+use bytecode::diagnostic::{Diagnostic, SourceSpan};
+use bytecode::engine::{BytecodeEngine, Definition, DefinitionId, StructKind};
+use bytecode::typecheck::builtin_type;
+
+/// One of the ways a value can be built, which patterns match against
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Constructor {
+    Struct(DefinitionId),
+    /// The tuple of the given type
+    Tuple(DefinitionId),
+    Variant(DefinitionId),
+    Bool(bool),
+    /// The integers from the first to the second, inclusive.  They're kept as by `int_pattern_value`
+    IntRange(i128, i128),
+}
+
+/// The shape of a pattern, as far as checking which values it matches is concerned
+#[derive(Debug, Clone)]
+pub(crate) enum Pattern {
+    /// Matches any value, eg) `_` or a binding
+    Wild,
+    /// Matches values built by the constructor, whose fields match the subpatterns
+    Constructor(Constructor, Vec<Pattern>),
+    /// A pattern that had an error.  It's already been reported, so the match isn't checked further
+    Error,
+}
+
+impl Pattern {
+    fn has_error(&self) -> bool {
+        match self {
+            Pattern::Wild => false,
+            Pattern::Constructor(_, subpatterns) => subpatterns.iter().any(|x| x.has_error()),
+            Pattern::Error => true,
+        }
+    }
+}
+
+impl Constructor {
+    /// Checks if every value this constructor builds is also built by the other
+    fn is_covered_by(&self, other: &Constructor) -> bool {
+        match (self, other) {
+            (Constructor::IntRange(lo, hi), Constructor::IntRange(other_lo, other_hi)) => {
+                other_lo <= lo && hi <= other_hi
+            }
+            _ => self == other,
+        }
+    }
+}
+
+/// The smallest and largest values of an integer type, kept as by `int_pattern_value`
+/// Integers without a type yet will become i32 if nothing else decides their type
+pub(crate) fn int_bounds(ty: DefinitionId) -> Option<(i128, i128)> {
+    let (bits, signed) = builtin_type::int_layout(ty)?;
+    match (bits, signed) {
+        (128, _) => Some((i128::MIN, i128::MAX)),
+        (_, false) => Some((0, (1 << bits) - 1)),
+        (_, true) => Some((-(1 << (bits - 1)), (1 << (bits - 1)) - 1)),
+    }
+}
+
+/// The integer a pattern matches, given its magnitude and whether it's negated.  The values of every type but u128
+/// fit in an i128 as they are.  Those of u128 are shifted down by 2^127 so that they do too, which keeps their order
+pub(crate) fn int_pattern_value(magnitude: u128, negated: bool, ty: DefinitionId) -> i128 {
+    match builtin_type::int_layout(ty) {
+        Some((128, false)) => (magnitude as i128) ^ i128::MIN,
+        _ if negated => (magnitude as i128).wrapping_neg(),
+        _ => magnitude as i128,
+    }
+}
+
+/// The integer as written in source, undoing the shift of u128 values
+fn int_pattern_text(value: i128, ty: DefinitionId) -> String {
+    match builtin_type::int_layout(ty) {
+        Some((128, false)) => ((value ^ i128::MIN) as u128).to_string(),
+        _ => value.to_string(),
+    }
+}
+
+/// The constructors that the first pattern in each row starts with
+fn head_constructors(rows: &[Vec<Pattern>]) -> Vec<&Constructor> {
+    rows.iter()
+        .filter_map(|row| match row[0] {
+            Pattern::Constructor(ref constructor, _) => Some(constructor),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The rest of the row if its first pattern matches values built by the constructor, with the fields of the
+/// constructor to be matched first
+fn specialize(row: &[Pattern], constructor: &Constructor, arity: usize) -> Option<Vec<Pattern>> {
+    let mut specialized = match row[0] {
+        Pattern::Wild | Pattern::Error => vec![Pattern::Wild; arity],
+        Pattern::Constructor(ref head, ref subpatterns) => {
+            if constructor.is_covered_by(head) {
+                subpatterns.clone()
+            } else {
+                return None;
+            }
+        }
+    };
+    specialized.extend_from_slice(&row[1..]);
+    Some(specialized)
+}
+
+/// The rows whose first pattern matches anything, without that pattern
+fn default_rows(rows: &[Vec<Pattern>]) -> Vec<Vec<Pattern>> {
+    rows.iter()
+        .filter_map(|row| match row[0] {
+            Pattern::Wild | Pattern::Error => Some(row[1..].to_vec()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Joins up the integer ranges that sit next to each other
+fn merge_ranges(constructors: Vec<Constructor>) -> Vec<Constructor> {
+    let mut merged: Vec<Constructor> = vec![];
+    for constructor in constructors {
+        if let Constructor::IntRange(lo, hi) = constructor {
+            if let Some(Constructor::IntRange(_, ref mut last_hi)) = merged.last_mut() {
+                if last_hi.checked_add(1) == Some(lo) {
+                    *last_hi = hi;
+                    continue;
+                }
+            }
+        }
+        merged.push(constructor);
+    }
+    merged
+}
+
+/// Lists the patterns the way rustc does, eg) "`A`, `B` and `C`"
+fn list_patterns(names: &[String]) -> String {
+    const MAX_LISTED: usize = 3;
+
+    let quoted: Vec<String> = names.iter().map(|name| format!("`{}`", name)).collect();
+    if quoted.len() == 1 {
+        quoted[0].clone()
+    } else if quoted.len() <= MAX_LISTED {
+        format!(
+            "{} and {}",
+            quoted[..quoted.len() - 1].join(", "),
+            quoted[quoted.len() - 1]
+        )
+    } else {
+        format!(
+            "{} and {} more",
+            quoted[..MAX_LISTED].join(", "),
+            quoted.len() - MAX_LISTED
+        )
+    }
+}
+
+impl BytecodeEngine {
+    /// Checks the patterns of a match's arms, reporting arms that can never match because of the arms before them,
+    /// and values that none of the arms match
+    pub(crate) fn check_match_patterns(
+        &mut self,
+        scrutinee_ty: DefinitionId,
+        arms: &[(Pattern, SourceSpan)],
+        match_span: SourceSpan,
+    ) {
+        if scrutinee_ty == builtin_type::ERROR || arms.iter().any(|arm| arm.0.has_error()) {
+            return;
+        }
+
+        let mut rows: Vec<Vec<Pattern>> = vec![];
+        for (pattern, span) in arms {
+            let row = vec![pattern.clone()];
+            if !self.is_useful(&rows, &row, &[scrutinee_ty]) {
+                self.warn(Diagnostic::warning("Unreachable pattern".to_string())
+                    .with_primary(
+                        span.clone(),
+                        "this arm can never match, as the arms above it match everything it does",
+                    ));
+            }
+            rows.push(row);
+        }
+
+        let uncovered = self.uncovered(&rows, &[scrutinee_ty]);
+        if !uncovered.is_empty() {
+            let names: Vec<String> = uncovered
+                .iter()
+                .map(|witness| self.printable_pattern(&witness[0], scrutinee_ty))
+                .collect();
+            let listed = list_patterns(&names);
+            self.report(Diagnostic::new(
+                "E0004",
+                format!("Non-exhaustive patterns: {} not covered", listed),
+            ).with_primary(
+                match_span,
+                &format!(
+                    "pattern{} {} not covered",
+                    if names.len() == 1 { "" } else { "s" },
+                    listed
+                ),
+            )
+                .with_note("add arms for the missing values, or a `_` arm to match anything left over"));
+        }
+    }
+
+    /// Checks that a pattern matches every value of its type, as the patterns of `let` and of function
+    /// parameters must
+    pub(crate) fn check_irrefutable(
+        &mut self,
+        pattern: &Pattern,
+        ty: DefinitionId,
+        span: SourceSpan,
+        context: &str,
+    ) {
+        if ty == builtin_type::ERROR || pattern.has_error() {
+            return;
+        }
+
+        let uncovered = self.uncovered(&[vec![pattern.clone()]], &[ty]);
+        if !uncovered.is_empty() {
+            let names: Vec<String> = uncovered
+                .iter()
+                .map(|witness| self.printable_pattern(&witness[0], ty))
+                .collect();
+            let listed = list_patterns(&names);
+            self.report(Diagnostic::new(
+                "E0005",
+                format!("Refutable pattern in {}: {} not covered", context, listed),
+            ).with_primary(
+                span,
+                &format!(
+                    "pattern{} {} not covered",
+                    if names.len() == 1 { "" } else { "s" },
+                    listed
+                ),
+            )
+                .with_note("use `if let` or `match` to handle the values the pattern doesn't match"));
+        }
+    }
+
+    /// The types of a constructor's fields
+    fn constructor_fields(&self, constructor: &Constructor) -> Vec<DefinitionId> {
+        match constructor {
+            Constructor::Struct(struct_id) => match self.definitions[*struct_id] {
+                Definition::Struct(ref struct_def) => {
+                    struct_def.fields.iter().map(|field| field.1).collect()
+                }
+                _ => vec![],
+            },
+            Constructor::Tuple(tuple_ty) => match self.definitions[*tuple_ty] {
+                Definition::Tuple(ref element_tys) => element_tys.clone(),
+                _ => vec![],
+            },
+            Constructor::Variant(variant_id) => match self.definitions[*variant_id] {
+                Definition::Variant(ref variant) => variant.fields.clone(),
+                _ => vec![],
+            },
+            _ => vec![],
+        }
+    }
+
+    /// Every constructor of the type, or None if the type's values can't be listed, in which case only patterns
+    /// matching anything can match it
+    /// Integer ranges are split at the edges of the ranges in use, so that each piece is either entirely inside or
+    /// entirely outside of each range
+    fn split_constructors(
+        &self,
+        ty: DefinitionId,
+        heads: &[&Constructor],
+    ) -> Option<Vec<Constructor>> {
+        if let Some((min, max)) = int_bounds(ty) {
+            // Each piece starts at one of the edges and runs up to the next.  The range can reach i128::MAX, so
+            // the end of the last piece is the top of the range rather than an edge past it
+            let mut edges = vec![min];
+            for head in heads {
+                if let Constructor::IntRange(lo, hi) = **head {
+                    if lo > max || hi < min {
+                        continue;
+                    }
+                    edges.push(lo.max(min));
+                    if hi < max {
+                        edges.push(hi + 1);
+                    }
+                }
+            }
+            edges.sort();
+            edges.dedup();
+            let ends = edges.iter().skip(1).map(|edge| edge - 1).chain(Some(max));
+            return Some(
+                edges
+                    .iter()
+                    .zip(ends)
+                    .map(|(lo, hi)| Constructor::IntRange(*lo, hi))
+                    .collect(),
+            );
+        }
+
+        match ty {
+            builtin_type::BOOL => Some(vec![Constructor::Bool(false), Constructor::Bool(true)]),
+            _ => match self.definitions[ty] {
+                Definition::Struct(_) => Some(vec![Constructor::Struct(ty)]),
+                Definition::Tuple(_) => Some(vec![Constructor::Tuple(ty)]),
+                Definition::Enum(ref enum_def) => Some(
+                    enum_def
+                        .variants
+                        .iter()
+                        .map(|variant_id| Constructor::Variant(*variant_id))
+                        .collect(),
+                ),
+                _ => None,
+            },
+        }
+    }
+
+    /// Checks if there's any value the row matches that none of the rows before it do
+    fn is_useful(&self, rows: &[Vec<Pattern>], row: &[Pattern], tys: &[DefinitionId]) -> bool {
+        if row.is_empty() {
+            return rows.is_empty();
+        }
+
+        let mut heads = head_constructors(rows);
+        match row[0] {
+            Pattern::Constructor(ref constructor, _) => {
+                heads.push(constructor);
+                let pieces: Vec<Constructor> = match self.split_constructors(tys[0], &heads) {
+                    Some(all) => all
+                        .into_iter()
+                        .filter(|piece| piece.is_covered_by(constructor))
+                        .collect(),
+                    None => vec![constructor.clone()],
+                };
+                pieces
+                    .iter()
+                    .any(|piece| self.is_useful_specialized(rows, row, tys, piece))
+            }
+            Pattern::Wild | Pattern::Error => match self.split_constructors(tys[0], &heads) {
+                Some(ref all)
+                    if all
+                        .iter()
+                        .all(|constructor| heads.iter().any(|head| constructor.is_covered_by(head))) =>
+                {
+                    all.iter()
+                        .any(|constructor| self.is_useful_specialized(rows, row, tys, constructor))
+                }
+                _ => self.is_useful(&default_rows(rows), &row[1..], &tys[1..]),
+            },
+        }
+    }
+
+    /// Checks usefulness for just the values built by the constructor
+    fn is_useful_specialized(
+        &self,
+        rows: &[Vec<Pattern>],
+        row: &[Pattern],
+        tys: &[DefinitionId],
+        constructor: &Constructor,
+    ) -> bool {
+        let mut sub_tys = self.constructor_fields(constructor);
+        let arity = sub_tys.len();
+        sub_tys.extend_from_slice(&tys[1..]);
+
+        let sub_rows: Vec<Vec<Pattern>> = rows
+            .iter()
+            .filter_map(|x| specialize(x, constructor, arity))
+            .collect();
+        match specialize(row, constructor, arity) {
+            Some(sub_row) => self.is_useful(&sub_rows, &sub_row, &sub_tys),
+            None => false,
+        }
+    }
+
+    /// Finds the values none of the rows match, given as patterns
+    fn uncovered(&self, rows: &[Vec<Pattern>], tys: &[DefinitionId]) -> Vec<Vec<Pattern>> {
+        if tys.is_empty() {
+            return if rows.is_empty() { vec![vec![]] } else { vec![] };
+        }
+
+        let heads = head_constructors(rows);
+        let all = self.split_constructors(tys[0], &heads);
+        let missing: Vec<Constructor> = match all {
+            Some(ref all) => all
+                .iter()
+                .filter(|constructor| !heads.iter().any(|head| constructor.is_covered_by(head)))
+                .cloned()
+                .collect(),
+            None => vec![],
+        };
+
+        match all {
+            Some(all) if missing.is_empty() => {
+                // Each constructor is matched by some row, so look for what's missing inside each of them
+                let mut witnesses = vec![];
+                for constructor in all {
+                    let mut sub_tys = self.constructor_fields(&constructor);
+                    let arity = sub_tys.len();
+                    sub_tys.extend_from_slice(&tys[1..]);
+
+                    let sub_rows: Vec<Vec<Pattern>> = rows
+                        .iter()
+                        .filter_map(|row| specialize(row, &constructor, arity))
+                        .collect();
+                    for mut witness in self.uncovered(&sub_rows, &sub_tys) {
+                        let rest = witness.split_off(arity);
+                        let mut full_witness = vec![Pattern::Constructor(constructor.clone(), witness)];
+                        full_witness.extend(rest);
+                        witnesses.push(full_witness);
+                    }
+                }
+                witnesses
+            }
+            _ => {
+                // Only the rows matching anything can match the missing constructors
+                let rest_witnesses = self.uncovered(&default_rows(rows), &tys[1..]);
+                let missing_heads: Vec<Pattern> = if heads.is_empty() || missing.is_empty() {
+                    vec![Pattern::Wild]
+                } else {
+                    merge_ranges(missing)
+                        .into_iter()
+                        .map(|constructor| {
+                            let arity = self.constructor_fields(&constructor).len();
+                            Pattern::Constructor(constructor, vec![Pattern::Wild; arity])
+                        })
+                        .collect()
+                };
+
+                let mut witnesses = vec![];
+                for rest in rest_witnesses {
+                    for head in &missing_heads {
+                        let mut witness = vec![head.clone()];
+                        witness.extend_from_slice(&rest);
+                        witnesses.push(witness);
+                    }
+                }
+                witnesses
+            }
+        }
+    }
+
+    /// Formats a pattern as it would be written in source
+    fn printable_pattern(&self, pattern: &Pattern, ty: DefinitionId) -> String {
+        match pattern {
+            Pattern::Wild | Pattern::Error => "_".to_string(),
+            Pattern::Constructor(Constructor::Bool(value), _) => value.to_string(),
+            Pattern::Constructor(Constructor::IntRange(lo, hi), _) => {
+                if lo == hi {
+                    self.printable_int(*lo, ty)
+                } else {
+                    format!(
+                        "{}..={}",
+                        self.printable_int(*lo, ty),
+                        self.printable_int(*hi, ty)
+                    )
+                }
+            }
+            Pattern::Constructor(Constructor::Struct(struct_id), subpatterns) => {
+                let name = self.printable_name(*struct_id);
+                let (kind, fields) = match self.definitions[*struct_id] {
+                    Definition::Struct(ref struct_def) => (struct_def.kind, struct_def.fields.clone()),
+                    _ => (StructKind::Unit, vec![]),
+                };
+                let subpatterns =
+                    self.printable_subpatterns(&Constructor::Struct(*struct_id), subpatterns);
+                match kind {
+                    StructKind::Named => {
+                        let fields: Vec<String> = subpatterns
+                            .iter()
+                            .zip(fields)
+                            .map(|(subpattern, field)| format!("{}: {}", field.0, subpattern))
+                            .collect();
+                        format!("{} {{ {} }}", name, fields.join(", "))
+                    }
+                    StructKind::Tuple => format!("{}({})", name, subpatterns.join(", ")),
+                    StructKind::Unit => name,
+                }
+            }
+            Pattern::Constructor(Constructor::Tuple(tuple_ty), subpatterns) => {
+                let subpatterns =
+                    self.printable_subpatterns(&Constructor::Tuple(*tuple_ty), subpatterns);
+                if subpatterns.len() == 1 {
+                    format!("({},)", subpatterns[0])
+                } else {
+                    format!("({})", subpatterns.join(", "))
+                }
+            }
+            Pattern::Constructor(Constructor::Variant(variant_id), subpatterns) => {
+                let name = self.printable_name(*variant_id);
+                if subpatterns.is_empty() {
+                    name
+                } else {
+                    let subpatterns =
+                        self.printable_subpatterns(&Constructor::Variant(*variant_id), subpatterns);
+                    format!("{}({})", name, subpatterns.join(", "))
+                }
+            }
+        }
+    }
+
+    /// Formats the patterns for the fields of a constructor
+    fn printable_subpatterns(&self, constructor: &Constructor, subpatterns: &[Pattern]) -> Vec<String> {
+        subpatterns
+            .iter()
+            .zip(self.constructor_fields(constructor))
+            .map(|(subpattern, field_ty)| self.printable_pattern(subpattern, field_ty))
+            .collect()
+    }
+
+    /// Formats an integer, naming the edges of its type's range like `i32::MAX`
+    fn printable_int(&self, value: i128, ty: DefinitionId) -> String {
+        let ty_name = match ty {
+            builtin_type::UNKNOWN_INT => "i32".to_string(),
+            _ => self.printable_name(ty),
+        };
+        match int_bounds(ty) {
+            Some((min, _)) if value == min && int_pattern_text(min, ty) != "0" => format!("{}::MIN", ty_name),
+            Some((_, max)) if value == max => format!("{}::MAX", ty_name),
+            _ => int_pattern_text(value, ty),
+        }
+    }
+}
@@ -1,6 +1,11 @@
 mod ast;
+mod borrowck;
+mod diagnostic;
 mod engine;
+mod exhaustiveness;
 mod typecheck;
 
-pub use self::engine::{Bytecode, BytecodeEngine, Definition, DefinitionId, Fun, VarStack};
+pub use self::diagnostic::{Diagnostic, Label, SourceSpan};
+pub use self::engine::{Bytecode, BytecodeEngine, Closure, Definition, DefinitionId, ForRange, Fun,
+                       Overflow, Param, Profile, VarStack};
 pub use self::typecheck::builtin_type;
@@ -1,4 +1,5 @@
-use bytecode::engine::{BytecodeEngine, DefinitionId};
+use bytecode::engine::{BytecodeEngine, Definition, DefinitionId};
+use bytecode::exhaustiveness::int_bounds;
 
 pub mod builtin_type {
     use super::*;
@@ -9,9 +10,76 @@ pub mod builtin_type {
     pub const U32: DefinitionId = 4;
     pub const I64: DefinitionId = 5;
     pub const I32: DefinitionId = 6;
-    pub const BOOL: DefinitionId = 7;
-    pub const VOID_PTR: DefinitionId = 8;
-    pub const ERROR: DefinitionId = 9;
+    pub const U8: DefinitionId = 7;
+    pub const U16: DefinitionId = 8;
+    pub const U128: DefinitionId = 9;
+    pub const USIZE: DefinitionId = 10;
+    pub const I8: DefinitionId = 11;
+    pub const I16: DefinitionId = 12;
+    pub const I128: DefinitionId = 13;
+    pub const ISIZE: DefinitionId = 14;
+    pub const F32: DefinitionId = 15;
+    pub const F64: DefinitionId = 16;
+    pub const UNKNOWN_FLOAT: DefinitionId = 17;
+    pub const BOOL: DefinitionId = 18;
+    pub const VOID_PTR: DefinitionId = 19;
+    pub const ERROR: DefinitionId = 20;
+    /// The type of an expression that never finishes, like `break` or `return`.  It can be used as any other type
+    pub const NEVER: DefinitionId = 21;
+
+    /// The builtin type with the given name as it's written in source, if there is one
+    pub fn named(name: &str) -> Option<DefinitionId> {
+        match name {
+            "u64" => Some(U64),
+            "u32" => Some(U32),
+            "i64" => Some(I64),
+            "i32" => Some(I32),
+            "u8" => Some(U8),
+            "u16" => Some(U16),
+            "u128" => Some(U128),
+            "usize" => Some(USIZE),
+            "i8" => Some(I8),
+            "i16" => Some(I16),
+            "i128" => Some(I128),
+            "isize" => Some(ISIZE),
+            "f32" => Some(F32),
+            "f64" => Some(F64),
+            "bool" => Some(BOOL),
+            _ => None,
+        }
+    }
+
+    /// The number of bits in an integer type, and whether it's signed.  An integer without a type is an i32, and
+    /// pointer-sized integers are 64 bits
+    pub fn int_layout(ty: DefinitionId) -> Option<(u32, bool)> {
+        match ty {
+            U8 => Some((8, false)),
+            U16 => Some((16, false)),
+            U32 => Some((32, false)),
+            U64 | USIZE => Some((64, false)),
+            U128 => Some((128, false)),
+            I8 => Some((8, true)),
+            I16 => Some((16, true)),
+            I32 | UNKNOWN_INT => Some((32, true)),
+            I64 | ISIZE => Some((64, true)),
+            I128 => Some((128, true)),
+            _ => None,
+        }
+    }
+
+    /// Whether the type is a floating point number, including a float without a type
+    pub fn is_float(ty: DefinitionId) -> bool {
+        matches!(ty, F32 | F64 | UNKNOWN_FLOAT)
+    }
+
+    /// The type a number without a type takes when nothing else decides it: i32 for an integer and f64 for a float
+    pub fn fallback(ty: DefinitionId) -> DefinitionId {
+        match ty {
+            UNKNOWN_INT => I32,
+            UNKNOWN_FLOAT => F64,
+            _ => ty,
+        }
+    }
 }
 
 impl BytecodeEngine {
@@ -24,9 +92,63 @@ impl BytecodeEngine {
             builtin_type::U32 => "u32".into(),
             builtin_type::I64 => "i64".into(),
             builtin_type::I32 => "i32".into(),
+            builtin_type::U8 => "u8".into(),
+            builtin_type::U16 => "u16".into(),
+            builtin_type::U128 => "u128".into(),
+            builtin_type::USIZE => "usize".into(),
+            builtin_type::I8 => "i8".into(),
+            builtin_type::I16 => "i16".into(),
+            builtin_type::I128 => "i128".into(),
+            builtin_type::ISIZE => "isize".into(),
+            builtin_type::F32 => "f32".into(),
+            builtin_type::F64 => "f64".into(),
+            builtin_type::UNKNOWN_FLOAT => "{unknown float}".into(),
             builtin_type::BOOL => "bool".into(),
+            builtin_type::VOID_PTR => "*const c_void".into(),
             builtin_type::ERROR => "{error}".into(),
-            _ => format!("{{custom type: {}}}", ty),
+            builtin_type::NEVER => "!".into(),
+            _ => match self.definitions[ty] {
+                Definition::Ref(referred_ty, true) => {
+                    format!("&mut {}", self.printable_name(referred_ty))
+                }
+                Definition::Ref(referred_ty, false) => {
+                    format!("&{}", self.printable_name(referred_ty))
+                }
+                Definition::Box(boxed_ty) => format!("Box<{}>", self.printable_name(boxed_ty)),
+                Definition::Array(element_ty, len) => {
+                    format!("[{}; {}]", self.printable_name(element_ty), len)
+                }
+                Definition::Tuple(ref element_tys) => {
+                    let names: Vec<String> = element_tys
+                        .iter()
+                        .map(|element_ty| self.printable_name(*element_ty))
+                        .collect();
+                    if names.len() == 1 {
+                        format!("({},)", names[0])
+                    } else {
+                        format!("({})", names.join(", "))
+                    }
+                }
+                Definition::DynTrait(ref dyn_trait) => {
+                    format!("dyn {}", self.printable_name(dyn_trait.trait_id))
+                }
+                Definition::Struct(ref struct_def) => struct_def.name.clone(),
+                Definition::Enum(ref enum_def) => enum_def.name.clone(),
+                Definition::Variant(ref variant) => {
+                    format!("{}::{}", self.printable_name(variant.enum_id), variant.name)
+                }
+                Definition::Trait(ref trait_def) => trait_def.name.clone(),
+                Definition::TypeVariable(ref name, _) => name.clone(),
+                Definition::Closure(_) => "{closure}".to_string(),
+                Definition::FnPtr(ref param_tys, return_ty) => {
+                    let names: Vec<String> = param_tys.iter().map(|ty| self.printable_name(*ty)).collect();
+                    match return_ty {
+                        builtin_type::VOID => format!("fn({})", names.join(", ")),
+                        _ => format!("fn({}) -> {}", names.join(", "), self.printable_name(return_ty)),
+                    }
+                }
+                _ => format!("{{custom type: {}}}", ty),
+            },
         }
     }
 
@@ -35,52 +157,115 @@ impl BytecodeEngine {
             return true;
         }
         match (lhs, rhs) {
-            | (builtin_type::U64, builtin_type::UNKNOWN_INT)
-            | (builtin_type::U32, builtin_type::UNKNOWN_INT)
-            | (builtin_type::I64, builtin_type::UNKNOWN_INT)
-            | (builtin_type::I32, builtin_type::UNKNOWN_INT)
-            | (builtin_type::UNKNOWN_INT, builtin_type::U64)
-            | (builtin_type::UNKNOWN_INT, builtin_type::U32)
-            | (builtin_type::UNKNOWN_INT, builtin_type::I64)
-            | (builtin_type::UNKNOWN_INT, builtin_type::I32)
-            | (builtin_type::UNKNOWN_INT, builtin_type::UNKNOWN_INT) => true,
+            // An error has already been reported for this expression, so don't report another
+            (builtin_type::ERROR, _) | (_, builtin_type::ERROR) => true,
+            (builtin_type::NEVER, _) | (_, builtin_type::NEVER) => true,
+            // An integer without a type can be used with an integer of any type
+            (builtin_type::UNKNOWN_INT, ty) | (ty, builtin_type::UNKNOWN_INT) => int_bounds(ty).is_some(),
+            // Likewise for a float without a type, though never with an integer
+            (builtin_type::UNKNOWN_FLOAT, ty) | (ty, builtin_type::UNKNOWN_FLOAT) => builtin_type::is_float(ty),
             _ => false,
         }
     }
 
+    /// Checks if the operands can be used with an operator that only works on integers, like `%`
+    pub(crate) fn integer_operator_compatible(&self, lhs: DefinitionId, rhs: DefinitionId) -> bool {
+        self.operator_compatible(lhs, rhs) && is_integer_or_error(lhs) && is_integer_or_error(rhs)
+    }
+
+    /// Checks if the operands are floats that can be used together, like with `%`
+    pub(crate) fn float_operator_compatible(&self, lhs: DefinitionId, rhs: DefinitionId) -> bool {
+        self.operator_compatible(lhs, rhs) && builtin_type::is_float(lhs) && builtin_type::is_float(rhs)
+    }
+
+    /// Checks if the operands can be used with `&`, `|` or `^`, which work on two integers or two bools
+    pub(crate) fn bitwise_compatible(&self, lhs: DefinitionId, rhs: DefinitionId) -> bool {
+        self.operator_compatible(lhs, rhs)
+            && (lhs == builtin_type::BOOL || is_integer_or_error(lhs))
+            && (rhs == builtin_type::BOOL || is_integer_or_error(rhs))
+    }
+
+    /// Checks if the lhs can be shifted by the rhs.  Unlike other operators, the amount to shift by can be an integer
+    /// of a different type
+    pub(crate) fn shift_compatible(&self, lhs: DefinitionId, rhs: DefinitionId) -> bool {
+        is_integer_or_error(lhs) && is_integer_or_error(rhs)
+    }
+
     pub(crate) fn assignment_compatible(&self, lhs: DefinitionId, rhs: DefinitionId) -> bool {
-        if lhs == rhs {
+        if lhs == rhs || self.has_error_type(lhs) || self.has_error_type(rhs) {
             return true;
         }
         match (lhs, rhs) {
-            (builtin_type::U64, builtin_type::UNKNOWN_INT)
-            | (builtin_type::U32, builtin_type::UNKNOWN_INT)
-            | (builtin_type::I64, builtin_type::UNKNOWN_INT)
-            | (builtin_type::I32, builtin_type::UNKNOWN_INT)
-            | (builtin_type::UNKNOWN, _)
-            | (builtin_type::UNKNOWN_INT, builtin_type::U64)
-            | (builtin_type::UNKNOWN_INT, builtin_type::U32)
-            | (builtin_type::UNKNOWN_INT, builtin_type::I64)
-            | (builtin_type::UNKNOWN_INT, builtin_type::I32) => true,
+            (builtin_type::UNKNOWN, _) | (_, builtin_type::NEVER) => true,
+            (builtin_type::UNKNOWN_INT, ty) | (ty, builtin_type::UNKNOWN_INT) if int_bounds(ty).is_some() => true,
+            (builtin_type::UNKNOWN_FLOAT, ty) | (ty, builtin_type::UNKNOWN_FLOAT) if builtin_type::is_float(ty) => {
+                true
+            }
+            _ => match (&self.definitions[lhs], &self.definitions[rhs]) {
+                // A `&mut` can be used where a `&` is expected, and a number without a type can be referred
+                // to as its fallback type
+                (Definition::Ref(lhs_referred, lhs_mutable), Definition::Ref(rhs_referred, rhs_mutable)) => {
+                    (*rhs_mutable || !*lhs_mutable)
+                        && (lhs_referred == rhs_referred || *lhs_referred == builtin_type::fallback(*rhs_referred))
+                }
+                _ => false,
+            },
+        }
+    }
+
+    /// Checks if the type is, or is made from, the type of something that already failed to typecheck.  It's
+    /// compatible with any other type, so that the failure isn't reported again
+    fn has_error_type(&self, ty: DefinitionId) -> bool {
+        match self.definitions[ty] {
+            _ if ty == builtin_type::ERROR => true,
+            Definition::Ref(referred_ty, _) | Definition::Box(referred_ty) | Definition::Array(referred_ty, _) => {
+                self.has_error_type(referred_ty)
+            }
+            Definition::Tuple(ref element_tys) => element_tys.iter().any(|ty| self.has_error_type(*ty)),
+            Definition::FnPtr(ref param_tys, return_ty) => {
+                param_tys.iter().any(|ty| self.has_error_type(*ty)) || self.has_error_type(return_ty)
+            }
+            Definition::Struct(ref st) => match st.instance_of {
+                Some((_, ref ty_args)) => ty_args.iter().any(|ty| self.has_error_type(*ty)),
+                None => false,
+            },
             _ => false,
         }
     }
 
+    /// Checks if a value of one type can be converted to the other with `as`: a number to any other number, a bool
+    /// to an integer, or a pointer to or from an integer
+    pub(crate) fn cast_compatible(&self, from: DefinitionId, to: DefinitionId) -> bool {
+        let is_int = |ty| int_bounds(ty).is_some();
+        let is_number = |ty| is_int(ty) || builtin_type::is_float(ty);
+
+        from == to
+            || from == builtin_type::ERROR
+            || to == builtin_type::ERROR
+            || (is_number(from) && is_number(to))
+            || (from == builtin_type::BOOL && is_int(to))
+            || (from == builtin_type::VOID_PTR && is_int(to))
+            || (is_int(from) && to == builtin_type::VOID_PTR)
+    }
+
     pub(crate) fn tighter_of_types(&self, lhs: DefinitionId, rhs: DefinitionId) -> DefinitionId {
         match (lhs, rhs) {
-            (builtin_type::U64, _) => builtin_type::U64,
-            (builtin_type::U32, _) => builtin_type::U32,
-            (builtin_type::I64, _) => builtin_type::I64,
-            (builtin_type::I32, _) => builtin_type::I32,
+            (builtin_type::ERROR, _) | (_, builtin_type::ERROR) => builtin_type::ERROR,
             (builtin_type::BOOL, _) => builtin_type::BOOL,
-            (_, builtin_type::U64) => builtin_type::U64,
-            (_, builtin_type::U32) => builtin_type::U32,
-            (_, builtin_type::I64) => builtin_type::I64,
-            (_, builtin_type::I32) => builtin_type::I32,
             (_, builtin_type::BOOL) => builtin_type::BOOL,
+            // An integer with a type is tighter than one without
+            (builtin_type::UNKNOWN_INT, rhs) if int_bounds(rhs).is_some() => rhs,
+            (lhs, _) if int_bounds(lhs).is_some() => lhs,
+            // Likewise for floats
+            (builtin_type::UNKNOWN_FLOAT, rhs) if builtin_type::is_float(rhs) => rhs,
+            (lhs, _) if builtin_type::is_float(lhs) => lhs,
             (builtin_type::UNKNOWN, rhs) => rhs.clone(),
             (lhs, builtin_type::UNKNOWN) => lhs.clone(),
             _ => lhs.clone(),
         }
     }
 }
+
+fn is_integer_or_error(ty: DefinitionId) -> bool {
+    ty == builtin_type::ERROR || int_bounds(ty).is_some()
+}
@@ -1,4 +1,5 @@
-use bytecode::{builtin_type, Bytecode, BytecodeEngine, Definition, DefinitionId, Fun};
+use bytecode::{builtin_type, Bytecode, BytecodeEngine, Closure, Definition, DefinitionId, Fun, Overflow, Param, Profile};
+use std::collections::HashMap;
 use time::PreciseTime;
 
 struct CFile {
@@ -42,15 +43,48 @@ fn codegen_type(
         builtin_type::U32 => "unsigned".into(),
         builtin_type::I64 => "signed long long".into(),
         builtin_type::I32 => "signed".into(),
+        builtin_type::U8 => "unsigned char".into(),
+        builtin_type::U16 => "unsigned short".into(),
+        builtin_type::U128 => "unsigned __int128".into(),
+        builtin_type::USIZE => "size_t".into(),
+        builtin_type::I8 => "signed char".into(),
+        builtin_type::I16 => "short".into(),
+        builtin_type::I128 => "__int128".into(),
+        builtin_type::ISIZE => "ptrdiff_t".into(),
         builtin_type::UNKNOWN_INT => "int".into(),
+        builtin_type::F32 => "float".into(),
+        builtin_type::F64 => "double".into(),
+        builtin_type::UNKNOWN_FLOAT => "double".into(),
         builtin_type::VOID_PTR => "void*".into(),
         builtin_type::VOID => "void".into(),
         builtin_type::BOOL => "bool".into(),
         ty => {
             if let Definition::Struct(_) = bc.definitions[definition_id] {
-                //For now, let's assume all custom types are structs
-                format!("struct struct_{}", ty)
-            } else if let Definition::TypeVariable = bc.definitions[definition_id] {
+                // An instance of a generic struct that uses type variables is the instance they're substituted to
+                match instantiations.and_then(|instantiations| instantiations.iter().find(|x| x.0 == definition_id)) {
+                    Some(x) => codegen_type(bc, x.1, instantiations),
+                    None => format!("struct struct_{}", ty),
+                }
+            } else if let Definition::Enum(_) = bc.definitions[definition_id] {
+                format!("struct enum_{}", ty)
+            } else if let Definition::Tuple(_) = bc.definitions[definition_id] {
+                format!("struct tuple_{}", ty)
+            } else if let Definition::Array(_, _) = bc.definitions[definition_id] {
+                format!("struct array_{}", ty)
+            } else if let Definition::Closure(_) = bc.definitions[definition_id] {
+                format!("struct closure_{}", ty)
+            } else if let Definition::FnPtr(_, _) = bc.definitions[definition_id] {
+                format!("fnptr_{}", ty)
+            } else if let Definition::Ref(referred_ty, _) | Definition::Box(referred_ty) =
+                bc.definitions[definition_id]
+            {
+                if let Definition::DynTrait(_) = bc.definitions[referred_ty] {
+                    // Pointers to trait objects carry their vtable along with them
+                    format!("struct dyn_{}", referred_ty)
+                } else {
+                    format!("{}*", codegen_type(bc, referred_ty, instantiations))
+                }
+            } else if let Definition::TypeVariable(..) = bc.definitions[definition_id] {
                 let sub = instantiations
                     .unwrap()
                     .iter()
@@ -69,11 +103,39 @@ fn codegen_type(
     codegen_ty
 }
 
+/// The name of a field in C.  Unnamed fields are named after their position, which C needs a letter in front of
+fn codegen_field_name(field: &str) -> String {
+    if field.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("f{}", field)
+    } else {
+        field.to_string()
+    }
+}
+
+/// Pops the given number of expressions, giving them back as the arguments of a call
+fn pop_args(cfile: &mut CFile, count: usize) -> String {
+    let first_arg = cfile.expression_stack.len() - count;
+    let args: Vec<String> = cfile.expression_stack.drain(first_arg..).collect();
+    args.join(", ")
+}
+
+/// The signature of a trait object's method, as the vtable sees it.  The receiver is passed as `void*`, as its
+/// type depends on which impl the method is from
+fn codegen_dyn_method_params(bc: &BytecodeEngine, params: &[Param]) -> Vec<String> {
+    let mut param_tys = vec!["void*".to_string()];
+    for param in params.iter().skip(1) {
+        param_tys.push(codegen_type(bc, param.ty, None));
+    }
+    param_tys
+}
+
+/// Outputs a function.  A closure's body is given the closure it was called through, which holds what it captured
 fn codegen_fn(
     cfile: &mut CFile,
     bc: &BytecodeEngine,
     fn_name: &str,
     fun: &Fun,
+    closure: Option<(DefinitionId, &Closure)>,
     instantiations: Option<&Vec<(DefinitionId, DefinitionId)>>,
 ) {
     let mut next_temp_id = 0;
@@ -81,13 +143,38 @@ fn codegen_fn(
     //TODO: This isn't the best solution, but it's an experiment
     let mut temp_id_stack = vec![];
 
+    // The loops around the current bytecode, innermost last, by the id of their labels
+    let mut loop_id_stack: Vec<usize> = vec![];
+
+    // The place of a temporary that's borrowed as soon as it's declared, eg) for `&5`
+    let mut borrowed_temporary = None;
+
     cfile.codegen_raw(&format!(
         "{} {}(",
         &codegen_type(bc, fun.return_ty, instantiations),
         fn_name
     ));
 
+    // Captured variables are found through the closure, by pointer if they're borrowed
+    let mut captured_names = HashMap::new();
     let mut first = true;
+    if let Some((closure_ty, closure)) = closure {
+        cfile.codegen_raw(&format!("struct closure_{}* closure_env", closure_ty));
+        for (index, capture) in closure.captures.iter().enumerate() {
+            let name = if closure.by_move {
+                format!("closure_env->c{}", index)
+            } else {
+                format!("(*closure_env->c{})", index)
+            };
+            captured_names.insert(capture.inner_var_id, name);
+        }
+        first = false;
+    }
+    let var_name = |var_id: usize| match captured_names.get(&var_id) {
+        Some(name) => name.clone(),
+        None => format!("v{}", var_id),
+    };
+
     for param in &fun.params {
         cfile.codegen_raw(&format!(
             "{}{} {}",
@@ -109,39 +196,126 @@ fn codegen_fn(
         ));
     }
 
-    for code in &fun.bytecode {
+    for (idx, code) in fun.bytecode.iter().enumerate() {
         match code {
             Bytecode::ReturnVoid => {
                 cfile.codegen_stmt("return;\n");
-                break;
             }
             Bytecode::ReturnLastStackValue => {
                 let retval = cfile.expression_stack.pop().unwrap();
                 cfile.codegen_stmt(&format!("return {};\n", retval));
-                break;
             }
             Bytecode::As(ty) => {
+                // Only builtin types need converting, and C can't cast to a struct anyway
+                if let Definition::Builtin = bc.definitions[*ty] {
+                    let val = cfile.expression_stack.pop().unwrap();
+                    cfile.delay_expr(format!(
+                        "(({})({}))",
+                        codegen_type(bc, *ty, instantiations),
+                        val
+                    ));
+                }
+            }
+            Bytecode::Cast(from_ty, to_ty) => {
                 let val = cfile.expression_stack.pop().unwrap();
-                cfile.delay_expr(format!(
-                    "(({})({}))",
-                    codegen_type(bc, *ty, instantiations),
-                    val
-                ));
+                cfile.delay_expr(codegen_cast(bc, *from_ty, *to_ty, &val));
+            }
+            Bytecode::BoxNew(box_ty) => {
+                let val = cfile.expression_stack.pop().unwrap();
+                cfile.delay_expr(format!("box_{}({})", box_ty, val));
+            }
+            Bytecode::MakeDyn(vtable_id) => {
+                if let Definition::Vtable(ref vtable) = bc.definitions[*vtable_id] {
+                    let val = cfile.expression_stack.pop().unwrap();
+                    cfile.delay_expr(format!(
+                        "((struct dyn_{}){{(void*)({}), &vtable_{}}})",
+                        vtable.dyn_ty, val, vtable_id
+                    ));
+                }
+            }
+            Bytecode::CallDyn(dyn_ty, method_index) => {
+                if let Definition::DynTrait(ref dyn_trait) = bc.definitions[*dyn_ty] {
+                    let args = pop_args(cfile, dyn_trait.methods[*method_index].0.len());
+                    cfile.delay_expr(format!("dyn_{}_{}({})", dyn_ty, method_index, args));
+                }
+            }
+            Bytecode::MakeClosure(closure_ty, _) => {
+                if let Definition::Closure(ref closure) = bc.definitions[*closure_ty] {
+                    let mut fields = vec![format!("closure_fn_{}", closure_ty)];
+                    for capture in &closure.captures {
+                        fields.push(match closure.by_move {
+                            true => var_name(capture.var_id),
+                            false => format!("&{}", var_name(capture.var_id)),
+                        });
+                    }
+                    cfile.delay_expr(format!("((struct closure_{}){{{}}})", closure_ty, fields.join(", ")));
+                }
+            }
+            Bytecode::CallClosure(closure_ty) => {
+                if let Definition::Closure(Closure {
+                    fun: Some(ref closure_fun),
+                    ..
+                }) = bc.definitions[*closure_ty]
+                {
+                    // The closure is referred to under the args, and is called through its function pointer with
+                    // itself passed first
+                    let args = pop_args(cfile, closure_fun.params.len());
+                    let closure = cfile.expression_stack.pop().unwrap();
+                    let args = if args.is_empty() {
+                        closure.clone()
+                    } else {
+                        format!("{}, {}", closure, args)
+                    };
+                    cfile.delay_expr(format!("({})->fn({})", closure, args));
+                }
             }
             Bytecode::PushU64(val) => {
-                cfile.delay_expr(val.to_string());
+                cfile.delay_expr(codegen_int_literal(u128::from(*val), false));
             }
             Bytecode::PushU32(val) => {
-                cfile.delay_expr(val.to_string());
+                cfile.delay_expr(codegen_int_literal(u128::from(*val), false));
             }
             Bytecode::PushI64(val) => {
-                cfile.delay_expr(val.to_string());
+                cfile.delay_expr(codegen_signed_literal(i128::from(*val)));
             }
             Bytecode::PushI32(val) => {
-                cfile.delay_expr(val.to_string());
+                cfile.delay_expr(codegen_signed_literal(i128::from(*val)));
+            }
+            Bytecode::PushU8(val) => {
+                cfile.delay_expr(codegen_int_literal(u128::from(*val), false));
+            }
+            Bytecode::PushU16(val) => {
+                cfile.delay_expr(codegen_int_literal(u128::from(*val), false));
+            }
+            Bytecode::PushU128(val) => {
+                cfile.delay_expr(codegen_int_literal(*val, false));
+            }
+            Bytecode::PushUsize(val) => {
+                cfile.delay_expr(codegen_int_literal(*val as u128, false));
+            }
+            Bytecode::PushI8(val) => {
+                cfile.delay_expr(codegen_signed_literal(i128::from(*val)));
+            }
+            Bytecode::PushI16(val) => {
+                cfile.delay_expr(codegen_signed_literal(i128::from(*val)));
+            }
+            Bytecode::PushI128(val) => {
+                cfile.delay_expr(codegen_signed_literal(*val));
+            }
+            Bytecode::PushIsize(val) => {
+                cfile.delay_expr(codegen_signed_literal(*val as i128));
             }
             Bytecode::PushUnknownInt(val) => {
-                cfile.delay_expr(val.to_string());
+                cfile.delay_expr(codegen_signed_literal(*val));
+            }
+            Bytecode::PushF32(val) => {
+                cfile.delay_expr(format!("{:?}f", val));
+            }
+            Bytecode::PushF64(val) => {
+                cfile.delay_expr(format!("{:?}", val));
+            }
+            Bytecode::PushUnknownFloat(val) => {
+                cfile.delay_expr(format!("{:?}", val));
             }
             Bytecode::PushRawPtr(p) => {
                 if p.is_null() {
@@ -153,58 +327,176 @@ fn codegen_fn(
             Bytecode::PushBool(val) => {
                 cfile.delay_expr(val.to_string());
             }
-            Bytecode::Neg => {
+            Bytecode::Neg(ty) => {
                 let val = cfile.expression_stack.pop().unwrap();
 
-                cfile.delay_expr(format!("(-{})", val));
+                let ty = builtin_type::fallback(*ty);
+                if builtin_type::int_layout(ty).is_some() {
+                    let c_ty = codegen_type(bc, ty, None);
+                    cfile.delay_expr(codegen_overflow_checked(bc, "sub", ty, &format!("({})0", c_ty), &val, "negate"));
+                } else {
+                    cfile.delay_expr(format!("(-{})", val));
+                }
+            }
+            Bytecode::Not(ty) => {
+                let val = cfile.expression_stack.pop().unwrap();
+
+                let ty = builtin_type::fallback(*ty);
+                if builtin_type::int_layout(ty).is_some() {
+                    // C promotes small integers to int before flipping their bits, so the result is cast back
+                    let c_ty = codegen_type(bc, ty, None);
+                    cfile.delay_expr(format!("(({})~{})", c_ty, val));
+                } else {
+                    cfile.delay_expr(format!("(!{})", val));
+                }
+            }
+            Bytecode::Add(_)
+            | Bytecode::Sub(_)
+            | Bytecode::Mul(_)
+            | Bytecode::Div(_)
+            | Bytecode::Rem(_)
+            | Bytecode::BitAnd
+            | Bytecode::BitOr
+            | Bytecode::BitXor
+            | Bytecode::Shl(_)
+            | Bytecode::Shr(_) => {
+                let rhs = cfile.expression_stack.pop().unwrap();
+                let lhs = cfile.expression_stack.pop().unwrap();
+
+                cfile.delay_expr(codegen_binary_op(bc, code, &lhs, &rhs));
             }
-            Bytecode::Add => {
+            Bytecode::OverflowingOp(op, overflow, ty) => {
+                let rhs = cfile.expression_stack.pop().unwrap();
+                // Negating subtracts the one operand from zero
+                let lhs = match **op {
+                    Bytecode::Neg(operand_ty) => format!("({})0", codegen_type(bc, operand_ty, None)),
+                    _ => cfile.expression_stack.pop().unwrap(),
+                };
+
+                cfile.delay_expr(codegen_overflowing_op(bc, op, *overflow, *ty, &lhs, &rhs));
+            }
+            Bytecode::Lt => {
                 let rhs = cfile.expression_stack.pop().unwrap();
                 let lhs = cfile.expression_stack.pop().unwrap();
 
-                cfile.delay_expr(format!("({}+{})", lhs, rhs));
-                // For checked add
-                //cfile.delay_expr(format!("add32({},{})", lhs, rhs));
+                cfile.delay_expr(format!("({} < {})", lhs, rhs));
             }
-            Bytecode::Sub => {
+            Bytecode::Le => {
                 let rhs = cfile.expression_stack.pop().unwrap();
                 let lhs = cfile.expression_stack.pop().unwrap();
 
-                cfile.delay_expr(format!("({}-{})", lhs, rhs));
+                cfile.delay_expr(format!("({} <= {})", lhs, rhs));
             }
-            Bytecode::Mul => {
+            Bytecode::Gt => {
                 let rhs = cfile.expression_stack.pop().unwrap();
                 let lhs = cfile.expression_stack.pop().unwrap();
 
-                cfile.delay_expr(format!("({}*{})", lhs, rhs));
+                cfile.delay_expr(format!("({} > {})", lhs, rhs));
             }
-            Bytecode::Div => {
+            Bytecode::Ge => {
                 let rhs = cfile.expression_stack.pop().unwrap();
                 let lhs = cfile.expression_stack.pop().unwrap();
 
-                cfile.delay_expr(format!("({}/{})", lhs, rhs));
+                cfile.delay_expr(format!("({} >= {})", lhs, rhs));
             }
-            Bytecode::Lt => {
+            Bytecode::Eq => {
                 let rhs = cfile.expression_stack.pop().unwrap();
                 let lhs = cfile.expression_stack.pop().unwrap();
 
-                cfile.delay_expr(format!("({} < {})", lhs, rhs));
+                cfile.delay_expr(format!("({} == {})", lhs, rhs));
+            }
+            Bytecode::Ne => {
+                let rhs = cfile.expression_stack.pop().unwrap();
+                let lhs = cfile.expression_stack.pop().unwrap();
+
+                cfile.delay_expr(format!("({} != {})", lhs, rhs));
+            }
+            Bytecode::IsVariant(variant_id) => {
+                if let Definition::Variant(ref variant) = bc.definitions[*variant_id] {
+                    let val = cfile.expression_stack.pop().unwrap();
+                    cfile.delay_expr(format!("(({}).tag == {})", val, variant.index));
+                }
+            }
+            Bytecode::VariantField(variant_id, field_idx) => {
+                if let Definition::Variant(ref variant) = bc.definitions[*variant_id] {
+                    let val = cfile.expression_stack.pop().unwrap();
+                    cfile.delay_expr(format!(
+                        "({}).data.v{}.f{}",
+                        val, variant.index, field_idx
+                    ));
+                }
+            }
+            Bytecode::LValueVariantField(variant_id, field_idx) => {
+                if let Definition::Variant(ref variant) = bc.definitions[*variant_id] {
+                    let lhs = cfile.expression_stack.pop().unwrap();
+                    cfile.delay_expr(format!(
+                        "({}).data.v{}.f{}",
+                        lhs, variant.index, field_idx
+                    ));
+                }
+            }
+            Bytecode::Unreachable(ty) => {
+                cfile.codegen_stmt("abort();\n");
+                // The if this is in still expects a result, even though it's never used
+                if *ty != builtin_type::VOID {
+                    cfile
+                        .expression_stack
+                        .push(format!("t{}", temp_id_stack.last().unwrap()));
+                }
+            }
+            Bytecode::ArrayRepeat(array_ty) => {
+                let val = cfile.expression_stack.pop().unwrap();
+                cfile.delay_expr(format!("repeat_array_{}({})", array_ty, val));
+            }
+            Bytecode::Index(array_ty) | Bytecode::LValueIndex(array_ty) => {
+                if let Definition::Array(_, len) = bc.definitions[*array_ty] {
+                    let index = cfile.expression_stack.pop().unwrap();
+                    let array = cfile.expression_stack.pop().unwrap();
+                    cfile.delay_expr(format!(
+                        "({}).data[bounds_check({}, {})]",
+                        array, index, len
+                    ));
+                }
             }
             Bytecode::Dot(field) => {
                 let lhs = cfile.expression_stack.pop().unwrap();
 
-                cfile.delay_expr(format!("{}.{}", lhs, field));
+                cfile.delay_expr(format!("{}.{}", lhs, codegen_field_name(field)));
             }
             Bytecode::LValueDot(field) => {
                 let lhs = cfile.expression_stack.pop().unwrap();
 
-                cfile.delay_expr(format!("{}.{}", lhs, field));
+                cfile.delay_expr(format!("{}.{}", lhs, codegen_field_name(field)));
+            }
+            Bytecode::Ref(_, _) => {
+                let lvalue = cfile.expression_stack.pop().unwrap();
+
+                cfile.delay_expr(format!("(&{})", lvalue));
+            }
+            Bytecode::Deref => {
+                let reference = cfile.expression_stack.pop().unwrap();
+
+                cfile.delay_expr(format!("(*{})", reference));
             }
             Bytecode::VarDecl(var_id) => {
                 let rhs = cfile.expression_stack.pop().unwrap();
 
                 let var = &fun.vars[*var_id];
 
+                if let Some(Bytecode::LValueVar(next_var_id, _)) = fun.bytecode.get(idx + 1) {
+                    if next_var_id == var_id && var.ident.is_empty() {
+                        // This may be in the middle of an expression, so declare it without flushing the
+                        // expressions waiting to be used, and initialize it where it's borrowed
+                        cfile.codegen_raw(&format!(
+                            "{} v{};\n",
+                            codegen_type(bc, var.ty, instantiations),
+                            *var_id
+                        ));
+                        borrowed_temporary = Some(format!("(*(v{} = {}, &v{}))", var_id, rhs, var_id));
+                        continue;
+                    }
+                }
+
                 cfile.codegen_stmt(&format!(
                     "{} v{} = {};\n",
                     codegen_type(bc, var.ty, instantiations),
@@ -220,18 +512,38 @@ fn codegen_fn(
                     *var_id
                 ));
             }
-            Bytecode::Var(var_id) => {
-                cfile.delay_expr(format!("v{}", var_id));
-            }
-            Bytecode::LValueVar(var_id) => {
-                cfile.delay_expr(format!("v{}", var_id));
+            Bytecode::Var(var_id, _) => {
+                cfile.delay_expr(var_name(*var_id));
             }
+            Bytecode::LValueVar(var_id, _) => match borrowed_temporary.take() {
+                Some(place) => cfile.delay_expr(place),
+                None => cfile.delay_expr(var_name(*var_id)),
+            },
             Bytecode::Assign => {
                 let lhs = cfile.expression_stack.pop().unwrap();
                 let rhs = cfile.expression_stack.pop().unwrap();
 
                 cfile.codegen_stmt(&format!("{} = {};\n", lhs, rhs));
             }
+            Bytecode::CompoundAssign(op, ty) => {
+                let lhs = cfile.expression_stack.pop().unwrap();
+                let rhs = cfile.expression_stack.pop().unwrap();
+
+                // Point at the place, so that anything done to find it, like calls in an index, is only done once
+                cfile.codegen_stmt(&format!(
+                    "{}* t{} = &{};\n",
+                    codegen_type(bc, *ty, instantiations),
+                    next_temp_id,
+                    lhs
+                ));
+                let place = format!("(*t{})", next_temp_id);
+                cfile.codegen_stmt(&format!(
+                    "{} = {};\n",
+                    place,
+                    codegen_binary_op(bc, op, &place, &rhs)
+                ));
+                next_temp_id += 1;
+            }
             Bytecode::Call(definition_id) => {
                 if let Definition::Fun(ref fun) = bc.definitions[*definition_id] {
                     let mut expr_string = String::new();
@@ -278,6 +590,15 @@ fn codegen_fn(
                     }
 
                     cfile.delay_expr(expr_string);
+                } else if let Definition::Variant(ref variant) = bc.definitions[*definition_id] {
+                    let args = pop_args(cfile, variant.fields.len());
+                    cfile.delay_expr(format!("init_variant_{}({})", definition_id, args));
+                } else if let Definition::Tuple(ref element_tys) = bc.definitions[*definition_id] {
+                    let args = pop_args(cfile, element_tys.len());
+                    cfile.delay_expr(format!("init_tuple_{}({})", definition_id, args));
+                } else if let Definition::Array(_, len) = bc.definitions[*definition_id] {
+                    let args = pop_args(cfile, len);
+                    cfile.delay_expr(format!("init_array_{}({})", definition_id, args));
                 } else if let Definition::InstantiatedFun(orig_id, _) =
                     bc.definitions[*definition_id]
                 {
@@ -308,22 +629,14 @@ fn codegen_fn(
             Bytecode::If(_, ty) => {
                 let cond = cfile.expression_stack.pop().unwrap();
 
-                match *ty {
-                    builtin_type::U64
-                    | builtin_type::BOOL
-                    | builtin_type::U32
-                    | builtin_type::I64
-                    | builtin_type::I32
-                    | builtin_type::UNKNOWN_INT => {
-                        cfile.codegen_stmt(&format!(
-                            "{} t{};\n",
-                            codegen_type(bc, *ty, instantiations),
-                            next_temp_id
-                        ));
-                        temp_id_stack.push(next_temp_id);
-                        next_temp_id += 1;
-                    }
-                    _ => {}
+                if *ty != builtin_type::VOID {
+                    cfile.codegen_stmt(&format!(
+                        "{} t{};\n",
+                        codegen_type(bc, *ty, instantiations),
+                        next_temp_id
+                    ));
+                    temp_id_stack.push(next_temp_id);
+                    next_temp_id += 1;
                 }
 
                 cfile.codegen_stmt(&format!("if ({}) {{\n", cond));
@@ -349,29 +662,128 @@ fn codegen_fn(
                     cfile.codegen_stmt("}\n");
                 }
             }
-            Bytecode::BeginWhile => {
+            Bytecode::BeginWhile(ty) => {
+                // Leaving a loop other than the innermost needs a goto, so each loop gets labels of its own
+                let loop_id = next_temp_id;
+                next_temp_id += 1;
+                if *ty != builtin_type::VOID {
+                    cfile.codegen_stmt(&format!("{} t{};\n", codegen_type(bc, *ty, instantiations), loop_id));
+                }
+                loop_id_stack.push(loop_id);
                 cfile.codegen_stmt("while(1) {\n");
             }
             Bytecode::WhileCond(_) => {
                 let cond = cfile.expression_stack.pop().unwrap();
                 cfile.codegen_stmt(&format!("if (!({})) break;\n", cond));
             }
-            Bytecode::EndWhile(_) => {
-                cfile.codegen_stmt("}\n");
+            Bytecode::EndWhile(_, ty) => {
+                let loop_id = loop_id_stack.pop().unwrap();
+                cfile.codegen_stmt(&format!("continue_{}: ;\n}}\nbreak_{}: ;\n", loop_id, loop_id));
+                if *ty != builtin_type::VOID {
+                    cfile.expression_stack.push(format!("t{}", loop_id));
+                }
+            }
+            Bytecode::BeginFor(var_id, range) => {
+                let step = cfile.expression_stack.pop().unwrap();
+                let end = cfile.expression_stack.pop().unwrap();
+                let start = cfile.expression_stack.pop().unwrap();
+
+                // The integers are worked out as unsigned __int128s that wrap around, which gives the right integer
+                // once truncated to its type
+                let signed = builtin_type::int_layout(range.ty).is_none_or(|(_, signed)| signed);
+                cfile.codegen_stmt(&format!("unsigned __int128 v{}_first = {};\n", var_id, start));
+                cfile.codegen_stmt(&format!(
+                    "unsigned __int128 v{}_len = range_len(v{}_first, {}, {}, {});\n",
+                    var_id, var_id, end, signed, range.inclusive
+                ));
+                cfile.codegen_stmt(&format!("unsigned __int128 v{}_step = range_step({});\n", var_id, step));
+                cfile.codegen_stmt(&format!(
+                    "unsigned __int128 v{}_count = v{}_len == 0 ? 0 : (v{}_len - 1) / v{}_step + 1;\n",
+                    var_id, var_id, var_id, var_id
+                ));
+                if range.rev {
+                    cfile.codegen_stmt(&format!("v{}_first += v{}_len - 1;\n", var_id, var_id));
+                    cfile.codegen_stmt(&format!("v{}_step = -v{}_step;\n", var_id, var_id));
+                }
+            }
+            Bytecode::ForNext(_, var_id, ty) => {
+                let loop_id = next_temp_id;
+                next_temp_id += 1;
+                loop_id_stack.push(loop_id);
+                cfile.codegen_stmt(&format!(
+                    "for (unsigned __int128 v{} = 0; v{} < v{}_count; v{}++) {{\n",
+                    var_id, var_id, var_id, var_id
+                ));
+                cfile.delay_expr(format!(
+                    "(({})(v{}_first + v{} * v{}_step))",
+                    codegen_type(bc, *ty, instantiations),
+                    var_id,
+                    var_id,
+                    var_id
+                ));
+            }
+            Bytecode::Break(_, loops_out, ty) => {
+                let loop_id = loop_id_stack[loop_id_stack.len() - 1 - loops_out];
+                if *ty != builtin_type::VOID {
+                    let result = cfile.expression_stack.pop().unwrap();
+                    cfile.codegen_stmt(&format!("t{} = {};\n", loop_id, result));
+                }
+                match loops_out {
+                    0 => cfile.codegen_stmt("break;\n"),
+                    _ => cfile.codegen_stmt(&format!("goto break_{};\n", loop_id)),
+                }
+            }
+            Bytecode::Continue(_, loops_out) => {
+                let loop_id = loop_id_stack[loop_id_stack.len() - 1 - loops_out];
+                match loops_out {
+                    0 => cfile.codegen_stmt("continue;\n"),
+                    _ => cfile.codegen_stmt(&format!("goto continue_{};\n", loop_id)),
+                }
+            }
+            Bytecode::PushFn(fn_id) => {
+                if let Definition::Fun(ref target_fun) = bc.definitions[*fn_id] {
+                    match target_fun.extern_name {
+                        Some(ref ex_name) => cfile.delay_expr(ex_name.clone()),
+                        None => cfile.delay_expr(format!("fun_{}", fn_id)),
+                    }
+                }
+            }
+            Bytecode::CallPtr(fn_ptr_ty) => {
+                if let Definition::FnPtr(ref param_tys, _) = bc.definitions[*fn_ptr_ty] {
+                    // The function pointer is under the args
+                    let args = pop_args(cfile, param_tys.len());
+                    let fn_ptr = cfile.expression_stack.pop().unwrap();
+                    cfile.delay_expr(format!("({})({})", fn_ptr, args));
+                }
+            }
+            Bytecode::CallTraitMethod(_, _, _, _) => {
+                unimplemented!("Codegen of a trait method call that wasn't resolved by instantiation")
+            }
+            Bytecode::Pop => {
+                // The expression is still run for what it does
+                let val = cfile.expression_stack.pop().unwrap();
+                cfile.codegen_stmt(&format!("{};\n", val));
             }
             Bytecode::DebugPrint(ty) => {
                 let val = cfile.expression_stack.pop().unwrap();
-                let result = match *ty {
-                    builtin_type::VOID => "DEBUG: <void>".into(),
-                    builtin_type::UNKNOWN => "DEBUG: <unknown>".into(),
-                    builtin_type::BOOL
-                    | builtin_type::U32
-                    | builtin_type::U64
-                    | builtin_type::I32
-                    | builtin_type::I64
-                    | builtin_type::UNKNOWN_INT => {
+                let result = match (*ty, builtin_type::int_layout(*ty)) {
+                    (builtin_type::VOID, _) => "DEBUG: <void>".into(),
+                    (builtin_type::UNKNOWN, _) => "DEBUG: <unknown>".into(),
+                    (builtin_type::BOOL, _) | (_, Some((8, _))) | (_, Some((16, _))) | (_, Some((32, true))) => {
                         format!("printf(\"DEBUG: %d\\n\", ({}));\n", val)
                     }
+                    (_, Some((32, false))) => format!("printf(\"DEBUG: %u\\n\", ({}));\n", val),
+                    (_, Some((64, true))) => format!("printf(\"DEBUG: %lld\\n\", (long long)({}));\n", val),
+                    (_, Some((64, false))) => {
+                        format!("printf(\"DEBUG: %llu\\n\", (unsigned long long)({}));\n", val)
+                    }
+                    // printf can't print 128-bit integers
+                    (_, Some((_, true))) => format!("printf(\"DEBUG: \");\nprint_i128({});\nprintf(\"\\n\");\n", val),
+                    (_, Some((_, false))) => format!("printf(\"DEBUG: \");\nprint_u128({});\nprintf(\"\\n\");\n", val),
+                    (builtin_type::F32, _) => format!("printf(\"DEBUG: \");\nprint_f32({});\nprintf(\"\\n\");\n", val),
+                    (builtin_type::F64, _) | (builtin_type::UNKNOWN_FLOAT, _) => {
+                        format!("printf(\"DEBUG: \");\nprint_f64({});\nprintf(\"\\n\");\n", val)
+                    }
                     _ => format!("printf(\"DEBUG: <custom type:%u>\\n\", ({}));\n", ty),
                 };
                 cfile.codegen_stmt(&result);
@@ -423,15 +835,96 @@ fn codegen_c_prototype(
 
             cfile.codegen_raw(");\n");
         }
-    } else if let Definition::Struct(_) = bc.definitions[definition_id] {
-        let struct_line = format!("struct struct_{};\n", definition_id);
-        cfile.codegen_raw(&struct_line);
-        let struct_init_line = format!(
-            "{} init_struct_{}();\n",
+    } else if let Definition::Box(boxed_ty) = bc.definitions[definition_id] {
+        if let Definition::DynTrait(_) = bc.definitions[boxed_ty] {
+            // Boxed trait objects are only made from other boxes
+            return;
+        }
+        let boxed_type = codegen_type(bc, boxed_ty, instantiations);
+        cfile.codegen_raw(&format!(
+            "{}* box_{}({} value);\n",
+            boxed_type, definition_id, boxed_type
+        ));
+    } else if let Definition::DynTrait(ref dyn_trait) = bc.definitions[definition_id] {
+        // The vtable and fat pointer are needed in full by anything holding a trait object, so output them here
+        cfile.codegen_raw(&format!("struct vtable_{} {{\n", definition_id));
+        for (method_index, method) in dyn_trait.methods.iter().enumerate() {
+            cfile.codegen_raw(&format!(
+                "{} (*m{})({});\n",
+                codegen_type(bc, method.1, None),
+                method_index,
+                codegen_dyn_method_params(bc, &method.0).join(", ")
+            ));
+        }
+        cfile.codegen_raw("};\n");
+
+        for (method_index, method) in dyn_trait.methods.iter().enumerate() {
+            let mut params = vec![format!("struct dyn_{} self", definition_id)];
+            for (param_index, param) in method.0.iter().enumerate().skip(1) {
+                params.push(format!("{} p{}", codegen_type(bc, param.ty, None), param_index));
+            }
+            cfile.codegen_raw(&format!(
+                "{} dyn_{}_{}({});\n",
+                codegen_type(bc, method.1, None),
+                definition_id,
+                method_index,
+                params.join(", ")
+            ));
+        }
+    } else if let Definition::Vtable(ref vtable) = bc.definitions[definition_id] {
+        cfile.codegen_raw(&format!(
+            "extern const struct vtable_{} vtable_{};\n",
+            vtable.dyn_ty, definition_id
+        ));
+    } else if let Definition::Struct(ref st) = bc.definitions[definition_id] {
+        // The fields are given in full, as a float can't be passed to a function declared without them
+        let fields: Vec<String> = st
+            .fields
+            .iter()
+            .map(|field| codegen_type(bc, field.1, instantiations))
+            .collect();
+        cfile.codegen_raw(&format!(
+            "{} init_struct_{}({});\n",
             codegen_type(bc, definition_id, instantiations),
-            definition_id
-        );
-        cfile.codegen_raw(&struct_init_line);
+            definition_id,
+            fields.join(", ")
+        ));
+    } else if let Definition::Variant(ref variant) = bc.definitions[definition_id] {
+        let fields: Vec<String> = variant
+            .fields
+            .iter()
+            .map(|field_ty| codegen_type(bc, *field_ty, instantiations))
+            .collect();
+        cfile.codegen_raw(&format!(
+            "{} init_variant_{}({});\n",
+            codegen_type(bc, variant.enum_id, instantiations),
+            definition_id,
+            fields.join(", ")
+        ));
+    } else if let Definition::Tuple(ref element_tys) = bc.definitions[definition_id] {
+        let elements: Vec<String> = element_tys
+            .iter()
+            .map(|element_ty| codegen_type(bc, *element_ty, instantiations))
+            .collect();
+        cfile.codegen_raw(&format!(
+            "{} init_tuple_{}({});\n",
+            codegen_type(bc, definition_id, instantiations),
+            definition_id,
+            elements.join(", ")
+        ));
+    } else if let Definition::Array(element_ty, len) = bc.definitions[definition_id] {
+        let array_type = codegen_type(bc, definition_id, instantiations);
+        let element_type = codegen_type(bc, element_ty, instantiations);
+        cfile.codegen_raw(&format!(
+            "{} init_array_{}({});\n",
+            array_type,
+            definition_id,
+            vec![element_type.clone(); len].join(", ")
+        ));
+        cfile.codegen_raw(&format!(
+            "{} repeat_array_{}({} value);\n",
+            array_type, definition_id, element_type
+        ));
     } else if let Definition::InstantiatedFun(orig_id, ref instantiations) =
         bc.definitions[definition_id]
     {
@@ -455,6 +948,20 @@ fn codegen_c_prototype(
 
             cfile.codegen_raw(");\n");
         }
+    } else if let Definition::Closure(Closure {
+        fun: Some(ref fun), ..
+    }) = bc.definitions[definition_id]
+    {
+        let mut params = vec![format!("struct closure_{}* closure_env", definition_id)];
+        for param in &fun.params {
+            params.push(format!("{} {}", codegen_type(bc, param.ty, instantiations), param.name));
+        }
+        cfile.codegen_raw(&format!(
+            "{} closure_fn_{}({});\n",
+            codegen_type(bc, fun.return_ty, instantiations),
+            definition_id,
+            params.join(", ")
+        ));
     }
 }
 
@@ -470,35 +977,81 @@ fn codegen_c_body(
         //Skip generic functions during codegen.  Instead, we'll codegen their instantiations
         if fun.extern_name.is_none() {
             if definition_id == starting_fn_id {
-                codegen_fn(cfile, bc, "main", fun, None);
+                codegen_fn(cfile, bc, "main", fun, None, None);
             } else {
                 codegen_fn(
                     cfile,
                     bc,
                     &format!("fun_{}", definition_id),
                     fun,
+                    None,
                     instantiations,
                 );
             }
         }
-    } else if let Definition::Struct(ref st) = bc.definitions[definition_id] {
-        if st.fields.len() == 0 {
+    } else if let Definition::Box(boxed_ty) = bc.definitions[definition_id] {
+        if let Definition::DynTrait(_) = bc.definitions[boxed_ty] {
+            return;
+        }
+        let boxed_type = codegen_type(bc, boxed_ty, instantiations);
+        cfile.codegen_raw(&format!(
+            "{}* box_{}({} value) {{\n",
+            boxed_type, definition_id, boxed_type
+        ));
+        cfile.codegen_raw(&format!(
+            "{}* temp = malloc(sizeof({}));\n",
+            boxed_type, boxed_type
+        ));
+        cfile.codegen_raw("*temp = value;\n");
+        cfile.codegen_raw("return temp;\n");
+        cfile.codegen_raw("}\n");
+    } else if let Definition::DynTrait(ref dyn_trait) = bc.definitions[definition_id] {
+        // Calling through a trait object looks up the method in the vtable it carries
+        for (method_index, method) in dyn_trait.methods.iter().enumerate() {
+            let mut params = vec![format!("struct dyn_{} self", definition_id)];
+            let mut args = vec!["self.data".to_string()];
+            for (param_index, param) in method.0.iter().enumerate().skip(1) {
+                params.push(format!("{} p{}", codegen_type(bc, param.ty, None), param_index));
+                args.push(format!("p{}", param_index));
+            }
             cfile.codegen_raw(&format!(
-                "struct struct_{} {{int dummy;}};\n",
-                definition_id
+                "{} dyn_{}_{}({}) {{\n",
+                codegen_type(bc, method.1, None),
+                definition_id,
+                method_index,
+                params.join(", ")
             ));
-        } else {
-            cfile.codegen_raw(&format!("struct struct_{} {{", definition_id));
-            for field in &st.fields {
+            cfile.codegen_raw(&format!(
+                "{}self.vtable->m{}({});\n",
+                if method.1 == builtin_type::VOID {
+                    ""
+                } else {
+                    "return "
+                },
+                method_index,
+                args.join(", ")
+            ));
+            cfile.codegen_raw("}\n");
+        }
+    } else if let Definition::Vtable(ref vtable) = bc.definitions[definition_id] {
+        if let Definition::DynTrait(ref dyn_trait) = bc.definitions[vtable.dyn_ty] {
+            cfile.codegen_raw(&format!(
+                "const struct vtable_{} vtable_{} = {{",
+                vtable.dyn_ty, definition_id
+            ));
+            for (method_index, method_id) in vtable.methods.iter().enumerate() {
+                let method = &dyn_trait.methods[method_index];
                 cfile.codegen_raw(&format!(
-                    "{} {};\n",
-                    codegen_type(bc, field.1, instantiations),
-                    field.0
+                    "{}({} (*)({}))fun_{}",
+                    if method_index > 0 { ", " } else { "" },
+                    codegen_type(bc, method.1, None),
+                    codegen_dyn_method_params(bc, &method.0).join(", "),
+                    method_id
                 ));
             }
             cfile.codegen_raw("};\n");
-        };
-
+        }
+    } else if let Definition::Struct(ref st) = bc.definitions[definition_id] {
         cfile.codegen_raw(&format!(
             "{} init_struct_{}(",
             codegen_type(bc, definition_id, instantiations),
@@ -512,7 +1065,7 @@ fn codegen_c_body(
                 "{}{} {}",
                 if !first { ", " } else { "" },
                 codegen_type(bc, field.1, instantiations),
-                field.0
+                codegen_field_name(&field.0)
             ));
             first = false;
         }
@@ -525,17 +1078,102 @@ fn codegen_c_body(
         ));
         cfile.codegen_raw("{");
         let mut first = true;
-        if st.fields.len() > 0 {
-            for field in &st.fields {
-                cfile.codegen_raw(&format!("{}{}", if !first { ", " } else { "" }, field.0,));
-                first = false;
-            }
-        } else {
-            cfile.codegen_raw("0");
+        for field in &st.fields {
+            cfile.codegen_raw(&format!(
+                "{}{}",
+                if !first { ", " } else { "" },
+                codegen_field_name(&field.0)
+            ));
+            first = false;
         }
         cfile.codegen_raw("};\n");
         cfile.codegen_raw("return temp;\n");
         cfile.codegen_raw("}\n");
+    } else if let Definition::Variant(ref variant) = bc.definitions[definition_id] {
+        let mut params = vec![];
+        for (field_idx, field_ty) in variant.fields.iter().enumerate() {
+            params.push(format!(
+                "{} f{}",
+                codegen_type(bc, *field_ty, instantiations),
+                field_idx
+            ));
+        }
+        cfile.codegen_raw(&format!(
+            "{} init_variant_{}({}) {{\n",
+            codegen_type(bc, variant.enum_id, instantiations),
+            definition_id,
+            params.join(", ")
+        ));
+        cfile.codegen_raw(&format!(
+            "{} temp;\n",
+            codegen_type(bc, variant.enum_id, instantiations)
+        ));
+        cfile.codegen_raw(&format!("temp.tag = {};\n", variant.index));
+        for field_idx in 0..variant.fields.len() {
+            cfile.codegen_raw(&format!(
+                "temp.data.v{}.f{} = f{};\n",
+                variant.index, field_idx, field_idx
+            ));
+        }
+        cfile.codegen_raw("return temp;\n");
+        cfile.codegen_raw("}\n");
+    } else if let Definition::Tuple(ref element_tys) = bc.definitions[definition_id] {
+        let mut params = vec![];
+        let mut elements = vec![];
+        for (index, element_ty) in element_tys.iter().enumerate() {
+            params.push(format!(
+                "{} f{}",
+                codegen_type(bc, *element_ty, instantiations),
+                index
+            ));
+            elements.push(format!("f{}", index));
+        }
+        cfile.codegen_raw(&format!(
+            "{} init_tuple_{}({}) {{\n",
+            codegen_type(bc, definition_id, instantiations),
+            definition_id,
+            params.join(", ")
+        ));
+        cfile.codegen_raw(&format!(
+            "{} temp = {{{}}};\n",
+            codegen_type(bc, definition_id, instantiations),
+            elements.join(", ")
+        ));
+        cfile.codegen_raw("return temp;\n");
+        cfile.codegen_raw("}\n");
+    } else if let Definition::Array(element_ty, len) = bc.definitions[definition_id] {
+        let array_type = codegen_type(bc, definition_id, instantiations);
+        let element_type = codegen_type(bc, element_ty, instantiations);
+
+        let params: Vec<String> = (0..len)
+            .map(|index| format!("{} e{}", element_type, index))
+            .collect();
+        let elements: Vec<String> = (0..len).map(|index| format!("e{}", index)).collect();
+        cfile.codegen_raw(&format!(
+            "{} init_array_{}({}) {{\n",
+            array_type,
+            definition_id,
+            params.join(", ")
+        ));
+        cfile.codegen_raw(&format!(
+            "{} temp = {{{{{}}}}};\n",
+            array_type,
+            elements.join(", ")
+        ));
+        cfile.codegen_raw("return temp;\n");
+        cfile.codegen_raw("}\n");
+
+        cfile.codegen_raw(&format!(
+            "{} repeat_array_{}({} value) {{\n",
+            array_type, definition_id, element_type
+        ));
+        cfile.codegen_raw(&format!("{} temp;\n", array_type));
+        cfile.codegen_raw(&format!(
+            "for (int i = 0; i < {}; i++) temp.data[i] = value;\n",
+            len
+        ));
+        cfile.codegen_raw("return temp;\n");
+        cfile.codegen_raw("}\n");
     } else if let Definition::InstantiatedFun(orig_id, ref instantiations) =
         bc.definitions[definition_id]
     {
@@ -545,9 +1183,397 @@ fn codegen_c_body(
                 bc,
                 &format!("fun_{}", definition_id),
                 fun,
+                None,
                 Some(instantiations),
             );
         }
+    } else if let Definition::Closure(ref closure) = bc.definitions[definition_id] {
+        if let Some(ref fun) = closure.fun {
+            codegen_fn(
+                cfile,
+                bc,
+                &format!("closure_fn_{}", definition_id),
+                fun,
+                Some((definition_id, closure)),
+                instantiations,
+            );
+        }
+    }
+}
+
+/// Outputs the full definition of a struct or enum, after the definitions of any types it holds by value
+fn codegen_c_type_definition(
+    cfile: &mut CFile,
+    bc: &BytecodeEngine,
+    definition_id: DefinitionId,
+    emitted: &mut [bool],
+) {
+    if emitted[definition_id] {
+        return;
+    }
+    emitted[definition_id] = true;
+
+    let field_tys: Vec<DefinitionId> = match bc.definitions[definition_id] {
+        Definition::Struct(ref st) => st.fields.iter().map(|field| field.1).collect(),
+        Definition::Enum(ref en) => en
+            .variants
+            .iter()
+            .flat_map(|variant_id| match bc.definitions[*variant_id] {
+                Definition::Variant(ref variant) => variant.fields.clone(),
+                _ => vec![],
+            })
+            .collect(),
+        Definition::Tuple(ref element_tys) => element_tys.clone(),
+        Definition::Array(element_ty, _) => vec![element_ty],
+        // Only what's moved into a closure is held by value
+        Definition::Closure(ref closure) if closure.by_move => {
+            closure.captures.iter().map(|capture| capture.ty).collect()
+        }
+        Definition::Closure(_) => vec![],
+        _ => return,
+    };
+    for field_ty in field_tys {
+        codegen_c_type_definition(cfile, bc, field_ty, emitted);
+    }
+
+    match bc.definitions[definition_id] {
+        Definition::Struct(ref st) => {
+            cfile.codegen_raw(&format!("struct struct_{} {{", definition_id));
+            for field in &st.fields {
+                cfile.codegen_raw(&format!(
+                    "{} {};\n",
+                    codegen_type(bc, field.1, None),
+                    codegen_field_name(&field.0)
+                ));
+            }
+            // A C struct needs at least one member, even if the struct has no fields
+            if st.fields.is_empty() {
+                cfile.codegen_raw("char _unused;\n");
+            }
+            cfile.codegen_raw("};\n");
+        }
+        Definition::Tuple(ref element_tys) => {
+            cfile.codegen_raw(&format!("struct tuple_{} {{", definition_id));
+            for (index, element_ty) in element_tys.iter().enumerate() {
+                cfile.codegen_raw(&format!("{} f{};", codegen_type(bc, *element_ty, None), index));
+            }
+            cfile.codegen_raw("};\n");
+        }
+        Definition::Array(element_ty, len) => {
+            // Arrays are wrapped in a struct, so that they're copied when assigned like other values
+            cfile.codegen_raw(&format!(
+                "struct array_{} {{{} data[{}];}};\n",
+                definition_id,
+                codegen_type(bc, element_ty, None),
+                len
+            ));
+        }
+        Definition::Enum(ref en) => {
+            // An enum is a tag saying which variant it is, with a union of the fields of each variant
+            cfile.codegen_raw(&format!("struct enum_{} {{int tag;", definition_id));
+            let mut union_src = String::new();
+            for variant_id in &en.variants {
+                if let Definition::Variant(ref variant) = bc.definitions[*variant_id] {
+                    if variant.fields.is_empty() {
+                        continue;
+                    }
+                    union_src += "struct {";
+                    for (field_idx, field_ty) in variant.fields.iter().enumerate() {
+                        union_src +=
+                            &format!("{} f{};", codegen_type(bc, *field_ty, None), field_idx);
+                    }
+                    union_src += &format!("}} v{};", variant.index);
+                }
+            }
+            if !union_src.is_empty() {
+                cfile.codegen_raw(&format!(" union {{{}}} data;", union_src));
+            }
+            cfile.codegen_raw("};\n");
+        }
+        Definition::Closure(ref closure) => {
+            // The closure's body, then what it captured
+            cfile.codegen_raw(&format!("struct closure_{} {{", definition_id));
+            if let Some(ref fun) = closure.fun {
+                let mut param_tys = vec![format!("struct closure_{}*", definition_id)];
+                for param in &fun.params {
+                    param_tys.push(codegen_type(bc, param.ty, None));
+                }
+                cfile.codegen_raw(&format!(
+                    "{} (*fn)({});",
+                    codegen_type(bc, fun.return_ty, None),
+                    param_tys.join(", ")
+                ));
+            }
+            for (index, capture) in closure.captures.iter().enumerate() {
+                cfile.codegen_raw(&format!(
+                    "{}{} c{};",
+                    codegen_type(bc, capture.ty, None),
+                    if closure.by_move { "" } else { "*" },
+                    index
+                ));
+            }
+            cfile.codegen_raw("};\n");
+        }
+        _ => {}
+    }
+}
+
+/// Checks if the definition is a generic function or struct, or an instance of one that still uses type variables
+/// from the generic function it was used in, or a tuple or array holding type variables.  None can be output, as
+/// their types aren't known
+fn is_generic_definition(bc: &BytecodeEngine, definition_id: DefinitionId) -> bool {
+    match bc.definitions[definition_id] {
+        Definition::Fun(ref fun) => fun.is_generic(),
+        Definition::Struct(_) | Definition::Tuple(_) | Definition::Array(_, _) | Definition::FnPtr(_, _) => {
+            bc.has_type_variable(definition_id)
+        }
+        Definition::InstantiatedFun(_, ref instantiations) => instantiations
+            .iter()
+            .any(|x| bc.has_type_variable(x.1)),
+        _ => false,
+    }
+}
+
+/// An integer literal, with the suffix C needs for it to have a type that's big enough.  C has no 128-bit literals, so
+/// the largest are built from two halves.  Those too big for a signed long long are unsigned in C, so if the literal
+/// is for a signed type, they're made signed again so that negating them works
+fn codegen_int_literal(val: u128, signed: bool) -> String {
+    if val <= i32::MAX as u128 {
+        return val.to_string();
+    } else if val <= i64::MAX as u128 {
+        return format!("{}LL", val);
+    }
+
+    let literal = if val <= u128::from(u64::MAX) {
+        format!("{}ULL", val)
+    } else {
+        format!("(((unsigned __int128){}ULL << 64) | {}ULL)", val >> 64, val as u64)
+    };
+    if signed {
+        format!("((__int128){})", literal)
+    } else {
+        literal
+    }
+}
+
+/// A signed integer literal.  A negative one is its positive counterpart negated, apart from the minimum of an
+/// i128, which has none
+fn codegen_signed_literal(val: i128) -> String {
+    match val.checked_neg() {
+        _ if val >= 0 => codegen_int_literal(val as u128, true),
+        Some(positive) => format!("(-{})", codegen_int_literal(positive as u128, true)),
+        None => format!("(-{} - 1)", codegen_int_literal(i128::MAX as u128, true)),
+    }
+}
+
+/// The smallest and largest values of an integer type, as C literals
+fn codegen_int_bounds(ty: DefinitionId) -> (String, String) {
+    match builtin_type::int_layout(ty) {
+        Some((bits, true)) => {
+            let min = i128::MIN >> (128 - bits);
+            (codegen_signed_literal(min), codegen_signed_literal(!min))
+        }
+        Some((bits, false)) => ("0".to_string(), codegen_int_literal(u128::MAX >> (128 - bits), false)),
+        None => unimplemented!("Bounds of a type that isn't an integer"),
+    }
+}
+
+/// `+`, `-` or `*` on integers, done by the GCC builtin that gives the result wrapped around and whether it
+/// overflowed.  In a debug build, overflowing panics
+fn codegen_overflow_checked(
+    bc: &BytecodeEngine,
+    builtin: &str,
+    ty: DefinitionId,
+    lhs: &str,
+    rhs: &str,
+    verb: &str,
+) -> String {
+    let check = match bc.profile {
+        Profile::Debug => format!(
+            "if (__builtin_{}_overflow({}, {}, &__result)) overflow_panic(\"{}\");",
+            builtin, lhs, rhs, verb
+        ),
+        Profile::Release => format!("__builtin_{}_overflow({}, {}, &__result);", builtin, lhs, rhs),
+    };
+    format!("({{ {} __result; {} __result; }})", codegen_type(bc, ty, None), check)
+}
+
+/// The C expression for an integer method like `checked_add`, whose result depends on whether the operator
+/// overflowed.  Checked gives an `Option`, and saturating finds the bound it went past from the signs of the operands.
+/// Dividing by zero gives `None` from a checked method, and panics otherwise
+fn codegen_overflowing_op(
+    bc: &BytecodeEngine,
+    op: &Bytecode,
+    overflow: Overflow,
+    ty: DefinitionId,
+    lhs: &str,
+    rhs: &str,
+) -> String {
+    let operand_ty = match op {
+        Bytecode::Add(operand_ty)
+        | Bytecode::Sub(operand_ty)
+        | Bytecode::Mul(operand_ty)
+        | Bytecode::Div(operand_ty)
+        | Bytecode::Rem(operand_ty)
+        | Bytecode::Neg(operand_ty)
+        | Bytecode::Shl(operand_ty)
+        | Bytecode::Shr(operand_ty) => *operand_ty,
+        _ => unimplemented!("{:?} can't overflow", op),
+    };
+    let (bits, signed) = builtin_type::int_layout(operand_ty).expect("Overflowing operator on an integer");
+    let c_ty = codegen_type(bc, operand_ty, None);
+    let (min, max) = codegen_int_bounds(operand_ty);
+    // Dividing overflows only for the minimum of a signed type divided by -1
+    let divisor_overflowed = if signed {
+        format!("__rhs == 0 || (__rhs == -1 && __lhs == {})", min)
+    } else {
+        "__rhs == 0".to_string()
+    };
+    let (rhs_ty, check_divisor, overflowed) = match op {
+        Bytecode::Add(_) => (c_ty.clone(), "", "__builtin_add_overflow(__lhs, __rhs, &__result)".to_string()),
+        Bytecode::Sub(_) | Bytecode::Neg(_) => {
+            (c_ty.clone(), "", "__builtin_sub_overflow(__lhs, __rhs, &__result)".to_string())
+        }
+        Bytecode::Mul(_) => (c_ty.clone(), "", "__builtin_mul_overflow(__lhs, __rhs, &__result)".to_string()),
+        Bytecode::Div(_) => (
+            c_ty.clone(),
+            "if (__rhs == 0) div_panic(\"by zero\"); ",
+            format!("({}) ? (__result = __lhs, 1) : (__result = __lhs / __rhs, 0)", divisor_overflowed),
+        ),
+        // The remainder of the minimum of a signed type divided by -1 wraps around to zero
+        Bytecode::Rem(_) => (
+            c_ty.clone(),
+            "if (__rhs == 0) rem_panic(\"a divisor of zero\"); ",
+            format!("({}) ? (__result = 0, 1) : (__result = __lhs % __rhs, 0)", divisor_overflowed),
+        ),
+        // Shifting by the width of the type or more overflows, and only the low bits of the amount are used
+        _ => (
+            codegen_type(bc, builtin_type::U32, None),
+            "",
+            format!(
+                "__result = {}, __rhs >= {}",
+                codegen_shift(op, &c_ty, signed, "__lhs", &format!("(__rhs & {})", bits - 1)),
+                bits
+            ),
+        ),
+    };
+    let check_divisor = match overflow {
+        Overflow::Checked => "",
+        _ => check_divisor,
+    };
+    let result = match overflow {
+        Overflow::Wrapping => format!("{}; __result", overflowed),
+        Overflow::Checked => match bc.definitions[ty] {
+            Definition::Enum(ref enum_def) => format!(
+                "({}) ? init_variant_{}() : init_variant_{}(__result)",
+                overflowed, enum_def.variants[0], enum_def.variants[1]
+            ),
+            _ => unimplemented!("Checked operation must give an Option"),
+        },
+        Overflow::Saturating => {
+            let bound = match op {
+                Bytecode::Add(_) => format!("(__rhs < 0 ? {} : {})", min, max),
+                Bytecode::Sub(_) | Bytecode::Neg(_) => format!("(__rhs < 0 ? {} : {})", max, min),
+                _ => format!("((__lhs < 0) != (__rhs < 0) ? {} : {})", min, max),
+            };
+            format!("({}) ? {} : __result", overflowed, bound)
+        }
+    };
+    format!(
+        "({{ {ty} __lhs = {}; {} __rhs = {}; {ty} __result; {}{}; }})",
+        lhs,
+        rhs_ty,
+        rhs,
+        check_divisor,
+        result,
+        ty = c_ty
+    )
+}
+
+/// The C expression for an arithmetic, bitwise or shift operator.  Where C leaves a case undefined that Rust doesn't,
+/// like shifting by the width of the type, a helper gives the result Rust would
+fn codegen_binary_op(bc: &BytecodeEngine, op: &Bytecode, lhs: &str, rhs: &str) -> String {
+    let int_ty = match op {
+        Bytecode::Add(ty) | Bytecode::Sub(ty) | Bytecode::Mul(ty) | Bytecode::Div(ty) => {
+            Some(builtin_type::fallback(*ty)).filter(|ty| builtin_type::int_layout(*ty).is_some())
+        }
+        _ => None,
+    };
+    match (op, int_ty) {
+        (Bytecode::Add(_), Some(ty)) => codegen_overflow_checked(bc, "add", ty, lhs, rhs, "add"),
+        (Bytecode::Sub(_), Some(ty)) => codegen_overflow_checked(bc, "sub", ty, lhs, rhs, "subtract"),
+        (Bytecode::Mul(_), Some(ty)) => codegen_overflow_checked(bc, "mul", ty, lhs, rhs, "multiply"),
+        (Bytecode::Div(_), Some(ty)) => {
+            let c_ty = codegen_type(bc, ty, None);
+            match builtin_type::int_layout(ty) {
+                Some((bits, true)) => format!("(({})div_signed({}, {}, {}))", c_ty, lhs, rhs, bits),
+                _ => format!("(({})div_unsigned({}, {}))", c_ty, lhs, rhs),
+            }
+        }
+        (Bytecode::Add(_), None) => format!("({}+{})", lhs, rhs),
+        (Bytecode::Sub(_), None) => format!("({}-{})", lhs, rhs),
+        (Bytecode::Mul(_), None) => format!("({}*{})", lhs, rhs),
+        (Bytecode::Div(_), None) => format!("({}/{})", lhs, rhs),
+        _ => codegen_bitwise_op(bc, op, lhs, rhs),
+    }
+}
+
+/// The C expression for a remainder, bitwise or shift operator
+fn codegen_bitwise_op(bc: &BytecodeEngine, op: &Bytecode, lhs: &str, rhs: &str) -> String {
+    match op {
+        Bytecode::BitAnd => format!("({} & {})", lhs, rhs),
+        Bytecode::BitOr => format!("({} | {})", lhs, rhs),
+        Bytecode::BitXor => format!("({} ^ {})", lhs, rhs),
+        Bytecode::Rem(builtin_type::F32) => format!("fmodf({}, {})", lhs, rhs),
+        Bytecode::Rem(builtin_type::F64) | Bytecode::Rem(builtin_type::UNKNOWN_FLOAT) => {
+            format!("fmod({}, {})", lhs, rhs)
+        }
+        Bytecode::Rem(ty) | Bytecode::Shl(ty) | Bytecode::Shr(ty) => {
+            let (bits, signed) = builtin_type::int_layout(*ty)
+                .unwrap_or_else(|| unimplemented!("{:?} on a value that isn't an integer", op));
+            let c_ty = codegen_type(bc, *ty, None);
+            // In a debug build, shifting by the width of the type or more panics, and in a release build only the
+            // low bits of the amount are used
+            let amount = match (op, bc.profile) {
+                (Bytecode::Shl(_), Profile::Debug) => format!("shift_amount({}, {}, \"left\")", rhs, bits),
+                (_, Profile::Debug) => format!("shift_amount({}, {}, \"right\")", rhs, bits),
+                (_, Profile::Release) => format!("(({}) & {})", rhs, bits - 1),
+            };
+            match (op, signed) {
+                (Bytecode::Rem(_), true) => format!("(({})rem_signed({}, {}, {}))", c_ty, lhs, rhs, bits),
+                (Bytecode::Rem(_), false) => format!("(({})rem_unsigned({}, {}))", c_ty, lhs, rhs),
+                _ => codegen_shift(op, &c_ty, signed, lhs, &amount),
+            }
+        }
+        _ => unimplemented!("{:?} is not a binary operator", op),
+    }
+}
+
+/// The C expression for a shift by an amount already known to be less than the width of the type
+fn codegen_shift(op: &Bytecode, c_ty: &str, signed: bool, lhs: &str, amount: &str) -> String {
+    match (op, signed) {
+        // Shifting is done unsigned, as shifting a signed value into its sign bit is undefined
+        (Bytecode::Shl(_), _) => format!("(({})((unsigned __int128)({}) << {}))", c_ty, lhs, amount),
+        (_, true) => format!("(({})shr_signed({}, {}))", c_ty, lhs, amount),
+        (_, false) => format!("({} >> {})", lhs, amount),
+    }
+}
+
+/// The C expression for an `as` cast.  C leaves converting an out of range float to an integer undefined, so a
+/// helper saturates it the way Rust does
+fn codegen_cast(bc: &BytecodeEngine, from_ty: DefinitionId, to_ty: DefinitionId, val: &str) -> String {
+    let c_ty = codegen_type(bc, to_ty, None);
+    match (from_ty, builtin_type::int_layout(to_ty)) {
+        (builtin_type::VOID_PTR, _) => format!("(({})(size_t)({}))", c_ty, val),
+        (_, Some((bits, signed))) if builtin_type::is_float(from_ty) => format!(
+            "(({})float_to_{}({}, {}))",
+            c_ty,
+            if signed { "signed" } else { "unsigned" },
+            val,
+            bits
+        ),
+        _ if to_ty == builtin_type::VOID_PTR => format!("((void*)(size_t)({}))", val),
+        _ => format!("(({})({}))", c_ty, val),
     }
 }
 
@@ -556,33 +1582,212 @@ fn codegen_c_from_bytecode(bc: &BytecodeEngine) -> String {
 
     cfile.codegen_raw("#include <stdio.h>\n");
     cfile.codegen_raw("#include <stdbool.h>\n");
-    /* 
-    // If we wanted checked add/sub we can use something like this:
-    cfile.codegen_raw("#include <assert.h>\n");
-
-    cfile.codegen_raw("int add32(int lhs, int rhs) {\n");
-    cfile.codegen_raw("int answer = lhs + rhs;\n");
-    cfile.codegen_raw("assert((lhs & 0x8000) == (answer & 0x8000));\n");
-    cfile.codegen_raw("return answer;\n");
+    cfile.codegen_raw("#include <stdlib.h>\n");
+    cfile.codegen_raw("#include <stddef.h>\n");
+    cfile.codegen_raw("#include <string.h>\n");
+    cfile.codegen_raw("#include <math.h>\n");
+
+    // Indexing out of bounds panics, as it does in Rust.  A panic writes out what's been printed so far and exits
+    // with the code Rust gives a panic
+    cfile.codegen_raw("unsigned long long bounds_check(unsigned long long index, unsigned long long len) {\n");
+    cfile.codegen_raw("if (index >= len) {\n");
+    cfile.codegen_raw("fflush(stdout);\n");
+    cfile.codegen_raw(
+        "fprintf(stderr, \"panicked at 'index out of bounds: the len is %llu but the index is %llu'\\n\", len, index);\n",
+    );
+    cfile.codegen_raw("exit(101);\n");
+    cfile.codegen_raw("}\n");
+    cfile.codegen_raw("return index;\n");
     cfile.codegen_raw("}\n");
 
-    cfile.codegen_raw("int sub32(int lhs, int rhs) {\n");
-    cfile.codegen_raw("int answer = lhs - rhs;\n");
-    cfile.codegen_raw("assert((lhs & 0x8000) == (answer & 0x8000));\n");
-    cfile.codegen_raw("return answer;\n");
+    // Integer arithmetic that overflows panics in a debug build
+    cfile.codegen_raw("void overflow_panic(const char* verb) {\n");
+    cfile.codegen_raw("fflush(stdout);\n");
+    cfile.codegen_raw("fprintf(stderr, \"panicked at 'attempt to %s with overflow'\\n\", verb);\n");
+    cfile.codegen_raw("exit(101);\n");
+    cfile.codegen_raw("}\n");
+    cfile.codegen_raw("int shift_amount(__int128 amount, int bits, const char* direction) {\n");
+    cfile.codegen_raw("if (amount < 0 || amount >= bits) {\n");
+    cfile.codegen_raw("fflush(stdout);\n");
+    cfile.codegen_raw("fprintf(stderr, \"panicked at 'attempt to shift %s with overflow'\\n\", direction);\n");
+    cfile.codegen_raw("exit(101);\n");
+    cfile.codegen_raw("}\n");
+    cfile.codegen_raw("return (int)amount;\n");
     cfile.codegen_raw("}\n");
-    */
 
-    let starting_fn_id = bc.scopes[0].definitions["main"];
+    // Dividing by zero, or the smallest value of a signed type by -1, panics whatever the profile
+    cfile.codegen_raw("void div_panic(const char* msg) {\n");
+    cfile.codegen_raw("fflush(stdout);\n");
+    cfile.codegen_raw("fprintf(stderr, \"panicked at 'attempt to divide %s'\\n\", msg);\n");
+    cfile.codegen_raw("exit(101);\n");
+    cfile.codegen_raw("}\n");
+    cfile.codegen_raw("__int128 div_signed(__int128 lhs, __int128 rhs, int bits) {\n");
+    cfile.codegen_raw("if (rhs == 0) div_panic(\"by zero\");\n");
+    cfile.codegen_raw("if (rhs == -1 && lhs == -(__int128)(((unsigned __int128)1 << (bits - 1)) - 1) - 1) {\n");
+    cfile.codegen_raw("div_panic(\"with overflow\");\n");
+    cfile.codegen_raw("}\n");
+    cfile.codegen_raw("return lhs / rhs;\n");
+    cfile.codegen_raw("}\n");
+    cfile.codegen_raw("unsigned __int128 div_unsigned(unsigned __int128 lhs, unsigned __int128 rhs) {\n");
+    cfile.codegen_raw("if (rhs == 0) div_panic(\"by zero\");\n");
+    cfile.codegen_raw("return lhs / rhs;\n");
+    cfile.codegen_raw("}\n");
+
+    // The remainder of dividing by zero, or of the smallest value of a signed type by -1, panics, as it does in Rust
+    cfile.codegen_raw("void rem_panic(const char* msg) {\n");
+    cfile.codegen_raw("fflush(stdout);\n");
+    cfile.codegen_raw("fprintf(stderr, \"panicked at 'attempt to calculate the remainder with %s'\\n\", msg);\n");
+    cfile.codegen_raw("exit(101);\n");
+    cfile.codegen_raw("}\n");
+    cfile.codegen_raw("__int128 rem_signed(__int128 lhs, __int128 rhs, int bits) {\n");
+    cfile.codegen_raw("if (rhs == 0) rem_panic(\"a divisor of zero\");\n");
+    cfile.codegen_raw("if (rhs == -1) {\n");
+    cfile.codegen_raw("__int128 min = -(__int128)(((unsigned __int128)1 << (bits - 1)) - 1) - 1;\n");
+    cfile.codegen_raw("if (lhs == min) rem_panic(\"overflow\");\n");
+    cfile.codegen_raw("return 0;\n");
+    cfile.codegen_raw("}\n");
+    cfile.codegen_raw("return lhs % rhs;\n");
+    cfile.codegen_raw("}\n");
+    cfile.codegen_raw("unsigned __int128 rem_unsigned(unsigned __int128 lhs, unsigned __int128 rhs) {\n");
+    cfile.codegen_raw("if (rhs == 0) rem_panic(\"a divisor of zero\");\n");
+    cfile.codegen_raw("return lhs % rhs;\n");
+    cfile.codegen_raw("}\n");
+
+    // The number of integers in a range, which needs the order of the type to find.  Stepping through one by zero
+    // panics, as it does in Rust
+    cfile.codegen_raw(
+        "unsigned __int128 range_len(unsigned __int128 start, unsigned __int128 end, bool is_signed, bool inclusive) {\n",
+    );
+    cfile.codegen_raw("if (is_signed ? (__int128)start > (__int128)end : start > end) return 0;\n");
+    cfile.codegen_raw("if (start == end) return inclusive ? 1 : 0;\n");
+    cfile.codegen_raw("return end - start + (inclusive ? 1 : 0);\n");
+    cfile.codegen_raw("}\n");
+    cfile.codegen_raw("unsigned __int128 range_step(unsigned __int128 step) {\n");
+    cfile.codegen_raw("if (step == 0) {\n");
+    cfile.codegen_raw("fflush(stdout);\n");
+    cfile.codegen_raw("fprintf(stderr, \"panicked at 'assertion failed: step != 0'\\n\");\n");
+    cfile.codegen_raw("exit(101);\n");
+    cfile.codegen_raw("}\n");
+    cfile.codegen_raw("return step;\n");
+    cfile.codegen_raw("}\n");
+
+    // Shifting a negative value right is implementation-defined in C, but always copies in the sign bit in Rust
+    cfile.codegen_raw("__int128 shr_signed(__int128 lhs, int amount) {\n");
+    cfile.codegen_raw("return lhs < 0 ? ~(~lhs >> amount) : lhs >> amount;\n");
+    cfile.codegen_raw("}\n");
+
+    cfile.codegen_raw("void print_u128(unsigned __int128 val) {\n");
+    cfile.codegen_raw("if (val >= 10) print_u128(val / 10);\n");
+    cfile.codegen_raw("putchar('0' + (int)(val % 10));\n");
+    cfile.codegen_raw("}\n");
+    cfile.codegen_raw("void print_i128(__int128 val) {\n");
+    cfile.codegen_raw("if (val < 0) {\n");
+    cfile.codegen_raw("putchar('-');\n");
+    cfile.codegen_raw("print_u128(-(unsigned __int128)val);\n");
+    cfile.codegen_raw("} else {\n");
+    cfile.codegen_raw("print_u128(val);\n");
+    cfile.codegen_raw("}\n");
+    cfile.codegen_raw("}\n");
+
+    // Casting a float to an integer truncates towards zero, saturating at the bounds of the type, and NaN gives 0
+    cfile.codegen_raw("__int128 float_to_signed(double val, int bits) {\n");
+    cfile.codegen_raw("__int128 max = (__int128)(((unsigned __int128)1 << (bits - 1)) - 1);\n");
+    cfile.codegen_raw("if (isnan(val)) return 0;\n");
+    cfile.codegen_raw("if (val >= ldexp(1.0, bits - 1)) return max;\n");
+    cfile.codegen_raw("if (val <= -ldexp(1.0, bits - 1)) return -max - 1;\n");
+    cfile.codegen_raw("return (__int128)val;\n");
+    cfile.codegen_raw("}\n");
+    cfile.codegen_raw("unsigned __int128 float_to_unsigned(double val, int bits) {\n");
+    cfile.codegen_raw("if (isnan(val) || val <= 0) return 0;\n");
+    cfile.codegen_raw("if (val >= ldexp(1.0, bits)) return bits == 128 ? ~(unsigned __int128)0 : ((unsigned __int128)1 << bits) - 1;\n");
+    cfile.codegen_raw("return (unsigned __int128)val;\n");
+    cfile.codegen_raw("}\n");
+
+    // Floats print like Rust does, with the fewest digits that read back as the same value and no exponent
+    for &(c_ty, name, max_digits, parse) in &[("double", "f64", 17, "strtod"), ("float", "f32", 9, "strtof")] {
+        cfile.codegen_raw(&format!("void print_{}({} val) {{\n", name, c_ty));
+        cfile.codegen_raw("char digits[32];\n");
+        cfile.codegen_raw("int precision = 0;\n");
+        cfile.codegen_raw("if (isnan(val)) { printf(\"NaN\"); return; }\n");
+        cfile.codegen_raw("if (isinf(val)) { printf(val < 0 ? \"-inf\" : \"inf\"); return; }\n");
+        cfile.codegen_raw("do {\n");
+        cfile.codegen_raw("precision++;\n");
+        cfile.codegen_raw("snprintf(digits, sizeof(digits), \"%.*e\", precision - 1, val);\n");
+        cfile.codegen_raw(&format!(
+            "}} while (precision < {} && {}(digits, NULL) != val);\n",
+            max_digits, parse
+        ));
+        cfile.codegen_raw("int decimals = precision - 1 - atoi(strchr(digits, 'e') + 1);\n");
+        cfile.codegen_raw("printf(\"%.*f\", decimals > 0 ? decimals : 0, val);\n");
+        cfile.codegen_raw("}\n");
+    }
+
+    let starting_fn_id = bc.scopes[0].definitions["main"].0;
+
+    // Declare the types up front, so that prototypes can use them in any order
+    for definition_id in 0..bc.definitions.len() {
+        match bc.definitions[definition_id] {
+            Definition::Struct(_) => {
+                cfile.codegen_raw(&format!("struct struct_{};\n", definition_id))
+            }
+            Definition::Enum(_) => cfile.codegen_raw(&format!("struct enum_{};\n", definition_id)),
+            Definition::Tuple(_) => {
+                cfile.codegen_raw(&format!("struct tuple_{};\n", definition_id))
+            }
+            Definition::Array(_, _) => {
+                cfile.codegen_raw(&format!("struct array_{};\n", definition_id))
+            }
+            Definition::DynTrait(_) => {
+                cfile.codegen_raw(&format!("struct dyn_{};\n", definition_id))
+            }
+            Definition::Closure(_) => {
+                cfile.codegen_raw(&format!("struct closure_{};\n", definition_id))
+            }
+            _ => {}
+        }
+    }
+
+    // Function pointer types are named, as C's syntax for them can't be written where the other types are.  Their
+    // params only need the types to be declared
+    for definition_id in 0..bc.definitions.len() {
+        if let Definition::FnPtr(ref param_tys, return_ty) = bc.definitions[definition_id] {
+            if is_generic_definition(bc, definition_id) {
+                continue;
+            }
+            let params: Vec<String> = param_tys.iter().map(|ty| codegen_type(bc, *ty, None)).collect();
+            cfile.codegen_raw(&format!(
+                "typedef {} (*fnptr_{})({});\n",
+                codegen_type(bc, return_ty, None),
+                definition_id,
+                if params.is_empty() { "void".to_string() } else { params.join(", ") }
+            ));
+        }
+    }
+
+    // Then define them, so that types holding other types by value come after them.  Trait objects only hold
+    // pointers, so can go first
+    for definition_id in 0..bc.definitions.len() {
+        if let Definition::DynTrait(_) = bc.definitions[definition_id] {
+            cfile.codegen_raw(&format!(
+                "struct vtable_{};\nstruct dyn_{} {{void* data; const struct vtable_{}* vtable;}};\n",
+                definition_id, definition_id, definition_id
+            ));
+        }
+    }
+    let mut emitted = vec![false; bc.definitions.len()];
+    for definition_id in 0..bc.definitions.len() {
+        if is_generic_definition(bc, definition_id) {
+            continue;
+        }
+        codegen_c_type_definition(&mut cfile, bc, definition_id, &mut emitted);
+    }
 
     //TODO: FIXME: just make two separate strings and concat them rather than iterating twice
     for definition_id in 0..bc.definitions.len() {
         // Skip generic functions during codegen, instead we'll output the instantitions when
         // we get to them
-        if let Definition::Fun(ref fun) = bc.definitions[definition_id] {
-            if fun.is_generic() {
-                continue;
-            }
+        if is_generic_definition(bc, definition_id) {
+            continue;
         }
         codegen_c_prototype(&mut cfile, bc, definition_id, starting_fn_id, None);
     }
@@ -590,10 +1795,8 @@ fn codegen_c_from_bytecode(bc: &BytecodeEngine) -> String {
     for definition_id in 0..bc.definitions.len() {
         // Skip generic functions during codegen, instead we'll output the instantitions when
         // we get to them
-        if let Definition::Fun(ref fun) = bc.definitions[definition_id] {
-            if fun.is_generic() {
-                continue;
-            }
+        if is_generic_definition(bc, definition_id) {
+            continue;
         }
         codegen_c_body(&mut cfile, bc, definition_id, starting_fn_id, None)
     }
@@ -627,19 +1830,20 @@ pub fn compile_bytecode(bc: &BytecodeEngine, output_fname: &str) -> ::std::io::R
     compile_file(path)
 }
 
+// The C that's output uses GCC and Clang extensions, like the `__builtin_*_overflow` checks, statement
+// expressions and `__int128`, so it's built with clang rather than MSVC's cl
 #[cfg(windows)]
 fn compile_file(path: ::std::path::PathBuf) -> ::std::io::Result<String> {
     let start = PreciseTime::now();
     use std::process::Command;
 
     let output_fname = String::new() + path.with_extension("exe").to_str().unwrap();
-    let output_objname = String::new() + path.with_extension("obj").to_str().unwrap();
 
-    let output = Command::new(r"cl.exe")
-        .arg("/w")
-        .arg(&format!("/Fe{}", output_fname))
-        .arg(&format!("/Fo{}", output_objname))
+    let output = Command::new(r"clang")
+        .arg("-w")
         .arg(path)
+        .arg("-o")
+        .arg(&output_fname)
         .output()?;
     let end = PreciseTime::now();
     let duration = start
@@ -682,6 +1886,7 @@ fn compile_file(path: ::std::path::PathBuf) -> ::std::io::Result<String> {
         .arg(path)
         .arg("-o")
         .arg(&output_fname)
+        .arg("-lm")
         .output()
         .expect("failed to execute compiler");
     let end = PreciseTime::now();
@@ -1,7 +1,10 @@
-use bytecode::{builtin_type, Bytecode, BytecodeEngine, Definition, Fun};
+use bytecode::{builtin_type, Bytecode, BytecodeEngine, Closure, Definition, DefinitionId, ForRange, Fun, Overflow,
+               Profile};
 use std::any::Any;
+use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::fmt;
+use std::ops::{Add, Div, Mul, Rem, Sub};
 use std::os::raw::c_void;
 use std::ptr;
 
@@ -11,13 +14,31 @@ pub enum Value {
     U32(u32),
     I64(i64),
     I32(i32),
-    UnknownInt(i32),
+    U8(u8),
+    U16(u16),
+    U128(u128),
+    Usize(usize),
+    I8(i8),
+    I16(i16),
+    I128(i128),
+    Isize(isize),
+    UnknownInt(i128), // wide enough for any literal, though it becomes an i32 if nothing else decides its type
+    F32(f32),
+    F64(f64),
+    UnknownFloat(f64), // becomes an f64 if nothing else decides its type
     Bool(bool),
     Error,
     Void,
-    Object(HashMap<String, usize>),
+    Object(HashMap<String, usize>), // the slot of each field
     RawPtr(*const c_void),
-    Reference(usize), // reference into the value stack
+    Reference(usize), // reference to a slot
+    Dyn(usize, DefinitionId), // trait object: the slot it refers to and its vtable
+    Enum(DefinitionId, Vec<usize>), // the variant and the slot of each of its fields
+    Tuple(Vec<usize>),              // the slot of each element
+    Array(Vec<usize>),              // the slot of each element
+    Range(u128, u128, u128, u128),  // a `for` loop's range: how many integers it's given, out of how many, the first and the step
+    Closure(DefinitionId, Vec<usize>), // a closure: its type, and the slot of each variable it captured
+    Fn(DefinitionId),               // a function pointer, to the given function
 }
 
 impl fmt::Display for Value {
@@ -30,13 +51,31 @@ impl fmt::Display for Value {
                 Value::U32(x) => x.to_string(),
                 Value::I64(x) => x.to_string(),
                 Value::I32(x) => x.to_string(),
+                Value::U8(x) => x.to_string(),
+                Value::U16(x) => x.to_string(),
+                Value::U128(x) => x.to_string(),
+                Value::Usize(x) => x.to_string(),
+                Value::I8(x) => x.to_string(),
+                Value::I16(x) => x.to_string(),
+                Value::I128(x) => x.to_string(),
+                Value::Isize(x) => x.to_string(),
                 Value::UnknownInt(x) => x.to_string(),
+                Value::F32(x) => x.to_string(),
+                Value::F64(x) => x.to_string(),
+                Value::UnknownFloat(x) => x.to_string(),
                 Value::Bool(b) => b.to_string(),
                 Value::Error => "error".to_string(),
                 Value::Void => "void".to_string(),
                 Value::Object(dict) => format!("object: {:?}", dict),
                 Value::RawPtr(_p) => "{raw ptr}".to_string(),
                 Value::Reference(pos) => format!("reference: {}", pos),
+                Value::Dyn(pos, vtable_id) => format!("trait object: {} (vtable {})", pos, vtable_id),
+                Value::Enum(variant_id, fields) => format!("variant {}: {:?}", variant_id, fields),
+                Value::Tuple(elements) => format!("tuple: {:?}", elements),
+                Value::Array(elements) => format!("array: {:?}", elements),
+                Value::Range(index, count, _, _) => format!("range: {} of {}", index, count),
+                Value::Closure(closure_ty, captured) => format!("closure {}: {:?}", closure_ty, captured),
+                Value::Fn(fn_id) => format!("fn {}", fn_id),
             }
         )
     }
@@ -49,7 +88,18 @@ impl Value {
             Value::U32(x) => Box::new(x),
             Value::I64(x) => Box::new(x),
             Value::I32(x) => Box::new(x),
-            Value::UnknownInt(x) => Box::new(x),
+            Value::U8(x) => Box::new(x),
+            Value::U16(x) => Box::new(x),
+            Value::U128(x) => Box::new(x),
+            Value::Usize(x) => Box::new(x),
+            Value::I8(x) => Box::new(x),
+            Value::I16(x) => Box::new(x),
+            Value::I128(x) => Box::new(x),
+            Value::Isize(x) => Box::new(x),
+            Value::UnknownInt(x) => Box::new(x as i32),
+            Value::F32(x) => Box::new(x),
+            Value::F64(x) => Box::new(x),
+            Value::UnknownFloat(x) => Box::new(x),
             Value::Bool(x) => Box::new(x),
             Value::RawPtr(x) => Box::new(x),
             _ => unimplemented!("Currently don't support conversion for this type"),
@@ -65,6 +115,26 @@ impl Value {
             Value::I64(*val)
         } else if let Some(val) = b.downcast_ref::<i32>() {
             Value::I32(*val)
+        } else if let Some(val) = b.downcast_ref::<u8>() {
+            Value::U8(*val)
+        } else if let Some(val) = b.downcast_ref::<u16>() {
+            Value::U16(*val)
+        } else if let Some(val) = b.downcast_ref::<u128>() {
+            Value::U128(*val)
+        } else if let Some(val) = b.downcast_ref::<usize>() {
+            Value::Usize(*val)
+        } else if let Some(val) = b.downcast_ref::<i8>() {
+            Value::I8(*val)
+        } else if let Some(val) = b.downcast_ref::<i16>() {
+            Value::I16(*val)
+        } else if let Some(val) = b.downcast_ref::<i128>() {
+            Value::I128(*val)
+        } else if let Some(val) = b.downcast_ref::<isize>() {
+            Value::Isize(*val)
+        } else if let Some(val) = b.downcast_ref::<f32>() {
+            Value::F32(*val)
+        } else if let Some(val) = b.downcast_ref::<f64>() {
+            Value::F64(*val)
         } else if let Some(val) = b.downcast_ref::<bool>() {
             Value::Bool(*val)
         } else if let Some(val) = b.downcast_ref::<*const c_void>() {
@@ -77,8 +147,29 @@ impl Value {
     }
 }
 
+/// An error that stops evaluation, the way a panic stops a Rust program
+#[derive(Debug, Clone)]
+pub struct Panic {
+    pub message: String,
+}
+
+impl fmt::Display for Panic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "panicked at '{}'", self.message)
+    }
+}
+
+/// Evaluates bytecode directly.
+/// Values being computed are kept on the value stack.  Variables and struct fields are kept in slots, which references
+/// point into.  A function's slots are freed when it returns, apart from those still reachable from its result or from
+/// the slots of its callers it assigned to, which are moved down to where its frame began.
 pub struct EvalEngine {
     pub value_stack: Vec<Value>,
+    pub slots: Vec<Value>,
+    /// The first slot of the function being evaluated
+    frame_base: usize,
+    /// Slots below the frame of the function that assigned to them, whose new values may point into that frame
+    escaped: Vec<usize>,
     extern_fns: HashMap<String, Box<Fn(&mut Vec<Value>) -> Value>>,
     pub debug_capture: Option<String>,
 }
@@ -87,6 +178,9 @@ impl EvalEngine {
     pub fn new() -> EvalEngine {
         EvalEngine {
             value_stack: vec![],
+            slots: vec![],
+            frame_base: 0,
+            escaped: vec![],
             extern_fns: HashMap::new(),
             debug_capture: None,
         }
@@ -97,156 +191,219 @@ impl EvalEngine {
         bc: &BytecodeEngine,
         bytecode: &Vec<Bytecode>,
         var_lookup: &mut HashMap<usize, usize>,
-    ) -> Value {
+    ) -> Result<Value, Panic> {
         let bytecode_len = bytecode.len();
         let mut idx = 0;
         while idx < bytecode_len {
             let code = &bytecode[idx];
             match code {
                 Bytecode::ReturnVoid => {
-                    return Value::Void;
+                    return Ok(Value::Void);
                 }
                 Bytecode::ReturnLastStackValue => match self.value_stack.pop() {
-                    Some(s) => return s,
-                    _ => return Value::Error,
+                    Some(s) => return Ok(s),
+                    _ => return Ok(Value::Error),
                 },
-                Bytecode::As(type_id) => match self.value_stack.pop() {
-                    Some(Value::UnknownInt(val)) => match *type_id {
-                        builtin_type::I32 => {
-                            self.value_stack.push(Value::I32(val as i32));
-                        }
-                        builtin_type::I64 => {
-                            self.value_stack.push(Value::I64(val as i64));
-                        }
-                        builtin_type::U32 => {
-                            self.value_stack.push(Value::U32(val as u32));
-                        }
-                        builtin_type::U64 => {
-                            self.value_stack.push(Value::U64(val as u64));
-                        }
-                        _ => {
-                            unimplemented!("Trying to convert {{unknown int}} to non-integer type");
-                        }
-                    },
-                    Some(x) => self.value_stack.push(x),
+                Bytecode::As(type_id) | Bytecode::Cast(_, type_id) => match self.value_stack.pop() {
+                    Some(x) => self.value_stack.push(cast(x, *type_id)),
                     None => unimplemented!("Can not do a type conversion of missing value"),
                 },
-                Bytecode::Neg => match self.value_stack.pop() {
-                    Some(Value::I64(val)) => {
-                        self.value_stack.push(Value::I64(-val));
+                Bytecode::Neg(ty) => match self.value_stack.pop().map(|val| typed_int(val, *ty)) {
+                    Some(Value::F32(val)) => {
+                        self.value_stack.push(Value::F32(-val));
                     }
-                    Some(Value::I32(val)) => {
-                        self.value_stack.push(Value::I32(-val));
+                    Some(Value::F64(val)) => {
+                        self.value_stack.push(Value::F64(-val));
                     }
-                    Some(Value::UnknownInt(val)) => {
-                        self.value_stack.push(Value::UnknownInt(-val));
+                    Some(Value::UnknownFloat(val)) => {
+                        self.value_stack.push(Value::UnknownFloat(-val));
                     }
-                    x => unimplemented!("Can't negate values of {:?}", x),
-                },
-                Bytecode::Add => match (self.value_stack.pop(), self.value_stack.pop()) {
-                    (Some(Value::U64(rhs)), Some(Value::U64(lhs))) => {
-                        self.value_stack.push(Value::U64(lhs + rhs));
+                    Some(val) => {
+                        // Negating is subtracting from zero, which overflows at the minimum of a signed type
+                        let zero = with_int_type(0, &val);
+                        let result = overflowing_op(&Bytecode::Sub(builtin_type::UNKNOWN_INT), zero, val);
+                        self.value_stack.push(overflow_checked(result, "negate", bc.profile)?);
                     }
-                    (Some(Value::U32(rhs)), Some(Value::U32(lhs))) => {
-                        self.value_stack.push(Value::U32(lhs + rhs));
-                    }
-                    (Some(Value::I64(rhs)), Some(Value::I64(lhs))) => {
-                        self.value_stack.push(Value::I64(lhs + rhs));
-                    }
-                    (Some(Value::I32(rhs)), Some(Value::I32(lhs))) => {
-                        self.value_stack.push(Value::I32(lhs + rhs));
-                    }
-                    (Some(Value::UnknownInt(rhs)), Some(Value::UnknownInt(lhs))) => {
-                        self.value_stack.push(Value::UnknownInt(lhs + rhs));
-                    }
-                    (x, y) => unimplemented!("Can't add values of {:?} and {:?}", x, y),
+                    None => unimplemented!("Can't negate a missing value"),
                 },
-                Bytecode::Sub => match (self.value_stack.pop(), self.value_stack.pop()) {
-                    (Some(Value::U64(rhs)), Some(Value::U64(lhs))) => {
-                        self.value_stack.push(Value::U64(lhs - rhs));
-                    }
-                    (Some(Value::U32(rhs)), Some(Value::U32(lhs))) => {
-                        self.value_stack.push(Value::U32(lhs - rhs));
-                    }
-                    (Some(Value::I64(rhs)), Some(Value::I64(lhs))) => {
-                        self.value_stack.push(Value::I64(lhs - rhs));
-                    }
-                    (Some(Value::I32(rhs)), Some(Value::I32(lhs))) => {
-                        self.value_stack.push(Value::I32(lhs - rhs));
+                Bytecode::Not(_) => match self.value_stack.pop() {
+                    Some(Value::Bool(val)) => {
+                        self.value_stack.push(Value::Bool(!val));
                     }
-                    (Some(Value::UnknownInt(rhs)), Some(Value::UnknownInt(lhs))) => {
-                        self.value_stack.push(Value::UnknownInt(lhs - rhs));
-                    }
-                    (x, y) => unimplemented!("Can't add values of {:?} and {:?}", x, y),
+                    // Flipping the bits of the i128 gives the right bits once truncated back to the integer's type
+                    Some(val) => match int_value(&val) {
+                        Some(int) => self.value_stack.push(with_int_type(!int, &val)),
+                        None => unimplemented!("Can't apply `!` to values of {:?}", val),
+                    },
+                    None => unimplemented!("Can't apply `!` to a missing value"),
                 },
-                Bytecode::Mul => match (self.value_stack.pop(), self.value_stack.pop()) {
-                    (Some(Value::U64(rhs)), Some(Value::U64(lhs))) => {
-                        self.value_stack.push(Value::U64(lhs * rhs));
-                    }
-                    (Some(Value::U32(rhs)), Some(Value::U32(lhs))) => {
-                        self.value_stack.push(Value::U32(lhs * rhs));
-                    }
-                    (Some(Value::I64(rhs)), Some(Value::I64(lhs))) => {
-                        self.value_stack.push(Value::I64(lhs * rhs));
+                Bytecode::Add(_)
+                | Bytecode::Sub(_)
+                | Bytecode::Mul(_)
+                | Bytecode::Div(_)
+                | Bytecode::Rem(_)
+                | Bytecode::BitAnd
+                | Bytecode::BitOr
+                | Bytecode::BitXor
+                | Bytecode::Shl(_)
+                | Bytecode::Shr(_) => match (self.value_stack.pop(), self.value_stack.pop()) {
+                    (Some(rhs), Some(lhs)) => {
+                        let result = binary_op(code, lhs, rhs, bc.profile)?;
+                        self.value_stack.push(result);
                     }
-                    (Some(Value::I32(rhs)), Some(Value::I32(lhs))) => {
-                        self.value_stack.push(Value::I32(lhs * rhs));
-                    }
-                    (Some(Value::UnknownInt(rhs)), Some(Value::UnknownInt(lhs))) => {
-                        self.value_stack.push(Value::UnknownInt(lhs * rhs));
-                    }
-                    (x, y) => unimplemented!("Can't add values of {:?} and {:?}", x, y),
+                    _ => unimplemented!("Operator missing its operands"),
                 },
-                Bytecode::Div => match (self.value_stack.pop(), self.value_stack.pop()) {
-                    (Some(Value::U64(rhs)), Some(Value::U64(lhs))) => {
-                        self.value_stack.push(Value::U64(lhs / rhs));
-                    }
-                    (Some(Value::U32(rhs)), Some(Value::U32(lhs))) => {
-                        self.value_stack.push(Value::U32(lhs / rhs));
-                    }
-                    (Some(Value::I64(rhs)), Some(Value::I64(lhs))) => {
-                        self.value_stack.push(Value::I64(lhs / rhs));
-                    }
-                    (Some(Value::I32(rhs)), Some(Value::I32(lhs))) => {
-                        self.value_stack.push(Value::I32(lhs / rhs));
-                    }
-                    (Some(Value::UnknownInt(rhs)), Some(Value::UnknownInt(lhs))) => {
-                        self.value_stack.push(Value::UnknownInt(lhs / rhs));
+                Bytecode::OverflowingOp(op, overflow, ty) => {
+                    let operand_ty = match **op {
+                        Bytecode::Add(ty)
+                        | Bytecode::Sub(ty)
+                        | Bytecode::Mul(ty)
+                        | Bytecode::Div(ty)
+                        | Bytecode::Rem(ty)
+                        | Bytecode::Neg(ty)
+                        | Bytecode::Shl(ty)
+                        | Bytecode::Shr(ty) => ty,
+                        _ => unimplemented!("{:?} can't overflow", op),
+                    };
+                    // The amount to shift by keeps its own type, and negating subtracts the one operand from zero
+                    let rhs = self.value_stack.pop().map(|rhs| match **op {
+                        Bytecode::Shl(_) | Bytecode::Shr(_) => rhs,
+                        _ => typed_int(rhs, operand_ty),
+                    });
+                    let lhs = match **op {
+                        Bytecode::Neg(_) => rhs.as_ref().map(|rhs| with_int_type(0, rhs)),
+                        _ => self.value_stack.pop().map(|lhs| typed_int(lhs, operand_ty)),
+                    };
+                    let (lhs, rhs) = match (lhs, rhs) {
+                        (Some(lhs), Some(rhs)) => (lhs, rhs),
+                        _ => unimplemented!("Operator missing its operands"),
+                    };
+                    let (result, overflowed) = match overflowing_method_op(op, lhs.clone(), rhs.clone()) {
+                        Some(result) => result,
+                        // Only the checked methods don't panic on a divisor of zero, giving None instead
+                        None if *overflow == Overflow::Checked => (lhs.clone(), true),
+                        None => {
+                            let message = match **op {
+                                Bytecode::Div(_) => "attempt to divide by zero",
+                                _ => "attempt to calculate the remainder with a divisor of zero",
+                            };
+                            return Err(Panic {
+                                message: message.to_string(),
+                            });
+                        }
+                    };
+                    let value = match overflow {
+                        Overflow::Wrapping => result,
+                        Overflow::Checked => {
+                            // The result is an `Option`, whose `None` is its first variant and `Some` its second
+                            let variants = match bc.definitions[*ty] {
+                                Definition::Enum(ref enum_def) => enum_def.variants.clone(),
+                                _ => unimplemented!("Checked operation must give an Option"),
+                            };
+                            if overflowed {
+                                Value::Enum(variants[0], vec![])
+                            } else {
+                                let slot = self.alloc_slot(result);
+                                Value::Enum(variants[1], vec![slot])
+                            }
+                        }
+                        Overflow::Saturating if overflowed => saturated(op, &lhs, &rhs),
+                        Overflow::Saturating => result,
+                    };
+                    self.value_stack.push(value);
+                }
+                Bytecode::Lt
+                | Bytecode::Le
+                | Bytecode::Gt
+                | Bytecode::Ge
+                | Bytecode::Eq
+                | Bytecode::Ne => {
+                    let rhs = self.value_stack.pop();
+                    let lhs = self.value_stack.pop();
+                    // There's no ordering when either side is NaN, so only `!=` is true
+                    let ordering = match (lhs, rhs) {
+                        (Some(Value::Bool(lhs)), Some(Value::Bool(rhs))) => Some(lhs.cmp(&rhs)),
+                        (Some(lhs), Some(rhs)) => match unify_numbers(lhs, rhs) {
+                            // The only integers too big to compare as i128s
+                            (Value::U128(lhs), Value::U128(rhs)) => Some(lhs.cmp(&rhs)),
+                            (Value::F32(lhs), Value::F32(rhs)) => lhs.partial_cmp(&rhs),
+                            (Value::F64(lhs), Value::F64(rhs))
+                            | (Value::UnknownFloat(lhs), Value::UnknownFloat(rhs)) => lhs.partial_cmp(&rhs),
+                            (lhs, rhs) => match (int_value(&lhs), int_value(&rhs)) {
+                                (Some(lhs), Some(rhs)) => Some(lhs.cmp(&rhs)),
+                                _ => unimplemented!("Can't compare values of {:?} and {:?}", lhs, rhs),
+                            },
+                        },
+                        (x, y) => unimplemented!("Can't compare values of {:?} and {:?}", x, y),
+                    };
+                    let result = match code {
+                        Bytecode::Lt => ordering == Some(Ordering::Less),
+                        Bytecode::Le => matches!(ordering, Some(Ordering::Less) | Some(Ordering::Equal)),
+                        Bytecode::Gt => ordering == Some(Ordering::Greater),
+                        Bytecode::Ge => matches!(ordering, Some(Ordering::Greater) | Some(Ordering::Equal)),
+                        Bytecode::Eq => ordering == Some(Ordering::Equal),
+                        _ => ordering != Some(Ordering::Equal),
+                    };
+                    self.value_stack.push(Value::Bool(result));
+                }
+                Bytecode::IsVariant(variant_id) => match self.value_stack.pop() {
+                    Some(Value::Enum(actual_id, _)) => {
+                        self.value_stack.push(Value::Bool(actual_id == *variant_id));
                     }
-                    (x, y) => unimplemented!("Can't add values of {:?} and {:?}", x, y),
+                    _ => unimplemented!("Variant test of a value that isn't an enum"),
                 },
-                Bytecode::Lt => match (self.value_stack.pop(), self.value_stack.pop()) {
-                    (Some(Value::U64(rhs)), Some(Value::U64(lhs))) => {
-                        self.value_stack.push(Value::Bool(lhs < rhs));
-                    }
-                    (Some(Value::U32(rhs)), Some(Value::U32(lhs))) => {
-                        self.value_stack.push(Value::Bool(lhs < rhs));
-                    }
-                    (Some(Value::I64(rhs)), Some(Value::I64(lhs))) => {
-                        self.value_stack.push(Value::Bool(lhs < rhs));
-                    }
-                    (Some(Value::I32(rhs)), Some(Value::I32(lhs))) => {
-                        self.value_stack.push(Value::Bool(lhs < rhs));
-                    }
-                    (Some(Value::UnknownInt(rhs)), Some(Value::UnknownInt(lhs))) => {
-                        self.value_stack.push(Value::Bool(lhs < rhs));
+                Bytecode::VariantField(_, field_idx) => match self.value_stack.pop() {
+                    Some(Value::Enum(_, ref fields)) => {
+                        let value = self.copy_slot(fields[*field_idx]);
+                        self.value_stack.push(value);
                     }
-                    (x, y) => unimplemented!("Can't add values of {:?} and {:?}", x, y),
+                    _ => unimplemented!("Variant field of a value that isn't an enum"),
                 },
+                Bytecode::Unreachable(_) => {
+                    unimplemented!("Reached code that should be unreachable: no match arm matched");
+                }
                 Bytecode::Dot(field) => match self.value_stack.pop() {
                     Some(Value::Object(obj)) => {
                         if obj.contains_key(field) {
-                            self.value_stack.push(self.value_stack[obj[field]].clone())
+                            let value = self.copy_slot(obj[field]);
+                            self.value_stack.push(value)
                         } else {
                             unimplemented!("Can not find field {} in object {:#?}", field, obj);
                         }
                     }
+                    Some(Value::Tuple(elements)) => {
+                        let value = self.copy_slot(elements[tuple_index(field)]);
+                        self.value_stack.push(value)
+                    }
                     _ => {
                         unimplemented!("Dot access on value that isn't an object");
                     }
                 },
+                Bytecode::Ref(_, _) => {
+                    // The lvalue is already a reference to its slot
+                }
+                Bytecode::BoxNew(_) => match self.value_stack.pop() {
+                    Some(value) => {
+                        let slot = self.alloc_slot(value);
+                        self.value_stack.push(Value::Reference(slot));
+                    }
+                    None => unimplemented!("Box of missing value"),
+                },
+                Bytecode::MakeDyn(vtable_id) => match self.value_stack.pop() {
+                    Some(Value::Reference(slot)) => {
+                        self.value_stack.push(Value::Dyn(slot, *vtable_id));
+                    }
+                    _ => unimplemented!("Trait object made from a value that isn't a reference"),
+                },
+                Bytecode::Deref => match self.value_stack.pop() {
+                    Some(Value::Reference(slot)) => {
+                        let value = self.copy_slot(slot);
+                        self.value_stack.push(value);
+                    }
+                    _ => unimplemented!("Dereference of a value that isn't a reference"),
+                },
                 Bytecode::LValueDot(field) => match self.value_stack.pop() {
-                    Some(Value::Reference(slot)) => match self.value_stack[slot] {
+                    Some(Value::Reference(slot)) => match self.slots[slot] {
                         Value::Object(ref obj) => {
                             if obj.contains_key(field) {
                                 self.value_stack.push(Value::Reference(obj[field]))
@@ -254,12 +411,54 @@ impl EvalEngine {
 
                             }
                         }
+                        Value::Tuple(ref elements) => {
+                            self.value_stack.push(Value::Reference(elements[tuple_index(field)]))
+                        }
                         _ => unimplemented!("Field access of non-object"),
                     },
                     _ => {
                         unimplemented!("Field access into unknown value");
                     }
                 },
+                Bytecode::ArrayRepeat(array_ty) => match (self.value_stack.pop(), &bc.definitions[*array_ty]) {
+                    (Some(value), Definition::Array(_, len)) => {
+                        let mut elements = vec![];
+                        for _ in 0..*len {
+                            let element = self.copy_value(value.clone());
+                            elements.push(self.alloc_slot(element));
+                        }
+                        self.value_stack.push(Value::Array(elements));
+                    }
+                    _ => unimplemented!("Repeat of a missing value"),
+                },
+                Bytecode::Index(_) => match (self.value_stack.pop(), self.value_stack.pop()) {
+                    (Some(index), Some(Value::Array(ref elements))) => {
+                        let slot = self.element_slot(elements, &index)?;
+                        let value = self.copy_slot(slot);
+                        self.value_stack.push(value);
+                    }
+                    _ => unimplemented!("Indexing into a value that isn't an array"),
+                },
+                Bytecode::LValueIndex(_) => match (self.value_stack.pop(), self.value_stack.pop()) {
+                    (Some(index), Some(Value::Reference(slot))) => {
+                        let elements = match self.slots[slot] {
+                            Value::Array(ref elements) => elements.clone(),
+                            _ => unimplemented!("Indexing into a value that isn't an array"),
+                        };
+                        let slot = self.element_slot(&elements, &index)?;
+                        self.value_stack.push(Value::Reference(slot));
+                    }
+                    _ => unimplemented!("Indexing into unknown value"),
+                },
+                Bytecode::LValueVariantField(_, field_idx) => match self.value_stack.pop() {
+                    Some(Value::Reference(slot)) => match self.slots[slot] {
+                        Value::Enum(_, ref fields) => {
+                            self.value_stack.push(Value::Reference(fields[*field_idx]))
+                        }
+                        _ => unimplemented!("Variant field of a value that isn't an enum"),
+                    },
+                    _ => unimplemented!("Variant field access into unknown value"),
+                },
                 Bytecode::PushU64(val) => {
                     self.value_stack.push(Value::U64(*val));
                 }
@@ -272,9 +471,42 @@ impl EvalEngine {
                 Bytecode::PushI32(val) => {
                     self.value_stack.push(Value::I32(*val));
                 }
+                Bytecode::PushU8(val) => {
+                    self.value_stack.push(Value::U8(*val));
+                }
+                Bytecode::PushU16(val) => {
+                    self.value_stack.push(Value::U16(*val));
+                }
+                Bytecode::PushU128(val) => {
+                    self.value_stack.push(Value::U128(*val));
+                }
+                Bytecode::PushUsize(val) => {
+                    self.value_stack.push(Value::Usize(*val));
+                }
+                Bytecode::PushI8(val) => {
+                    self.value_stack.push(Value::I8(*val));
+                }
+                Bytecode::PushI16(val) => {
+                    self.value_stack.push(Value::I16(*val));
+                }
+                Bytecode::PushI128(val) => {
+                    self.value_stack.push(Value::I128(*val));
+                }
+                Bytecode::PushIsize(val) => {
+                    self.value_stack.push(Value::Isize(*val));
+                }
                 Bytecode::PushUnknownInt(val) => {
                     self.value_stack.push(Value::UnknownInt(*val));
                 }
+                Bytecode::PushF32(val) => {
+                    self.value_stack.push(Value::F32(*val));
+                }
+                Bytecode::PushF64(val) => {
+                    self.value_stack.push(Value::F64(*val));
+                }
+                Bytecode::PushUnknownFloat(val) => {
+                    self.value_stack.push(Value::UnknownFloat(*val));
+                }
                 Bytecode::PushBool(val) => {
                     self.value_stack.push(Value::Bool(*val));
                 }
@@ -299,7 +531,7 @@ impl EvalEngine {
                     continue;
                 }
                 Bytecode::EndIf(_) => {}
-                Bytecode::BeginWhile => {}
+                Bytecode::BeginWhile(_) => {}
                 Bytecode::WhileCond(offset) => match self.value_stack.pop() {
                     Some(Value::Bool(cond)) => {
                         if !cond {
@@ -309,29 +541,75 @@ impl EvalEngine {
                     }
                     _ => unimplemented!("Expected boolean condition for if"),
                 },
-                Bytecode::EndWhile(offset) => {
+                Bytecode::EndWhile(offset, _) | Bytecode::Continue(offset, _) => {
                     idx -= offset;
                     continue;
                 }
-                Bytecode::VarDecl(var_id) => {
-                    var_lookup.insert(*var_id, self.value_stack.len() - 1);
+                Bytecode::BeginFor(var_id, range) => match (
+                    self.value_stack.pop(),
+                    self.value_stack.pop(),
+                    self.value_stack.pop(),
+                ) {
+                    (Some(step), Some(end), Some(start)) => {
+                        let state = range_state(&start, &end, &step, range)?;
+                        let slot = self.alloc_slot(state);
+                        var_lookup.insert(*var_id, slot);
+                    }
+                    _ => unimplemented!("Range missing its start, end or step"),
+                },
+                Bytecode::ForNext(offset, var_id, ty) => {
+                    let slot = var_lookup[var_id];
+                    match self.slots[slot] {
+                        Value::Range(index, count, first, step) if index < count => {
+                            self.slots[slot] = Value::Range(index + 1, count, first, step);
+                            let val = first.wrapping_add(index.wrapping_mul(step)) as i128;
+                            self.value_stack.push(int_of_type(val, *ty).unwrap_or(Value::UnknownInt(val)));
+                        }
+                        _ => {
+                            idx += offset + 1; // Eval will also want to skip the EndWhile
+                            continue;
+                        }
+                    }
+                }
+                Bytecode::Break(offset, _, _) => {
+                    // Any value the loop gives is left on the stack
+                    idx += offset;
+                    continue;
                 }
+                Bytecode::VarDecl(var_id) => match self.value_stack.pop() {
+                    Some(value) => {
+                        let slot = self.alloc_slot(value);
+                        var_lookup.insert(*var_id, slot);
+                    }
+                    None => unimplemented!("Variable declaration missing its value"),
+                },
                 Bytecode::VarDeclUninit(var_id) => {
-                    //push a dummy value on the stack to give us a slot for a value later
-                    self.value_stack.push(Value::Void);
-                    var_lookup.insert(*var_id, self.value_stack.len() - 1);
+                    //use a dummy value to give us a slot for a value later
+                    let slot = self.alloc_slot(Value::Void);
+                    var_lookup.insert(*var_id, slot);
                 }
-                Bytecode::Var(var_id) => {
+                Bytecode::Var(var_id, _) => {
                     let pos: usize = var_lookup[var_id];
-                    self.value_stack.push(self.value_stack[pos].clone());
+                    let value = self.copy_slot(pos);
+                    self.value_stack.push(value);
                 }
-                Bytecode::LValueVar(var_id) => {
+                Bytecode::LValueVar(var_id, _) => {
                     let pos: usize = var_lookup[var_id];
                     self.value_stack.push(Value::Reference(pos));
                 }
                 Bytecode::Assign => match (self.value_stack.pop(), self.value_stack.pop()) {
                     (Some(Value::Reference(slot)), Some(rhs)) => {
-                        self.value_stack[slot] = rhs;
+                        if slot < self.frame_base {
+                            self.escaped.push(slot);
+                        }
+                        self.slots[slot] = rhs;
+                    }
+                    _ => unimplemented!("Assignment missing right-hand side value"),
+                },
+                Bytecode::CompoundAssign(op, _) => match (self.value_stack.pop(), self.value_stack.pop()) {
+                    (Some(Value::Reference(slot)), Some(rhs)) => {
+                        let lhs = self.slots[slot].clone();
+                        self.slots[slot] = binary_op(op, lhs, rhs, bc.profile)?;
                     }
                     _ => unimplemented!("Assignment missing right-hand side value"),
                 },
@@ -341,28 +619,158 @@ impl EvalEngine {
                             let result = self.extern_fns[ex_name](&mut self.value_stack);
                             self.value_stack.push(result);
                         } else {
-                            let result = self.eval_fn_bytecode(bc, target_fun);
+                            let result = self.eval_fn_bytecode(bc, target_fun, HashMap::new())?;
                             self.value_stack.push(result);
                         }
                     } else if let Definition::Struct(ref st) = bc.definitions[*definition_id] {
                         let mut hash = HashMap::new();
-                        let mut offset = 1;
                         for field in st.fields.iter().rev() {
-                            hash.insert(field.0.clone(), self.value_stack.len() - offset);
-                            offset += 1;
+                            match self.value_stack.pop() {
+                                Some(value) => {
+                                    let slot = self.alloc_slot(value);
+                                    hash.insert(field.0.clone(), slot);
+                                }
+                                None => unimplemented!("Struct missing value for field {}", field.0),
+                            }
                         }
                         self.value_stack.push(Value::Object(hash))
+                    } else if let Definition::Variant(ref variant) = bc.definitions[*definition_id] {
+                        let mut fields = vec![];
+                        for _ in &variant.fields {
+                            match self.value_stack.pop() {
+                                Some(value) => fields.push(self.alloc_slot(value)),
+                                None => unimplemented!("Variant {} missing a field", variant.name),
+                            }
+                        }
+                        fields.reverse();
+                        self.value_stack.push(Value::Enum(*definition_id, fields))
+                    } else if let Definition::Tuple(ref element_tys) = bc.definitions[*definition_id] {
+                        let mut elements = vec![];
+                        for _ in element_tys {
+                            match self.value_stack.pop() {
+                                Some(value) => elements.push(self.alloc_slot(value)),
+                                None => unimplemented!("Tuple missing an element"),
+                            }
+                        }
+                        elements.reverse();
+                        self.value_stack.push(Value::Tuple(elements))
+                    } else if let Definition::Array(_, len) = bc.definitions[*definition_id] {
+                        let mut elements = vec![];
+                        for _ in 0..len {
+                            match self.value_stack.pop() {
+                                Some(value) => elements.push(self.alloc_slot(value)),
+                                None => unimplemented!("Array missing an element"),
+                            }
+                        }
+                        elements.reverse();
+                        self.value_stack.push(Value::Array(elements))
                     } else if let Definition::InstantiatedFun(orig_id, _) =
                         bc.definitions[*definition_id]
                     {
                         if let Definition::Fun(ref target_fun) = bc.definitions[orig_id] {
-                            let result = self.eval_fn_bytecode(bc, target_fun);
+                            let result = self.eval_fn_bytecode(bc, target_fun, HashMap::new())?;
                             self.value_stack.push(result);
                         }
                     } else {
                         unimplemented!("Eval of unprocessed function");
                     }
                 }
+                Bytecode::CallDyn(dyn_ty, method_index) => {
+                    // The trait object is the first arg, under the rest.  Its vtable has the method to call, which
+                    // is given a plain reference as `self`
+                    let num_params = match bc.definitions[*dyn_ty] {
+                        Definition::DynTrait(ref dyn_trait) => dyn_trait.methods[*method_index].0.len(),
+                        _ => unimplemented!("Dynamic call on a type that isn't a trait object"),
+                    };
+                    let receiver_pos = self.value_stack.len() - num_params;
+                    let method_id = match self.value_stack[receiver_pos] {
+                        Value::Dyn(slot, vtable_id) => {
+                            self.value_stack[receiver_pos] = Value::Reference(slot);
+                            match bc.definitions[vtable_id] {
+                                Definition::Vtable(ref vtable) => vtable.methods[*method_index],
+                                _ => unimplemented!("Trait object without a vtable"),
+                            }
+                        }
+                        _ => unimplemented!("Dynamic call without a trait object"),
+                    };
+
+                    if let Definition::Fun(ref target_fun) = bc.definitions[method_id] {
+                        let result = self.eval_fn_bytecode(bc, target_fun, HashMap::new())?;
+                        self.value_stack.push(result);
+                    } else {
+                        unimplemented!("Eval of unprocessed function");
+                    }
+                }
+                Bytecode::MakeClosure(closure_ty, _) => match bc.definitions[*closure_ty] {
+                    Definition::Closure(ref closure) => {
+                        // A borrowed variable is shared with the closure, while one moved in is copied into it
+                        let mut captured = vec![];
+                        for capture in &closure.captures {
+                            let slot = var_lookup[&capture.var_id];
+                            if closure.by_move {
+                                let value = self.copy_slot(slot);
+                                captured.push(self.alloc_slot(value));
+                            } else {
+                                captured.push(slot);
+                            }
+                        }
+                        self.value_stack.push(Value::Closure(*closure_ty, captured));
+                    }
+                    _ => unimplemented!("Closure made of a type that isn't a closure"),
+                },
+                Bytecode::CallClosure(closure_ty) => match bc.definitions[*closure_ty] {
+                    Definition::Closure(Closure {
+                        fun: Some(ref closure_fun),
+                        ref captures,
+                        ..
+                    }) => {
+                        // The closure is referred to under the args.  Its body finds what it captured in the slots
+                        // the closure holds
+                        let closure_pos = self.value_stack.len() - closure_fun.params.len() - 1;
+                        let captured = match self.value_stack.remove(closure_pos) {
+                            Value::Reference(slot) => match self.slots[slot] {
+                                Value::Closure(_, ref captured) => captured.clone(),
+                                _ => unimplemented!("Closure call on a value that isn't a closure"),
+                            },
+                            _ => unimplemented!("Closure call without a reference to the closure"),
+                        };
+                        let mut var_lookup = HashMap::new();
+                        for (capture, slot) in captures.iter().zip(captured) {
+                            var_lookup.insert(capture.inner_var_id, slot);
+                        }
+                        let result = self.eval_fn_bytecode(bc, closure_fun, var_lookup)?;
+                        self.value_stack.push(result);
+                    }
+                    _ => unimplemented!("Eval of a closure that wasn't converted"),
+                },
+                Bytecode::PushFn(fn_id) => self.value_stack.push(Value::Fn(*fn_id)),
+                Bytecode::CallPtr(fn_ptr_ty) => {
+                    // The function pointer is under the args
+                    let num_params = match bc.definitions[*fn_ptr_ty] {
+                        Definition::FnPtr(ref param_tys, _) => param_tys.len(),
+                        _ => unimplemented!("Call through a type that isn't a function pointer"),
+                    };
+                    let fn_pos = self.value_stack.len() - num_params - 1;
+                    let fn_id = match self.value_stack.remove(fn_pos) {
+                        Value::Fn(fn_id) => fn_id,
+                        _ => unimplemented!("Call through a value that isn't a function pointer"),
+                    };
+                    if let Definition::Fun(ref target_fun) = bc.definitions[fn_id] {
+                        let result = match target_fun.extern_name {
+                            Some(ref ex_name) => self.extern_fns[ex_name](&mut self.value_stack),
+                            None => self.eval_fn_bytecode(bc, target_fun, HashMap::new())?,
+                        };
+                        self.value_stack.push(result);
+                    } else {
+                        unimplemented!("Eval of unprocessed function");
+                    }
+                }
+                Bytecode::CallTraitMethod(_, _, _, _) => {
+                    unimplemented!("Eval of a trait method call that wasn't resolved by instantiation")
+                }
+                Bytecode::Pop => {
+                    self.value_stack.pop();
+                }
                 Bytecode::DebugPrint(_) => match self.value_stack.pop() {
                     Some(s) => match self.debug_capture {
                         Some(ref mut debug_log) => {
@@ -379,29 +787,145 @@ impl EvalEngine {
             idx += 1;
         }
 
-        Value::Void
+        Ok(Value::Void)
     }
 
-    fn eval_fn_bytecode(&mut self, bc: &BytecodeEngine, fun: &Fun) -> Value {
-        let mut var_lookup: HashMap<usize, usize> = HashMap::new();
+    fn alloc_slot(&mut self, value: Value) -> usize {
+        self.slots.push(value);
+        self.slots.len() - 1
+    }
 
-        let mut param_offset = fun.params.len();
-        for param in &fun.params {
-            var_lookup.insert(param.var_id, self.value_stack.len() - param_offset);
-            param_offset -= 1;
+    /// Reads the value in a slot.  As values are copied or moved when they're read, the copy gets its own slots
+    /// for anything it holds, so that changing one doesn't change the other
+    fn copy_slot(&mut self, slot: usize) -> Value {
+        let value = self.slots[slot].clone();
+        self.copy_value(value)
+    }
+
+    fn copy_value(&mut self, value: Value) -> Value {
+        match value {
+            Value::Object(fields) => {
+                let mut copied = HashMap::new();
+                for (name, slot) in fields {
+                    let field = self.copy_slot(slot);
+                    copied.insert(name, self.alloc_slot(field));
+                }
+                Value::Object(copied)
+            }
+            Value::Enum(variant_id, fields) => Value::Enum(variant_id, self.copy_slots(&fields)),
+            Value::Tuple(elements) => Value::Tuple(self.copy_slots(&elements)),
+            Value::Array(elements) => Value::Array(self.copy_slots(&elements)),
+            value => value,
         }
+    }
 
-        self.eval_block_bytecode(bc, &fun.bytecode, &mut var_lookup)
+    fn copy_slots(&mut self, slots: &[usize]) -> Vec<usize> {
+        let mut copied = vec![];
+        for slot in slots {
+            let value = self.copy_slot(*slot);
+            copied.push(self.alloc_slot(value));
+        }
+        copied
+    }
+
+    /// The slot of the array element at the index, or a panic if the index is out of bounds
+    fn element_slot(&self, elements: &[usize], index: &Value) -> Result<usize, Panic> {
+        let index = match int_value(index) {
+            Some(index) => index,
+            None => unimplemented!("Indexing with a value of {:?}", index),
+        };
+        if index < 0 || index >= elements.len() as i128 {
+            return Err(Panic {
+                message: format!(
+                    "index out of bounds: the len is {} but the index is {}",
+                    elements.len(),
+                    index
+                ),
+            });
+        }
+        Ok(elements[index as usize])
+    }
+
+    /// Calls the function with the args on top of the stack.  Its variables start out in the given slots, which
+    /// is how a closure's body finds what it captured
+    fn eval_fn_bytecode(
+        &mut self,
+        bc: &BytecodeEngine,
+        fun: &Fun,
+        mut var_lookup: HashMap<usize, usize>,
+    ) -> Result<Value, Panic> {
+        let caller_frame_base = self.frame_base;
+        let escaped_height = self.escaped.len();
+        self.frame_base = self.slots.len();
+
+        for param in fun.params.iter().rev() {
+            match self.value_stack.pop() {
+                Some(value) => {
+                    let slot = self.alloc_slot(value);
+                    var_lookup.insert(param.var_id, slot);
+                }
+                None => unimplemented!("Missing argument for parameter {}", param.name),
+            }
+        }
+
+        // Anything the function leaves on the value stack (like the values of expression statements) is dropped
+        // when it returns
+        let stack_height = self.value_stack.len();
+        let result = self.eval_block_bytecode(bc, &fun.bytecode, &mut var_lookup)?;
+        self.value_stack.truncate(stack_height);
+
+        let result = self.free_frame(result, escaped_height);
+        self.frame_base = caller_frame_base;
+
+        Ok(result)
+    }
+
+    /// Frees the slots of the frame being returned from.  The slots still reachable from its result, or from the
+    /// slots below it that it assigned to, are moved down to where the frame began, and what refers to them updated.
+    fn free_frame(&mut self, result: Value, escaped_height: usize) -> Value {
+        let base = self.frame_base;
+        let mut escaped: Vec<usize> = self.escaped.split_off(escaped_height);
+        escaped.retain(|slot| *slot < base);
+        escaped.sort_unstable();
+        escaped.dedup();
+
+        // Number the reachable slots in the order they're found, which is the order they'll be kept in
+        let mut moved_to = HashMap::new();
+        let mut kept = vec![];
+        let mut pending = slots_of(&result);
+        for slot in &escaped {
+            pending.extend(slots_of(&self.slots[*slot]));
+        }
+        while let Some(slot) = pending.pop() {
+            if slot >= base && !moved_to.contains_key(&slot) {
+                moved_to.insert(slot, base + kept.len());
+                kept.push(slot);
+                pending.extend(slots_of(&self.slots[slot]));
+            }
+        }
+
+        let kept: Vec<Value> = kept.iter().map(|slot| with_moved_slots(&self.slots[*slot], &moved_to)).collect();
+        self.slots.truncate(base);
+        self.slots.extend(kept);
+        for slot in &escaped {
+            self.slots[*slot] = with_moved_slots(&self.slots[*slot], &moved_to);
+        }
+
+        // The caller's caller may need to move what those slots point to as well
+        self.escaped.extend(escaped);
+
+        with_moved_slots(&result, &moved_to)
     }
 
     /// Begin evaluating the bytecode starting at the given function name.  Optionally, capture the debug output for later use.
-    pub fn eval_program(&mut self, bc: &BytecodeEngine, starting_fn_name: &str) -> Value {
+    /// If the program panics, evaluation stops and the panic is returned.
+    pub fn eval_program(&mut self, bc: &BytecodeEngine, starting_fn_name: &str) -> Result<Value, Panic> {
         // begin evaluating with the first function
         // We assume scope 0 is the file root scope of the starting file, where will find the main
 
         let fun = bc.get_fn(starting_fn_name, 0);
 
-        self.eval_fn_bytecode(bc, &fun)
+        self.eval_fn_bytecode(bc, &fun, HashMap::new())
     }
 
     pub fn register_extern_fn_0<Ret: Any>(
@@ -572,3 +1096,460 @@ impl EvalEngine {
         self.extern_fns.insert(name.to_string(), fun);
     }
 }
+
+/// Applies an operator to two integers of the same type, giving an integer of that type
+macro_rules! int_op {
+    ($verb:expr, $lhs:expr, $rhs:expr, |$l:ident, $r:ident| $result:expr) => {
+        match ($lhs, $rhs) {
+            (Value::U64($l), Value::U64($r)) => Value::U64($result),
+            (Value::U32($l), Value::U32($r)) => Value::U32($result),
+            (Value::I64($l), Value::I64($r)) => Value::I64($result),
+            (Value::I32($l), Value::I32($r)) => Value::I32($result),
+            (Value::U8($l), Value::U8($r)) => Value::U8($result),
+            (Value::U16($l), Value::U16($r)) => Value::U16($result),
+            (Value::U128($l), Value::U128($r)) => Value::U128($result),
+            (Value::Usize($l), Value::Usize($r)) => Value::Usize($result),
+            (Value::I8($l), Value::I8($r)) => Value::I8($result),
+            (Value::I16($l), Value::I16($r)) => Value::I16($result),
+            (Value::I128($l), Value::I128($r)) => Value::I128($result),
+            (Value::Isize($l), Value::Isize($r)) => Value::Isize($result),
+            (Value::UnknownInt($l), Value::UnknownInt($r)) => Value::UnknownInt($result),
+            (x, y) => unimplemented!("Can't {} values of {:?} and {:?}", $verb, x, y),
+        }
+    };
+}
+
+/// Applies an arithmetic operator to two integers of the same type, giving the result wrapped around at the bounds of
+/// the type and whether it had to be.  An integer without a type is an i32, as it would be in Rust
+macro_rules! overflowing_int_op {
+    ($verb:expr, $lhs:expr, $rhs:expr, $method:ident) => {
+        match ($lhs, $rhs) {
+            (Value::U64(lhs), Value::U64(rhs)) => with_overflow(Value::U64, lhs.$method(rhs)),
+            (Value::U32(lhs), Value::U32(rhs)) => with_overflow(Value::U32, lhs.$method(rhs)),
+            (Value::I64(lhs), Value::I64(rhs)) => with_overflow(Value::I64, lhs.$method(rhs)),
+            (Value::I32(lhs), Value::I32(rhs)) => with_overflow(Value::I32, lhs.$method(rhs)),
+            (Value::U8(lhs), Value::U8(rhs)) => with_overflow(Value::U8, lhs.$method(rhs)),
+            (Value::U16(lhs), Value::U16(rhs)) => with_overflow(Value::U16, lhs.$method(rhs)),
+            (Value::U128(lhs), Value::U128(rhs)) => with_overflow(Value::U128, lhs.$method(rhs)),
+            (Value::Usize(lhs), Value::Usize(rhs)) => with_overflow(Value::Usize, lhs.$method(rhs)),
+            (Value::I8(lhs), Value::I8(rhs)) => with_overflow(Value::I8, lhs.$method(rhs)),
+            (Value::I16(lhs), Value::I16(rhs)) => with_overflow(Value::I16, lhs.$method(rhs)),
+            (Value::I128(lhs), Value::I128(rhs)) => with_overflow(Value::I128, lhs.$method(rhs)),
+            (Value::Isize(lhs), Value::Isize(rhs)) => with_overflow(Value::Isize, lhs.$method(rhs)),
+            (Value::UnknownInt(lhs), Value::UnknownInt(rhs)) => {
+                let (result, overflowed) = (lhs as i32).$method(rhs as i32);
+                (Value::UnknownInt(i128::from(result)), overflowed)
+            }
+            (x, y) => unimplemented!("Can't {} values of {:?} and {:?}", $verb, x, y),
+        }
+    };
+}
+
+fn with_overflow<T>(make: fn(T) -> Value, (result, overflowed): (T, bool)) -> (Value, bool) {
+    (make(result), overflowed)
+}
+
+/// Applies `+`, `-`, `*` or `/` to two integers of the same type, wrapping around on overflow.  Dividing by zero
+/// must be ruled out first
+fn overflowing_op(op: &Bytecode, lhs: Value, rhs: Value) -> (Value, bool) {
+    match op {
+        Bytecode::Add(_) => overflowing_int_op!("add", lhs, rhs, overflowing_add),
+        Bytecode::Sub(_) => overflowing_int_op!("subtract", lhs, rhs, overflowing_sub),
+        Bytecode::Mul(_) => overflowing_int_op!("multiply", lhs, rhs, overflowing_mul),
+        Bytecode::Div(_) => overflowing_int_op!("divide", lhs, rhs, overflowing_div),
+        _ => unimplemented!("{:?} can't overflow", op),
+    }
+}
+
+/// Applies the operator of an integer method like `wrapping_div` or `checked_shl`, giving the result wrapped around at
+/// the bounds of the type and whether it had to be.  Dividing by zero has no result, so gives None
+fn overflowing_method_op(op: &Bytecode, lhs: Value, rhs: Value) -> Option<(Value, bool)> {
+    match op {
+        Bytecode::Div(_) | Bytecode::Rem(_) if int_value(&rhs) == Some(0) => None,
+        Bytecode::Rem(_) => Some(overflowing_int_op!("calculate the remainder of", lhs, rhs, overflowing_rem)),
+        // Negating is subtracting from zero, which is already on the left
+        Bytecode::Neg(ty) => Some(overflowing_op(&Bytecode::Sub(*ty), lhs, rhs)),
+        // Shifting by the width of the type or more overflows, and only the low bits of the amount are used
+        Bytecode::Shl(_) | Bytecode::Shr(_) => {
+            let amount = match int_value(&rhs) {
+                Some(amount) => amount,
+                None => unimplemented!("Can't shift by a value of {:?}", rhs),
+            };
+            let bits = builtin_type::int_layout(int_type(&lhs)).map_or(32, |(bits, _)| bits);
+            Some((shifted(op, lhs, amount as u32), amount >= i128::from(bits)))
+        }
+        _ => Some(overflowing_op(op, lhs, rhs)),
+    }
+}
+
+/// The result of an operation that may have overflowed, which panics in a debug build and wraps around in a
+/// release one
+fn overflow_checked((result, overflowed): (Value, bool), verb: &str, profile: Profile) -> Result<Value, Panic> {
+    if overflowed && profile == Profile::Debug {
+        Err(Panic {
+            message: format!("attempt to {} with overflow", verb),
+        })
+    } else {
+        Ok(result)
+    }
+}
+
+/// The bound of the integer type that an operation overflowing past it stops at, found from the signs of its
+/// operands
+fn saturated(op: &Bytecode, lhs: &Value, rhs: &Value) -> Value {
+    let ty = int_type(lhs);
+    let (min, max) = match builtin_type::int_layout(ty) {
+        Some((bits, true)) => {
+            let min = i128::MIN >> (128 - bits);
+            (min, !min)
+        }
+        // All ones, once truncated to the type
+        _ => (0, -1),
+    };
+    let is_negative = |value: &Value| int_value(value).is_some_and(|val| val < 0);
+    let bound = match op {
+        Bytecode::Add(_) if is_negative(rhs) => min,
+        Bytecode::Sub(_) | Bytecode::Neg(_) if !is_negative(rhs) => min,
+        // Dividing overflows only for the minimum of a signed type divided by -1, which gives the maximum
+        Bytecode::Mul(_) if is_negative(lhs) != is_negative(rhs) => min,
+        _ => max,
+    };
+    with_int_type(bound, lhs)
+}
+
+/// Applies an arithmetic, bitwise or shift operator to two values
+fn binary_op(op: &Bytecode, lhs: Value, rhs: Value, profile: Profile) -> Result<Value, Panic> {
+    let (lhs, rhs) = match op {
+        Bytecode::Shl(ty) | Bytecode::Shr(ty) => (typed_int(lhs, *ty), rhs),
+        Bytecode::Add(ty) | Bytecode::Sub(ty) | Bytecode::Mul(ty) | Bytecode::Div(ty) | Bytecode::Rem(ty) => {
+            unify_numbers(typed_int(lhs, *ty), typed_int(rhs, *ty))
+        }
+        _ => unify_numbers(lhs, rhs),
+    };
+    if let Some(result) = float_op(op, &lhs, &rhs) {
+        return Ok(result);
+    }
+    let result = match op {
+        Bytecode::Add(_) => overflow_checked(overflowing_op(op, lhs, rhs), "add", profile)?,
+        Bytecode::Sub(_) => overflow_checked(overflowing_op(op, lhs, rhs), "subtract", profile)?,
+        Bytecode::Mul(_) => overflow_checked(overflowing_op(op, lhs, rhs), "multiply", profile)?,
+        Bytecode::Div(_) => {
+            if int_value(&rhs) == Some(0) {
+                return Err(Panic {
+                    message: "attempt to divide by zero".to_string(),
+                });
+            }
+            // Only the minimum of a signed type divided by -1 overflows, which panics whatever the profile
+            overflow_checked(overflowing_op(op, lhs, rhs), "divide", Profile::Debug)?
+        }
+        Bytecode::Rem(_) => {
+            if int_value(&rhs) == Some(0) {
+                return Err(Panic {
+                    message: "attempt to calculate the remainder with a divisor of zero".to_string(),
+                });
+            }
+            int_op!("calculate the remainder of", lhs, rhs, |lhs, rhs| match lhs.checked_rem(rhs) {
+                Some(result) => result,
+                None => {
+                    return Err(Panic {
+                        message: "attempt to calculate the remainder with overflow".to_string(),
+                    })
+                }
+            })
+        }
+        Bytecode::BitAnd | Bytecode::BitOr | Bytecode::BitXor => match (lhs, rhs) {
+            (Value::Bool(lhs), Value::Bool(rhs)) => Value::Bool(match op {
+                Bytecode::BitAnd => lhs & rhs,
+                Bytecode::BitOr => lhs | rhs,
+                _ => lhs ^ rhs,
+            }),
+            (lhs, rhs) => match op {
+                Bytecode::BitAnd => int_op!("and", lhs, rhs, |lhs, rhs| lhs & rhs),
+                Bytecode::BitOr => int_op!("or", lhs, rhs, |lhs, rhs| lhs | rhs),
+                _ => int_op!("xor", lhs, rhs, |lhs, rhs| lhs ^ rhs),
+            },
+        },
+        Bytecode::Shl(_) | Bytecode::Shr(_) => {
+            // Only the low bits of the amount are used, so shifting by the width of the type or more wraps around
+            // in a release build
+            let amount = match int_value(&rhs) {
+                Some(amount) => amount,
+                None => unimplemented!("Can't shift by a value of {:?}", rhs),
+            };
+            let bits = builtin_type::int_layout(int_type(&lhs)).map_or(32, |(bits, _)| bits);
+            if profile == Profile::Debug && (amount < 0 || amount >= i128::from(bits)) {
+                let direction = match op {
+                    Bytecode::Shl(_) => "left",
+                    _ => "right",
+                };
+                return Err(Panic {
+                    message: format!("attempt to shift {} with overflow", direction),
+                });
+            }
+            shifted(op, lhs, amount as u32)
+        }
+        _ => unimplemented!("{:?} is not a binary operator", op),
+    };
+    Ok(result)
+}
+
+/// Shifts an integer left or right, using only the low bits of the amount so that shifting by the width of the type or
+/// more wraps around.  Shifting a signed value right copies its sign bit in, as the shift is arithmetic
+fn shifted(op: &Bytecode, lhs: Value, amount: u32) -> Value {
+    match (op, lhs) {
+        (Bytecode::Shl(_), Value::U64(lhs)) => Value::U64(lhs.wrapping_shl(amount)),
+        (Bytecode::Shl(_), Value::U32(lhs)) => Value::U32(lhs.wrapping_shl(amount)),
+        (Bytecode::Shl(_), Value::I64(lhs)) => Value::I64(lhs.wrapping_shl(amount)),
+        (Bytecode::Shl(_), Value::I32(lhs)) => Value::I32(lhs.wrapping_shl(amount)),
+        (Bytecode::Shl(_), Value::U8(lhs)) => Value::U8(lhs.wrapping_shl(amount)),
+        (Bytecode::Shl(_), Value::U16(lhs)) => Value::U16(lhs.wrapping_shl(amount)),
+        (Bytecode::Shl(_), Value::U128(lhs)) => Value::U128(lhs.wrapping_shl(amount)),
+        (Bytecode::Shl(_), Value::Usize(lhs)) => Value::Usize(lhs.wrapping_shl(amount)),
+        (Bytecode::Shl(_), Value::I8(lhs)) => Value::I8(lhs.wrapping_shl(amount)),
+        (Bytecode::Shl(_), Value::I16(lhs)) => Value::I16(lhs.wrapping_shl(amount)),
+        (Bytecode::Shl(_), Value::I128(lhs)) => Value::I128(lhs.wrapping_shl(amount)),
+        (Bytecode::Shl(_), Value::Isize(lhs)) => Value::Isize(lhs.wrapping_shl(amount)),
+        (Bytecode::Shl(_), Value::UnknownInt(lhs)) => Value::UnknownInt(i128::from((lhs as i32).wrapping_shl(amount))),
+        (_, Value::U64(lhs)) => Value::U64(lhs.wrapping_shr(amount)),
+        (_, Value::U32(lhs)) => Value::U32(lhs.wrapping_shr(amount)),
+        (_, Value::I64(lhs)) => Value::I64(lhs.wrapping_shr(amount)),
+        (_, Value::I32(lhs)) => Value::I32(lhs.wrapping_shr(amount)),
+        (_, Value::U8(lhs)) => Value::U8(lhs.wrapping_shr(amount)),
+        (_, Value::U16(lhs)) => Value::U16(lhs.wrapping_shr(amount)),
+        (_, Value::U128(lhs)) => Value::U128(lhs.wrapping_shr(amount)),
+        (_, Value::Usize(lhs)) => Value::Usize(lhs.wrapping_shr(amount)),
+        (_, Value::I8(lhs)) => Value::I8(lhs.wrapping_shr(amount)),
+        (_, Value::I16(lhs)) => Value::I16(lhs.wrapping_shr(amount)),
+        (_, Value::I128(lhs)) => Value::I128(lhs.wrapping_shr(amount)),
+        (_, Value::Isize(lhs)) => Value::Isize(lhs.wrapping_shr(amount)),
+        (_, Value::UnknownInt(lhs)) => Value::UnknownInt(i128::from((lhs as i32).wrapping_shr(amount))),
+        (_, lhs) => unimplemented!("Can't shift a value of {:?}", lhs),
+    }
+}
+
+/// The slots a value points to
+fn slots_of(value: &Value) -> Vec<usize> {
+    match value {
+        Value::Object(fields) => fields.values().cloned().collect(),
+        Value::Reference(slot) | Value::Dyn(slot, _) => vec![*slot],
+        Value::Enum(_, slots) | Value::Tuple(slots) | Value::Array(slots) | Value::Closure(_, slots) => slots.clone(),
+        _ => vec![],
+    }
+}
+
+/// The value, pointing to where the slots it points to have been moved
+fn with_moved_slots(value: &Value, moved_to: &HashMap<usize, usize>) -> Value {
+    let moved = |slot: &usize| *moved_to.get(slot).unwrap_or(slot);
+    let moved_all = |slots: &[usize]| slots.iter().map(moved).collect();
+    match value {
+        Value::Object(fields) => Value::Object(fields.iter().map(|(name, slot)| (name.clone(), moved(slot))).collect()),
+        Value::Reference(slot) => Value::Reference(moved(slot)),
+        Value::Dyn(slot, vtable_id) => Value::Dyn(moved(slot), *vtable_id),
+        Value::Enum(variant_id, slots) => Value::Enum(*variant_id, moved_all(slots)),
+        Value::Tuple(slots) => Value::Tuple(moved_all(slots)),
+        Value::Array(slots) => Value::Array(moved_all(slots)),
+        Value::Closure(closure_id, slots) => Value::Closure(*closure_id, moved_all(slots)),
+        value => value.clone(),
+    }
+}
+
+/// Applies an arithmetic operator to two floats of the same type, or gives None if they aren't floats
+fn float_op(op: &Bytecode, lhs: &Value, rhs: &Value) -> Option<Value> {
+    Some(match (lhs, rhs) {
+        (Value::F32(lhs), Value::F32(rhs)) => Value::F32(float_arithmetic(op, *lhs, *rhs)),
+        (Value::F64(lhs), Value::F64(rhs)) => Value::F64(float_arithmetic(op, *lhs, *rhs)),
+        (Value::UnknownFloat(lhs), Value::UnknownFloat(rhs)) => Value::UnknownFloat(float_arithmetic(op, *lhs, *rhs)),
+        _ => return None,
+    })
+}
+
+/// Floats never panic: dividing by zero gives an infinity or NaN, as it does in Rust
+fn float_arithmetic<T>(op: &Bytecode, lhs: T, rhs: T) -> T
+where
+    T: Add<Output = T> + Sub<Output = T> + Mul<Output = T> + Div<Output = T> + Rem<Output = T>,
+{
+    match op {
+        Bytecode::Add(_) => lhs + rhs,
+        Bytecode::Sub(_) => lhs - rhs,
+        Bytecode::Mul(_) => lhs * rhs,
+        Bytecode::Div(_) => lhs / rhs,
+        Bytecode::Rem(_) => lhs % rhs,
+        _ => unimplemented!("Can't apply {:?} to floats", op),
+    }
+}
+
+/// Gives a number without a type the type of the number it's used with, as the typechecker has already allowed
+fn unify_numbers(lhs: Value, rhs: Value) -> (Value, Value) {
+    match (lhs, rhs) {
+        (Value::UnknownInt(lhs), rhs) => (with_int_type(lhs, &rhs), rhs),
+        (lhs, Value::UnknownInt(rhs)) => {
+            let rhs = with_int_type(rhs, &lhs);
+            (lhs, rhs)
+        }
+        (Value::UnknownFloat(lhs), Value::F32(rhs)) => (Value::F32(lhs as f32), Value::F32(rhs)),
+        (Value::F32(lhs), Value::UnknownFloat(rhs)) => (Value::F32(lhs), Value::F32(rhs as f32)),
+        (Value::UnknownFloat(lhs), Value::F64(rhs)) => (Value::F64(lhs), Value::F64(rhs)),
+        (Value::F64(lhs), Value::UnknownFloat(rhs)) => (Value::F64(lhs), Value::F64(rhs)),
+        (lhs, rhs) => (lhs, rhs),
+    }
+}
+
+/// Gives an integer without a type the type an operator was checked with, so that it overflows where the typed
+/// value would.  Anything else is left as it is
+fn typed_int(value: Value, ty: DefinitionId) -> Value {
+    match value {
+        Value::UnknownInt(val) => int_of_type(val, ty).unwrap_or(value),
+        value => value,
+    }
+}
+
+/// The state of a `for` loop going through a range, before it's given any integers.  Integers of every type are
+/// worked with as u128s that wrap around, which gives the right integer once truncated to its type
+fn range_state(start: &Value, end: &Value, step: &Value, range: &ForRange) -> Result<Value, Panic> {
+    let step = match int_value(step) {
+        Some(0) => {
+            return Err(Panic {
+                message: "assertion failed: step != 0".to_string(),
+            })
+        }
+        Some(step) => step as u128,
+        None => unimplemented!("Can't step through a range by {:?}", step),
+    };
+    let (start, end) = match (int_value(start), int_value(end)) {
+        (Some(start), Some(end)) => (start as u128, end as u128),
+        _ => unimplemented!("Can't go through a range from {:?} to {:?}", start, end),
+    };
+
+    let ordering = match builtin_type::int_layout(range.ty) {
+        Some((_, false)) => start.cmp(&end),
+        _ => (start as i128).cmp(&(end as i128)),
+    };
+    let len = match ordering {
+        Ordering::Less => end.wrapping_sub(start).wrapping_add(range.inclusive as u128),
+        Ordering::Equal if range.inclusive => 1,
+        _ => 0,
+    };
+    let count = if len == 0 { 0 } else { (len - 1) / step + 1 };
+    Ok(if range.rev {
+        Value::Range(0, count, start.wrapping_add(len).wrapping_sub(1), step.wrapping_neg())
+    } else {
+        Value::Range(0, count, start, step)
+    })
+}
+
+/// The integer as a value of the same type as the given value, if that's an integer
+fn with_int_type(val: i128, like: &Value) -> Value {
+    int_of_type(val, int_type(like)).unwrap_or(Value::UnknownInt(val))
+}
+
+/// The type of an integer value, where anything else is treated as an integer without a type
+fn int_type(value: &Value) -> DefinitionId {
+    match value {
+        Value::U64(_) => builtin_type::U64,
+        Value::U32(_) => builtin_type::U32,
+        Value::I64(_) => builtin_type::I64,
+        Value::I32(_) => builtin_type::I32,
+        Value::U8(_) => builtin_type::U8,
+        Value::U16(_) => builtin_type::U16,
+        Value::U128(_) => builtin_type::U128,
+        Value::Usize(_) => builtin_type::USIZE,
+        Value::I8(_) => builtin_type::I8,
+        Value::I16(_) => builtin_type::I16,
+        Value::I128(_) => builtin_type::I128,
+        Value::Isize(_) => builtin_type::ISIZE,
+        _ => builtin_type::UNKNOWN_INT,
+    }
+}
+
+/// The integer as a value of the given integer type
+fn int_of_type(val: i128, ty: DefinitionId) -> Option<Value> {
+    match ty {
+        builtin_type::U64 => Some(Value::U64(val as u64)),
+        builtin_type::U32 => Some(Value::U32(val as u32)),
+        builtin_type::I64 => Some(Value::I64(val as i64)),
+        builtin_type::I32 => Some(Value::I32(val as i32)),
+        builtin_type::U8 => Some(Value::U8(val as u8)),
+        builtin_type::U16 => Some(Value::U16(val as u16)),
+        builtin_type::U128 => Some(Value::U128(val as u128)),
+        builtin_type::USIZE => Some(Value::Usize(val as usize)),
+        builtin_type::I8 => Some(Value::I8(val as i8)),
+        builtin_type::I16 => Some(Value::I16(val as i16)),
+        builtin_type::I128 => Some(Value::I128(val)),
+        builtin_type::ISIZE => Some(Value::Isize(val as isize)),
+        builtin_type::UNKNOWN_INT => Some(Value::UnknownInt(val)),
+        _ => None,
+    }
+}
+
+/// Converts a number with `as` to a value of the given builtin type, if it can be
+macro_rules! num_cast {
+    ($val:expr, $ty:expr) => {
+        match $ty {
+            builtin_type::U64 => Some(Value::U64($val as u64)),
+            builtin_type::U32 => Some(Value::U32($val as u32)),
+            builtin_type::I64 => Some(Value::I64($val as i64)),
+            builtin_type::I32 => Some(Value::I32($val as i32)),
+            builtin_type::U8 => Some(Value::U8($val as u8)),
+            builtin_type::U16 => Some(Value::U16($val as u16)),
+            builtin_type::U128 => Some(Value::U128($val as u128)),
+            builtin_type::USIZE => Some(Value::Usize($val as usize)),
+            builtin_type::I8 => Some(Value::I8($val as i8)),
+            builtin_type::I16 => Some(Value::I16($val as i16)),
+            builtin_type::I128 => Some(Value::I128($val as i128)),
+            builtin_type::ISIZE => Some(Value::Isize($val as isize)),
+            builtin_type::UNKNOWN_INT => Some(Value::UnknownInt($val as i128)),
+            builtin_type::F32 => Some(Value::F32($val as f32)),
+            builtin_type::F64 => Some(Value::F64($val as f64)),
+            builtin_type::UNKNOWN_FLOAT => Some(Value::UnknownFloat($val as f64)),
+            builtin_type::VOID_PTR => Some(Value::RawPtr($val as usize as *const c_void)),
+            _ => None,
+        }
+    };
+}
+
+/// Converts a value to the given type, as `as` does in Rust.  Integers are truncated or extended, floats are
+/// truncated towards zero and saturate at the bounds of an integer type, and anything that isn't a number, bool or
+/// pointer is left as it is
+fn cast(value: Value, ty: DefinitionId) -> Value {
+    let cast_value = match value {
+        Value::F32(val) => num_cast!(val, ty),
+        Value::F64(val) | Value::UnknownFloat(val) => num_cast!(val, ty),
+        // The only integers that can't be held in an i128
+        Value::U128(val) => num_cast!(val, ty),
+        Value::Bool(val) => num_cast!(val as u8, ty),
+        Value::RawPtr(val) => num_cast!(val as usize, ty),
+        ref other => match int_value(other) {
+            Some(val) => num_cast!(val, ty),
+            None => None,
+        },
+    };
+    cast_value.unwrap_or(value)
+}
+
+/// The value of an integer, widened so that integers of any type can be compared. An integer without a type
+/// can meet one with a type, as they've already been checked to be compatible
+fn int_value(value: &Value) -> Option<i128> {
+    match value {
+        Value::U64(val) => Some(i128::from(*val)),
+        Value::U32(val) => Some(i128::from(*val)),
+        Value::I64(val) => Some(i128::from(*val)),
+        Value::I32(val) => Some(i128::from(*val)),
+        Value::U8(val) => Some(i128::from(*val)),
+        Value::U16(val) => Some(i128::from(*val)),
+        Value::U128(val) => Some(*val as i128), // only exact up to i128::MAX
+        Value::Usize(val) => Some(*val as i128),
+        Value::I8(val) => Some(i128::from(*val)),
+        Value::I16(val) => Some(i128::from(*val)),
+        Value::I128(val) => Some(*val),
+        Value::Isize(val) => Some(*val as i128),
+        Value::UnknownInt(val) => Some(*val),
+        _ => None,
+    }
+}
+
+/// The position of a tuple's element, from the name it's accessed by
+fn tuple_index(field: &str) -> usize {
+    field
+        .parse()
+        .unwrap_or_else(|_| unimplemented!("Tuple element {} is not a position", field))
+}
@@ -15,6 +15,7 @@ mod compile;
 mod eval;
 mod tests;
 
-pub use bytecode::{builtin_type, Bytecode, BytecodeEngine, Fun, VarStack};
+pub use bytecode::{builtin_type, Bytecode, BytecodeEngine, Diagnostic, Fun, Label, Overflow, Profile,
+                   SourceSpan, VarStack};
 pub use compile::compile_bytecode;
-pub use eval::{EvalEngine, Value};
+pub use eval::{EvalEngine, Panic, Value};
@@ -2,36 +2,53 @@
 mod tests {
     use std::process::Command;
 
-    use bytecode::{builtin_type, BytecodeEngine};
+    use bytecode::{builtin_type, BytecodeEngine, Diagnostic, Profile};
     use compile;
     use eval::EvalEngine;
 
-    fn load_to_bc(fname: &str) -> BytecodeEngine {
+    fn load_to_bc(fname: &str) -> Result<BytecodeEngine, Vec<Diagnostic>> {
         let mut bc = BytecodeEngine::new();
 
         // Step 1: Load up the parsed file so that we can lazily convert it
         bc.set_project_root("test_files");
-        bc.load_file(fname);
+        bc.load_file(fname)?;
 
         // Step 2: Convert to bytecode from the given location
         // We assume the starting function is found in scope 0, the starting scope
-        bc.process_fn("main", 0);
+        bc.process_fn("main", 0)?;
 
-        bc
+        Ok(bc)
     }
 
     fn run_test(fname: &str, eval_expect: &str, compile_expect: &str) {
-        let bc = load_to_bc(fname);
+        run_test_with_profile(fname, Profile::Debug, eval_expect, compile_expect);
+    }
+
+    /// Runs a program built for release, where arithmetic that overflows wraps around instead of panicking
+    fn run_release_test(fname: &str, eval_expect: &str, compile_expect: &str) {
+        run_test_with_profile(fname, Profile::Release, eval_expect, compile_expect);
+    }
+
+    fn run_test_with_profile(fname: &str, profile: Profile, eval_expect: &str, compile_expect: &str) {
+        let mut bc = match load_to_bc(fname) {
+            Ok(bc) => bc,
+            Err(errors) => panic!("{}", errors[0]),
+        };
+        bc.set_profile(profile);
 
         extern "C" {
             fn abs(input: i32) -> i32;
+            fn fabs(input: f64) -> f64;
         }
 
         // Eval stage
         let mut ee = EvalEngine::new();
         ee.debug_capture = Some(String::new());
         ee.register_extern_fn_1("abs", abs);
-        ee.eval_program(&bc, "main");
+        ee.register_extern_fn_1("fabs", fabs);
+        if let Err(panic) = ee.eval_program(&bc, "main") {
+            panic!("{}", panic);
+        }
         assert_eq!(eval_expect, ee.debug_capture.unwrap().trim());
 
         // Compile stage
@@ -46,17 +63,57 @@ mod tests {
         assert_eq!(test_output.trim(), compile_expect);
     }
 
-    fn run_bad_test(fname: &str, expected_error_msg: &str) {
-        use std::panic;
+    /// Runs a program that panics, checking that both backends print the expected output before stopping with the
+    /// expected message.  A compiled program exits with the code Rust gives a panic
+    fn run_panic_test(fname: &str, eval_expect: &str, compile_expect: &str, expected_panic_msg: &str) {
+        let bc = match load_to_bc(fname) {
+            Ok(bc) => bc,
+            Err(errors) => panic!("{}", errors[0]),
+        };
 
-        let result = panic::catch_unwind(|| {
-            load_to_bc(fname);
-        });
+        // Eval stage
+        let mut ee = EvalEngine::new();
+        ee.debug_capture = Some(String::new());
+        match ee.eval_program(&bc, "main") {
+            Err(panic) => assert!(
+                panic.message.contains(expected_panic_msg),
+                "unexpected panic: {}",
+                panic
+            ),
+            Ok(_) => panic!("Expected panicking test is succeeding"),
+        }
+        assert_eq!(eval_expect, ee.debug_capture.unwrap().trim());
 
-        match result {
-            Err(e) => {
-                let error_msg = e.downcast_ref::<String>().unwrap();
-                assert!(error_msg.contains(expected_error_msg));
+        // Compile stage
+        let compile_result = compile::compile_bytecode(&bc, fname);
+        assert!(compile_result.is_ok());
+
+        let cmd = Command::new(compile_result.unwrap())
+            .output()
+            .expect("failed to execute test");
+
+        assert_eq!(cmd.status.code(), Some(101));
+        let test_output = String::from_utf8(cmd.stdout).unwrap();
+        assert_eq!(test_output.trim(), compile_expect);
+        let test_stderr = String::from_utf8(cmd.stderr).unwrap();
+        assert!(
+            test_stderr.contains(expected_panic_msg),
+            "unexpected output: {}",
+            test_stderr
+        );
+    }
+
+    fn run_bad_test(fname: &str, expected_error_msg: &str) -> Vec<Diagnostic> {
+        match load_to_bc(fname) {
+            Err(errors) => {
+                assert!(
+                    errors
+                        .iter()
+                        .any(|diagnostic| diagnostic.message.contains(expected_error_msg)),
+                    "unexpected error: {}",
+                    errors[0]
+                );
+                errors
             }
             _ => panic!("Expected failing test is succeeding"),
         }
@@ -154,22 +211,43 @@ mod tests {
 
     #[test]
     fn test_expr_bad01() {
-        run_bad_test("expr_bad01.rs", "Can't add values of");
+        let errors = run_bad_test("expr_bad01.rs", "Can't add values of");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].code, Some("E0369"));
+
+        let primary = errors[0].primary.clone().unwrap();
+        assert_eq!(primary.span.file, "expr_bad01.rs");
+        assert_eq!(primary.span.start_line, 2);
+        assert_eq!(primary.span.start_column, 24);
+        assert_eq!(errors[0].secondary.len(), 2);
+    }
+
+    #[test]
+    fn test_expr_bad02() {
+        let errors = run_bad_test("expr_bad02.rs", "Could not find z");
+        let codes: Vec<_> = errors.iter().map(|diagnostic| diagnostic.code).collect();
+        assert_eq!(codes, vec![Some("E0308"), Some("E0425"), Some("E0369")]);
+
+        let lines: Vec<_> = errors
+            .iter()
+            .map(|diagnostic| diagnostic.primary.as_ref().unwrap().span.start_line)
+            .collect();
+        assert_eq!(lines, vec![6, 7, 2]);
     }
 
     #[test]
     fn test_fn01() {
-        run_test("fn01.rs", "DEBUG: UnknownInt(6)", "DEBUG: 6");
+        run_test("fn01.rs", "DEBUG: U64(6)", "DEBUG: 6");
     }
 
     #[test]
     fn test_fn02() {
-        run_test("fn02.rs", "DEBUG: UnknownInt(11)", "DEBUG: 11");
+        run_test("fn02.rs", "DEBUG: U64(11)", "DEBUG: 11");
     }
 
     #[test]
     fn test_fn03() {
-        run_test("fn03.rs", "DEBUG: UnknownInt(2)", "DEBUG: 2");
+        run_test("fn03.rs", "DEBUG: U64(2)", "DEBUG: 2");
     }
 
     #[test]
@@ -194,7 +272,29 @@ mod tests {
 
     #[test]
     fn test_fn08() {
-        run_test("fn08.rs", "DEBUG: UnknownInt(8)", "DEBUG: 8");
+        run_test("fn08.rs", "DEBUG: U64(8)", "DEBUG: 8");
+    }
+
+    #[test]
+    fn test_fn10() {
+        run_test("fn10.rs", "DEBUG: I64(1500514)", "DEBUG: 1500514");
+
+        // Each call's slots are freed when it returns, apart from what it gives back, so the loop doesn't pile them up
+        let bc = match load_to_bc("fn10.rs") {
+            Ok(bc) => bc,
+            Err(errors) => panic!("{}", errors[0]),
+        };
+        let mut ee = EvalEngine::new();
+        ee.debug_capture = Some(String::new());
+        if let Err(panic) = ee.eval_program(&bc, "main") {
+            panic!("{}", panic);
+        }
+        assert!(ee.slots.len() < 100, "{} slots left", ee.slots.len());
+    }
+
+    #[test]
+    fn test_fn_bad01() {
+        run_bad_test("fn_bad01.rs", "Mismatched return types: {unknown int} and bool");
     }
 
     #[test]
@@ -204,7 +304,7 @@ mod tests {
 
     #[test]
     fn test_var02() {
-        run_test("var02.rs", "DEBUG: UnknownInt(3)", "DEBUG: 3");
+        run_test("var02.rs", "DEBUG: U64(3)", "DEBUG: 3");
     }
 
     #[test]
@@ -212,6 +312,15 @@ mod tests {
         run_test("var03.rs", "DEBUG: Bool(true)", "DEBUG: 1");
     }
 
+    #[test]
+    fn test_var04() {
+        run_test(
+            "var04.rs",
+            "DEBUG: I32(50)\nDEBUG: I64(3000000000)\nDEBUG: U8(200)\nDEBUG: U16(60000)\nDEBUG: I32(7)",
+            "DEBUG: 50\nDEBUG: 3000000000\nDEBUG: 200\nDEBUG: 60000\nDEBUG: 7",
+        );
+    }
+
     #[test]
     fn test_var_bad01() {
         run_bad_test("var_bad01.rs", "used before being given a value");
@@ -284,12 +393,12 @@ mod tests {
 
     #[test]
     fn test_mod07() {
-        run_test("mod07.rs", "DEBUG: UnknownInt(1)", "DEBUG: 1");
+        run_test("mod07.rs", "DEBUG: U64(1)", "DEBUG: 1");
     }
 
     #[test]
     fn test_mod08() {
-        run_test("mod08.rs", "DEBUG: UnknownInt(3)", "DEBUG: 3");
+        run_test("mod08.rs", "DEBUG: U64(3)", "DEBUG: 3");
     }
 
     #[test]
@@ -312,6 +421,48 @@ mod tests {
         run_bad_test("mod_bad01.rs", "Can not find mod");
     }
 
+    #[test]
+    fn test_vis01() {
+        run_test("vis01.rs", "DEBUG: I32(51)", "DEBUG: 51");
+    }
+
+    #[test]
+    fn test_vis_bad01() {
+        run_bad_test("vis_bad01.rs", "Module `bar` is private");
+    }
+
+    #[test]
+    fn test_vis_bad02() {
+        run_bad_test("vis_bad02.rs", "Field `height` of struct `Rect` is private");
+    }
+
+    #[test]
+    fn test_vis_bad03() {
+        run_bad_test("vis_bad03.rs", "Method `reset` is private");
+    }
+
+    #[test]
+    fn test_vis_bad04() {
+        run_bad_test("vis_bad04.rs", "Function `helper` is private");
+    }
+
+    #[test]
+    fn test_vis_bad05() {
+        run_bad_test("vis_bad05.rs", "Function `hidden` is private");
+    }
+
+    #[test]
+    fn test_vis_bad06() {
+        run_bad_test("vis_bad06.rs", "Visibility restricted to a path is not yet supported");
+    }
+
+    #[test]
+    fn test_vis_bad07() {
+        let errors = run_bad_test("vis_bad07.rs", "Tuple struct constructor `T` is private");
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|diagnostic| diagnostic.code == Some("E0603")));
+    }
+
     #[test]
     fn test_scope01() {
         run_test("scope01.rs", "DEBUG: UnknownInt(3)", "DEBUG: 3");
@@ -324,33 +475,633 @@ mod tests {
 
     #[test]
     fn test_struct01() {
-        let custom_type_id_string = format!("DEBUG: <custom type:{}>", builtin_type::ERROR + 1);
+        let custom_type_id_string = format!("DEBUG: <custom type:{}>", builtin_type::NEVER + 1);
         run_test("struct01.rs", "DEBUG: Object({})", &custom_type_id_string);
     }
 
     #[test]
     fn test_struct02() {
-        run_test("struct02.rs", "DEBUG: UnknownInt(3)", "DEBUG: 3");
+        run_test("struct02.rs", "DEBUG: U64(3)", "DEBUG: 3");
     }
 
     #[test]
     fn test_struct03() {
-        run_test("struct03.rs", "DEBUG: UnknownInt(5)", "DEBUG: 5");
+        run_test("struct03.rs", "DEBUG: U64(5)", "DEBUG: 5");
     }
 
     #[test]
     fn test_struct04() {
-        run_test("struct04.rs", "DEBUG: UnknownInt(4)", "DEBUG: 4");
+        run_test("struct04.rs", "DEBUG: U64(4)", "DEBUG: 4");
     }
 
     #[test]
     fn test_struct05() {
-        run_test("struct05.rs", "DEBUG: UnknownInt(4)", "DEBUG: 4");
+        run_test("struct05.rs", "DEBUG: U64(4)", "DEBUG: 4");
     }
 
     #[test]
     fn test_struct06() {
-        run_test("struct06.rs", "DEBUG: UnknownInt(5)", "DEBUG: 5");
+        run_test("struct06.rs", "DEBUG: U64(5)", "DEBUG: 5");
+    }
+
+    #[test]
+    fn test_impl01() {
+        run_test("impl01.rs", "DEBUG: UnknownInt(7)", "DEBUG: 7");
+    }
+
+    #[test]
+    fn test_impl02() {
+        run_test("impl02.rs", "DEBUG: I32(14)", "DEBUG: 14");
+    }
+
+    #[test]
+    fn test_impl03() {
+        run_test("impl03.rs", "DEBUG: I32(10)", "DEBUG: 10");
+    }
+
+    #[test]
+    fn test_impl_bad01() {
+        run_bad_test("impl_bad01.rs", "No method named `baz`");
+    }
+
+    #[test]
+    fn test_trait01() {
+        run_test("trait01.rs", "DEBUG: I32(27)", "DEBUG: 27");
+    }
+
+    #[test]
+    fn test_trait02() {
+        run_test("trait02.rs", "DEBUG: I32(35)", "DEBUG: 35");
+    }
+
+    #[test]
+    fn test_trait_bad01() {
+        run_bad_test("trait_bad01.rs", "missing: `sides`");
+    }
+
+    #[test]
+    fn test_trait_bad02() {
+        run_bad_test("trait_bad02.rs", "The trait bound `Circle: Shape` is not satisfied");
+    }
+
+    #[test]
+    fn test_trait03() {
+        run_test("trait03.rs", "DEBUG: I32(7)\nDEBUG: I32(50)", "DEBUG: 7\nDEBUG: 50");
+    }
+
+    #[test]
+    fn test_trait_bad03() {
+        let errors = run_bad_test("trait_bad03.rs", "multiple applicable items in scope");
+        assert_eq!(errors[0].code, Some("E0034"));
+    }
+
+    #[test]
+    fn test_trait_bad04() {
+        let errors = run_bad_test("trait_bad04.rs", "multiple applicable items in scope");
+        assert_eq!(errors[0].code, Some("E0034"));
+    }
+
+    #[test]
+    fn test_dyn01() {
+        run_test("dyn01.rs", "DEBUG: I32(29)", "DEBUG: 29");
+    }
+
+    #[test]
+    fn test_dyn02() {
+        run_test("dyn02.rs", "DEBUG: I32(50)", "DEBUG: 50");
+    }
+
+    #[test]
+    fn test_dyn_bad01() {
+        let errors = run_bad_test("dyn_bad01.rs", "The trait `Shape` cannot be made into an object");
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_dyn_bad02() {
+        // Only the trait object type is reported, not the uses of values of it
+        let errors = run_bad_test("dyn_bad02.rs", "The trait `Shape` cannot be made into an object");
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_enum01() {
+        run_test("enum01.rs", "DEBUG: I32(180)", "DEBUG: 180");
+    }
+
+    #[test]
+    fn test_enum02() {
+        run_test("enum02.rs", "DEBUG: I32(39)", "DEBUG: 39");
+    }
+
+    #[test]
+    fn test_enum03() {
+        run_test("enum03.rs", "DEBUG: U8(200)\nDEBUG: I64(3000000000)", "DEBUG: 200\nDEBUG: 3000000000");
+    }
+
+    #[test]
+    fn test_enum_bad01() {
+        run_bad_test("enum_bad01.rs", "Match arms have incompatible types");
+    }
+
+    #[test]
+    fn test_enum_bad02() {
+        // Only the variant with named fields is reported, not its uses or the rest of the enum
+        let errors = run_bad_test("enum_bad02.rs", "Enum variants with named fields are not yet supported");
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_match01() {
+        run_test("match01.rs", "DEBUG: I32(313)", "DEBUG: 313");
+    }
+
+    #[test]
+    fn test_match_bad01() {
+        run_bad_test(
+            "match_bad01.rs",
+            "Non-exhaustive patterns: `Shape::Square(_)` not covered",
+        );
+    }
+
+    #[test]
+    fn test_match_bad02() {
+        run_bad_test(
+            "match_bad02.rs",
+            "Non-exhaustive patterns: `i32::MIN..=-1` and `2..=i32::MAX` not covered",
+        );
+    }
+
+    #[test]
+    fn test_match02() {
+        // An arm that can never match is only warned about, as it is in rustc
+        run_test("match02.rs", "DEBUG: I32(1)", "DEBUG: 1");
+        let warnings = load_to_bc("match02.rs").unwrap().take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].message.contains("Unreachable pattern"));
+    }
+
+    #[test]
+    fn test_match03() {
+        run_test("match03.rs", "DEBUG: I32(1)\nDEBUG: I32(2)", "DEBUG: 1\nDEBUG: 2");
+    }
+
+    #[test]
+    fn test_match_bad04() {
+        let errors = run_bad_test("match_bad04.rs", "Match guards are not yet supported");
+        assert_eq!(errors[0].code, Some("E0658"));
+    }
+
+    #[test]
+    fn test_match_bad05() {
+        run_bad_test("match_bad05.rs", "Alternative patterns are not yet supported");
+    }
+
+    #[test]
+    fn test_match_bad06() {
+        let errors = run_bad_test(
+            "match_bad06.rs",
+            "`10..=199999999999999999999999999999999999999` and `200000000000000000000000000000000000001..=u128::MAX`",
+        );
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn test_destructure01() {
+        run_test("destructure01.rs", "DEBUG: I32(138)", "DEBUG: 138");
+    }
+
+    #[test]
+    fn test_destructure02() {
+        // Parentheses around a pattern only group it, without making a tuple
+        run_test("destructure02.rs", "DEBUG: I32(144)", "DEBUG: 144");
+    }
+
+    #[test]
+    fn test_destructure_bad01() {
+        run_bad_test(
+            "destructure_bad01.rs",
+            "Refutable pattern in local binding: `Shape::Empty` not covered",
+        );
+    }
+
+    #[test]
+    fn test_destructure_bad02() {
+        run_bad_test("destructure_bad02.rs", "Pattern does not mention field(s) `y`");
+    }
+
+    #[test]
+    fn test_destructure_bad03() {
+        // The variables are still declared, so only the declaration is reported
+        let errors = run_bad_test(
+            "destructure_bad03.rs",
+            "Destructuring a variable declared without a value is not yet supported",
+        );
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn test_destructure_bad04() {
+        run_bad_test(
+            "destructure_bad04.rs",
+            "Mismatched types in pattern: expected {unknown int}, found a tuple of 1 element(s)",
+        );
+    }
+
+    #[test]
+    fn test_tuple01() {
+        run_test("tuple01.rs", "DEBUG: I32(172)", "DEBUG: 172");
+    }
+
+    #[test]
+    fn test_tuple02() {
+        run_test("tuple02.rs", "DEBUG: I32(1365)", "DEBUG: 1365");
+    }
+
+    #[test]
+    fn test_tuple03() {
+        run_test("tuple03.rs", "DEBUG: I64(3000000494)", "DEBUG: 3000000494");
+    }
+
+    #[test]
+    fn test_tuple_bad01() {
+        run_bad_test("tuple_bad01.rs", "Field access of 2 not found on type (i32, i32)");
+    }
+
+    #[test]
+    fn test_tuple_bad02() {
+        run_bad_test(
+            "tuple_bad02.rs",
+            "Non-exhaustive patterns: `(false, false)` not covered",
+        );
+    }
+
+    #[test]
+    fn test_tuple_bad03() {
+        run_bad_test("tuple_bad03.rs", "Pair has 2 field(s) but 1 were supplied");
+    }
+
+    #[test]
+    fn test_array01() {
+        run_test("array01.rs", "DEBUG: I32(94)", "DEBUG: 94");
+    }
+
+    #[test]
+    fn test_array02() {
+        run_test("array02.rs", "DEBUG: I64(6533)", "DEBUG: 6533");
+    }
+
+    #[test]
+    fn test_array_panic01() {
+        run_panic_test(
+            "array_panic01.rs",
+            "DEBUG: UnknownInt(1)",
+            "DEBUG: 1",
+            "index out of bounds: the len is 3 but the index is 3",
+        );
+    }
+
+    #[test]
+    fn test_array_bad01() {
+        run_bad_test("array_bad01.rs", "Can not index into a value of type bool");
+    }
+
+    #[test]
+    fn test_array_bad02() {
+        run_bad_test("array_bad02.rs", "Array elements have incompatible types");
+    }
+
+    #[test]
+    fn test_array_bad03() {
+        let errors = run_bad_test("array_bad03.rs", "Arrays can only be indexed by usize, found i32");
+        assert_eq!(errors[0].code, Some("E0277"));
+    }
+
+    #[test]
+    fn test_ref01() {
+        run_test("ref01.rs", "DEBUG: I32(76)", "DEBUG: 76");
+    }
+
+    #[test]
+    fn test_ref_bad01() {
+        run_bad_test("ref_bad01.rs", "Type bool cannot be dereferenced");
+    }
+
+    #[test]
+    fn test_ref_bad02() {
+        run_bad_test("ref_bad02.rs", "Argument target expects &mut i32, found &i32");
+    }
+
+    #[test]
+    fn test_mut01() {
+        run_test("mut01.rs", "DEBUG: I32(18)", "DEBUG: 18");
+    }
+
+    #[test]
+    fn test_mut02() {
+        run_test("mut02.rs", "DEBUG: I32(54)", "DEBUG: 54");
+    }
+
+    #[test]
+    fn test_mut03() {
+        run_test("mut03.rs", "DEBUG: I32(126)", "DEBUG: 126");
+    }
+
+    #[test]
+    fn test_mut_bad01() {
+        run_bad_test(
+            "mut_bad01.rs",
+            "Cannot assign to `bar.x`, as `bar` is not declared as mutable",
+        );
+    }
+
+    #[test]
+    fn test_mut_bad02() {
+        run_bad_test(
+            "mut_bad02.rs",
+            "Cannot borrow `x` as mutable, as it is not declared as mutable",
+        );
+    }
+
+    #[test]
+    fn test_mut_bad03() {
+        run_bad_test(
+            "mut_bad03.rs",
+            "Cannot assign to `p.x`, which is behind a `&` reference",
+        );
+    }
+
+    #[test]
+    fn test_mut_bad04() {
+        run_bad_test("mut_bad04.rs", "Cannot assign twice to immutable variable `x`");
+    }
+
+    #[test]
+    fn test_mut_bad05() {
+        // Assigning twice is still found in a function with a type error
+        let errors = run_bad_test("mut_bad05.rs", "Cannot assign twice to immutable variable `x`");
+        assert!(errors.iter().any(|diagnostic| diagnostic.code == Some("E0308")));
+    }
+
+    #[test]
+    fn test_mut_bad06() {
+        run_bad_test(
+            "mut_bad06.rs",
+            "Cannot borrow `t.1` as mutable, as `t` is not declared as mutable",
+        );
+    }
+
+    #[test]
+    fn test_cmp01() {
+        run_test("cmp01.rs", "DEBUG: I32(327)", "DEBUG: 327");
+    }
+
+    #[test]
+    fn test_cmp02() {
+        run_test(
+            "cmp02.rs",
+            "DEBUG: U8(250)\nDEBUG: I32(-6)\nDEBUG: UnknownInt(-6)\nDEBUG: Bool(true)\nDEBUG: I8(127)",
+            "DEBUG: 250\nDEBUG: -6\nDEBUG: -6\nDEBUG: 1\nDEBUG: 127",
+        );
+    }
+
+    #[test]
+    fn test_cmp_bad01() {
+        run_bad_test("cmp_bad01.rs", "Can't compare values of type Point");
+    }
+
+    #[test]
+    fn test_cmp_bad02() {
+        run_bad_test("cmp_bad02.rs", "Operands of `&&` need to be boolean");
+    }
+
+    #[test]
+    fn test_cmp_bad03() {
+        run_bad_test("cmp_bad03.rs", "Can't apply `!` to value of type Point");
+    }
+
+    #[test]
+    fn test_ops01() {
+        run_test("ops01.rs", "DEBUG: I32(1033010)", "DEBUG: 1033010");
+    }
+
+    #[test]
+    fn test_ops_bad01() {
+        run_bad_test("ops_bad01.rs", "Can't shift values of bool and {unknown int}");
+    }
+
+    #[test]
+    fn test_ops_bad02() {
+        run_bad_test("ops_bad02.rs", "Cannot assign twice to immutable variable `x`");
+    }
+
+    #[test]
+    fn test_ops_panic01() {
+        run_panic_test(
+            "ops_panic01.rs",
+            "",
+            "",
+            "attempt to calculate the remainder with a divisor of zero",
+        );
+    }
+
+    #[test]
+    fn test_overflow01() {
+        run_test("overflow01.rs", "DEBUG: I32(2147483445)", "DEBUG: 2147483445");
+    }
+
+    #[test]
+    fn test_overflow02() {
+        run_test(
+            "overflow02.rs",
+            "DEBUG: U64(1099511627776)\nDEBUG: U64(6000000000)\nDEBUG: I64(2147483648)",
+            "DEBUG: 1099511627776\nDEBUG: 6000000000\nDEBUG: 2147483648",
+        );
+    }
+
+    #[test]
+    fn test_overflow03() {
+        run_test(
+            "overflow03.rs",
+            "DEBUG: I32(-2147483648)\nDEBUG: I32(2147483647)\nDEBUG: I32(-1)\nDEBUG: UnknownInt(100)\n\
+             DEBUG: Bool(true)\nDEBUG: I32(2147483647)\nDEBUG: I32(-5)\nDEBUG: U8(251)\nDEBUG: U8(3)\n\
+             DEBUG: I64(-4)\nDEBUG: UnknownInt(-1)\nDEBUG: U8(66)",
+            "DEBUG: -2147483648\nDEBUG: 2147483647\nDEBUG: -1\nDEBUG: 100\nDEBUG: 1\nDEBUG: 2147483647\nDEBUG: -5\n\
+             DEBUG: 251\nDEBUG: 3\nDEBUG: -4\nDEBUG: -1\nDEBUG: 66",
+        );
+    }
+
+    #[test]
+    fn test_overflow_release01() {
+        run_release_test("overflow_release01.rs", "DEBUG: I32(44)", "DEBUG: 44");
+    }
+
+    #[test]
+    fn test_overflow_panic01() {
+        run_panic_test("overflow_panic01.rs", "DEBUG: U8(200)", "DEBUG: 200", "attempt to add with overflow");
+    }
+
+    #[test]
+    fn test_overflow_panic02() {
+        run_panic_test("overflow_panic02.rs", "", "", "attempt to negate with overflow");
+    }
+
+    #[test]
+    fn test_overflow_panic03() {
+        run_panic_test("overflow_panic03.rs", "", "", "attempt to shift left with overflow");
+    }
+
+    #[test]
+    fn test_int01() {
+        run_test("int01.rs", "DEBUG: U32(255)", "DEBUG: 255");
+    }
+
+    #[test]
+    fn test_int02() {
+        run_test(
+            "int02.rs",
+            "DEBUG: U128(340282366920938463426481119284349108225)",
+            "DEBUG: 340282366920938463426481119284349108225",
+        );
+    }
+
+    #[test]
+    fn test_int03() {
+        run_test(
+            "int03.rs",
+            "DEBUG: I128(-170141183460469231731687303715884105728)\n\
+             DEBUG: U128(340282366920938463463374607431768211455)\nDEBUG: I8(-128)",
+            "DEBUG: -170141183460469231731687303715884105728\n\
+             DEBUG: 340282366920938463463374607431768211455\nDEBUG: -128",
+        );
+    }
+
+    #[test]
+    fn test_int04() {
+        run_test(
+            "int04.rs",
+            "DEBUG: U128(170141183460469231731687303715884105728)\nDEBUG: U64(10000000000)",
+            "DEBUG: 170141183460469231731687303715884105728\nDEBUG: 10000000000",
+        );
+    }
+
+    #[test]
+    fn test_int_bad01() {
+        let errors = run_bad_test("int_bad01.rs", "Integer literal is too large");
+        assert_eq!(errors[0].code, Some("E0080"));
+    }
+
+    #[test]
+    fn test_int_bad02() {
+        let errors = run_bad_test("int_bad02.rs", "Literal out of range for u8");
+        assert_eq!(errors[0].code, Some("E0080"));
+    }
+
+    #[test]
+    fn test_int_bad03() {
+        run_bad_test("int_bad03.rs", "Literal out of range for u8");
+    }
+
+    #[test]
+    fn test_int_bad04() {
+        run_bad_test("int_bad04.rs", "Literal out of range for i32");
+    }
+
+    #[test]
+    fn test_float01() {
+        run_test("float01.rs", "DEBUG: UnknownFloat(103.125)", "DEBUG: 103.125");
+    }
+
+    #[test]
+    fn test_float02() {
+        run_test("float02.rs", "DEBUG: F32(0.33333334)", "DEBUG: 0.33333334");
+    }
+
+    #[test]
+    fn test_float_bad01() {
+        run_bad_test("float_bad01.rs", "Can't add values of {unknown float} and {unknown int}");
+    }
+
+    #[test]
+    fn test_cast01() {
+        run_test("cast01.rs", "DEBUG: F32(255.5)", "DEBUG: 255.5");
+    }
+
+    #[test]
+    fn test_cast_bad01() {
+        run_bad_test("cast_bad01.rs", "Can't cast Point as i32");
+    }
+
+    #[test]
+    fn test_cast_bad02() {
+        run_bad_test("cast_bad02.rs", "Can't cast bool as f64");
+    }
+
+    #[test]
+    fn test_borrow01() {
+        run_test("borrow01.rs", "DEBUG: I32(167)", "DEBUG: 167");
+    }
+
+    #[test]
+    fn test_borrow02() {
+        run_test("borrow02.rs", "DEBUG: I64(402010)", "DEBUG: 402010");
+    }
+
+    #[test]
+    fn test_borrow03() {
+        run_test("borrow03.rs", "DEBUG: I32(103)", "DEBUG: 103");
+    }
+
+    #[test]
+    fn test_borrow_bad01() {
+        run_bad_test(
+            "borrow_bad01.rs",
+            "Cannot borrow `x` as mutable because it is also borrowed as immutable",
+        );
+    }
+
+    #[test]
+    fn test_borrow_bad02() {
+        run_bad_test("borrow_bad02.rs", "Use of moved value: `p`");
+    }
+
+    #[test]
+    fn test_borrow_bad03() {
+        run_bad_test(
+            "borrow_bad03.rs",
+            "Cannot return reference to local variable `local`",
+        );
+    }
+
+    #[test]
+    fn test_borrow_bad04() {
+        run_bad_test(
+            "borrow_bad04.rs",
+            "Cannot borrow `x` as mutable more than once at a time",
+        );
+    }
+
+    #[test]
+    fn test_borrow_bad05() {
+        run_bad_test("borrow_bad05.rs", "Use of moved value: `t`");
+    }
+
+    #[test]
+    fn test_borrow_bad06() {
+        run_bad_test("borrow_bad06.rs", "Cannot assign to `x` because it is borrowed");
+    }
+
+    #[test]
+    fn test_borrow_bad07() {
+        // The borrow is still checked in a function with a type error
+        let errors = run_bad_test("borrow_bad07.rs", "Cannot assign to `x` because it is borrowed");
+        assert!(errors.iter().any(|diagnostic| diagnostic.code == Some("E0308")));
+    }
+
+    #[test]
+    fn test_borrow_bad08() {
+        let errors = run_bad_test("borrow_bad08.rs", "Cannot use `x` because it was mutably borrowed");
+        assert_eq!(errors[0].code, Some("E0503"));
     }
 
     #[test]
@@ -373,16 +1124,196 @@ mod tests {
         run_bad_test("generic_bad01.rs", "add values of");
     }
 
+    #[test]
+    fn test_generic_bad02() {
+        run_bad_test("generic_bad02.rs", "does not match expression type 'T'");
+    }
+
     #[test]
     fn test_while01() {
         run_test("while01.rs", "DEBUG: UnknownInt(10)", "DEBUG: 10");
     }
 
+    #[test]
+    fn test_loop01() {
+        run_test("loop01.rs", "DEBUG: I32(116)", "DEBUG: 116");
+    }
+
+    #[test]
+    fn test_loop02() {
+        run_test("loop02.rs", "DEBUG: U32(8)\nDEBUG: U8(12)", "DEBUG: 8\nDEBUG: 12");
+    }
+
+    #[test]
+    fn test_loop_bad01() {
+        run_bad_test("loop_bad01.rs", "`break` with value from a `while` loop");
+    }
+
+    #[test]
+    fn test_loop_bad02() {
+        run_bad_test("loop_bad02.rs", "Use of undeclared label `'inner`");
+    }
+
+    #[test]
+    fn test_loop_bad03() {
+        run_bad_test("loop_bad03.rs", "`continue` outside of a loop");
+    }
+
+    #[test]
+    fn test_for01() {
+        run_test("for01.rs", "DEBUG: I32(135561)", "DEBUG: 135561");
+    }
+
+    #[test]
+    fn test_for_bad01() {
+        run_bad_test("for_bad01.rs", "`{unknown int}` is not an iterator");
+    }
+
+    #[test]
+    fn test_for_bad02() {
+        run_bad_test("for_bad02.rs", "Can't iterate over a range of f64");
+    }
+
+    #[test]
+    fn test_for_panic01() {
+        run_panic_test("for_panic01.rs", "", "", "assertion failed: step != 0");
+    }
+
+    #[test]
+    fn test_never01() {
+        run_test(
+            "never01.rs",
+            "DEBUG: UnknownInt(93)\nDEBUG: I32(7)\nDEBUG: I32(9)\nDEBUG: I32(3)",
+            "DEBUG: 93\nDEBUG: 7\nDEBUG: 9\nDEBUG: 3",
+        );
+    }
+
+    #[test]
+    fn test_closure01() {
+        run_test(
+            "closure01.rs",
+            "DEBUG: I32(21)\nDEBUG: I32(5)\nDEBUG: I32(101)\nDEBUG: I32(102)\nDEBUG: I32(34)\n\
+             DEBUG: UnknownInt(100)\nDEBUG: I32(6)\nDEBUG: UnknownInt(1)\nDEBUG: I32(18)",
+            "DEBUG: 21\nDEBUG: 5\nDEBUG: 101\nDEBUG: 102\nDEBUG: 34\nDEBUG: 100\nDEBUG: 6\nDEBUG: 1\nDEBUG: 18",
+        );
+    }
+
+    #[test]
+    fn test_closure_bad01() {
+        run_bad_test("closure_bad01.rs", "Cannot borrow `inc` as mutable");
+    }
+
+    #[test]
+    fn test_closure_bad02() {
+        run_bad_test("closure_bad02.rs", "Type annotations needed");
+    }
+
+    #[test]
+    fn test_closure_bad03() {
+        run_bad_test("closure_bad03.rs", "Cannot borrow `count` as immutable");
+    }
+
+    #[test]
+    fn test_closure_bad04() {
+        run_bad_test("closure_bad04.rs", "This closure takes 2 argument(s) but 1 were supplied");
+    }
+
+    #[test]
+    fn test_closure_bad05() {
+        run_bad_test("closure_bad05.rs", "Use of moved value: `boxed`");
+    }
+
+    #[test]
+    fn test_fn_ptr01() {
+        run_test("fn_ptr01.rs", "DEBUG: I32(2738096)", "DEBUG: 2738096");
+    }
+
+    #[test]
+    fn test_fn_ptr_bad01() {
+        run_bad_test("fn_ptr_bad01.rs", "This function takes 1 argument(s) but 2 were supplied");
+    }
+
+    #[test]
+    fn test_fn_ptr_bad02() {
+        run_bad_test("fn_ptr_bad02.rs", "does not match expression type 'fn(i32) -> bool'");
+    }
+
+    #[test]
+    fn test_fn_ptr_bad03() {
+        run_bad_test("fn_ptr_bad03.rs", "Can not call function on type i32");
+    }
+
+    #[test]
+    fn test_fn_ptr_bad04() {
+        run_bad_test("fn_ptr_bad04.rs", "Argument f expects fn(i64) -> i64, found fn(i32) -> i32");
+    }
+
+    #[test]
+    fn test_generic_struct01() {
+        run_test("generic_struct01.rs", "DEBUG: I64(6551413437)", "DEBUG: 6551413437");
+    }
+
+    #[test]
+    fn test_generic_struct02() {
+        run_test("generic_struct02.rs", "DEBUG: I64(6)", "DEBUG: 6");
+    }
+
+    #[test]
+    fn test_generic_struct03() {
+        run_test(
+            "generic_struct03.rs",
+            "DEBUG: U8(250)\nDEBUG: U8(127)\nDEBUG: I64(3000000001)",
+            "DEBUG: 250\nDEBUG: 127\nDEBUG: 3000000001",
+        );
+    }
+
+    #[test]
+    fn test_generic_struct_bad01() {
+        run_bad_test("generic_struct_bad01.rs", "Field b expects i32, found bool");
+    }
+
+    #[test]
+    fn test_generic_struct_bad02() {
+        run_bad_test("generic_struct_bad02.rs", "Struct Pair takes 1 type argument(s) but 2 were supplied");
+    }
+
+    #[test]
+    fn test_generic_struct_bad03() {
+        run_bad_test("generic_struct_bad03.rs", "Field 1 expects f64");
+    }
+
+    #[test]
+    fn test_generic_struct_bad04() {
+        run_bad_test("generic_struct_bad04.rs", "Argument p expects Pair<i32>, found Pair<u8>");
+    }
+
+    #[test]
+    fn test_generic_struct_bad05() {
+        let errors = run_bad_test("generic_struct_bad05.rs", "Generic impls are not yet supported");
+        assert_eq!(errors[0].code, Some("E0658"));
+    }
+
+    #[test]
+    fn test_generic_struct04() {
+        run_test("generic_struct04.rs", "DEBUG: UnknownInt(8)\nDEBUG: U8(9)", "DEBUG: 8\nDEBUG: 9");
+    }
+
+    #[test]
+    fn test_generic_struct_bad06() {
+        let errors = run_bad_test("generic_struct_bad06.rs", "No method named `get` found for type W<u8>");
+        assert_eq!(errors[0].code, Some("E0599"));
+    }
+
     #[test]
     fn test_ffi01() {
         run_test("ffi01.rs", "DEBUG: I32(1)", "DEBUG: 1");
     }
 
+    #[test]
+    fn test_ffi02() {
+        run_test("ffi02.rs", "DEBUG: F64(2.75)", "DEBUG: 2.75");
+    }
+
     #[test]
     fn test_pain01() {
         run_test("pain01.rs", "DEBUG: UnknownInt(1)", "DEBUG: 1");
@@ -0,0 +1,29 @@
+struct Grid {
+    cells: [i32; 3],
+}
+
+fn sum(a: [i32; 3]) -> i32 {
+    a[0] + a[1] + a[2]
+}
+
+fn make() -> [i32; 3] {
+    [4, 5, 6]
+}
+
+fn main() {
+    let a = [1, 2, 3];
+    let mut b = a;
+    b[0] = 10;
+    let zeros = [0; 4];
+    let mut g = Grid { cells: make() };
+    g.cells[1] = 20;
+    let m = [[1, 2], [3, 4]];
+    let i = 2;
+    let mut c = [0; 2];
+    c[1] = 37;
+
+    let mut total = sum(a) + sum(b) + zeros[3];
+    total = total + sum(g.cells) + m[1][0] + a[i];
+    total = total + c[0] + c[1];
+    println!("{}", total);
+}
@@ -0,0 +1,47 @@
+trait Shape {
+    fn area(&self) -> i64;
+}
+
+struct Square {
+    side: i64,
+}
+
+struct Circle {
+    r: i64,
+}
+
+impl Shape for Square {
+    fn area(&self) -> i64 {
+        self.side * self.side
+    }
+}
+
+impl Shape for Circle {
+    fn area(&self) -> i64 {
+        3 * self.r * self.r
+    }
+}
+
+fn grid(flip: bool) -> [[u8; 2]; 2] {
+    if flip {
+        [[4, 3], [2, 1]]
+    } else {
+        [[1, 2], [3, 4]]
+    }
+}
+
+fn main() {
+    let a: [u8; 3] = [1, 2, 255];
+    let z: [u8; 4] = [250; 4];
+    let g = grid(false);
+    let i: usize = 1;
+    let _empty: [i64; 0] = [];
+    let shapes: [Box<dyn Shape>; 2] = [Box::new(Square { side: 2 }), Box::new(Circle { r: 1 })];
+    let pair: (Box<dyn Shape>, i64) = (Box::new(Circle { r: 2 }), 5);
+    let total = a[0] as i64 + a[2] as i64 + z[3] as i64 + g[i][0] as i64 + shapes[0].area() + shapes[1].area();
+    let mut last = 0;
+    for j in 0..2 {
+        last = g[j][1] as i64 + grid(true)[j][0] as i64;
+    }
+    println!("{}", total + pair.0.area() + pair.1 + last * 1000);
+}
@@ -0,0 +1,4 @@
+fn main() {
+    let flag = true;
+    println!("{}", flag[0]);
+}
@@ -0,0 +1,4 @@
+fn main() {
+    let a = [1, 2, true];
+    println!("{}", a[0]);
+}
@@ -0,0 +1,9 @@
+fn last(a: [i32; 3], n: usize) -> i32 {
+    a[n]
+}
+
+fn main() {
+    let a = [1, 2, 3];
+    println!("{}", last(a, 0));
+    println!("{}", last(a, 3));
+}
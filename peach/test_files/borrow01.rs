@@ -0,0 +1,52 @@
+struct Counter {
+    count: i32,
+    step: i32,
+}
+
+impl Counter {
+    fn get(&self) -> i32 {
+        self.count
+    }
+
+    fn set(&mut self, count: i32) {
+        self.count = count;
+    }
+}
+
+fn take(c: Counter) -> i32 {
+    c.count + c.step
+}
+
+fn bump(x: &mut i32) {
+    *x = *x + 1;
+}
+
+fn main() {
+    let mut c = Counter { count: 1, step: 2 };
+    c.set(c.get() + 10);
+
+    // A borrow is over once the reference isn't used any more
+    let r = &c;
+    let seen = r.count;
+    let m = &mut c;
+    m.step = 4;
+
+    // Different fields can be borrowed at the same time
+    let x = &mut c.count;
+    let s = &c.step;
+    bump(x);
+    let step = *s;
+
+    let mut i = 0;
+    let mut sum = 0;
+    while i < 3 {
+        let current = &c;
+        sum = sum + current.count;
+        i = i + 1;
+    }
+
+    let total = take(c);
+    c = Counter { count: 100, step: 0 };
+    let after = c.get();
+    println!("{}", seen + step + sum + total + after);
+}
@@ -0,0 +1,59 @@
+struct Token {
+    value: i64,
+}
+
+trait Score {
+    fn score(&self) -> i64;
+}
+
+impl Score for Token {
+    fn score(&self) -> i64 {
+        self.value * 2
+    }
+}
+
+fn keep<T: Score>(kept: T, other: &T) -> T {
+    kept
+}
+
+fn make() -> Token {
+    let other = Token { value: 1 };
+    keep(Token { value: 4 }, &other)
+}
+
+fn scored<T: Score>(item: T) -> i64 {
+    let local = item;
+    local.score()
+}
+
+fn take(t: Token) -> i64 {
+    t.value
+}
+
+fn main() {
+    // Neither call returns a reference, so nothing borrowed is returned
+    let copied = make().value;
+    let from_trait = scored(Token { value: 3 });
+
+    // A value moved in a loop is fine if the loop is left right after, or if it's given a new value
+    let mut t = Token { value: 100 };
+    let mut total = 0;
+    loop {
+        if total > 200 {
+            total += take(t);
+            break;
+        }
+        total += take(t);
+        t = Token { value: 100 };
+    }
+    let mut i = 0;
+    while i < 3 {
+        i += 1;
+        let step = &mut i;
+        if *step == 2 {
+            continue;
+        }
+        total += 1;
+    }
+    println!("{}", copied + from_trait + total * 1000);
+}
@@ -0,0 +1,35 @@
+struct Counter {
+    n: i32,
+    step: i32,
+}
+
+impl Counter {
+    fn inc(&mut self) -> &mut Self {
+        self.n += self.step;
+        self
+    }
+
+    fn add(&mut self, k: i32) {
+        self.n += k;
+    }
+}
+
+fn peek(x: &i32) -> &i32 {
+    x
+}
+
+fn main() {
+    // The references these statements give are dropped straight away
+    let mut c = Counter { n: 1, step: 2 };
+    c.inc();
+    c.add(5);
+    let mut a = 1;
+    peek(&a);
+    a = 3;
+
+    // A field can be read while another field is mutably borrowed
+    let m = &mut c.n;
+    let step = c.step;
+    *m += step;
+    println!("{}", c.n * 10 + a);
+}
@@ -0,0 +1,14 @@
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+fn consume(p: Point) -> i32 {
+    p.x + p.y
+}
+
+fn main() {
+    let p = Point { x: 1, y: 2 };
+    let a = consume(p);
+    println!("{}", a + p.x);
+}
@@ -0,0 +1,9 @@
+fn next(a: &i32) -> &i32 {
+    let local = *a + 1;
+    &local
+}
+
+fn main() {
+    let x = 1;
+    println!("{}", *next(&x));
+}
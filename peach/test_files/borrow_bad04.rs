@@ -0,0 +1,8 @@
+fn main() {
+    let mut x = 5;
+    let a = &mut x;
+    let b = &mut x;
+    *a = 1;
+    *b = 2;
+    println!("{}", x);
+}
@@ -0,0 +1,18 @@
+struct Token {
+    value: i32,
+}
+
+fn take(t: Token) -> i32 {
+    t.value
+}
+
+fn main() {
+    let t = Token { value: 1 };
+    let mut total = 0;
+    let mut i = 0;
+    while i < 3 {
+        total += take(t);
+        i += 1;
+    }
+    println!("{}", total);
+}
@@ -0,0 +1,14 @@
+fn main() {
+    let mut x = 1;
+    let mut total = 0;
+    let r = &mut x;
+    loop {
+        x += 1;
+        *r += 1;
+        if x > 5 {
+            break;
+        }
+    }
+    let wrong: bool = total;
+    println!("{}", x);
+}
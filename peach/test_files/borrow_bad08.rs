@@ -0,0 +1,6 @@
+fn main() {
+    let mut x = 1;
+    let m = &mut x;
+    println!("{}", x);
+    *m = 4;
+}
@@ -0,0 +1,43 @@
+fn main() {
+    let big: i32 = 300;
+    let neg: i32 = -1;
+    let wide: u64 = 0x1_0000_00FF;
+    let mut total = 0;
+
+    // Narrowing keeps the low bits, and widening a signed value copies its sign bit in
+    if big as u8 == 44 && neg as u32 == 4294967295 && wide as i32 == 255 && -1i8 as u8 == 255 && 200u8 as i8 == -56 {
+        total += 1;
+    }
+    if neg as i64 == -1 && neg as u128 == 340282366920938463463374607431768211455u128 && 255u8 as i16 == 255 {
+        total += 2;
+    }
+
+    // Floats truncate towards zero and saturate at the bounds of the type, with NaN giving 0
+    let nan = 0.0f64 / 0.0;
+    if 2.9f64 as i32 == 2 && -2.9f32 as i32 == -2 && 1e10 as i32 == 2147483647 && -1e10 as i32 == -2147483648 {
+        total += 4;
+    }
+    if -5.0 as u8 == 0 && 300.5 as u8 == 255 && nan as i64 == 0 && 1e40 as u128 == 340282366920938463463374607431768211455u128 {
+        total += 8;
+    }
+
+    // Integers round to the nearest float
+    let max = 340282366920938463463374607431768211455u128;
+    if 16777217 as f32 == 16777216.0 && max as f32 > 1e38 && max as f32 == 1.0 / 0.0 && -3i64 as f64 == -3.0 {
+        total += 16;
+    }
+    if (0.1f64 as f32) as f64 != 0.1 && 1e300f64 as f32 == 1.0 / 0.0 {
+        total += 32;
+    }
+
+    if true as i32 == 1 && false as u8 == 0 {
+        total += 64;
+    }
+
+    let ptr = 4096usize as *const u8;
+    if ptr as usize == 4096 && 0 as *const u8 as u64 == 0 {
+        total += 128;
+    }
+
+    println!("{}", total as f32 + 0.5);
+}
@@ -0,0 +1,4 @@
+fn main() {
+    let on = true;
+    println!("{}", on as f64);
+}
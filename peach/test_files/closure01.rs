@@ -0,0 +1,40 @@
+fn apply(x: i32) -> i32 {
+    let offset = 10;
+    let add = |y| y + offset + x;
+    add(5)
+}
+
+fn main() {
+    let base = 7;
+    let scale = |x: i32| -> i32 { x * base };
+    let mut count = 0;
+    let mut inc = |by| count += by;
+    inc(2);
+    inc(3);
+    let mut total = 100;
+    let mut bump = move |by: i32| {
+        total += by;
+        total
+    };
+    let first = bump(1);
+    let second = bump(1);
+    let outer = |a: i32| {
+        let inner = |b: i32| a * b + base;
+        inner(2) + inner(3)
+    };
+    let mut sum = 0;
+    for i in 0..4 {
+        let add_i = |s: i32| s + i;
+        sum = add_i(sum);
+    }
+    let pick = |flag: bool| if flag { 1 } else { 2 };
+    println!("{}", scale(3));
+    println!("{}", count);
+    println!("{}", first);
+    println!("{}", second);
+    println!("{}", outer(4));
+    println!("{}", total);
+    println!("{}", sum);
+    println!("{}", pick(true));
+    println!("{}", apply(3));
+}
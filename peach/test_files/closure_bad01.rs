@@ -0,0 +1,6 @@
+fn main() {
+    let mut count = 0;
+    let inc = |by: i32| count += by;
+    inc(2);
+    println!("{}", count);
+}
@@ -0,0 +1,4 @@
+fn main() {
+    let same = |x| x;
+    println!("{}", 3);
+}
@@ -0,0 +1,7 @@
+fn main() {
+    let mut count = 0;
+    let mut inc = || count += 1;
+    let r = &count;
+    inc();
+    println!("{}", r);
+}
@@ -0,0 +1,5 @@
+fn main() {
+    let boxed = Box::new(5);
+    let get = move || *boxed + 1;
+    println!("{}", *boxed + get());
+}
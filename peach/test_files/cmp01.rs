@@ -0,0 +1,44 @@
+fn check(counter: &mut i32, result: bool) -> bool {
+    *counter = *counter + 1;
+    result
+}
+
+fn main() {
+    let x: i64 = 5;
+    let y: u32 = 7;
+    let t = true;
+    let mut total = 0;
+
+    if x == 5 {
+        total = total + 1;
+    }
+    if x != 4 {
+        total = total + 2;
+    }
+    if y <= 7 {
+        total = total + 4;
+    }
+    if y > 6 {
+        total = total + 8;
+    }
+    if x >= 6 {
+        total = total + 1000;
+    }
+    if t == !false {
+        total = total + 16;
+    }
+    if false < t {
+        total = total + 32;
+    }
+
+    let mut calls = 0;
+    let a = x < 3 && check(&mut calls, true);
+    let b = x > 3 || check(&mut calls, false);
+    let c = x > 3 && check(&mut calls, false);
+    let d = !(x < 3) && (y == 0 || check(&mut calls, true));
+    if !a && b && !c && d {
+        total = total + 64;
+    }
+
+    println!("{}", total + calls * 100);
+}
@@ -0,0 +1,12 @@
+fn main() {
+    let a: u8 = 5;
+    let b: i32 = 5;
+    let c = 5;
+    let d: u64 = 0;
+    let e: i8 = -128;
+    println!("{}", !a);
+    println!("{}", !b);
+    println!("{}", !c);
+    println!("{}", !d == 18446744073709551615);
+    println!("{}", !e);
+}
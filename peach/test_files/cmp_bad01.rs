@@ -0,0 +1,11 @@
+struct Point {
+    x: i32,
+}
+
+fn main() {
+    let a = Point { x: 1 };
+    let b = Point { x: 2 };
+    if a == b {
+        println!("{}", a.x);
+    }
+}
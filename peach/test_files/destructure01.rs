@@ -0,0 +1,67 @@
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+struct Wrapper {
+    inner: Point,
+}
+
+enum Shape {
+    Circle(Point, i32),
+    Empty,
+}
+
+enum Step {
+    More(i32),
+    Done,
+}
+
+fn sum(Point { x, y }: Point) -> i32 {
+    x + y
+}
+
+fn radius(shape: Shape) -> i32 {
+    if let Shape::Circle(_, r) = shape {
+        r
+    } else {
+        0
+    }
+}
+
+fn describe(shape: Shape) -> i32 {
+    if let Shape::Empty = shape {
+        0
+    } else if let Shape::Circle(Point { x: 0, .. }, r) = shape {
+        r
+    } else {
+        100
+    }
+}
+
+fn countdown(start: i32) -> i32 {
+    let mut total = 0;
+    let mut state = Step::More(start);
+    while let Step::More(n) = state {
+        total = total + n;
+        state = if 1 < n { Step::More(n - 1) } else { Step::Done };
+    }
+    total
+}
+
+fn main() {
+    let p = Point { x: 3, y: 4 };
+    let Point { x: a, .. } = p;
+    let w = Wrapper {
+        inner: Point { x: 5, y: 6 },
+    };
+    let Wrapper { ref inner } = w;
+
+    let mut total = sum(p) + a + inner.x;
+    total = total + radius(Shape::Circle(Point { x: 1, y: 2 }, 10));
+    total = total + describe(Shape::Empty);
+    total = total + describe(Shape::Circle(Point { x: 0, y: 0 }, 7));
+    total = total + describe(Shape::Circle(Point { x: 1, y: 0 }, 7));
+    total = total + countdown(3);
+    println!("{}", total);
+}
@@ -0,0 +1,19 @@
+fn twice((n): i32) -> i32 {
+    n * 2
+}
+
+fn main() {
+    let (x) = 5;
+    let ((mut y)) = 6;
+    y = y + 1;
+    let (a,) = (10,);
+    let mut total = 0;
+    for (i) in 0..5 {
+        total = total + i;
+    }
+    let m = match (3, (4,)) {
+        ((1), _) => 0,
+        (b, (c,)) => b * c,
+    };
+    println!("{}", x + y + a + total + m + twice(50));
+}
@@ -0,0 +1,10 @@
+enum Shape {
+    Circle(i32),
+    Empty,
+}
+
+fn main() {
+    let shape = Shape::Circle(3);
+    let Shape::Circle(r) = shape;
+    println!("{}", r);
+}
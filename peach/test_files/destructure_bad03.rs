@@ -0,0 +1,6 @@
+fn main() {
+    let (a, b): (i32, i32);
+    a = 1;
+    b = 2;
+    println!("{}", a + b);
+}
@@ -0,0 +1,45 @@
+trait Shape {
+    fn area(&self) -> i32;
+
+    fn scaled(&self, factor: i32) -> i32 {
+        self.area() * factor
+    }
+}
+
+struct Square {
+    side: i32,
+}
+
+struct Rect {
+    width: i32,
+    height: i32,
+}
+
+impl Shape for Square {
+    fn area(&self) -> i32 {
+        self.side * self.side
+    }
+}
+
+impl Shape for Rect {
+    fn area(&self) -> i32 {
+        self.width * self.height
+    }
+}
+
+struct Pair {
+    first: Box<dyn Shape>,
+    second: Box<dyn Shape>,
+}
+
+fn total(pair: Pair) -> i32 {
+    pair.first.area() + pair.second.scaled(2)
+}
+
+fn main() {
+    let pair = Pair {
+        first: Box::new(Square { side: 3 }),
+        second: Box::new(Rect { width: 2, height: 5 }),
+    };
+    println!("{}", total(pair));
+}
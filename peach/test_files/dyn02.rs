@@ -0,0 +1,29 @@
+trait Named {
+    fn id(&self) -> i32;
+}
+
+struct Cat {
+    lives: i32,
+}
+
+impl Named for Cat {
+    fn id(&self) -> i32 {
+        self.lives
+    }
+}
+
+impl Cat {
+    fn describe(&self) -> i32 {
+        show(self) + 1
+    }
+}
+
+fn show(named: &dyn Named) -> i32 {
+    named.id() * 10
+}
+
+fn main() {
+    let boxed: Box<dyn Named> = Box::new(Cat { lives: 9 });
+    let cat = Cat { lives: 4 };
+    println!("{}", boxed.id() + cat.describe());
+}
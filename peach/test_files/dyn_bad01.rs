@@ -0,0 +1,23 @@
+trait Shape {
+    fn area(&self) -> i32;
+    fn new(size: i32) -> Self;
+}
+
+struct Square {
+    side: i32,
+}
+
+impl Shape for Square {
+    fn area(&self) -> i32 {
+        self.side * self.side
+    }
+
+    fn new(size: i32) -> Self {
+        Square { side: size }
+    }
+}
+
+fn main() {
+    let shape: Box<dyn Shape> = Box::new(Square::new(2));
+    println!("{}", shape.area());
+}
@@ -0,0 +1,27 @@
+trait Shape {
+    fn area(&self) -> i32;
+    fn new(size: i32) -> Self;
+}
+
+struct Square {
+    side: i32,
+}
+
+impl Shape for Square {
+    fn area(&self) -> i32 {
+        self.side * self.side
+    }
+
+    fn new(size: i32) -> Self {
+        Square { side: size }
+    }
+}
+
+fn area_of(shape: &dyn Shape) -> i32 {
+    shape.area()
+}
+
+fn main() {
+    let square = Square::new(2);
+    println!("{}", area_of(&square));
+}
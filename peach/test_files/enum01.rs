@@ -0,0 +1,29 @@
+enum Direction {
+    North,
+    East,
+    South,
+    West,
+}
+
+fn turn(dir: Direction) -> Direction {
+    match dir {
+        Direction::North => Direction::East,
+        Direction::East => Direction::South,
+        Direction::South => Direction::West,
+        Direction::West => Direction::North,
+    }
+}
+
+fn degrees(dir: Direction) -> i32 {
+    match dir {
+        Direction::North => 0,
+        Direction::East => 90,
+        Direction::South => 180,
+        _ => 270,
+    }
+}
+
+fn main() {
+    let dir = turn(turn(Direction::North));
+    println!("{}", degrees(dir));
+}
@@ -0,0 +1,32 @@
+enum Shape {
+    Circle(i32),
+    Rect(i32, i32),
+    Empty,
+}
+
+impl Shape {
+    fn area(&self) -> i32 {
+        match self {
+            Shape::Circle(r) => 3 * r * r,
+            Shape::Rect(w, h) => w * h,
+            Shape::Empty => 0,
+        }
+    }
+}
+
+fn describe(shape: Shape) -> i32 {
+    match shape {
+        Shape::Rect(1, h) => h,
+        Shape::Rect(w, 1) => w,
+        Shape::Circle(0) => 0,
+        other => other.area(),
+    }
+}
+
+fn main() {
+    let a = describe(Shape::Rect(1, 7));
+    let b = describe(Shape::Rect(4, 5));
+    let c = describe(Shape::Circle(2));
+    let d = describe(Shape::Empty);
+    println!("{}", a + b + c + d);
+}
@@ -0,0 +1,23 @@
+enum Size {
+    Small,
+    Large,
+}
+
+fn bytes(n: u8) -> u8 {
+    match n {
+        0 => 200,
+        m => m,
+    }
+}
+
+fn limit(s: Size) -> i64 {
+    match s {
+        Size::Small => 10,
+        Size::Large => 3000000000,
+    }
+}
+
+fn main() {
+    println!("{}", bytes(0));
+    println!("{}", limit(Size::Large));
+}
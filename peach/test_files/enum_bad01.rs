@@ -0,0 +1,15 @@
+enum Shape {
+    Circle(i32),
+    Square(i32),
+}
+
+fn area(shape: Shape) -> i32 {
+    match shape {
+        Shape::Circle(r) => 3 * r * r,
+        Shape::Square(s) => true,
+    }
+}
+
+fn main() {
+    println!("{}", area(Shape::Square(2)));
+}
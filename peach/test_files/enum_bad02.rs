@@ -0,0 +1,17 @@
+enum Shape {
+    Pt { x: i32 },
+    Circle(i32),
+}
+
+fn area(s: Shape) -> i32 {
+    match s {
+        Shape::Pt { x } => x,
+        Shape::Circle(r) => r * r * 3,
+    }
+}
+
+fn main() {
+    let c = Shape::Circle(2);
+    let p = Shape::Pt { x: 1 };
+    println!("{}", area(c) + area(p));
+}
@@ -0,0 +1,12 @@
+fn foo(x: u64) -> u64 {
+    x + true
+}
+
+fn main() {
+    let x: bool = 3;
+    let y = z + 1;
+    if y {
+        println!("{}", foo(y));
+    }
+    println!("{}", x);
+}
@@ -0,0 +1,11 @@
+extern "C" {
+    fn fabs(input: f64) -> f64;
+}
+
+fn main() {
+    let x: f64 = -2.75;
+
+    unsafe {
+        println!("{}", fabs(x));
+    }
+}
@@ -0,0 +1,34 @@
+struct Particle {
+    x: f32,
+    speed: f32,
+}
+
+fn area(width: f64, height: f64) -> f64 {
+    width * height
+}
+
+fn main() {
+    let mut p = Particle { x: 1.5, speed: 0.25f32 };
+    p.x += p.speed * 2.0;
+    p.x -= 0.5;
+    p.x /= 2.;
+
+    let a = area(2.5, 4.0) - 1e1;
+    let half = 7.0 % 2.0;
+    let neg = -half;
+
+    // Comparisons with NaN are all false, except `!=`
+    let nan = 0.0 / 0.0;
+    let mut total = 0.0;
+    if nan != nan && !(nan == nan) && !(nan < 1.0) && !(nan >= 1.0) {
+        total += 100.0;
+    }
+    if p.x == 1.0 && a == 0.0 && neg < -0.5 && neg <= -1.0 && half > 0.5 && half >= 1.0 {
+        total += 20.0;
+    }
+    if 1.0 / 0.0 > 1e300 {
+        total += 3.0;
+    }
+
+    println!("{}", total + 0.125);
+}
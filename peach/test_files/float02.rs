@@ -0,0 +1,4 @@
+fn main() {
+    let third: f32 = 1.0 / 3.0;
+    println!("{}", third);
+}
@@ -0,0 +1,5 @@
+fn main() {
+    let x = 1.5;
+    let y = x + 2;
+    println!("{}", y);
+}
@@ -0,0 +1,43 @@
+struct Pair {
+    boxed: Box<i64>,
+    both: (i64, i64),
+}
+
+fn make(n: i64) -> Pair {
+    let both = (n, n + 1);
+    Pair {
+        boxed: Box::new(n * 2),
+        both,
+    }
+}
+
+fn update(pair: &mut Pair, n: i64) {
+    let both = (n, n * 3);
+    pair.both = both;
+    pair.boxed = Box::new(n);
+}
+
+fn reset(both: &mut (i64, i64)) {
+    *both = (7i64, 8i64);
+}
+
+fn reset_twice(pair: &mut Pair) {
+    let mut both = (0i64, 0i64);
+    reset(&mut both);
+    pair.both = both;
+    reset(&mut pair.both);
+}
+
+fn main() {
+    let mut pair = make(1);
+    let mut i = 0;
+    let mut sum = 0;
+    while i < 1000 {
+        let made = make(i);
+        sum = sum + *made.boxed + made.both.1;
+        update(&mut pair, i);
+        i = i + 1;
+    }
+    reset_twice(&mut pair);
+    println!("{}", sum + *pair.boxed + pair.both.0 + pair.both.1);
+}
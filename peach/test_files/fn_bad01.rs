@@ -0,0 +1,7 @@
+fn f() -> bool {
+    3
+}
+
+fn main() {
+    f();
+}
@@ -0,0 +1,51 @@
+struct Op {
+    apply: fn(i32, i32) -> i32,
+    bias: i32,
+}
+
+fn double(x: i32) -> i32 {
+    x * 2
+}
+
+fn square(x: i32) -> i32 {
+    x * x
+}
+
+fn add(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+fn mul(a: i32, b: i32) -> i32 {
+    a * b
+}
+
+fn twice(f: fn(i32) -> i32, x: i32) -> i32 {
+    f(f(x))
+}
+
+fn pick(use_square: bool) -> fn(i32) -> i32 {
+    if use_square {
+        square
+    } else {
+        double
+    }
+}
+
+fn run(op: &Op, a: i32, b: i32) -> i32 {
+    (op.apply)(a, b) + op.bias
+}
+
+fn main() {
+    let f: fn(i32) -> i32 = double;
+    let mut g = f;
+    let first = g(3);
+    g = square;
+    let second = g(3);
+    let ops = [Op { apply: add, bias: 1 }, Op { apply: mul, bias: 2 }];
+    let mut total = 0;
+    for i in 0..2 {
+        total += run(&ops[i], 4, 5);
+    }
+    let chosen = pick(true)(4) + pick(false)(4);
+    println!("{}", first + second * 10 + twice(double, 5) * 100 + twice(square, 2) * 1000 + total * 10000 + chosen * 100000);
+}
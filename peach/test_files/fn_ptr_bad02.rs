@@ -0,0 +1,8 @@
+fn is_even(x: i32) -> bool {
+    x % 2 == 0
+}
+
+fn main() {
+    let f: fn(i32) -> i32 = is_even;
+    println!("{}", f(2));
+}
@@ -0,0 +1,11 @@
+fn apply(f: fn(i64) -> i64, x: i64) -> i64 {
+    f(x)
+}
+
+fn double(x: i32) -> i32 {
+    x * 2
+}
+
+fn main() {
+    println!("{}", apply(double, 3));
+}
@@ -0,0 +1,46 @@
+fn sum_to(n: u64) -> u64 {
+    let mut total = 0;
+    for i in 1..=n {
+        total += i;
+    }
+    total
+}
+
+fn main() {
+    let mut total = 0;
+    for i in 0..10 {
+        total += i;
+    }
+    for i in (1..=5).rev() {
+        total = total * 2 + i;
+    }
+    for i in (0..20).step_by(7) {
+        total += i;
+    }
+    for i in (-5..5).rev().step_by(3) {
+        total = total * 3 + i;
+    }
+    for _ in 3..3 {
+        total += 1000;
+    }
+
+    let n: u8 = 255;
+    let mut count: u32 = 0;
+    for x in 250..=n {
+        count += x as u32;
+    }
+
+    'outer: for i in 0..5 {
+        for j in 0..5 {
+            if j > i {
+                continue 'outer;
+            }
+            if i == 4 {
+                break 'outer;
+            }
+            total += j * 10;
+        }
+    }
+
+    println!("{}", total + count as i32 + sum_to(100) as i32);
+}
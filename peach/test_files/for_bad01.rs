@@ -0,0 +1,7 @@
+fn main() {
+    let mut total = 0;
+    for i in 10 {
+        total += i;
+    }
+    println!("{}", total);
+}
@@ -0,0 +1,7 @@
+fn main() {
+    let mut total = 0.0;
+    for x in 0.0..1.0 {
+        total += x;
+    }
+    println!("{}", total);
+}
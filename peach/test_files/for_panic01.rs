@@ -0,0 +1,7 @@
+fn main() {
+    let mut total = 0;
+    for i in (0..10).step_by(0) {
+        total += i;
+    }
+    println!("{}", total);
+}
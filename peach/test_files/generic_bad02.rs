@@ -0,0 +1,8 @@
+fn id<T>(x: T) -> T {
+    let y: i32 = x;
+    x
+}
+
+fn main() {
+    println!("{}", id(1));
+}
@@ -0,0 +1,44 @@
+struct Pair<T> {
+    a: T,
+    b: T,
+}
+
+struct Labeled<K, V> {
+    key: K,
+    value: V,
+}
+
+struct Wrapper<T>(T);
+
+fn sum_pair(p: Pair<i64>) -> i64 {
+    p.a + p.b
+}
+
+fn swap<T>(p: Pair<T>) -> Pair<T> {
+    Pair { a: p.b, b: p.a }
+}
+
+fn first<T>(p: &Pair<T>) -> &T {
+    &p.a
+}
+
+fn main() {
+    let ints = Pair { a: 3, b: 4 };
+    let longs: Pair<i64> = Pair { a: 10, b: 20 };
+    let flags = Pair { a: true, b: false };
+    let mut nested: Pair<Pair<u8>> = Pair { a: Pair { a: 1, b: 2 }, b: Pair { a: 3, b: 4 } };
+    nested.b.a = 7;
+    let labeled: Labeled<u16, Wrapper<f64>> = Labeled { key: 5, value: Wrapper(2.5) };
+    let Pair { a: x, b: y } = swap(ints);
+    let Wrapper(w) = labeled.value;
+    let Labeled { key, .. } = labeled;
+    let mut total = sum_pair(longs) + x as i64 * 100 + y as i64 * 1000;
+    if flags.a && !flags.b {
+        total += 10000;
+    }
+    total += (nested.a.a + nested.a.b + nested.b.a + nested.b.b) as i64 * 100000;
+    total += key as i64 * 10000000 + (w * 2.0) as i64 * 100000000;
+    let boxed = Pair { a: Box::new(6), b: Box::new(7) };
+    total += **first(&boxed) as i64 * 1000000000 + *boxed.b as i64;
+    println!("{}", total);
+}
@@ -0,0 +1,13 @@
+struct Apply<T> {
+    f: fn(T) -> T,
+    value: T,
+}
+
+fn double(x: i64) -> i64 {
+    x * 2
+}
+
+fn main() {
+    let a = Apply { f: double, value: 3 };
+    println!("{}", (a.f)(a.value));
+}
@@ -0,0 +1,16 @@
+struct Pair<T> {
+    a: T,
+    b: T,
+}
+
+fn sum_bytes(p: Pair<u8>) -> u8 {
+    p.a + p.b
+}
+
+fn main() {
+    let annotated: Pair<u8> = Pair { a: 200, b: 50 };
+    let fished = Pair::<i64> { a: 3000000000, b: 1 };
+    println!("{}", annotated.a + annotated.b);
+    println!("{}", sum_bytes(Pair { a: 100, b: 27 }));
+    println!("{}", fished.a + fished.b);
+}
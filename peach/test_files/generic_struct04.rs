@@ -0,0 +1,19 @@
+struct W<T> {
+    v: T,
+}
+
+impl W<i32> {
+    fn get(&self) -> i32 {
+        self.v
+    }
+}
+
+fn un<T>(w: W<T>) -> T {
+    w.v
+}
+
+fn main() {
+    let w = W { v: 8 };
+    println!("{}", w.get());
+    println!("{}", un(W { v: 9u8 }));
+}
@@ -0,0 +1,9 @@
+struct Pair<T> {
+    a: T,
+    b: T,
+}
+
+fn main() {
+    let p = Pair { a: 1, b: true };
+    println!("{}", p.a);
+}
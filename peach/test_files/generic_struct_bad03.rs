@@ -0,0 +1,6 @@
+struct Both<T>(T, T);
+
+fn main() {
+    let b = Both(1.5, 2);
+    println!("{}", b.0);
+}
@@ -0,0 +1,13 @@
+struct Pair<T> {
+    a: T,
+    b: T,
+}
+
+fn total(p: Pair<i32>) -> i32 {
+    p.a + p.b
+}
+
+fn main() {
+    let p = Pair { a: 1u8, b: 2u8 };
+    println!("{}", total(p));
+}
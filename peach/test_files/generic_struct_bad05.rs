@@ -0,0 +1,14 @@
+struct Wrapper<T> {
+    value: T,
+}
+
+impl<T> Wrapper<T> {
+    fn get(self) -> T {
+        self.value
+    }
+}
+
+fn main() {
+    let w = Wrapper { value: 1 };
+    println!("{}", w.get());
+}
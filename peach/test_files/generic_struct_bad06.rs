@@ -0,0 +1,14 @@
+struct W<T> {
+    v: T,
+}
+
+impl W<i32> {
+    fn get(&self) -> i32 {
+        self.v
+    }
+}
+
+fn main() {
+    let w = W { v: 8u8 };
+    println!("{}", w.get());
+}
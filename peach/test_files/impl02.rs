@@ -0,0 +1,28 @@
+struct Counter {
+    count: i32,
+}
+
+impl Counter {
+    fn new(start: i32) -> Self {
+        Self { count: start }
+    }
+
+    fn get(&self) -> i32 {
+        self.count
+    }
+
+    fn bump(&mut self, amount: i32) {
+        self.count = self.count + amount;
+    }
+
+    fn into_count(self) -> i32 {
+        self.count
+    }
+}
+
+fn main() {
+    let mut c = Counter::new(3);
+    c.bump(4);
+    c.bump(c.get());
+    println!("{}", c.into_count());
+}
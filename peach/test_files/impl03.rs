@@ -0,0 +1,41 @@
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+impl Point {
+    fn origin() -> Point {
+        Point { x: 0, y: 0 }
+    }
+
+    fn shifted(self, dx: i32) -> Point {
+        Point {
+            x: self.x + dx,
+            y: self.y,
+        }
+    }
+
+    fn sum(&self) -> i32 {
+        self.x + self.y
+    }
+}
+
+struct Line {
+    start: Point,
+    end: Point,
+}
+
+impl Line {
+    fn stretch(&mut self) {
+        self.end.x = self.end.x + self.end.sum();
+    }
+}
+
+fn main() {
+    let mut line = Line {
+        start: Point::origin(),
+        end: Point { x: 2, y: 3 },
+    };
+    line.stretch();
+    println!("{}", Point::origin().shifted(line.end.sum()).sum());
+}
@@ -0,0 +1,10 @@
+struct Foo;
+
+impl Foo {
+    fn bar() {}
+}
+
+fn main() {
+    let foo = Foo;
+    foo.baz();
+}
@@ -0,0 +1,39 @@
+fn main() {
+    let a: u8 = 200;
+    let b: i8 = -100;
+    let c: u16 = 60000;
+    let d: i16 = -30000;
+    let e: usize = 3;
+    let f: isize = -7;
+    let g: i128 = -18446744073709551615;
+    let big: i64 = 10_000_000_000;
+    let max: u64 = 18446744073709551615;
+    let mut checks: u32 = 0;
+
+    if a + 55 == 255 && a / 3 == 66 {
+        checks += 1;
+    }
+    if b - 28 == -128 && -b == 100 {
+        checks += 2;
+    }
+    if c > 59999 && c % 7 == 3 {
+        checks += 4;
+    }
+    if d < -29999 && d >> 4 == -1875 {
+        checks += 8;
+    }
+    if e * 5 == 15 && e << 2 == 12 {
+        checks += 16;
+    }
+    if f % 4 == -3 && f < 0 {
+        checks += 32;
+    }
+    if g * 4 == -73786976294838206460 && g < -10_000_000_000_000 {
+        checks += 64;
+    }
+    if big / 1_000_000 == 10_000 && max - 1 > 9_223_372_036_854_775_807 {
+        checks += 128;
+    }
+
+    println!("{}", checks);
+}
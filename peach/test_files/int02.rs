@@ -0,0 +1,4 @@
+fn main() {
+    let wide: u128 = 18446744073709551615;
+    println!("{}", wide * wide);
+}
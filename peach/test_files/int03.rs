@@ -0,0 +1,8 @@
+fn main() {
+    let min = -170141183460469231731687303715884105728i128;
+    let max = 340282366920938463463374607431768211455u128;
+    let small: i8 = -128;
+    println!("{}", min);
+    println!("{}", max);
+    println!("{}", small);
+}
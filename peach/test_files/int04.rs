@@ -0,0 +1,6 @@
+fn main() {
+    let h: u128 = 170141183460469231731687303715884105728;
+    let wide: u64 = 10_000_000_000;
+    println!("{}", h);
+    println!("{}", wide);
+}
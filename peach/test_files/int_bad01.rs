@@ -0,0 +1,4 @@
+fn main() {
+    let x = 170141183460469231731687303715884105728;
+    println!("{}", x);
+}
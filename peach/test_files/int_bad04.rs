@@ -0,0 +1,4 @@
+fn main() {
+    let x = 10_000_000_000;
+    println!("{}", x);
+}
@@ -0,0 +1,36 @@
+fn find(limit: i32) -> i32 {
+    let mut count = 0;
+    let mut i = 0;
+    'outer: loop {
+        i += 1;
+        if i > limit {
+            break;
+        }
+        let mut j = 0;
+        while j < i {
+            j += 1;
+            if j == 3 {
+                continue;
+            }
+            if i * j > 20 {
+                continue 'outer;
+            }
+            if i + j == 15 {
+                break 'outer;
+            }
+            count += j;
+        }
+    }
+    count
+}
+
+fn main() {
+    let mut n = 0;
+    let first = loop {
+        n += 1;
+        if n * n > 50 {
+            break n * 10;
+        }
+    };
+    println!("{}", find(10) + first);
+}
@@ -0,0 +1,23 @@
+fn first_square_over(limit: u32) -> u32 {
+    let mut n = 0;
+    loop {
+        n += 1;
+        if n * n > limit {
+            return n;
+        }
+    }
+}
+
+fn grid(filled: bool) -> [[u8; 2]; 2] {
+    if filled {
+        return [[9, 9], [9, 9]];
+    }
+    [[0, 1], [2, 3]]
+}
+
+fn main() {
+    let full = grid(true);
+    let empty = grid(false);
+    println!("{}", first_square_over(50));
+    println!("{}", full[1][0] + empty[1][1]);
+}
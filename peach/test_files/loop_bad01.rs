@@ -0,0 +1,8 @@
+fn main() {
+    let mut x = 0;
+    while x < 10 {
+        x += 1;
+        break x;
+    }
+    println!("{}", x);
+}
@@ -0,0 +1,10 @@
+fn main() {
+    let mut x = 0;
+    'outer: loop {
+        x += 1;
+        if x > 3 {
+            break 'inner;
+        }
+    }
+    println!("{}", x);
+}
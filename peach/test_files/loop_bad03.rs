@@ -0,0 +1,7 @@
+fn main() {
+    let x = 1;
+    if x > 0 {
+        continue;
+    }
+    println!("{}", x);
+}
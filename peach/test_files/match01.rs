@@ -0,0 +1,37 @@
+enum Shape {
+    Circle(i32),
+    Rect(i32, i32),
+}
+
+fn sign(n: i32) -> i32 {
+    match n {
+        0 => 0,
+        1..=100 => 1,
+        _ => 2,
+    }
+}
+
+fn flip(b: bool) -> bool {
+    match b {
+        true => false,
+        false => true,
+    }
+}
+
+fn kind(shape: Shape) -> i32 {
+    match shape {
+        Shape::Circle(_) => 1,
+        Shape::Rect(0, _) => 2,
+        Shape::Rect(_, 0) => 3,
+        Shape::Rect(_, _) => 4,
+    }
+}
+
+fn main() {
+    let mut total = sign(0) + sign(50) + sign(500);
+    if flip(false) {
+        total = total + 10;
+    }
+    total = total + kind(Shape::Rect(3, 0)) * 100;
+    println!("{}", total);
+}
@@ -0,0 +1,11 @@
+fn describe(n: i32) -> i32 {
+    match n {
+        0..=9 => 1,
+        5 => 2,
+        _ => 3,
+    }
+}
+
+fn main() {
+    println!("{}", describe(5));
+}
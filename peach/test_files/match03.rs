@@ -0,0 +1,18 @@
+fn f(x: i128) -> i32 {
+    match x {
+        0 => 1,
+        _ => 2,
+    }
+}
+
+fn g(x: u128) -> i32 {
+    match x {
+        0..=9 => 1,
+        10..=340282366920938463463374607431768211455 => 2,
+    }
+}
+
+fn main() {
+    println!("{}", f(0));
+    println!("{}", g(340282366920938463463374607431768211455));
+}
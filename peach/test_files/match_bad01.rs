@@ -0,0 +1,16 @@
+enum Shape {
+    Circle(i32),
+    Square(i32),
+    Empty,
+}
+
+fn size(shape: Shape) -> i32 {
+    match shape {
+        Shape::Circle(r) => r,
+        Shape::Empty => 0,
+    }
+}
+
+fn main() {
+    println!("{}", size(Shape::Square(2)));
+}
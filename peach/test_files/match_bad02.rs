@@ -0,0 +1,10 @@
+fn describe(n: i32) -> i32 {
+    match n {
+        0 => 10,
+        1 => 20,
+    }
+}
+
+fn main() {
+    println!("{}", describe(1));
+}
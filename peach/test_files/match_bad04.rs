@@ -0,0 +1,8 @@
+fn main() {
+    let x = 3;
+    let size = match x {
+        n if n > 2 => 1,
+        _ => 0,
+    };
+    println!("{}", size);
+}
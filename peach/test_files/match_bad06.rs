@@ -0,0 +1,16 @@
+fn f(x: i128) -> i32 {
+    match x {
+        0 => 1,
+    }
+}
+
+fn g(x: u128) -> i32 {
+    match x {
+        0..=9 => 1,
+        200000000000000000000000000000000000000 => 2,
+    }
+}
+
+fn main() {
+    println!("{}", f(0) + g(1));
+}
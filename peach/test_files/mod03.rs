@@ -1,10 +1,10 @@
 mod foo {
-    mod other {
+    pub mod other {
         pub fn baz() {
             println!("{}", 4);
         }
     }
-    mod bar {
+    pub mod bar {
         pub fn baz() {
             println!("{}", 3);
         }
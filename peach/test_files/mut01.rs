@@ -0,0 +1,42 @@
+struct Counter {
+    count: i32,
+}
+
+impl Counter {
+    fn bump(&mut self) {
+        self.count = self.count + 1;
+    }
+
+    fn reset(mut self) -> Counter {
+        self.count = 0;
+        self
+    }
+}
+
+fn add_to(total: &mut i32, amount: i32) {
+    *total = *total + amount;
+}
+
+fn main() {
+    let mut total = 0;
+
+    // The reference can be written through even though it can't be changed itself
+    let r = &mut total;
+    add_to(r, 5);
+    *r = *r + 1;
+
+    // A variable declared without a value can be given one later, once
+    let x;
+    if 3 < total {
+        x = 10;
+    } else {
+        x = 20;
+    }
+
+    let mut c = Counter { count: 1 };
+    c.bump();
+    let bumped = c.count;
+    let c = c.reset();
+
+    println!("{}", total + x + bumped + c.count);
+}
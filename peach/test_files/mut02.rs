@@ -0,0 +1,8 @@
+fn main() {
+    let mut t = (1, 2);
+    let (_, ref mut r) = t;
+    *r = 5;
+    let (ref mut a, _) = (3, 4);
+    *a = *a + 1;
+    println!("{}", t.1 * 10 + *a);
+}
@@ -0,0 +1,17 @@
+fn f((ref mut a, b): (i32, i32)) -> i32 {
+    *a = *a + 10;
+    *a + b
+}
+
+fn main() {
+    let mut total = 0;
+    for ref mut i in 0..3 {
+        *i = *i * 2;
+        total = total + *i;
+    }
+    let g = |(ref mut a, b): (i32, i32)| {
+        *a = *a + 100;
+        *a + b
+    };
+    println!("{}", total + f((1, 2)) + g((3, 4)));
+}
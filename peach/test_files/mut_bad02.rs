@@ -0,0 +1,6 @@
+fn main() {
+    let x = 5;
+    let r = &mut x;
+    *r = 6;
+    println!("{}", x);
+}
@@ -0,0 +1,14 @@
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+fn reset(p: &Point) {
+    p.x = 0;
+}
+
+fn main() {
+    let mut p = Point { x: 1, y: 2 };
+    reset(&p);
+    println!("{}", p.x + p.y);
+}
@@ -0,0 +1,6 @@
+fn main() {
+    let x = 1;
+    let flag: bool = 5;
+    x = 2;
+    println!("{}", x);
+}
@@ -0,0 +1,6 @@
+fn main() {
+    let t = (1, 2);
+    let (ref r1, ref mut r2) = t;
+    *r2 = 5;
+    println!("{}", *r1 + *r2);
+}
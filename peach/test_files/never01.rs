@@ -0,0 +1,43 @@
+enum O {
+    S(i32),
+    N,
+}
+
+fn get(o: O) -> i32 {
+    let v = match o {
+        O::S(x) => x,
+        O::N => return -1,
+    };
+    v * 2
+}
+
+fn sign(x: i32) -> i32 {
+    if x < 0 {
+        return -1
+    } else {
+        return 1
+    }
+}
+
+fn main() {
+    let mut total = 0;
+    let mut i = 0;
+    loop {
+        i += 1;
+        let n = match i {
+            3 => break,
+            n => n,
+        };
+        total += n;
+    }
+    for i in 0..6 {
+        let c = i % 2 == 0;
+        let k = if c { continue } else { i };
+        total += k * 10;
+    }
+    let f = |x: i32| if x > 0 { x } else { return 0 };
+    println!("{}", total);
+    println!("{}", get(O::S(4)) + get(O::N));
+    println!("{}", sign(-5) + sign(5) * 10);
+    println!("{}", f(3) + f(-3));
+}
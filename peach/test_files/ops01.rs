@@ -0,0 +1,56 @@
+struct Counter {
+    count: i32,
+}
+
+fn next(calls: &mut i32) -> usize {
+    *calls += 1;
+    (*calls - 1) as usize
+}
+
+fn main() {
+    let a: i32 = -17;
+    let b: u32 = 0xF0;
+    let shift: u64 = 2;
+    let mut total: i32 = 0;
+
+    // Remainder truncates towards zero, like division
+    if a % 5 == -2 && 17 % -5 == 2 {
+        total += 1;
+    }
+    if b & 0x3C == 0x30 && (b | 0x0F) == 0xFF && (b ^ 0xFF) == 0x0F {
+        total += 2;
+    }
+    // A signed value keeps its sign when shifted right
+    if a >> 2 == -5 && a << shift == -68 && b >> 4 == 0x0F {
+        total += 4;
+    }
+    if a >> 31 == -1 && b >> 31 == 0 {
+        total += 8;
+    }
+    if (true & false) == false && (true | false) && (true ^ true) == false {
+        total += 16;
+    }
+
+    let mut x = 6;
+    x += 4;
+    x -= 1;
+    x *= 3;
+    x /= 2;
+    x %= 7;
+    x <<= 4;
+    x >>= 1;
+    x |= 1;
+    x &= 27;
+    x ^= 2;
+
+    let mut c = Counter { count: 5 };
+    c.count *= 20;
+    c.count -= 1;
+
+    // The place is only worked out once
+    let mut calls = 0;
+    let mut arr = [1, 2, 3];
+    arr[next(&mut calls)] += 10;
+
+    println!("{}", total * 1000 + x * 100 + c.count + arr[0] + calls * 1000000);
+}
@@ -0,0 +1,5 @@
+fn main() {
+    let flag = true;
+    let x = flag << 2;
+    println!("{}", x);
+}
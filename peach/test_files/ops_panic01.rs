@@ -0,0 +1,8 @@
+fn divisor(x: i32) -> i32 {
+    x - 3
+}
+
+fn main() {
+    let x = 10 % divisor(3);
+    println!("{}", x);
+}
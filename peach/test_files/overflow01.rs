@@ -0,0 +1,16 @@
+fn checked(x: u8, y: u8) -> u8 {
+    match x.checked_add(y) {
+        Some(v) => v,
+        None => 0,
+    }
+}
+
+fn main() {
+    let a = checked(200, 100) + checked(20, 30);
+    let b = 250u8.wrapping_add(10);
+    let c = (-100i8).saturating_sub(100);
+    let d = 100i8.saturating_mul(-2);
+    let e = 5u32.saturating_sub(10);
+    let f = 1000000i32.saturating_mul(10000);
+    println!("{}", a as i32 + b as i32 + c as i32 + d as i32 + e as i32 + f);
+}
@@ -0,0 +1,8 @@
+fn main() {
+    let shifted: u64 = 1 << 40;
+    let product: u64 = 3000000000 * 2;
+    let sum: i64 = 2147483647 + 1;
+    println!("{}", shifted);
+    println!("{}", product);
+    println!("{}", sum);
+}
@@ -0,0 +1,35 @@
+fn main() {
+    let min: i32 = -2147483647 - 1;
+    println!("{}", min.wrapping_div(-1));
+    println!("{}", min.saturating_div(-1));
+    let by_zero = match 7i32.checked_div(0) {
+        Some(v) => v,
+        None => -1,
+    };
+    let overflowed = match min.checked_div(-1) {
+        Some(v) => v,
+        None => -1,
+    };
+    println!("{}", by_zero + overflowed + min.wrapping_rem(-1) + 7i32.wrapping_rem(-3));
+    let rem = match min.checked_rem(-1) {
+        Some(v) => v,
+        None => 100,
+    };
+    println!("{}", rem);
+    println!("{}", min.wrapping_neg() == min);
+    println!("{}", min.saturating_neg());
+    let neg = match 5i32.checked_neg() {
+        Some(v) => v,
+        None => 0,
+    };
+    println!("{}", neg);
+    println!("{}", 5u8.wrapping_neg());
+    println!("{}", 1u8.wrapping_shl(9) + 128u8.wrapping_shr(15));
+    println!("{}", (-16i64).wrapping_shr(66));
+    let shl = match 1i32.checked_shl(32) {
+        Some(v) => v,
+        None => -1,
+    };
+    println!("{}", shl);
+    println!("{}", 200u8.saturating_div(3));
+}
@@ -0,0 +1,9 @@
+fn add(x: u8, y: u8) -> u8 {
+    x + y
+}
+
+fn main() {
+    println!("{}", add(200, 0));
+    let x = add(200, 100);
+    println!("{}", x);
+}
@@ -0,0 +1,8 @@
+fn negate(x: i32) -> i32 {
+    -x
+}
+
+fn main() {
+    let x = negate(-2147483648);
+    println!("{}", x);
+}
@@ -0,0 +1,8 @@
+fn shift(x: u32, amount: u32) -> u32 {
+    x << amount
+}
+
+fn main() {
+    let x = shift(1, 32);
+    println!("{}", x);
+}
@@ -0,0 +1,16 @@
+fn add(x: u8, y: u8) -> u8 {
+    x + y
+}
+
+fn mul(x: i32, y: i32) -> i32 {
+    x * y
+}
+
+fn shift(x: u32, amount: u32) -> u32 {
+    x << amount
+}
+
+fn main() {
+    let x = add(200, 100) as i32 + mul(2147483647, 2) + shift(1, 33) as i32;
+    println!("{}", x);
+}
@@ -0,0 +1,49 @@
+struct Point {
+    x: i32,
+    y: i32,
+}
+
+fn bump(p: &mut Point, by: i32) {
+    p.x = p.x + by;
+    (*p).y = (*p).y + by;
+}
+
+fn get_x(p: &Point) -> i32 {
+    p.x
+}
+
+fn pick(p: &Point) -> &i32 {
+    &p.y
+}
+
+fn set(target: &mut i32, value: i32) {
+    *target = value;
+}
+
+fn first(a: &[i32; 3]) -> i32 {
+    a[0]
+}
+
+fn main() {
+    let mut pt = Point { x: 1, y: 2 };
+    bump(&mut pt, 10);
+
+    let mut n = 0;
+    set(&mut n, 5);
+    let r = &n;
+    let m = *r + 1;
+
+    let arr = [7, 8, 9];
+    let a = first(&arr);
+    let y = *pick(&pt);
+
+    let rr = &&pt;
+    let through = rr.x;
+
+    let t = &mut pt;
+    t.x = 20;
+
+    let mut total = get_x(&pt) + pt.y + n + m;
+    total = total + a + y + through + *&3;
+    println!("{}", total);
+}
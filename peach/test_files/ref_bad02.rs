@@ -0,0 +1,9 @@
+fn set(target: &mut i32, value: i32) {
+    *target = value;
+}
+
+fn main() {
+    let x: i32 = 1;
+    set(&x, 2);
+    println!("{}", x);
+}
@@ -3,7 +3,7 @@ struct Bar {
 }
 
 fn main() {
-    let bar = Bar { x: 3 };
+    let mut bar = Bar { x: 3 };
 
     bar.x = bar.x + 2;
 
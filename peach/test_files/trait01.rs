@@ -0,0 +1,22 @@
+trait Shape {
+    fn area(&self) -> i32;
+
+    fn double_area(&self) -> i32 {
+        self.area() * 2
+    }
+}
+
+struct Square {
+    side: i32,
+}
+
+impl Shape for Square {
+    fn area(&self) -> i32 {
+        self.side * self.side
+    }
+}
+
+fn main() {
+    let s = Square { side: 3 };
+    println!("{}", s.area() + s.double_area());
+}
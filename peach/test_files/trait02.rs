@@ -0,0 +1,44 @@
+trait Score {
+    fn score(self) -> i32;
+
+    fn bonus(&self) -> i32 {
+        1
+    }
+}
+
+struct Player {
+    points: i32,
+}
+
+impl Score for Player {
+    fn score(self) -> i32 {
+        self.points
+    }
+
+    fn bonus(&self) -> i32 {
+        10
+    }
+}
+
+impl Score for i32 {
+    fn score(self) -> i32 {
+        self
+    }
+}
+
+fn total<T: Score>(x: T) -> i32 {
+    x.bonus() + x.score()
+}
+
+fn total_both<T>(x: T, y: T) -> i32
+where
+    T: Score,
+{
+    total(x) + total(y)
+}
+
+fn main() {
+    let p = Player { points: 5 };
+    let q = Player { points: 5 };
+    println!("{}", total_both(p, q) + total(4));
+}
@@ -0,0 +1,45 @@
+trait Make {
+    fn new() -> Self;
+    fn val(&self) -> i32;
+}
+
+trait Name {
+    fn val(&self) -> i32;
+}
+
+struct S {
+    x: i32,
+}
+
+impl S {
+    fn val(&self) -> i32 {
+        self.x * 10
+    }
+}
+
+impl Make for S {
+    fn new() -> Self {
+        S { x: 5 }
+    }
+    fn val(&self) -> i32 {
+        self.x
+    }
+}
+
+impl Name for S {
+    fn val(&self) -> i32 {
+        self.x + 1
+    }
+}
+
+fn make_like<T: Make>(t: &T) -> T {
+    let made = T::new();
+    println!("{}", made.val() + t.val());
+    made
+}
+
+fn main() {
+    let s = S { x: 2 };
+    let made = make_like(&s);
+    println!("{}", made.val());
+}
@@ -0,0 +1,19 @@
+trait Shape {
+    fn area(&self) -> i32;
+    fn sides(&self) -> i32;
+}
+
+struct Square {
+    side: i32,
+}
+
+impl Shape for Square {
+    fn area(&self) -> i32 {
+        self.side * self.side
+    }
+}
+
+fn main() {
+    let s = Square { side: 3 };
+    println!("{}", s.area());
+}
@@ -0,0 +1,16 @@
+trait Shape {
+    fn area(&self) -> i32;
+}
+
+struct Circle {
+    radius: i32,
+}
+
+fn area_of<T: Shape>(x: T) -> i32 {
+    x.area()
+}
+
+fn main() {
+    let c = Circle { radius: 3 };
+    println!("{}", area_of(c));
+}
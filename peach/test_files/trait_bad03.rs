@@ -0,0 +1,26 @@
+trait A {
+    fn a(&self) -> i32;
+}
+
+trait B {
+    fn a(&self) -> i32;
+}
+
+struct S {}
+
+impl A for S {
+    fn a(&self) -> i32 {
+        1
+    }
+}
+
+impl B for S {
+    fn a(&self) -> i32 {
+        2
+    }
+}
+
+fn main() {
+    let s = S {};
+    println!("{}", s.a());
+}
@@ -0,0 +1,29 @@
+trait A {
+    fn a(&self) -> i32;
+}
+
+trait B {
+    fn a(&self) -> i32;
+}
+
+struct S {}
+
+impl A for S {
+    fn a(&self) -> i32 {
+        1
+    }
+}
+
+impl B for S {
+    fn a(&self) -> i32 {
+        2
+    }
+}
+
+fn both<T: A + B>(t: &T) -> i32 {
+    t.a()
+}
+
+fn main() {
+    println!("{}", both(&S {}));
+}
@@ -0,0 +1,31 @@
+fn divmod(a: i32, b: i32) -> (i32, i32) {
+    let q = a / b;
+    (q, a - q * b)
+}
+
+fn add((x, y): (i32, i32)) -> i32 {
+    x + y
+}
+
+fn both(flags: (bool, bool)) -> i32 {
+    match flags {
+        (true, true) => 3,
+        (true, false) => 2,
+        (false, _) => 1,
+    }
+}
+
+fn main() {
+    let (q, r) = divmod(17, 5);
+    let pair = divmod(30, 7);
+    let mut nested = ((1, 2), 10);
+    nested.0 .1 = 20;
+    let (first, ..) = (100, true, 7);
+
+    let mut total = q * 10 + r;
+    total = total + pair.0 + pair.1;
+    total = total + add(nested.0) + nested.1;
+    total = total + first;
+    total = total + both((true, false)) + both((false, true));
+    println!("{}", total);
+}
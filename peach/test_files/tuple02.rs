@@ -0,0 +1,36 @@
+struct Meters(i32);
+
+struct Pair(i32, i32);
+
+struct Origin;
+
+impl Pair {
+    fn sum(&self) -> i32 {
+        self.0 + self.1
+    }
+}
+
+fn at_origin(_: Origin) -> i32 {
+    1000
+}
+
+fn longer(m: Meters) -> Meters {
+    Meters(m.0 * 2)
+}
+
+fn main() {
+    let m = longer(Meters(21));
+    let mut p = Pair(3, 4);
+    p.1 = 5;
+    let Pair(a, b) = p;
+    let Meters(len) = m;
+    let o = Origin;
+    let Origin = o;
+
+    let kind = match p {
+        Pair(3, _) => 10,
+        Pair(..) => 20,
+    };
+
+    println!("{}", a * 100 + b + len + p.sum() + kind + at_origin(Origin));
+}
@@ -0,0 +1,31 @@
+struct Marker;
+
+fn small() -> (u8, bool) {
+    (200, true)
+}
+
+fn large(early: bool) -> (i64, (u8, bool)) {
+    if early {
+        return (1, (2, false));
+    }
+    (3000000000, small())
+}
+
+fn total(pair: (i64, u8)) -> i64 {
+    pair.0 + pair.1 as i64
+}
+
+fn mark(_: Marker) -> i64 {
+    7
+}
+
+fn main() {
+    let unit = ();
+    let same = unit;
+    let annotated: () = same;
+    let t: (u8, bool) = (255, false);
+    let (big, (byte, flag)) = large(false);
+    let early = large(true);
+    let sum = big + byte as i64 + t.0 as i64 + (early.1).0 as i64 + total((10, 20)) + mark(Marker);
+    println!("{}", if flag && !t.1 { sum } else { 0 });
+}
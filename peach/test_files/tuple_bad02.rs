@@ -0,0 +1,8 @@
+fn main() {
+    let t = (true, false);
+    let x = match t {
+        (true, _) => 1,
+        (false, true) => 2,
+    };
+    println!("{}", x);
+}
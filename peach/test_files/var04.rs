@@ -0,0 +1,20 @@
+struct S {
+    a: [i32; 3],
+    b: i64,
+}
+
+fn main() {
+    let mut s = S { a: [1, 2, 3], b: 0 };
+    s.a[0] = 50;
+    s.b = 3000000000;
+    let mut x: u8 = 0;
+    x = 200;
+    let mut arr = [0u16; 2];
+    arr[1] = 60000;
+    s.a[1] += 5;
+    println!("{}", s.a[0]);
+    println!("{}", s.b);
+    println!("{}", x);
+    println!("{}", arr[1]);
+    println!("{}", s.a[1]);
+}
@@ -0,0 +1,51 @@
+mod shapes {
+    pub struct Rect {
+        pub width: i32,
+        height: i32,
+    }
+
+    impl Rect {
+        pub fn new(width: i32, height: i32) -> Rect {
+            Rect {
+                width: width,
+                height: height,
+            }
+        }
+
+        pub fn area(&self) -> i32 {
+            self.width * self.height
+        }
+
+        fn factor(&self) -> i32 {
+            2
+        }
+
+        pub(crate) fn scaled_area(&self) -> i32 {
+            self.area() * self.factor()
+        }
+    }
+
+    pub mod inner {
+        pub(super) fn helper() -> i32 {
+            5
+        }
+
+        pub(crate) fn shared() -> i32 {
+            7
+        }
+    }
+
+    pub use inner::shared;
+
+    pub fn use_helper() -> i32 {
+        inner::helper()
+    }
+}
+
+use shapes::Rect;
+
+fn main() {
+    let r = Rect::new(3, 4);
+    let w = r.width;
+    println!("{}", r.area() + r.scaled_area() + w + shapes::use_helper() + shapes::shared());
+}
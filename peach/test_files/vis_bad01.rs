@@ -0,0 +1,11 @@
+mod foo {
+    mod bar {
+        pub fn baz() {
+            println!("{}", 2);
+        }
+    }
+}
+
+fn main() {
+    foo::bar::baz();
+}
@@ -0,0 +1,18 @@
+mod shapes {
+    pub struct Rect {
+        pub width: i32,
+        height: i32,
+    }
+
+    pub fn square(size: i32) -> Rect {
+        Rect {
+            width: size,
+            height: size,
+        }
+    }
+}
+
+fn main() {
+    let r = shapes::square(3);
+    println!("{}", r.width * r.height);
+}
@@ -0,0 +1,23 @@
+mod counter {
+    pub struct Counter {
+        pub count: i32,
+    }
+
+    impl Counter {
+        pub fn get(&self) -> i32 {
+            self.count
+        }
+
+        fn reset(&mut self) {
+            self.count = 0;
+        }
+    }
+}
+
+use counter::Counter;
+
+fn main() {
+    let mut c = Counter { count: 4 };
+    c.reset();
+    println!("{}", c.get());
+}
@@ -0,0 +1,17 @@
+mod outer {
+    pub mod inner {
+        pub(super) fn helper() -> i32 {
+            5
+        }
+    }
+
+    pub fn call_helper() -> i32 {
+        inner::helper()
+    }
+}
+
+use outer::inner::helper;
+
+fn main() {
+    println!("{}", outer::call_helper() + helper());
+}
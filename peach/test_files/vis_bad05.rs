@@ -0,0 +1,13 @@
+mod secrets {
+    fn hidden() -> i32 {
+        42
+    }
+
+    pub fn revealed() -> i32 {
+        hidden()
+    }
+}
+
+fn main() {
+    println!("{}", secrets::revealed() + secrets::hidden());
+}
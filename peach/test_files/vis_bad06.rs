@@ -0,0 +1,15 @@
+mod outer {
+    pub mod inner {
+        pub(in outer) fn helper() -> i32 {
+            42
+        }
+    }
+
+    pub fn answer() -> i32 {
+        inner::helper()
+    }
+}
+
+fn main() {
+    println!("{}", outer::answer());
+}
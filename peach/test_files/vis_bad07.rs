@@ -0,0 +1,15 @@
+mod a {
+    pub struct T(i32);
+    pub struct U(pub i32);
+
+    pub fn make() -> T {
+        T(3)
+    }
+}
+
+fn main() {
+    let t = a::T(1);
+    let a::T(x) = a::make();
+    let a::U(y) = a::U(2);
+    println!("{}", y);
+}
@@ -0,0 +1,131 @@
+//! Renders diagnostics from the bytecode engine as rustc-style source snippets
+
+use peach::{BytecodeEngine, Diagnostic, Label};
+
+/// The labels that start on a single line of a file, with whether each is the primary label
+type LineLabels<'a> = (&'a str, usize, Vec<(&'a Label, bool)>);
+
+/// Renders a diagnostic, eg)
+/// ```text
+/// error[E0369]: Can't add values of bool and {unknown int}
+///  --> expr_bad01.rs:2:25
+///   |
+/// 2 |     println!("{}", true + 3);
+///   |                         ^ incompatible operand types
+///   |                    ---- bool
+///   |                           - {unknown int}
+/// ```
+pub fn render(bc: &BytecodeEngine, diagnostic: &Diagnostic) -> String {
+    let mut output = match diagnostic.code {
+        Some(code) => format!("{}[{}]: {}\n", diagnostic.level(), code, diagnostic.message),
+        None => format!("{}: {}\n", diagnostic.level(), diagnostic.message),
+    };
+
+    // Primary label first, so that it gets the ` --> ` header and is drawn closest to the code
+    let mut labels: Vec<(&Label, bool)> = vec![];
+    if let Some(ref primary) = diagnostic.primary {
+        labels.push((primary, true));
+    }
+    for secondary in &diagnostic.secondary {
+        labels.push((secondary, false));
+    }
+
+    let gutter_width = labels
+        .iter()
+        .map(|&(label, _)| label.span.start_line.to_string().len())
+        .max()
+        .unwrap_or(0);
+    let gutter = " ".repeat(gutter_width);
+
+    // Group labels by the file and line they start on, keeping the order they were first seen
+    let mut lines: Vec<LineLabels> = vec![];
+    for &(label, is_primary) in &labels {
+        let key = (label.span.file.as_str(), label.span.start_line);
+        match lines.iter().position(|x| (x.0, x.1) == key) {
+            Some(pos) => lines[pos].2.push((label, is_primary)),
+            None => lines.push((key.0, key.1, vec![(label, is_primary)])),
+        }
+    }
+
+    let mut current_file: Option<&str> = None;
+    for (file, line, line_labels) in lines {
+        let first = line_labels[0].0;
+        if current_file.is_none() {
+            output += &format!(
+                "{}--> {}:{}:{}\n",
+                gutter,
+                file,
+                line,
+                first.span.start_column + 1
+            );
+        } else if current_file != Some(file) {
+            output += &format!(
+                "{}::: {}:{}:{}\n",
+                gutter,
+                file,
+                line,
+                first.span.start_column + 1
+            );
+        }
+        current_file = Some(file);
+
+        let source_line = bc.source_text(file)
+            .and_then(|src| src.lines().nth(line - 1));
+
+        match source_line {
+            Some(source_line) => {
+                output += &format!("{} |\n", gutter);
+                output += &format!("{:>width$} | {}\n", line, source_line, width = gutter_width);
+
+                for (label, is_primary) in line_labels {
+                    let start = label.span.start_column;
+                    // Spans covering several lines are underlined to the end of their first line
+                    let end = if label.span.end_line == label.span.start_line {
+                        label.span.end_column
+                    } else {
+                        source_line.len()
+                    };
+                    let width = if end > start { end - start } else { 1 };
+                    let marker = if is_primary { "^" } else { "-" };
+
+                    output += &format!(
+                        "{} | {}{} {}\n",
+                        gutter,
+                        " ".repeat(start),
+                        marker.repeat(width),
+                        label.message
+                    );
+                }
+            }
+            None => for (label, _) in line_labels {
+                output += &format!("{} = {}\n", gutter, label.message);
+            },
+        }
+    }
+
+    if !diagnostic.notes.is_empty() {
+        output += &format!("{} |\n", gutter);
+    }
+    for note in &diagnostic.notes {
+        output += &format!("{} = note: {}\n", gutter, note);
+    }
+
+    output
+}
+
+/// Renders each of the diagnostics, followed by a count of how many errors were found
+pub fn render_all(bc: &BytecodeEngine, diagnostics: &[Diagnostic]) -> String {
+    let mut output = String::new();
+    for diagnostic in diagnostics {
+        output += &render(bc, diagnostic);
+        output += "\n";
+    }
+
+    if diagnostics.len() == 1 {
+        output += "error: aborting due to previous error\n";
+    } else {
+        output += &format!("error: aborting due to {} previous errors\n", diagnostics.len());
+    }
+
+    output
+}
@@ -4,11 +4,16 @@
 //!   * "build" - builds given project to a binary (uses the system C compiler)
 //!   * "run" - converts the project to bytecode, which it runs immediately
 //!   * "repl" - creates a repl to interact with the code directly
+//!
+//! "build" and "run" follow a debug build, where arithmetic that overflows panics, unless given `--release`, which
+//! makes it wrap around instead
 
 extern crate peach;
 extern crate syn;
 
-use peach::{compile_bytecode, Bytecode, BytecodeEngine, EvalEngine, VarStack};
+mod diagnostics;
+
+use peach::{compile_bytecode, Bytecode, BytecodeEngine, EvalEngine, Panic, Profile, VarStack};
 
 use std::collections::HashMap;
 use std::path::Path;
@@ -51,7 +56,8 @@ pub fn repl() {
         }
 
         if input == ":stack" || input == ":s" {
-            println!("{:?}", ee.value_stack);
+            println!("stack: {:?}", ee.value_stack);
+            println!("slots: {:?}", ee.slots);
             continue;
         }
 
@@ -59,7 +65,7 @@ pub fn repl() {
             println!(":h(elp) - print this help message");
             println!(":t(ype) - print the result type");
             println!(":b(ytecode) - print the bytecode");
-            println!(":s(tack) - print the contents of the stack");
+            println!(":s(tack) - print the contents of the stack and variable slots");
             println!(":q(uit) - quit repl");
             continue;
         }
@@ -71,23 +77,22 @@ pub fn repl() {
             if show_bytecode {
                 println!("bytecode: {:?}", bytecode);
             }
-            ee.eval_block_bytecode(&bc, &bytecode, &mut var_lookup);
-
-            // This funny little trick should, in theory, let us pop off temporaries without popping off our variables
-            let last = if ee.value_stack.len() > var_lookup.len() {
-                ee.value_stack.pop().unwrap()
-            } else {
-                ee.value_stack.last().unwrap().clone()
-            };
-
-            println!("{}", last);
+            match ee.eval_block_bytecode(&bc, &bytecode, &mut var_lookup) {
+                // Variables live in slots, so anything left on the stack is the value of the expression
+                Ok(_) => if let Some(last) = ee.value_stack.pop() {
+                    println!("{}", last);
+                },
+                Err(panic) => report_panic(&mut ee, &panic),
+            }
         } else {
             match bc.process_raw_stmt_str(&input, &mut bytecode, &mut var_stack) {
                 Ok(_) => {
                     if show_bytecode {
                         println!("bytecode: {:?}", bytecode);
                     }
-                    ee.eval_block_bytecode(&bc, &bytecode, &mut var_lookup);
+                    if let Err(panic) = ee.eval_block_bytecode(&bc, &bytecode, &mut var_lookup) {
+                        report_panic(&mut ee, &panic);
+                    }
                 }
                 Err(_) => {
                     input.push(';');
@@ -97,11 +102,15 @@ pub fn repl() {
                             if show_bytecode {
                                 println!("bytecode: {:?}", bytecode);
                             }
-                            ee.eval_block_bytecode(&bc, &bytecode, &mut var_lookup);
-                        }
-                        Err(e) => {
-                            println!("Error: {}", e);
+                            if let Err(panic) =
+                                ee.eval_block_bytecode(&bc, &bytecode, &mut var_lookup)
+                            {
+                                report_panic(&mut ee, &panic);
+                            }
                         }
+                        Err(errors) => for diagnostic in &errors {
+                            print!("{}", diagnostics::render(&bc, diagnostic));
+                        },
                     }
                 }
             }
@@ -109,32 +118,65 @@ pub fn repl() {
     }
 }
 
-fn process(fname: &str, start_fn: &str) -> BytecodeEngine {
+/// Reports a panic in the repl, dropping whatever the input left half-computed so the repl can carry on
+fn report_panic(ee: &mut EvalEngine, panic: &Panic) {
+    ee.value_stack.clear();
+    println!("{}", panic);
+}
+
+/// Converts the given file to bytecode, starting at the given function.
+/// Returns the rendered diagnostics if the file has errors.
+fn process(fname: &str, start_fn: &str) -> Result<BytecodeEngine, String> {
     let mut bc = BytecodeEngine::new();
 
     // Step 1: Load up the parsed file so that we can lazily convert it
     //TODO: FIXME: we should probably take &str or Path
     let path = Path::new(fname).canonicalize().unwrap();
     bc.set_project_root(path.parent().unwrap().to_str().unwrap());
-    bc.load_file(path.file_name().unwrap().to_str().unwrap());
+    let result = bc.load_file(path.file_name().unwrap().to_str().unwrap())
+        // Step 2: Convert to bytecode from the given location
+        // We assume the starting function is found in scope 0, the starting scope
+        .and_then(|_| bc.process_fn(start_fn, 0));
 
-    // Step 2: Convert to bytecode from the given location
-    // We assume the starting function is found in scope 0, the starting scope
-    bc.process_fn(start_fn, 0);
-    //println!("{:#?}", bc.processed_fns);
+    for warning in bc.take_warnings() {
+        eprintln!("{}", diagnostics::render(&bc, &warning));
+    }
 
-    bc
+    match result {
+        Ok(_) => Ok(bc),
+        Err(errors) => Err(diagnostics::render_all(&bc, &errors)),
+    }
+}
+
+/// Processes the file for the given profile, exiting with the rendered errors if it fails to process
+fn process_or_exit(fname: &str, start_fn: &str, profile: Profile) -> BytecodeEngine {
+    match process(fname, start_fn) {
+        Ok(mut bc) => {
+            bc.set_profile(profile);
+            bc
+        }
+        Err(rendered) => {
+            eprint!("{}", rendered);
+            ::std::process::exit(1);
+        }
+    }
 }
 
 fn main() {
     use std::env;
 
-    let mut args = env::args();
-    let _ = args.next(); // executable name
+    let mut args: Vec<String> = env::args().skip(1).collect(); // skipping the executable name
+    let profile = if args.iter().any(|arg| arg == "--release") {
+        Profile::Release
+    } else {
+        Profile::Debug
+    };
+    args.retain(|arg| arg != "--release");
+    let mut args = args.into_iter();
 
     match (args.next(), args.next()) {
         (Some(ref cmd), Some(ref fname)) if cmd == "build" => {
-            let bc = process(&fname, "main");
+            let bc = process_or_exit(&fname, "main", profile);
             let compile_result = compile_bytecode(&bc, &fname);
             match compile_result {
                 Ok(msg) => println!("\nCompile succeeded: {}", msg),
@@ -142,11 +184,15 @@ fn main() {
             }
         }
         (Some(ref cmd), Some(ref fname)) if cmd == "run" => {
-            let bc = process(&fname, "main");
+            let bc = process_or_exit(&fname, "main", profile);
             let mut ee = EvalEngine::new();
 
             println!("Eval result:");
-            ee.eval_program(&bc, "main");
+            if let Err(panic) = ee.eval_program(&bc, "main") {
+                eprintln!("{}", panic);
+                // Rust exits with this code when the main thread panics
+                ::std::process::exit(101);
+            }
         }
         (Some(ref cmd), _) if cmd == "repl" => {
             repl();
@@ -156,8 +202,8 @@ fn main() {
         }
         (None, _) => {
             println!("Usage:");
-            println!("   build <filename>");
-            println!("   run <filename>");
+            println!("   build [--release] <filename>");
+            println!("   run [--release] <filename>");
             println!("   repl");
         }
     }